@@ -0,0 +1,5 @@
+fn main() {
+    // the gRPC service is only generated for builds with the grpc feature
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/admin.proto").expect("can not generate proto/admin.proto");
+}
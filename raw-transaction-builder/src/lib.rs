@@ -1,7 +1,7 @@
 // Copyright (c) 2017-2019, Substratum LLC (https://substratum.net) and/or its affiliates. All rights reserved.
 #![allow(deprecated)]
 
-use ethereum_types::{Address, U256};
+use ethereum_types::{Address, H256, U256};
 use ethsign::Signature;
 use ethsign_crypto::Keccak256;
 use rlp::RlpStream;
@@ -31,26 +31,87 @@ pub struct RawTransaction {
     pub data: Vec<u8>,
 }
 
+/// An EIP-2930 access list entry, a contract and the storage slots the
+/// transaction touches in it. Serialized like `eth_createAccessList` returns it.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AccessListItem {
+    pub address: Address,
+    #[serde(rename = "storageKeys")]
+    pub storage_keys: Vec<H256>,
+}
+
+/// EIP-2718 type of EIP-2930 transactions.
+const ACCESS_LIST_TX_TYPE: u8 = 1;
+
 impl RawTransaction {
     /// Signs and returns the RLP-encoded transaction
-    pub fn sign(&self, bip32ec_keypair: &Bip32ECKeyPair, chain_id: u8) -> Vec<u8> {
+    pub fn sign(&self, bip32ec_keypair: &Bip32ECKeyPair, chain_id: u64) -> Vec<u8> {
         let hash = self.tx_hash(chain_id);
-        let sig = ecdsa_sign(&hash, bip32ec_keypair, chain_id);
+        let sig = ecdsa_sign(&hash, bip32ec_keypair);
+        // EIP-155, does not fit in the u8 of the signature for chain ids above 109
+        let v = u64::from(sig.v) + chain_id * 2 + 35;
         let mut tx = RlpStream::new();
         tx.begin_unbounded_list();
         self.encode(&mut tx);
-        tx.append(&sig.v);
+        tx.append(&v);
         tx.append(&sig.r.to_vec());
         tx.append(&sig.s.to_vec());
         tx.complete_unbounded_list();
         tx.out()
     }
 
-    fn tx_hash(&self, chain_id: u8) -> Vec<u8> {
+    /// Signs and returns an EIP-2930 transaction, which declares the storage
+    /// it touches upfront and pays less for accessing it.
+    pub fn sign_with_access_list(
+        &self,
+        bip32ec_keypair: &Bip32ECKeyPair,
+        chain_id: u64,
+        access_list: &[AccessListItem],
+    ) -> Vec<u8> {
+        let mut unsigned = RlpStream::new();
+        unsigned.begin_unbounded_list();
+        self.encode_with_access_list(&mut unsigned, chain_id, access_list);
+        unsigned.complete_unbounded_list();
+        let hash = typed(unsigned.out()).keccak256();
+        let sig = ecdsa_sign(&hash, bip32ec_keypair);
+        let mut tx = RlpStream::new();
+        tx.begin_unbounded_list();
+        self.encode_with_access_list(&mut tx, chain_id, access_list);
+        tx.append(&sig.v);
+        // as integers, without leading zeros
+        tx.append(&U256::from(&sig.r[..]));
+        tx.append(&U256::from(&sig.s[..]));
+        tx.complete_unbounded_list();
+        typed(tx.out())
+    }
+
+    fn encode_with_access_list(
+        &self,
+        s: &mut RlpStream,
+        chain_id: u64,
+        access_list: &[AccessListItem],
+    ) {
+        s.append(&chain_id);
+        self.encode(s);
+        s.begin_list(access_list.len());
+        for item in access_list {
+            s.begin_list(2);
+            s.append(&item.address);
+            s.append_list(&item.storage_keys);
+        }
+    }
+
+    fn tx_hash(&self, chain_id: u64) -> Vec<u8> {
         let mut hash = RlpStream::new();
         hash.begin_unbounded_list();
         self.encode(&mut hash);
-        hash.append(&vec![chain_id]);
+        // chain id 0 is hashed as the byte 0x00, as signers always did, not as
+        // the empty integer; every other id encodes the same either way
+        if chain_id == 0 {
+            hash.append(&vec![0u8]);
+        } else {
+            hash.append(&chain_id);
+        }
         hash.append(&U256::zero());
         hash.append(&U256::zero());
         hash.complete_unbounded_list();
@@ -71,13 +132,15 @@ impl RawTransaction {
     }
 }
 
-fn ecdsa_sign(hash: &[u8], bip32ec_keypair: &Bip32ECKeyPair, chain_id: u8) -> Signature {
+fn typed(payload: Vec<u8>) -> Vec<u8> {
+    let mut tx = vec![ACCESS_LIST_TX_TYPE];
+    tx.extend(payload);
+    tx
+}
+
+fn ecdsa_sign(hash: &[u8], bip32ec_keypair: &Bip32ECKeyPair) -> Signature {
     match bip32ec_keypair.sign(hash) {
-        Ok(s) => Signature {
-            v: s.v + chain_id * 2 + 35,
-            r: s.r,
-            s: s.s,
-        },
+        Ok(s) => s,
         Err(e) => panic!("{:?}", e),
     }
 }
@@ -85,8 +148,7 @@ fn ecdsa_sign(hash: &[u8], bip32ec_keypair: &Bip32ECKeyPair, chain_id: u8) -> Si
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::blockchain::bip32::Bip32ECKeyPair;
-    use ethereum_types::H256;
+    use rustc_hex::{FromHex, ToHex};
 
     #[derive(Deserialize)]
     struct Signing {
@@ -103,20 +165,54 @@ mod tests {
         ]"#);
 
         let txs: Vec<(RawTransaction, Signing)> = serde_json::from_str(&text_txs_json).unwrap();
-        let chain_id = 0u8;
+        let chain_id = 0u64;
         for (tx, signed) in txs.into_iter() {
             assert_eq!(
                 signed.signed,
                 tx.sign(
-                    &Wallet::from(
-                        Bip32ECKeyPair::from_raw_secret(&signed.private_key.0.as_ref()).unwrap()
-                    ),
+                    &Bip32ECKeyPair::from_raw_secret(&signed.private_key.0.as_ref()).unwrap(),
                     chain_id
                 )
             );
         }
     }
 
+    #[test]
+    fn test_signs_access_list_transaction() {
+        // the access list example of eth-account's `Account.sign_transaction`
+        let tx = RawTransaction {
+            nonce: 34u64.into(),
+            to: Some("09616C3d61b3331fc4109a9E41a8BDB7d9776609".parse().unwrap()),
+            value: 0x5af3_107a_4000u64.into(),
+            gas_price: 1_000_000_000u64.into(),
+            gas_limit: 100_000u64.into(),
+            data: b"abcdef".to_vec(),
+        };
+        let access_list = [AccessListItem {
+            address: Address::from_low_u64_be(1),
+            storage_keys: vec![H256::from_slice(&[&[1][..], &[0; 31][..]].concat())],
+        }];
+        let private_key = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318"
+            .from_hex::<Vec<u8>>()
+            .unwrap();
+        let signed = concat!(
+            "01f8ad82076c22843b9aca00830186a09409616c3d61b3331fc4109a9e41a8bdb7d9776609865af3107a",
+            "400086616263646566f838f7940000000000000000000000000000000000000001e1a001000000000000",
+            "0000000000000000000000000000000000000000000000000080a0ea38506c4afe4bb402e030877fbe10",
+            "11fa1da47aabcf215db8da8fee5d3af086a051e9af653b8eb98e74e894a766cf88904dbdb10b0bc1fbd1",
+            "2f18f661fa2797a4"
+        )
+        .from_hex::<Vec<u8>>()
+        .unwrap();
+        let key_pair = Bip32ECKeyPair::from_raw_secret(&private_key).unwrap();
+        let raw = tx.sign_with_access_list(&key_pair, 1900, &access_list);
+        assert_eq!(signed, raw);
+        assert_eq!(
+            "2864ca20a74ca5e044067ad4139a22ff5a0853434f5f1dc00108f24ef5f1f783",
+            raw.keccak256().to_hex::<String>()
+        );
+    }
+
     #[test]
     fn test_signs_transaction_ropsten() {
         let text_txs_json = String::from(r#"[
@@ -126,14 +222,12 @@ mod tests {
         ]"#);
 
         let txs: Vec<(RawTransaction, Signing)> = serde_json::from_str(&text_txs_json).unwrap();
-        let chain_id = 3u8;
+        let chain_id = 3u64;
         for (tx, signed) in txs.into_iter() {
             assert_eq!(
                 signed.signed,
                 tx.sign(
-                    &Wallet::from(
-                        Bip32ECKeyPair::from_raw_secret(&signed.private_key.0.as_ref()).unwrap()
-                    ),
+                    &Bip32ECKeyPair::from_raw_secret(&signed.private_key.0.as_ref()).unwrap(),
                     chain_id
                 )
             );
@@ -148,14 +242,12 @@ mod tests {
         ]"#);
 
         let txs: Vec<(RawTransaction, Signing)> = serde_json::from_str(&txt_txs_json).unwrap();
-        let chain_id = 3u8;
+        let chain_id = 3u64;
         for (tx, signed) in txs.into_iter() {
             assert_eq!(
                 signed.signed,
                 tx.sign(
-                    &Wallet::from(
-                        Bip32ECKeyPair::from_raw_secret(&signed.private_key.0.as_ref()).unwrap()
-                    ),
+                    &Bip32ECKeyPair::from_raw_secret(&signed.private_key.0.as_ref()).unwrap(),
                     chain_id
                 )
             );
@@ -170,14 +262,12 @@ mod tests {
         ]"#);
 
         let txs: Vec<(RawTransaction, Signing)> = serde_json::from_str(&txt_txs_json).unwrap();
-        let chain_id = 1u8;
+        let chain_id = 1u64;
         for (tx, signed) in txs.into_iter() {
             assert_eq!(
                 signed.signed,
                 tx.sign(
-                    &Wallet::from(
-                        Bip32ECKeyPair::from_raw_secret(&signed.private_key.0.as_ref()).unwrap()
-                    ),
+                    &Bip32ECKeyPair::from_raw_secret(&signed.private_key.0.as_ref()).unwrap(),
                     chain_id
                 )
             );
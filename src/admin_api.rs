@@ -0,0 +1,438 @@
+use serde::Serialize;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::thread;
+
+use crate::admin_operations;
+use crate::admin_service::{parse_message_id, AdminService, Error};
+use crate::approvals::Decision;
+#[cfg(feature = "sqlite")]
+use crate::database::MessageFilter;
+use crate::fees;
+use crate::http::{self, Request, Response};
+use crate::secret::Secret;
+use crate::transfer_index::Filter;
+
+/// What a caller may do. Observers, e.g. monitoring dashboards, can only
+/// read; operators can also change what the validator does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Observer,
+    Operator,
+}
+
+/// Bearer tokens for the roles. The observer token is optional.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tokens {
+    pub operator: Secret<String>,
+    pub observer: Option<Secret<String>>,
+}
+
+impl Tokens {
+    fn role(&self, request: &Request) -> Option<Role> {
+        self.role_of(request.headers.get("authorization")?)
+    }
+
+    /// The role of an `Authorization: Bearer <token>` header.
+    pub fn role_of(&self, provided: &str) -> Option<Role> {
+        if matches(provided, &self.operator) {
+            return Some(Role::Operator);
+        }
+        match &self.observer {
+            Some(observer) if matches(provided, observer) => Some(Role::Observer),
+            _ => None,
+        }
+    }
+}
+
+/// HTTP API for operators. Every request needs `Authorization: Bearer
+/// <token>`, reads take the `ADMIN_API_OBSERVER_TOKEN` or the
+/// `ADMIN_API_TOKEN`, changes only the latter.
+///
+/// - `GET /approvals` lists transfers waiting for manual approval
+/// - `POST /approvals/<message_id>/approve` releases one to the executor
+/// - `POST /approvals/<message_id>/reject` drops it
+/// - `GET /fees`, `GET /fees.csv` report what each message cost this validator
+/// - `GET /fees/<message_id>` reports one message
+/// - `GET /messages/<message_id>` lists what was sent and decided for a
+///   message, with explorer links and links to its subgraph entities
+/// - `GET /transfers?address=<address>&since=<unix time>&until=<unix time>
+///   &from_block=<block>&to_block=<block>` lists the transfers seen since
+///   startup with their latest stage, every parameter is optional
+/// - `GET /indexer/messages?status=<status>&address=<address>
+///   &from_block=<block>&to_block=<block>` lists the messages the embedded
+///   indexer stored in the database, with the `sqlite` feature
+/// - `GET /debug/state` dumps the controller queues, graph node offsets,
+///   nonce and pending submissions, for bug reports
+/// - `POST /trace/<message_id>` logs everything done for one message at every
+///   level, `DELETE /trace/<message_id>` stops, `GET /trace` lists them
+/// - `POST /operations/erc20-transfer/<token>/<to>/<amount>` and
+///   `POST /operations/bridge-call/<method>/<args>...` send an admin operation
+///   through the executor and answer with its transaction hash and nonce
+pub fn serve(
+    address: SocketAddr,
+    tokens: Tokens,
+    service: Arc<AdminService>,
+) -> Result<thread::JoinHandle<()>, String> {
+    http::serve("admin_api", address, move |request| {
+        handle(request, &tokens, &service)
+    })
+}
+
+fn handle(request: &Request, tokens: &Tokens, service: &AdminService) -> Response {
+    let role = match tokens.role(request) {
+        Some(role) => role,
+        None => return Response::error("401 Unauthorized", "missing or invalid token"),
+    };
+    let required = match request.method.as_str() {
+        "GET" | "HEAD" => Role::Observer,
+        _ => Role::Operator,
+    };
+    if role < required {
+        return Response::error("403 Forbidden", "needs the operator token");
+    }
+    let path = request
+        .path
+        .trim_matches('/')
+        .split('/')
+        .collect::<Vec<_>>();
+    let result = match (request.method.as_str(), path.as_slice()) {
+        ("GET", ["approvals"]) => json(&service.approvals()),
+        ("POST", ["approvals", message_id, action]) => {
+            let decision = match *action {
+                "approve" => Decision::Approved,
+                "reject" => Decision::Rejected,
+                _ => return Response::error("404 Not Found", "expected approve or reject"),
+            };
+            parse_message_id(message_id)
+                .and_then(|message_id| service.decide(&message_id, decision))
+                .map(|()| Response::json("{}".to_string()))
+        }
+        ("GET", ["fees"]) => service.fees().map(|report| json(&report)),
+        ("GET", ["fees.csv"]) => service.fees().map(|report| Response {
+            content_type: "text/csv",
+            ..Response::text(fees::to_csv(&report))
+        }),
+        ("GET", ["fees", message_id]) => parse_message_id(message_id)
+            .and_then(|message_id| service.message_fees(&message_id))
+            .map(|fees| json(&fees)),
+        ("GET", ["messages", message_id]) => parse_message_id(message_id)
+            .and_then(|message_id| service.message(&message_id))
+            .map(|message| Response::json(message.to_string())),
+        ("GET", ["transfers"]) => filter(request)
+            .and_then(|filter| service.transfers(&filter))
+            .map(|transfers| json(&transfers)),
+        #[cfg(feature = "sqlite")]
+        ("GET", ["indexer", "messages"]) => message_filter(request)
+            .and_then(|filter| service.indexed_messages(&filter))
+            .map(|messages| json(&messages)),
+        ("GET", ["debug", "state"]) => Ok(Response::json(service.state().to_string())),
+        ("GET", ["trace"]) => Ok(json(&service.traced())),
+        ("POST", ["trace", message_id]) => parse_message_id(message_id).map(|message_id| {
+            service.start_trace(message_id);
+            Response::json("{}".to_string())
+        }),
+        ("DELETE", ["trace", message_id]) => parse_message_id(message_id)
+            .and_then(|message_id| service.stop_trace(&message_id))
+            .map(|()| Response::json("{}".to_string())),
+        ("POST", ["operations", kind, ..]) => {
+            let args = path[2..]
+                .iter()
+                .map(|arg| arg.to_string())
+                .collect::<Vec<_>>();
+            admin_operations::parse(kind, &args)
+                .map_err(Error::Invalid)
+                .and_then(|operation| service.execute(operation))
+                .and_then(|submitted| json(&submitted))
+        }
+        _ => return Response::error("404 Not Found", "not found"),
+    };
+    result.unwrap_or_else(|e| match e {
+        Error::Invalid(e) => Response::error("400 Bad Request", &e),
+        Error::NotFound(e) => Response::error("404 Not Found", &e),
+        Error::Internal(e) => Response::error("500 Internal Server Error", &e),
+        Error::Unavailable(e) => Response::error("503 Service Unavailable", &e),
+    })
+}
+
+fn json<T: Serialize>(value: &T) -> Result<Response, Error> {
+    Ok(Response::json(
+        serde_json::to_string(value).expect("response serializes"),
+    ))
+}
+
+fn number<T: std::str::FromStr>(request: &Request, name: &str) -> Result<Option<T>, Error> {
+    match request.query.get(name) {
+        Some(value) => value.parse().map(Some).map_err(|_| {
+            Error::Invalid(format!(
+                "{} must be a non-negative integer, got {:?}",
+                name, value
+            ))
+        }),
+        None => Ok(None),
+    }
+}
+
+fn filter(request: &Request) -> Result<Filter, Error> {
+    Ok(Filter {
+        address: request.query.get("address").cloned(),
+        since: number(request, "since")?,
+        until: number(request, "until")?,
+        from_block: number(request, "from_block")?,
+        to_block: number(request, "to_block")?,
+    })
+}
+
+#[cfg(feature = "sqlite")]
+fn message_filter(request: &Request) -> Result<MessageFilter, Error> {
+    Ok(MessageFilter {
+        status: request
+            .query
+            .get("status")
+            .map(|status| status.to_uppercase()),
+        address: request.query.get("address").cloned(),
+        from_block: number(request, "from_block")?,
+        to_block: number(request, "to_block")?,
+    })
+}
+
+fn matches(provided: &str, token: &Secret<String>) -> bool {
+    let expected = format!("Bearer {}", token.expose());
+    // compares every byte, so the time taken does not reveal the token
+    provided.len() == expected.len()
+        && provided
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::approvals::Approvals;
+    use crate::controller::Event;
+    use crate::debug_state;
+    use crate::links::Links;
+    use crate::submissions;
+    use crate::test_dir::TestDir;
+    use crate::transfer_index::TransferIndex;
+    use serde_json::{json, Value};
+    use web3::types::H256;
+
+    fn service(approvals: Arc<Approvals>, data_dir: &str, links: Links) -> AdminService {
+        AdminService::new(
+            approvals,
+            data_dir.to_string(),
+            links,
+            Arc::new(TransferIndex::default()),
+        )
+    }
+
+    fn request(method: &str, path: &str, token: &str) -> Request {
+        let mut request = Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            ..Request::default()
+        };
+        request
+            .headers
+            .insert("authorization".to_string(), format!("Bearer {}", token));
+        request
+    }
+
+    fn tokens() -> Tokens {
+        Tokens {
+            operator: Secret::new("operator".to_string()),
+            observer: Some(Secret::new("observer".to_string())),
+        }
+    }
+
+    #[test]
+    fn approvals_tests() {
+        let tokens = tokens();
+        let approvals = Arc::new(Approvals::in_memory());
+        let message_id = format!("0x{}", "01".repeat(32));
+        approvals.flag(Event::eth_withdraw_message(H256::from_slice(&[1; 32]), 10));
+
+        let response = handle(
+            &request("GET", "/approvals", "operator"),
+            &tokens,
+            &service(approvals.clone(), "data", Links::default()),
+        );
+        assert_eq!("200 OK", response.status);
+        assert!(response.body.contains("EthWithdrawMessage"));
+
+        let approve = format!("/approvals/{}/approve", message_id);
+        let response = handle(
+            &request("POST", &approve, "operator"),
+            &tokens,
+            &service(approvals.clone(), "data", Links::default()),
+        );
+        assert_eq!("200 OK", response.status);
+        assert_eq!(1, approvals.take_decided().len());
+        let response = handle(
+            &request("POST", &approve, "operator"),
+            &tokens,
+            &service(approvals.clone(), "data", Links::default()),
+        );
+        assert_eq!("404 Not Found", response.status);
+    }
+
+    #[test]
+    fn roles_tests() {
+        let tokens = tokens();
+        let approvals = Arc::new(Approvals::in_memory());
+        let approve = format!("/approvals/0x{}/approve", "01".repeat(32));
+        approvals.flag(Event::eth_withdraw_message(H256::from_slice(&[1; 32]), 10));
+
+        let status = |method: &str, path: &str, token: &str| {
+            handle(
+                &request(method, path, token),
+                &tokens,
+                &service(approvals.clone(), "data", Links::default()),
+            )
+            .status
+        };
+        assert_eq!("401 Unauthorized", status("GET", "/approvals", "wrong"));
+        assert_eq!("200 OK", status("GET", "/approvals", "observer"));
+        assert_eq!("403 Forbidden", status("POST", &approve, "observer"));
+        assert_eq!(1, approvals.pending().len());
+        assert_eq!(None, approvals.pending()[0].decision);
+
+        let without_observer = Tokens {
+            observer: None,
+            ..tokens.clone()
+        };
+        let response = handle(
+            &request("GET", "/approvals", "observer"),
+            &without_observer,
+            &service(approvals.clone(), "data", Links::default()),
+        );
+        assert_eq!("401 Unauthorized", response.status);
+    }
+
+    #[test]
+    fn fees_tests() {
+        let dir = TestDir::new("admin_api");
+        let data_dir = dir.path();
+        let message_id = H256::from_slice(&[1; 32]);
+        fees::FeeLog::open(data_dir).record(
+            message_id,
+            crate::submissions::Chain::Ethereum,
+            "approveTransfer",
+            H256::zero(),
+            21_000.into(),
+            Some(1.into()),
+        );
+        let tokens = tokens();
+        let approvals = Arc::new(Approvals::in_memory());
+        let get = |path: &str| {
+            handle(
+                &request("GET", path, "observer"),
+                &tokens,
+                &service(approvals.clone(), data_dir, Links::default()),
+            )
+        };
+
+        let response = get("/fees.csv");
+        assert_eq!("text/csv", response.content_type);
+        assert!(response.body.ends_with(",21000,0,1\n"));
+        let response = get(&format!("/fees/{:?}", message_id));
+        assert_eq!("200 OK", response.status);
+        assert!(response.body.contains("\"transactions\":1"));
+        let response = get(&format!("/fees/{:?}", H256::zero()));
+        assert_eq!("404 Not Found", response.status);
+    }
+
+    #[test]
+    fn messages_tests() {
+        let dir = TestDir::new("admin_api_messages");
+        let data_dir = dir.path();
+        let message_id = H256::from_slice(&[1; 32]);
+        submissions::SubmissionLog::open(data_dir).record(
+            message_id,
+            submissions::Chain::Ethereum,
+            "approveTransfer",
+            H256::from_slice(&[2; 32]),
+        );
+        let links = Links {
+            eth_explorer_url: Some("https://etherscan.io".to_string()),
+            ..Links::default()
+        };
+
+        let response = handle(
+            &request("GET", &format!("/messages/{:?}", message_id), "observer"),
+            &tokens(),
+            &service(Arc::new(Approvals::in_memory()), data_dir, links),
+        );
+        assert_eq!("200 OK", response.status);
+        let message: Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(
+            json!(format!("https://etherscan.io/tx/0x{}", "02".repeat(32))),
+            message["submissions"][0]["link"]
+        );
+        assert_eq!(json!([]), message["attestations"]);
+    }
+
+    #[test]
+    fn transfers_tests() {
+        let get = |query: &[(&str, &str)]| {
+            let mut request = request("GET", "/transfers", "observer");
+            request.query = query
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect();
+            handle(
+                &request,
+                &tokens(),
+                &service(Arc::new(Approvals::in_memory()), "data", Links::default()),
+            )
+        };
+        let response = get(&[("address", "0x01"), ("since", "10")]);
+        assert_eq!("200 OK", response.status);
+        assert_eq!("[]", response.body);
+        assert_eq!("400 Bad Request", get(&[("to_block", "soon")]).status);
+    }
+
+    #[test]
+    fn operations_tests() {
+        let status = |path: &str, token: &str| {
+            handle(
+                &request("POST", path, token),
+                &tokens(),
+                &service(Arc::new(Approvals::in_memory()), "data", Links::default()),
+            )
+            .status
+        };
+        let transfer = format!(
+            "/operations/erc20-transfer/0x{}/0x{}/1000",
+            "01".repeat(20),
+            "02".repeat(20)
+        );
+        assert_eq!("403 Forbidden", status(&transfer, "observer"));
+        assert_eq!(
+            "400 Bad Request",
+            status("/operations/erc20-transfer/0x01", "operator")
+        );
+        assert_eq!("400 Bad Request", status("/operations/mint", "operator"));
+        assert_eq!("404 Not Found", status("/operations", "operator"));
+    }
+
+    #[test]
+    fn debug_state_tests() {
+        let tokens = tokens();
+        let approvals = Arc::new(Approvals::in_memory());
+        debug_state::publish("admin_api_test", serde_json::json!({ "next_nonce": 7 }));
+
+        let response = handle(
+            &request("GET", "/debug/state", "observer"),
+            &tokens,
+            &service(approvals.clone(), "data", Links::default()),
+        );
+        assert_eq!("200 OK", response.status);
+        let state: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(7, state["admin_api_test"]["next_nonce"]);
+    }
+}
@@ -0,0 +1,290 @@
+use ethabi::token::{LenientTokenizer, Tokenizer};
+use lazy_static::lazy_static;
+use serde::Serialize;
+use web3::types::{H160, H256, U256};
+
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::config::Config;
+use crate::ethereum_transactions;
+use crate::executor;
+
+/// Operational contract calls that are not driven by bridge events. Operators
+/// send them through the admin API, the executor signs them with the
+/// validator key at its next nonce and the receipt watcher follows them like
+/// the calls of event handlers, so no separate script holds the same key.
+#[derive(Debug, PartialEq)]
+pub enum AdminOperation {
+    /// Transfers ERC-20 tokens held by the validator account, e.g. accumulated dust.
+    Erc20Transfer { token: H160, to: H160, amount: U256 },
+    /// Calls a token bridge method; `args` are parsed against the method's ABI.
+    BridgeCall { function: String, args: Vec<String> },
+}
+
+/// The transaction an operation was sent with. Its outcomes and submissions
+/// are recorded with `tx_hash` as the message id, so `GET /messages/<tx_hash>`
+/// follows it through gas price bumps.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Submitted {
+    pub tx_hash: H256,
+    pub nonce: U256,
+}
+
+/// Why the executor did not send an operation.
+#[derive(Debug, PartialEq)]
+pub enum Refused {
+    /// The operation can not be encoded, or is not allowed in this mode.
+    Invalid(String),
+    /// The validator can not send it now, e.g. while its nodes are behind.
+    Unavailable(String),
+}
+
+struct Request {
+    operation: AdminOperation,
+    reply: Sender<Result<Submitted, Refused>>,
+}
+
+lazy_static! {
+    static ref EXECUTOR: Mutex<Option<Sender<Request>>> = Mutex::new(None);
+}
+
+/// `erc20-transfer <token> <to> <amount>` or `bridge-call <method> [args...]`,
+/// as the admin API takes them.
+pub fn parse(kind: &str, args: &[String]) -> Result<AdminOperation, String> {
+    match kind {
+        "erc20-transfer" => match args {
+            [token, to, amount] => Ok(AdminOperation::Erc20Transfer {
+                token: parse_address(token)?,
+                to: parse_address(to)?,
+                amount: U256::from_dec_str(amount)
+                    .map_err(|_| format!("invalid amount {:?}", amount))?,
+            }),
+            _ => Err("erc20-transfer takes <token> <to> <amount>".to_string()),
+        },
+        "bridge-call" => match args.split_first() {
+            Some((function, args)) => Ok(AdminOperation::BridgeCall {
+                function: function.clone(),
+                args: args.to_vec(),
+            }),
+            None => Err("bridge-call takes <method> [args...]".to_string()),
+        },
+        _ => Err(format!(
+            "unknown operation {:?}, expected erc20-transfer or bridge-call",
+            kind
+        )),
+    }
+}
+
+fn parse_address(value: &str) -> Result<H160, String> {
+    value
+        .trim_start_matches("0x")
+        .parse()
+        .map_err(|_| format!("invalid address {:?}", value))
+}
+
+impl AdminOperation {
+    /// The contract, the method and the call data of the operation.
+    pub fn call(&self, config: &Config) -> Result<(H160, String, Vec<u8>), String> {
+        match self {
+            AdminOperation::Erc20Transfer { token, to, amount } => {
+                let data = ethereum_transactions::build_transaction_data(
+                    &executor::get_erc20_abi(),
+                    "transfer",
+                    (*to, *amount),
+                )
+                .map_err(|e| e.to_string())?;
+                Ok((*token, "transfer".to_string(), data))
+            }
+            AdminOperation::BridgeCall { function, args } => {
+                let data = build_bridge_call_data(&config.token_bridge.abi, function, args)?;
+                Ok((config.token_bridge.address, function.clone(), data))
+            }
+        }
+    }
+}
+
+/// Hands the operations operators request to `execute`, one at a time, until
+/// `stop` is called. The executor serves them once it is connected.
+pub fn serve<F>(execute: F) -> thread::JoinHandle<()>
+where
+    F: Fn(&AdminOperation) -> Result<Submitted, Refused> + Send + 'static,
+{
+    let (sender, requests) = channel::<Request>();
+    *EXECUTOR.lock().expect("admin operations lock poisoned") = Some(sender);
+    thread::Builder::new()
+        .name("admin_operations".to_string())
+        .spawn(move || {
+            for request in requests {
+                let result = execute(&request.operation);
+                let _ = request.reply.send(result);
+            }
+        })
+        .expect("can not start admin_operations")
+}
+
+/// Stops serving once the operations in progress are done.
+pub fn stop() {
+    EXECUTOR
+        .lock()
+        .expect("admin operations lock poisoned")
+        .take();
+}
+
+/// Sends the operation through the executor and waits until the node
+/// accepted its transaction.
+pub fn execute(operation: AdminOperation) -> Result<Submitted, Refused> {
+    let unavailable = || Refused::Unavailable("the executor is not running".to_string());
+    let executor = EXECUTOR
+        .lock()
+        .expect("admin operations lock poisoned")
+        .clone()
+        .ok_or_else(unavailable)?;
+    let (reply, result) = channel();
+    executor
+        .send(Request { operation, reply })
+        .map_err(|_| unavailable())?;
+    result.recv().map_err(|_| unavailable())?
+}
+
+fn build_bridge_call_data(
+    abi: &ethabi::Contract,
+    function: &str,
+    args: &[String],
+) -> Result<Vec<u8>, String> {
+    let function = abi
+        .function(function)
+        .map_err(|_| format!("token bridge has no method {:?}", function))?;
+    if function.inputs.len() != args.len() {
+        return Err(format!(
+            "{} expects {} arguments, got {}",
+            function.name,
+            function.inputs.len(),
+            args.len()
+        ));
+    }
+    let tokens = function
+        .inputs
+        .iter()
+        .zip(args)
+        .map(|(param, arg)| {
+            let invalid = || {
+                format!(
+                    "can not parse {:?} as {} for {}",
+                    arg, param.kind, param.name
+                )
+            };
+            // the tokenizer takes hex without 0x, but numbers in decimal
+            let arg = match param.kind {
+                ethabi::ParamType::Address
+                | ethabi::ParamType::Bytes
+                | ethabi::ParamType::FixedBytes(_) => arg.trim_start_matches("0x").to_string(),
+                ethabi::ParamType::Uint(_) if arg.starts_with("0x") => {
+                    arg[2..].parse::<U256>().map_err(|_| invalid())?.to_string()
+                }
+                ethabi::ParamType::Int(_) if arg.starts_with("0x") => {
+                    return Err(format!("{} takes a decimal {}", param.name, param.kind))
+                }
+                _ => arg.clone(),
+            };
+            LenientTokenizer::tokenize(&param.kind, &arg).map_err(|_| invalid())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    function
+        .encode_input(&tokens)
+        .map_err(|e| format!("can not encode {} call: {:?}", function.name, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::get_contract_abi;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_tests() {
+        let token = "0x813a4658007ed3c7b31f02009e8699bef8539cd8";
+        let to = "0xc6870aed119d01ce3f7a377775bd489839c51815";
+        assert_eq!(
+            Ok(AdminOperation::Erc20Transfer {
+                token: token[2..].parse().unwrap(),
+                to: to[2..].parse().unwrap(),
+                amount: U256::from(1000),
+            }),
+            parse("erc20-transfer", &args(&[token, to, "1000"]))
+        );
+        assert_eq!(
+            Ok(AdminOperation::BridgeCall {
+                function: "pauseBridge".to_string(),
+                args: vec![],
+            }),
+            parse("bridge-call", &args(&["pauseBridge"]))
+        );
+        assert!(parse("erc20-transfer", &args(&[token, to])).is_err());
+        assert!(parse("erc20-transfer", &args(&[token, to, "0x10"])).is_err());
+        assert!(parse("bridge-call", &[]).is_err());
+        assert!(parse("selfdestruct", &[]).is_err());
+    }
+
+    #[test]
+    fn build_bridge_call_data_tests() {
+        let abi = get_contract_abi();
+        let sender = format!("0x{}", "ab".repeat(32));
+        let data = build_bridge_call_data(&abi, "setPausedStatusForGuestAddress", &[sender])
+            .expect("valid call");
+        assert_eq!(4 + 32, data.len());
+        assert_eq!(vec![0xab; 32], data[4..].to_vec());
+
+        assert!(build_bridge_call_data(&abi, "pauseBridge", &["1".to_string()]).is_err());
+        assert!(build_bridge_call_data(&abi, "noSuchMethod", &[]).is_err());
+        assert!(build_bridge_call_data(
+            &abi,
+            "setPausedStatusForGuestAddress",
+            &["xyz".to_string()]
+        )
+        .is_err());
+
+        // a 0x amount is hex, not the decimal digits after the prefix
+        let guest = format!("0x{}", "cd".repeat(32));
+        let amount = |amount: &str| {
+            build_bridge_call_data(&abi, "setTransfer", &[amount.to_string(), guest.clone()])
+                .map(|data| U256::from_big_endian(&data[4..36]))
+        };
+        assert_eq!(Ok(U256::from(16)), amount("0x10"));
+        assert_eq!(Ok(U256::from(10)), amount("10"));
+        assert!(amount("0xzz").is_err());
+    }
+
+    #[test]
+    fn execute_test() {
+        let operation = AdminOperation::BridgeCall {
+            function: "pauseBridge".to_string(),
+            args: vec![],
+        };
+        let submitted = Submitted {
+            tx_hash: H256::from_slice(&[1; 32]),
+            nonce: 7.into(),
+        };
+        let expected = submitted.clone();
+        let server = serve(move |operation| match operation {
+            AdminOperation::BridgeCall { .. } => Ok(submitted.clone()),
+            _ => Err(Refused::Invalid("not a bridge call".to_string())),
+        });
+        assert_eq!(Ok(expected), execute(operation));
+        stop();
+        server.join().unwrap();
+        assert_eq!(
+            Err(Refused::Unavailable(
+                "the executor is not running".to_string()
+            )),
+            execute(AdminOperation::BridgeCall {
+                function: "pauseBridge".to_string(),
+                args: vec![],
+            })
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use log;
+use serde_json::{json, Value};
+use web3::types::H256;
+
+use std::sync::Arc;
+
+use crate::admin_operations::{self, AdminOperation, Refused, Submitted};
+use crate::approvals::{Approvals, Decision, PendingApproval};
+use crate::attestations;
+#[cfg(feature = "sqlite")]
+use crate::database::{self, MessageFilter, MessageRow};
+use crate::debug_state;
+use crate::fees::{self, MessageFees, Report};
+use crate::links::Links;
+use crate::message_trace;
+use crate::submissions;
+use crate::transfer_index::{Filter, Transfer, TransferIndex};
+
+/// Why an operation was refused, each transport answers with its own status.
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    Invalid(String),
+    NotFound(String),
+    /// What the validator recorded could not be read.
+    Internal(String),
+    /// The validator can not do it now, e.g. while its nodes are behind.
+    Unavailable(String),
+}
+
+/// What operators can do, shared by the HTTP admin API and the gRPC service
+/// so both answer the same. Callers check the role before they call.
+pub struct AdminService {
+    approvals: Arc<Approvals>,
+    data_dir: String,
+    links: Links,
+    transfers: Arc<TransferIndex>,
+}
+
+pub fn parse_message_id(value: &str) -> Result<H256, Error> {
+    value
+        .trim_start_matches("0x")
+        .parse()
+        .map_err(|_| Error::Invalid("invalid message id".to_string()))
+}
+
+impl AdminService {
+    pub fn new(
+        approvals: Arc<Approvals>,
+        data_dir: String,
+        links: Links,
+        transfers: Arc<TransferIndex>,
+    ) -> Self {
+        AdminService {
+            approvals,
+            data_dir,
+            links,
+            transfers,
+        }
+    }
+
+    pub fn approvals(&self) -> Vec<PendingApproval> {
+        self.approvals.pending()
+    }
+
+    pub fn decide(&self, message_id: &H256, decision: Decision) -> Result<(), Error> {
+        self.approvals
+            .decide(message_id, decision)
+            .map_err(Error::NotFound)?;
+        log::info!("operator decided {:?} for {:?}", decision, message_id);
+        Ok(())
+    }
+
+    pub fn fees(&self) -> Result<Report, Error> {
+        fees::report(&self.data_dir).map_err(Error::Internal)
+    }
+
+    pub fn message_fees(&self, message_id: &H256) -> Result<MessageFees, Error> {
+        self.fees()?
+            .messages
+            .into_iter()
+            .find(|fees| fees.message_id == *message_id)
+            .ok_or_else(|| Error::NotFound("no fees recorded for the message".to_string()))
+    }
+
+    /// The submissions and attestations recorded for the message, each with
+    /// a link to where it can be looked at.
+    pub fn message(&self, message_id: &H256) -> Result<Value, Error> {
+        let submissions = submissions::find(&self.data_dir, message_id)
+            .map_err(Error::Internal)?
+            .into_iter()
+            .map(|submission| {
+                let mut value = serde_json::to_value(&submission).expect("submission serializes");
+                value["link"] = json!(self.links.tx(submission.chain, &submission.tx_hash));
+                value
+            })
+            .collect::<Vec<_>>();
+        let attestations = attestations::find(&self.data_dir, message_id)
+            .map_err(Error::Internal)?
+            .into_iter()
+            .map(|attestation| {
+                let mut value = serde_json::to_value(&attestation).expect("attestation serializes");
+                value["entity_link"] = json!(attestation
+                    .entity
+                    .as_ref()
+                    .and_then(|entity| self.links.entity(entity)));
+                value
+            })
+            .collect::<Vec<_>>();
+        Ok(json!({
+            "message_id": message_id,
+            "submissions": submissions,
+            "attestations": attestations,
+        }))
+    }
+
+    pub fn transfers(&self, filter: &Filter) -> Result<Vec<Transfer>, Error> {
+        self.transfers.find(filter).map_err(Error::Internal)
+    }
+
+    #[cfg(feature = "sqlite")]
+    pub fn indexed_messages(&self, filter: &MessageFilter) -> Result<Vec<MessageRow>, Error> {
+        database::shared(&self.data_dir)
+            .and_then(|database| {
+                database
+                    .lock()
+                    .expect("database lock poisoned")
+                    .messages(filter)
+            })
+            .map_err(Error::Internal)
+    }
+
+    /// The controller queues, graph node offsets, nonce and pending
+    /// submissions, for bug reports.
+    pub fn state(&self) -> Value {
+        debug_state::dump()
+    }
+
+    pub fn traced(&self) -> Vec<H256> {
+        message_trace::traced()
+    }
+
+    pub fn start_trace(&self, message_id: H256) {
+        message_trace::start(message_id)
+    }
+
+    pub fn stop_trace(&self, message_id: &H256) -> Result<(), Error> {
+        if message_trace::stop(message_id) {
+            Ok(())
+        } else {
+            Err(Error::NotFound("the message is not traced".to_string()))
+        }
+    }
+
+    /// Sends the operation through the executor, waiting until the node
+    /// accepted its transaction.
+    pub fn execute(&self, operation: AdminOperation) -> Result<Submitted, Error> {
+        log::info!("operator requested {:?}", operation);
+        admin_operations::execute(operation).map_err(|refused| match refused {
+            Refused::Invalid(e) => Error::Invalid(e),
+            Refused::Unavailable(e) => Error::Unavailable(e),
+        })
+    }
+}
@@ -0,0 +1,241 @@
+use log;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use web3::types::H256;
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::clock;
+use crate::controller::Event;
+use crate::versioned::{self, Migration};
+
+const FILE_NAME: &str = "approvals.json";
+
+/// How `approvals.json` is upgraded from one version to the next.
+const MIGRATIONS: &[Migration] = &[name_event_fields];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Decision {
+    Approved,
+    Rejected,
+}
+
+/// A transfer event held until an operator decides on it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingApproval {
+    pub event: Event,
+    pub flagged_at: u64,
+    pub decision: Option<Decision>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    pending: BTreeMap<H256, PendingApproval>,
+    /// Messages released by an approval, their events are not flagged again.
+    approved: HashSet<H256>,
+    /// Messages an operator rejected, their events are dropped.
+    rejected: HashSet<H256>,
+}
+
+/// Transfers waiting for manual approval, shared by the controller, which
+/// flags and releases them, and the admin API, where operators decide.
+/// Kept in `<data_dir>/approvals.json` so a restart does not lose them.
+#[derive(Debug)]
+pub struct Approvals {
+    path: Option<PathBuf>,
+    state: Mutex<State>,
+}
+
+impl Approvals {
+    pub fn in_memory() -> Self {
+        Approvals {
+            path: None,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    pub fn open(data_dir: &str) -> Result<Self, String> {
+        let path = Path::new(data_dir).join(FILE_NAME);
+        let state = match fs::read_to_string(&path) {
+            Ok(json) => versioned::from_str(&json, MIGRATIONS)
+                .map_err(|e| format!("can not parse {:?}: {}", path, e))?,
+            Err(_) => State::default(),
+        };
+        Ok(Approvals {
+            path: Some(path),
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Holds the event for a decision. Events seen again, e.g. after a
+    /// restart, keep the entry they already have.
+    pub fn flag(&self, event: Event) {
+        let mut state = self.state.lock().expect("approvals lock poisoned");
+        let message_id = *event.message_id();
+        if state.rejected.contains(&message_id) {
+            log::info!("dropping event of rejected message: {:?}", event);
+            return;
+        }
+        if state.pending.contains_key(&message_id) {
+            return;
+        }
+        state.pending.insert(
+            message_id,
+            PendingApproval {
+                event,
+                flagged_at: clock::now(),
+                decision: None,
+            },
+        );
+        self.save(&state);
+    }
+
+    #[cfg(feature = "admin-api")]
+    pub fn pending(&self) -> Vec<PendingApproval> {
+        let state = self.state.lock().expect("approvals lock poisoned");
+        state.pending.values().cloned().collect()
+    }
+
+    #[cfg(feature = "admin-api")]
+    pub fn decide(&self, message_id: &H256, decision: Decision) -> Result<(), String> {
+        let mut state = self.state.lock().expect("approvals lock poisoned");
+        match state.pending.get_mut(message_id) {
+            Some(pending) => pending.decision = Some(decision),
+            None => return Err(format!("{:?} is not waiting for approval", message_id)),
+        }
+        self.save(&state);
+        Ok(())
+    }
+
+    /// Removes the events operators have decided on. Decisions are
+    /// remembered, so later events of an approved message pass and those of
+    /// a rejected one are dropped.
+    pub fn take_decided(&self) -> Vec<(Event, Decision)> {
+        let mut state = self.state.lock().expect("approvals lock poisoned");
+        let decided = state
+            .pending
+            .iter()
+            .filter_map(|(message_id, pending)| pending.decision.map(|_| *message_id))
+            .collect::<Vec<_>>();
+        if decided.is_empty() {
+            return vec![];
+        }
+        let decided = decided
+            .into_iter()
+            .filter_map(|message_id| state.pending.remove(&message_id))
+            .filter_map(|pending| pending.decision.map(|decision| (pending.event, decision)))
+            .collect::<Vec<_>>();
+        for (event, decision) in &decided {
+            match decision {
+                Decision::Approved => state.approved.insert(*event.message_id()),
+                Decision::Rejected => state.rejected.insert(*event.message_id()),
+            };
+        }
+        self.save(&state);
+        decided
+    }
+
+    pub fn is_approved(&self, message_id: &H256) -> bool {
+        let state = self.state.lock().expect("approvals lock poisoned");
+        state.approved.contains(message_id)
+    }
+
+    fn save(&self, state: &State) {
+        if let Some(path) = &self.path {
+            let json = versioned::to_string(state, MIGRATIONS).expect("approvals serialize");
+            if let Err(e) = fs::write(path, json) {
+                log::error!("can not save approvals to {:?}: {}", path, e);
+            }
+        }
+    }
+}
+
+/// Version 2 named the fields of events.
+fn name_event_fields(mut state: Value) -> Result<Value, String> {
+    if let Some(pending) = state.get_mut("pending").and_then(Value::as_object_mut) {
+        for approval in pending.values_mut() {
+            if let Some(event) = approval.get_mut("event") {
+                *event = versioned::name_event_fields(event.take())?;
+            }
+        }
+    }
+    Ok(state)
+}
+
+#[cfg(all(test, feature = "admin-api"))]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use web3::types::H160;
+
+    #[test]
+    fn decide_and_take_test() {
+        let dir = TestDir::new("approvals");
+        let data_dir = dir.path();
+        let approved = Event::eth_withdraw_message(H256::from_slice(&[1; 32]), 10);
+        let rejected = Event::eth_withdraw_message(H256::from_slice(&[2; 32]), 10);
+
+        let approvals = Approvals::open(data_dir).unwrap();
+        approvals.flag(approved.clone());
+        approvals.flag(rejected.clone());
+        assert!(approvals
+            .decide(&H256::from_slice(&[3; 32]), Decision::Approved)
+            .is_err());
+        approvals
+            .decide(approved.message_id(), Decision::Approved)
+            .unwrap();
+
+        // decisions survive a restart
+        let approvals = Approvals::open(data_dir).unwrap();
+        approvals
+            .decide(rejected.message_id(), Decision::Rejected)
+            .unwrap();
+        assert_eq!(
+            vec![
+                (approved.clone(), Decision::Approved),
+                (rejected.clone(), Decision::Rejected)
+            ],
+            approvals.take_decided()
+        );
+        assert!(approvals.pending().is_empty());
+        assert!(approvals.is_approved(approved.message_id()));
+        assert!(!approvals.is_approved(rejected.message_id()));
+        approvals.flag(rejected);
+        assert!(approvals.pending().is_empty());
+    }
+
+    #[test]
+    fn open_v1_test() {
+        let dir = TestDir::new("approvals_v1");
+        let v1 = include_str!("../res/fixtures/approvals_v1.json");
+        fs::write(dir.join(FILE_NAME), v1).unwrap();
+        let data_dir = dir.path();
+
+        let approvals = Approvals::open(data_dir).unwrap();
+        let relay = Event::eth_relay_message(
+            H256::from_slice(&[5; 32]),
+            H160::from_slice(&[0xab; 20]),
+            H256::from_slice(&[2; 32]),
+            100.into(),
+            None,
+            11,
+        );
+        assert_eq!(relay, approvals.pending()[0].event);
+        assert!(approvals.is_approved(&H256::from_slice(&[0x13; 32])));
+        // saved in the latest version, which opens as is
+        approvals
+            .decide(&H256::from_slice(&[5; 32]), Decision::Rejected)
+            .unwrap();
+        let saved = fs::read_to_string(dir.join(FILE_NAME)).unwrap();
+        let saved: Value = serde_json::from_str(&saved).unwrap();
+        assert_eq!(
+            MIGRATIONS.len() + 1,
+            saved["version"].as_u64().unwrap() as usize
+        );
+        let approvals = Approvals::open(data_dir).unwrap();
+        assert_eq!(2, approvals.take_decided().len());
+    }
+}
@@ -0,0 +1,544 @@
+#[cfg(feature = "sqlite")]
+use log;
+use primitives::{hashing, sr25519, Pair};
+use rustc_hex::{FromHex, ToHex};
+use serde::{Deserialize, Serialize};
+use web3::types::{H160, H256, U256};
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use crate::controller::{Entity, Event, GuestLimits, Outcome};
+#[cfg(feature = "sqlite")]
+use crate::database::{self, Shared};
+use crate::jsonl::{self, JsonLog};
+use crate::secret::Secret;
+use crate::submissions::Chain;
+use crate::substrate_transactions::get_sr25519_pair;
+
+pub const FILE_NAME: &str = "attestations.jsonl";
+/// Starts every payload, so its hash can not be taken for that of anything
+/// else the key signs. The trailing number is the version of the encoding in
+/// `payload_bytes`, a change to it takes a new one.
+const PAYLOAD_DOMAIN: &[u8] = b"validator/attestation-payload/v1";
+
+/// A decision of this validator on a message, signed with its Substrate key,
+/// so it can be checked against the validator's account without trusting
+/// anything else the validator wrote.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attestation {
+    pub message_id: H256,
+    pub action: String,
+    /// blake2-256 of the event encoded by `payload_bytes`. Attestations from
+    /// before it hash the event as JSON.
+    pub payload_hash: H256,
+    pub attested_at: i64,
+    /// sr25519 public key, i.e. the validator's Substrate account, as hex.
+    pub signer: String,
+    /// sr25519 signature of `signed_bytes`, as hex.
+    pub signature: String,
+    /// The subgraph entity the event was converted from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entity: Option<Entity>,
+}
+
+impl Attestation {
+    /// Message id, payload hash and the big-endian attestation time, then
+    /// the action and, if there is one, `/<entity kind>:<entity id>` as UTF-8.
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.message_id.as_bytes().to_vec();
+        bytes.extend_from_slice(self.payload_hash.as_bytes());
+        bytes.extend_from_slice(&self.attested_at.to_be_bytes());
+        bytes.extend_from_slice(self.action.as_bytes());
+        if let Some(entity) = &self.entity {
+            bytes.extend_from_slice(format!("/{}:{}", entity.kind, entity.id).as_bytes());
+        }
+        bytes
+    }
+
+    pub fn verify(&self) -> bool {
+        let (signer, signature) = match (parse_hex(&self.signer), parse_hex(&self.signature)) {
+            (Some(signer), Some(signature)) if signer.len() == 32 && signature.len() == 64 => {
+                (signer, signature)
+            }
+            _ => return false,
+        };
+        let mut public = [0; 32];
+        public.copy_from_slice(&signer);
+        let mut raw = [0; 64];
+        raw.copy_from_slice(&signature);
+        sr25519::Pair::verify(
+            &sr25519::Signature::from_raw(raw),
+            self.signed_bytes(),
+            &sr25519::Public::from_raw(public),
+        )
+    }
+}
+
+fn parse_hex(value: &str) -> Option<Vec<u8>> {
+    value.trim_start_matches("0x").from_hex().ok()
+}
+
+pub fn payload_hash(event: &Event) -> H256 {
+    H256::from(hashing::blake2_256(&payload_bytes(event)))
+}
+
+/// `PAYLOAD_DOMAIN`, the event name and then its fields in declaration order.
+/// Hashes and addresses are their bytes, amounts 32 big-endian bytes, block
+/// numbers 16 and timestamps 8. Options start with a 0 or 1 byte, strings and
+/// lists with their length as 4 big-endian bytes.
+fn payload_bytes(event: &Event) -> Vec<u8> {
+    let mut payload = Payload(PAYLOAD_DOMAIN.to_vec());
+    payload.text(event.name());
+    match event {
+        Event::EthBridgePausedMessage {
+            message_id,
+            block_number,
+        }
+        | Event::EthBridgeResumedMessage {
+            message_id,
+            block_number,
+        }
+        | Event::EthWithdrawMessage {
+            message_id,
+            block_number,
+        }
+        | Event::SubRelayMessage {
+            message_id,
+            block_number,
+        } => payload.hash(message_id).block(*block_number),
+        Event::EthBridgeStartedMessage {
+            message_id,
+            sender,
+            block_number,
+        }
+        | Event::EthBridgeStoppedMessage {
+            message_id,
+            sender,
+            block_number,
+        } => payload
+            .hash(message_id)
+            .address(sender.as_ref())
+            .block(*block_number),
+        Event::EthRelayMessage {
+            message_id,
+            sender,
+            recipient,
+            amount,
+            token,
+            block_number,
+        } => payload
+            .hash(message_id)
+            .bytes(sender.as_bytes())
+            .hash(recipient)
+            .amount(amount)
+            .address(token.as_ref())
+            .block(*block_number),
+        Event::EthApprovedRelayMessage {
+            message_id,
+            sender,
+            recipient,
+            amount,
+            block_number,
+        } => payload
+            .hash(message_id)
+            .bytes(sender.as_bytes())
+            .hash(recipient)
+            .amount(amount)
+            .block(*block_number),
+        Event::EthRevertMessage {
+            message_id,
+            sender,
+            amount,
+            block_number,
+        } => payload
+            .hash(message_id)
+            .bytes(sender.as_bytes())
+            .amount(amount)
+            .block(*block_number),
+        Event::EthHostAccountPausedMessage {
+            message_id,
+            account,
+            timestamp,
+            block_number,
+        }
+        | Event::EthHostAccountResumedMessage {
+            message_id,
+            account,
+            timestamp,
+            block_number,
+        } => payload
+            .hash(message_id)
+            .bytes(account.as_bytes())
+            .timestamp(*timestamp)
+            .block(*block_number),
+        Event::EthGuestAccountPausedMessage {
+            message_id,
+            account,
+            timestamp,
+            block_number,
+        }
+        | Event::EthGuestAccountResumedMessage {
+            message_id,
+            account,
+            timestamp,
+            block_number,
+        } => payload
+            .hash(message_id)
+            .hash(account)
+            .timestamp(*timestamp)
+            .block(*block_number),
+        Event::EthSetNewLimits {
+            message_id,
+            limits,
+            block_number,
+        } => payload
+            .hash(message_id)
+            .amount(&limits.min_host_transaction_value)
+            .amount(&limits.max_host_transaction_value)
+            .amount(&limits.day_host_max_limit)
+            .amount(&limits.day_host_max_limit_for_one_address)
+            .amount(&limits.max_host_pending_transaction_limit)
+            .limits(&limits.guest)
+            .block(*block_number),
+        Event::EthValidatorsListMessage {
+            message_id,
+            validators,
+            quorum,
+            block_number,
+        } => {
+            payload.hash(message_id).length(validators.len());
+            for validator in validators {
+                payload.hash(validator);
+            }
+            payload.amount(quorum).block(*block_number)
+        }
+        Event::SubApprovedRelayMessage {
+            message_id,
+            sender,
+            recipient,
+            amount,
+            token_id,
+            block_number,
+        }
+        | Event::SubBurnedMessage {
+            message_id,
+            sender,
+            recipient,
+            amount,
+            token_id,
+            block_number,
+        } => payload
+            .hash(message_id)
+            .hash(sender)
+            .bytes(recipient.as_bytes())
+            .amount(amount)
+            .amount(token_id)
+            .block(*block_number),
+        Event::SubMintedMessage {
+            message_id,
+            token_id,
+            block_number,
+        }
+        | Event::SubCancellationConfirmedMessage {
+            message_id,
+            token_id,
+            block_number,
+        } => payload
+            .hash(message_id)
+            .amount(token_id)
+            .block(*block_number),
+        Event::SubAccountPausedMessage {
+            message_id,
+            account,
+            timestamp,
+            token_id,
+            block_number,
+        }
+        | Event::SubAccountResumedMessage {
+            message_id,
+            account,
+            timestamp,
+            token_id,
+            block_number,
+        } => payload
+            .hash(message_id)
+            .hash(account)
+            .timestamp(*timestamp)
+            .amount(token_id)
+            .block(*block_number),
+        Event::SubSetNewLimits {
+            message_id,
+            limits,
+            block_number,
+        } => payload.hash(message_id).limits(limits).block(*block_number),
+        Event::Unknown {
+            message_id,
+            origin,
+            payload: raw,
+            block_number,
+        } => payload
+            .hash(message_id)
+            .text(origin)
+            .text(raw)
+            .block(*block_number),
+        Event::Outcome {
+            message_id,
+            outcome,
+        } => payload.hash(message_id).outcome(outcome),
+    };
+    payload.0
+}
+
+struct Payload(Vec<u8>);
+
+impl Payload {
+    fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.0.extend_from_slice(bytes);
+        self
+    }
+
+    fn hash(&mut self, hash: &H256) -> &mut Self {
+        self.bytes(hash.as_bytes())
+    }
+
+    fn address(&mut self, address: Option<&H160>) -> &mut Self {
+        match address {
+            Some(address) => self.bytes(&[1]).bytes(address.as_bytes()),
+            None => self.bytes(&[0]),
+        }
+    }
+
+    fn amount(&mut self, amount: &U256) -> &mut Self {
+        let mut bytes = [0; 32];
+        amount.to_big_endian(&mut bytes);
+        self.bytes(&bytes)
+    }
+
+    fn block(&mut self, block_number: u128) -> &mut Self {
+        self.bytes(&block_number.to_be_bytes())
+    }
+
+    fn timestamp(&mut self, timestamp: u64) -> &mut Self {
+        self.bytes(&timestamp.to_be_bytes())
+    }
+
+    fn length(&mut self, length: usize) -> &mut Self {
+        self.bytes(&(length as u32).to_be_bytes())
+    }
+
+    fn text(&mut self, text: &str) -> &mut Self {
+        self.length(text.len()).bytes(text.as_bytes())
+    }
+
+    fn limits(&mut self, limits: &GuestLimits) -> &mut Self {
+        self.amount(&limits.min_transaction_value)
+            .amount(&limits.max_transaction_value)
+            .amount(&limits.day_max_limit)
+            .amount(&limits.day_max_limit_for_one_address)
+            .amount(&limits.max_pending_transaction_limit)
+    }
+
+    /// The outcome's name, the chain, the call and then the transaction hash
+    /// or the reason it failed.
+    fn outcome(&mut self, outcome: &Outcome) -> &mut Self {
+        let (name, chain, call) = match outcome {
+            Outcome::Submitted(chain, call, _) => ("submitted", chain, call),
+            Outcome::Confirmed(chain, call, _) => ("confirmed", chain, call),
+            Outcome::Failed(chain, call, _) => ("failed", chain, call),
+        };
+        let chain = match chain {
+            Chain::Ethereum => "ethereum",
+            Chain::Substrate => "substrate",
+        };
+        self.text(name).text(chain).text(call);
+        match outcome {
+            Outcome::Submitted(_, _, hash) | Outcome::Confirmed(_, _, hash) => self.hash(hash),
+            Outcome::Failed(_, _, reason) => self.text(reason),
+        }
+    }
+}
+
+/// Append-only record of attestations in `<data_dir>/attestations.jsonl`.
+/// A follower has no key and attests nothing.
+#[derive(Debug)]
+pub struct AttestationLog {
+    log: Option<JsonLog>,
+    #[cfg(feature = "sqlite")]
+    database: Option<Shared>,
+    pair: Option<Secret<sr25519::Pair>>,
+}
+
+impl AttestationLog {
+    pub fn open(data_dir: &str, mnemonic_phrase: &Secret<String>) -> Self {
+        let pair = match mnemonic_phrase.expose().as_str() {
+            "" => None,
+            phrase => Some(Secret::new(get_sr25519_pair(phrase))),
+        };
+        AttestationLog {
+            log: pair
+                .as_ref()
+                .map(|_| JsonLog::open(data_dir, FILE_NAME, "decisions are not attested")),
+            #[cfg(feature = "sqlite")]
+            database: pair.as_ref().and_then(|_| database::shared(data_dir).ok()),
+            pair,
+        }
+    }
+
+    /// Signs and records that `action` was decided for the event.
+    pub fn attest(&self, event: &Event, entity: Option<&Entity>, action: &str) {
+        let pair = match &self.pair {
+            Some(pair) => pair.expose(),
+            None => return,
+        };
+        let mut attestation = Attestation {
+            message_id: *event.message_id(),
+            action: action.to_string(),
+            payload_hash: payload_hash(event),
+            attested_at: time::now_utc().to_timespec().sec,
+            signer: format!("0x{}", pair.public().0.to_hex::<String>()),
+            signature: String::new(),
+            entity: entity.cloned(),
+        };
+        let signature = pair.sign(&attestation.signed_bytes());
+        attestation.signature = format!("0x{}", signature.0[..].to_hex::<String>());
+        if let Some(log) = &self.log {
+            log.append(&attestation);
+        }
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = &self.database {
+            let recorded = database
+                .lock()
+                .expect("database lock poisoned")
+                .record_attestation(&attestation);
+            if let Err(e) = recorded {
+                log::error!("{}", e);
+            }
+        }
+    }
+}
+
+/// Returns the attestations for the message, oldest first. Read from
+/// `attestations.jsonl` when there is no database.
+#[cfg(feature = "admin-api")]
+pub fn find(data_dir: &str, message_id: &H256) -> Result<Vec<Attestation>, String> {
+    #[cfg(feature = "sqlite")]
+    {
+        if let Ok(database) = database::shared(data_dir) {
+            return database
+                .lock()
+                .expect("database lock poisoned")
+                .attestations(message_id);
+        }
+    }
+    Ok(read(data_dir)?
+        .into_iter()
+        .filter(|attestation| attestation.message_id == *message_id)
+        .collect())
+}
+
+/// Every attestation in `attestations.jsonl`, oldest first.
+pub fn read(data_dir: &str) -> Result<Vec<Attestation>, String> {
+    jsonl::read(data_dir, FILE_NAME)
+}
+
+/// Checks every attestation in `path`. Returns how many were checked and the
+/// lines that are malformed or not signed by their signer.
+pub fn verify_file(path: &str) -> Result<(usize, Vec<String>), String> {
+    let file = File::open(path).map_err(|e| format!("can not open {:?}: {}", path, e))?;
+    let mut checked = 0;
+    let mut invalid = vec![];
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| format!("can not read {:?}: {}", path, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        checked += 1;
+        match serde_json::from_str::<Attestation>(&line) {
+            Ok(attestation) if attestation.verify() => (),
+            _ => invalid.push(line),
+        }
+    }
+    Ok((checked, invalid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use std::fs;
+
+    #[test]
+    fn attest_and_verify_test() {
+        let dir = TestDir::new("attestations");
+        let data_dir = dir.path();
+        let (pair, phrase, _) = sr25519::Pair::generate_with_phrase(None);
+        let log = AttestationLog::open(data_dir, &Secret::new(phrase));
+        let event = Event::eth_withdraw_message(H256::from_slice(&[1; 32]), 1);
+        let entity = Entity::new("message", &format!("{:?}", event.message_id()));
+        log.attest(&event, Some(&entity), "forward");
+        log.attest(&event, None, "timelock");
+
+        let path = dir.join(FILE_NAME);
+        let path = path.to_str().unwrap();
+        assert_eq!(Ok((2, vec![])), verify_file(path));
+        let content = fs::read_to_string(path).unwrap();
+        let attestation: Attestation =
+            serde_json::from_str(content.lines().next().unwrap()).unwrap();
+        assert_eq!(
+            format!("0x{}", pair.public().0.to_hex::<String>()),
+            attestation.signer
+        );
+        let forged = Attestation {
+            action: "reject".to_string(),
+            ..attestation
+        };
+        assert!(!forged.verify());
+
+        // a follower has no key
+        let follower = AttestationLog::open(data_dir, &Secret::new(String::new()));
+        follower.attest(&event, None, "forward");
+        assert_eq!(Ok((2, vec![])), verify_file(path));
+        #[cfg(feature = "admin-api")]
+        {
+            let found = find(data_dir, event.message_id()).unwrap();
+            assert_eq!(Some(entity), found[0].entity);
+            assert_eq!(None, found[1].entity);
+        }
+    }
+
+    #[test]
+    fn payload_hash_test() {
+        let relay = |token| {
+            Event::eth_relay_message(
+                H256::from_slice(&[1; 32]),
+                H160::from_slice(&[0xab; 20]),
+                H256::from_slice(&[2; 32]),
+                U256::from(100),
+                token,
+                7,
+            )
+        };
+        let mut expected = PAYLOAD_DOMAIN.to_vec();
+        expected.extend_from_slice(&15u32.to_be_bytes());
+        expected.extend_from_slice(b"EthRelayMessage");
+        expected.extend_from_slice(&[1; 32]);
+        expected.extend_from_slice(&[0xab; 20]);
+        expected.extend_from_slice(&[2; 32]);
+        expected.extend_from_slice(&[0; 31]);
+        expected.push(100);
+        expected.push(1);
+        expected.extend_from_slice(&[0xcd; 20]);
+        expected.extend_from_slice(&7u128.to_be_bytes());
+        let event = relay(Some(H160::from_slice(&[0xcd; 20])));
+        assert_eq!(expected, payload_bytes(&event));
+        assert_eq!(
+            "7251e65b4828e10517bd968df7e3be4a5130713ba8f887461226c511824472e1",
+            payload_hash(&event).as_bytes().to_hex::<String>()
+        );
+        assert_eq!(
+            "f6d1ce99e05a87ec92403d0903bd1a1eca026e1011f68f2d5e365559a8d387ed",
+            payload_hash(&relay(None)).as_bytes().to_hex::<String>()
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::controller::Outcome;
+use crate::submissions::Chain;
+
+/// Failure reasons that mean a chain rejects this validator rather than one
+/// call: the token bridge reverts calls of accounts that are not validators
+/// with a reason naming it, which the receipt watcher replays the call for,
+/// the Bridge pallet answers them with `BadOrigin`. Other reverts are left
+/// out, a call on a message other validators already finished reverts as well.
+const AUTH_FAILURES: [&str; 6] = [
+    "badorigin",
+    "not a validator",
+    "unauthorized",
+    "bad signature",
+    "invalid signature",
+    "badproof",
+];
+
+/// Set once the guard trips, for the executor to refuse calls however they
+/// reach it.
+static HALTED: AtomicBool = AtomicBool::new(false);
+
+/// Stops all further calls until the validator is restarted.
+pub fn halt() {
+    HALTED.store(true, Ordering::SeqCst);
+}
+
+pub fn is_halted() -> bool {
+    HALTED.load(Ordering::SeqCst)
+}
+
+/// Counts consecutive authorization failures per chain and trips after
+/// `limit` of them, so a validator that was removed from the set or runs with
+/// the wrong key stops burning gas. A confirmed call resets its chain's count.
+#[derive(Debug)]
+pub struct AuthGuard {
+    limit: u32,
+    consecutive: HashMap<Chain, u32>,
+}
+
+impl AuthGuard {
+    /// A `limit` of 0 never trips.
+    pub fn new(limit: u32) -> Self {
+        AuthGuard {
+            limit,
+            consecutive: HashMap::new(),
+        }
+    }
+
+    /// Returns the chain whose count reaches the limit with this outcome.
+    pub fn observe(&mut self, outcome: &Outcome) -> Option<Chain> {
+        match outcome {
+            Outcome::Confirmed(chain, _, _) => {
+                self.consecutive.remove(chain);
+                None
+            }
+            Outcome::Failed(chain, _, reason) if is_auth_failure(reason) => {
+                let count = self.consecutive.entry(*chain).or_insert(0);
+                *count += 1;
+                if self.limit > 0 && *count == self.limit {
+                    Some(*chain)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+fn is_auth_failure(reason: &str) -> bool {
+    let reason = reason.to_lowercase();
+    AUTH_FAILURES.iter().any(|failure| reason.contains(failure))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use web3::types::H256;
+
+    #[test]
+    fn observe_tests() {
+        let mut guard = AuthGuard::new(2);
+        let unauthorized = Outcome::Failed(
+            Chain::Ethereum,
+            "confirmTransfer".to_string(),
+            "confirmTransfer(0x01) reverted: not a validator".to_string(),
+        );
+        let reverted = Outcome::Failed(
+            Chain::Ethereum,
+            "confirmTransfer".to_string(),
+            "confirmTransfer(0x01) reverted".to_string(),
+        );
+        let bad_origin = Outcome::Failed(
+            Chain::Substrate,
+            "approve_transfer".to_string(),
+            "extrinsic failed: BadOrigin".to_string(),
+        );
+        let timeout = Outcome::Failed(
+            Chain::Ethereum,
+            "confirmTransfer".to_string(),
+            "can not get nonce".to_string(),
+        );
+        let confirmed =
+            Outcome::Confirmed(Chain::Ethereum, "confirmTransfer".to_string(), H256::zero());
+        assert_eq!(None, guard.observe(&unauthorized));
+        assert_eq!(None, guard.observe(&timeout));
+        assert_eq!(None, guard.observe(&confirmed));
+        assert_eq!(None, guard.observe(&unauthorized));
+        assert_eq!(None, guard.observe(&bad_origin));
+        assert_eq!(None, guard.observe(&reverted));
+        assert_eq!(None, guard.observe(&reverted));
+        assert_eq!(Some(Chain::Ethereum), guard.observe(&unauthorized));
+        assert_eq!(Some(Chain::Substrate), guard.observe(&bad_origin));
+        assert_eq!(None, AuthGuard::new(0).observe(&unauthorized));
+    }
+}
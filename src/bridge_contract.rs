@@ -0,0 +1,107 @@
+use web3::{
+    contract::{tokens::Detokenize, tokens::Tokenize, Contract, Error, Options},
+    futures::Future,
+    types::{H160, H256, U256},
+    Transport, Web3,
+};
+
+use crate::config::TokenBridge;
+
+/// Votes the token bridge has collected for one multi-signed operation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Votes {
+    pub count: U256,
+    /// Bit `i` is set if validator `i` has voted.
+    pub mask: U256,
+}
+
+impl Votes {
+    pub fn has_voted(&self, validator_index: usize) -> bool {
+        validator_index < 256 && self.mask.bit(validator_index)
+    }
+}
+
+/// Typed reads of the token bridge state, so features that look at the
+/// contract share one place that knows its ABI instead of each calling
+/// `query` with raw method names.
+///
+/// The bridge contract ABI exposes the validator set, the quorum and the
+/// votes per operation. Messages, limits and the bridge status live in
+/// contracts it does not expose.
+#[derive(Debug)]
+pub struct BridgeContract<T: Transport> {
+    contract: Contract<T>,
+}
+
+impl<T: Transport> BridgeContract<T> {
+    /// Reads through the ABI configured for `token_bridge`.
+    pub fn new(web3: &Web3<T>, token_bridge: &TokenBridge) -> Self {
+        BridgeContract {
+            contract: Contract::new(web3.eth(), token_bridge.address, token_bridge.abi.clone()),
+        }
+    }
+
+    pub fn validators(&self) -> Result<Vec<H160>, Error> {
+        let count: U256 = self.query("validatorsCount", ())?;
+        (0..count.low_u64())
+            .map(|index| self.query("validators", (U256::from(index),)))
+            .collect()
+    }
+
+    pub fn is_validator(&self, address: H160) -> Result<bool, Error> {
+        self.query("isExistValidator", (address,))
+    }
+
+    /// Number of validator votes an operation needs.
+    pub fn quorum(&self) -> Result<U256, Error> {
+        self.query("howManyValidatorsDecide", ())
+    }
+
+    /// Increases every time the validator set changes.
+    pub fn validators_generation(&self) -> Result<U256, Error> {
+        self.query("validatorsGeneration", ())
+    }
+
+    /// `operation` is the keccak hash of the calldata the validators vote with.
+    pub fn votes(&self, operation: H256) -> Result<Votes, Error> {
+        Ok(Votes {
+            count: self.query("votesCountByOperation", (operation,))?,
+            mask: self.query("votesMaskByOperation", (operation,))?,
+        })
+    }
+
+    /// Multi-signed operations that are still collecting votes.
+    pub fn pending_operations(&self) -> Result<Vec<H256>, Error> {
+        let count: U256 = self.query("allOperationsCount", ())?;
+        (0..count.low_u64())
+            .map(|index| self.query("allOperations", (U256::from(index),)))
+            .collect()
+    }
+
+    fn query<R, P>(&self, function: &str, params: P) -> Result<R, Error>
+    where
+        R: Detokenize,
+        P: Tokenize,
+    {
+        self.contract
+            .query(function, params, None, Options::default(), None)
+            .wait()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn has_voted_test() {
+        let votes = Votes {
+            count: U256::from(2),
+            mask: U256::from(0b101),
+        };
+        assert!(votes.has_voted(0));
+        assert!(!votes.has_voted(1));
+        assert!(votes.has_voted(2));
+        assert!(!votes.has_voted(300));
+    }
+}
@@ -0,0 +1,190 @@
+use log;
+
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+
+use crate::controller::{Envelope, Event, EventType};
+use crate::fatal;
+use crate::metrics;
+
+/// What an envelope on the bus is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Topic {
+    /// Transfer stages the listeners saw on either chain.
+    Transfers,
+    /// Bridge status, account pauses, limits and validator list changes.
+    Management,
+    /// Messages the listeners could not convert.
+    Unknown,
+    /// What became of the executor's calls.
+    Outcomes,
+    /// Events the controller let through to the executor.
+    Dispatched,
+}
+
+/// Everything that flows into the controller.
+pub const OBSERVED: [Topic; 4] = [
+    Topic::Transfers,
+    Topic::Management,
+    Topic::Unknown,
+    Topic::Outcomes,
+];
+
+impl Topic {
+    /// The topic an event is published on when it enters the validator.
+    pub fn of(event: &Event) -> Topic {
+        match event {
+            Event::Unknown { .. } => Topic::Unknown,
+            Event::Outcome { .. } => Topic::Outcomes,
+            Event::EthRevertMessage { .. } | Event::SubCancellationConfirmedMessage { .. } => {
+                Topic::Transfers
+            }
+            event if event.event_type() == EventType::Transfer => Topic::Transfers,
+            _ => Topic::Management,
+        }
+    }
+}
+
+/// Broadcasts envelopes to every subscriber of their topic, so components
+/// that observe the same events subscribe instead of being handed a clone of
+/// someone else's sender.
+#[derive(Debug, Default)]
+pub struct Bus {
+    subscribers: Mutex<HashMap<Topic, Vec<Subscriber>>>,
+}
+
+#[derive(Debug)]
+struct Subscriber {
+    sender: Sender<Envelope>,
+    /// The validator can not run without it, see `subscribe_essential`.
+    essential: bool,
+}
+
+impl Bus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// One receiver for all of `topics`, which keeps the order in which each
+    /// publisher published across them. Subscribe before the publishers start.
+    pub fn subscribe(&self, topics: &[Topic]) -> Receiver<Envelope> {
+        self.add_subscriber(topics, false)
+    }
+
+    /// Like `subscribe`, for the components that handle the events, e.g. the
+    /// controller and the executor. Once such a subscriber is gone nobody
+    /// handles its topics any more, so the validator stops instead of
+    /// publishing into the void. Observers like the transfer index do not
+    /// keep it alive.
+    pub fn subscribe_essential(&self, topics: &[Topic]) -> Receiver<Envelope> {
+        self.add_subscriber(topics, true)
+    }
+
+    fn add_subscriber(&self, topics: &[Topic], essential: bool) -> Receiver<Envelope> {
+        let (tx, rx) = channel();
+        let mut subscribers = self.subscribers.lock().expect("bus lock poisoned");
+        for topic in topics {
+            subscribers.entry(*topic).or_default().push(Subscriber {
+                sender: tx.clone(),
+                essential,
+            });
+        }
+        rx
+    }
+
+    /// Publishes an event on its own topic.
+    pub fn publish(&self, envelope: Envelope) {
+        self.publish_to(Topic::of(&envelope.event), envelope)
+    }
+
+    /// Subscribers whose receiver is gone are dropped and counted. The
+    /// validator stops once an essential one is gone, after the bus is
+    /// unlocked.
+    pub fn publish_to(&self, topic: Topic, envelope: Envelope) {
+        let _context = fatal::message_context(*envelope.event.message_id());
+        log::debug!("publishing on {:?}: {:?}", topic, envelope.event);
+        if let Err(e) = self.send(topic, &envelope) {
+            fatal::stop(&e);
+        }
+    }
+
+    /// Sends to every subscriber of `topic`, an error if an essential one is
+    /// gone.
+    fn send(&self, topic: Topic, envelope: &Envelope) -> Result<(), String> {
+        let mut subscribers = self.subscribers.lock().expect("bus lock poisoned");
+        let subscribers = match subscribers.get_mut(&topic) {
+            Some(subscribers) => subscribers,
+            None => return Ok(()),
+        };
+        let name = format!("{:?}", topic);
+        let mut lost_essential = false;
+        subscribers.retain(|subscriber| {
+            let sent = subscriber.sender.send(envelope.clone()).is_ok();
+            if !sent {
+                log::error!("a subscriber of {} is gone, dropping it", name);
+                metrics::increment("validator_bus_send_failures_total", &[("topic", &name)]);
+                lost_essential |= subscriber.essential;
+            }
+            sent
+        });
+        if lost_essential {
+            return Err(format!("the handler of {} is gone", name));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::Source;
+    use web3::types::H256;
+
+    #[test]
+    fn publish_tests() {
+        let bus = Bus::new();
+        let controller = bus.subscribe_essential(&OBSERVED);
+        let executor = bus.subscribe_essential(&[Topic::Dispatched]);
+        let audit = bus.subscribe(&[Topic::Transfers]);
+        drop(audit);
+
+        let message_id = H256::from_slice(&[1; 32]);
+        let withdraw = Event::eth_withdraw_message(message_id, 1);
+        let paused = Event::eth_bridge_paused_message(message_id, 2);
+        bus.publish(Envelope::new(withdraw.clone(), Source::GraphNode));
+        bus.publish(Envelope::new(paused.clone(), Source::GraphNode));
+        bus.publish_to(
+            Topic::Dispatched,
+            Envelope::new(withdraw.clone(), Source::Replay),
+        );
+
+        let received = controller.try_iter().map(|e| e.event).collect::<Vec<_>>();
+        assert_eq!(vec![withdraw.clone(), paused], received);
+        assert_eq!(withdraw, executor.try_recv().unwrap().event);
+        let subscribers = bus.subscribers.lock().unwrap();
+        assert_eq!(1, subscribers[&Topic::Transfers].len());
+    }
+
+    #[test]
+    fn send_tests() {
+        let bus = Bus::new();
+        let controller = bus.subscribe_essential(&OBSERVED);
+        let transfer_index = bus.subscribe(&[Topic::Transfers, Topic::Outcomes]);
+        let envelope = Envelope::new(
+            Event::eth_withdraw_message(H256::zero(), 1),
+            Source::GraphNode,
+        );
+        // an observer alone does not keep the validator running
+        drop(controller);
+        assert!(bus.send(Topic::Transfers, &envelope).is_err());
+        assert_eq!(1, transfer_index.try_iter().count());
+
+        let bus = Bus::new();
+        let _controller = bus.subscribe_essential(&OBSERVED);
+        drop(bus.subscribe(&[Topic::Transfers]));
+        assert_eq!(Ok(()), bus.send(Topic::Transfers, &envelope));
+        // nobody subscribed
+        assert_eq!(Ok(()), bus.send(Topic::Dispatched, &envelope));
+    }
+}
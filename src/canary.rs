@@ -0,0 +1,270 @@
+use graphql_client::{GraphQLQuery, Response};
+use log;
+use web3::{
+    futures::Future,
+    transports::WebSocket,
+    types::{BlockNumber, Bytes, H160, H256, U256},
+};
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::ethereum_transactions;
+use crate::executor;
+use crate::metrics;
+use crate::net;
+use crate::secret::Secret;
+use crate::substrate_transactions;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A tiny transfer to the validator's own substrate account, sent through
+/// the bridge every `interval` from a dedicated Ethereum account, so the
+/// canary never competes with the validator for nonces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canary {
+    pub private_key: Secret<String>,
+    pub address: H160,
+    pub token: H160,
+    pub amount: U256,
+    pub interval: Duration,
+    pub sla: Duration,
+}
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "res/graph_node_schema.graphql",
+    query_path = "res/graph_node_canary_messages.graphql",
+    response_derives = "Debug,Clone"
+)]
+struct CanaryMessages;
+
+use canary_messages::{CanaryMessagesMessages as Message, Status};
+
+/// Checks the whole pipeline, from the deposit on Ethereum through the
+/// graph node, the validators and Substrate back to the confirmation on
+/// Ethereum, and alerts when a canary transfer misses its SLA.
+pub fn spawn(config: Config, canary: Canary) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("canary".to_string())
+        .spawn(move || loop {
+            let started = Instant::now();
+            match run(&config, &canary) {
+                Ok(message_id) => {
+                    let elapsed = started.elapsed();
+                    log::info!("canary transfer {} completed in {:?}", message_id, elapsed);
+                    metrics::observe_seconds(
+                        "validator_canary_seconds",
+                        &[],
+                        elapsed.as_secs_f64(),
+                    );
+                    metrics::set_gauge("validator_canary_ok", &[], 1.0);
+                }
+                Err(e) => {
+                    log::error!("canary transfer failed: {}", e);
+                    metrics::set_gauge("validator_canary_ok", &[], 0.0);
+                }
+            }
+            thread::sleep(
+                canary
+                    .interval
+                    .checked_sub(started.elapsed())
+                    .unwrap_or_default(),
+            );
+        })
+        .expect("can not started canary")
+}
+
+/// Sends one canary transfer and follows it until it completes, returning
+/// its message id.
+fn run(config: &Config, canary: &Canary) -> Result<String, String> {
+    let deadline = Instant::now() + canary.sla;
+    let (_eloop, transport) = WebSocket::new(&config.eth_api_url)
+        .map_err(|e| format!("can not connect to {}: {:?}", config.eth_api_url, e))?;
+    let web3 = web3::Web3::new(transport);
+    let account =
+        substrate_transactions::get_account_id(config.sub_validator_mnemonic_phrase.expose());
+    let recipient: &[u8] = account.as_ref();
+
+    let approve = ethereum_transactions::build_transaction_data(
+        &executor::get_erc20_abi(),
+        "approve",
+        (config.token_bridge.address, canary.amount),
+    )
+    .map_err(|e| e.to_string())?;
+    let deposit = ethereum_transactions::build_transaction_data(
+        &config.token_bridge.abi,
+        "setTransfer",
+        (canary.amount, H256::from_slice(recipient)),
+    )
+    .map_err(|e| e.to_string())?;
+    let nonce = web3
+        .eth()
+        .transaction_count(canary.address, Some(BlockNumber::Pending))
+        .wait()
+        .map_err(|e| format!("can not get nonce: {:?}", e))?;
+    send(&web3, config, canary, canary.token, nonce, approve)?;
+    let tx_hash = send(
+        &web3,
+        config,
+        canary,
+        config.token_bridge.address,
+        nonce + 1,
+        deposit,
+    )?;
+    let block_number = wait_for_receipt(&web3, config, tx_hash, deadline)?;
+    log::info!(
+        "canary deposit {:?} of {} mined in block {}",
+        tx_hash,
+        canary.amount,
+        block_number
+    );
+
+    let mut message_id = None;
+    loop {
+        match query_messages(config, canary.address, block_number) {
+            Ok(messages) => {
+                if let Some(message) = find(&messages, canary.amount, message_id.as_ref()) {
+                    message_id = Some(message.id.clone());
+                    match message.status {
+                        Status::CONFIRMED => return Ok(message.id.clone()),
+                        Status::CANCELED => {
+                            return Err(format!("message {} was canceled", message.id))
+                        }
+                        _ => log::debug!("canary message {} is {:?}", message.id, message.status),
+                    }
+                }
+            }
+            Err(e) => log::warn!("can not query canary messages: {}", e),
+        }
+        if Instant::now() >= deadline {
+            return Err(match message_id {
+                Some(message_id) => format!(
+                    "message {} did not complete within {:?}",
+                    message_id, canary.sla
+                ),
+                None => format!(
+                    "deposit {:?} did not reach the graph node within {:?}",
+                    tx_hash, canary.sla
+                ),
+            });
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn send(
+    web3: &web3::Web3<WebSocket>,
+    config: &Config,
+    canary: &Canary,
+    to: H160,
+    nonce: U256,
+    data: Vec<u8>,
+) -> Result<H256, String> {
+    let tx = ethereum_transactions::build(
+        canary.private_key.clone(),
+        to,
+        nonce,
+        0,
+        config.eth_gas_price,
+        config.eth_gas,
+        data,
+        config.eth_chain_id,
+    );
+    web3.eth()
+        .send_raw_transaction(Bytes::from(tx))
+        .wait()
+        .map_err(|e| format!("can not send canary transaction to {:?}: {:?}", to, e))
+}
+
+fn wait_for_receipt(
+    web3: &web3::Web3<WebSocket>,
+    config: &Config,
+    tx_hash: H256,
+    deadline: Instant,
+) -> Result<u64, String> {
+    while Instant::now() < deadline {
+        let receipt = web3
+            .eth()
+            .transaction_receipt(tx_hash)
+            .wait()
+            .map_err(|e| format!("can not get receipt of {:?}: {:?}", tx_hash, e))?;
+        match receipt {
+            Some(receipt) if receipt.status == Some(0u64.into()) => {
+                return Err(format!("deposit {:?} reverted", tx_hash))
+            }
+            Some(receipt) => {
+                if let Some(block_number) = receipt.block_number {
+                    return Ok(block_number.low_u64());
+                }
+            }
+            None => (),
+        }
+        thread::sleep(config.host_chain.block_time);
+    }
+    Err(format!("deposit {:?} was not mined in time", tx_hash))
+}
+
+fn query_messages(
+    config: &Config,
+    address: H160,
+    block_number: u64,
+) -> Result<Vec<Message>, String> {
+    let request_body = CanaryMessages::build_query(canary_messages::Variables {
+        eth_address: format!("{:?}", address),
+        block_number: block_number as i64,
+    });
+    let response: Response<canary_messages::ResponseData> = net::http_client()
+        .post(&config.graph_node_api_url)
+        .json(&request_body)
+        .send()
+        .and_then(|mut response| response.json())
+        .map_err(|e| e.to_string())?;
+    response
+        .data
+        .map(|data| data.messages)
+        .ok_or_else(|| format!("no data in response, errors: {:?}", response.errors))
+}
+
+/// The canary's message: the one already followed, or else the first with
+/// the canary amount.
+fn find<'a>(messages: &'a [Message], amount: U256, id: Option<&String>) -> Option<&'a Message> {
+    messages.iter().find(|message| match id {
+        Some(id) => message.id == *id,
+        None => U256::from_dec_str(&message.amount) == Ok(amount),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: &str, amount: &str, status: Status) -> Message {
+        Message {
+            id: id.to_string(),
+            amount: amount.to_string(),
+            status,
+            eth_block_number: "10".to_string(),
+        }
+    }
+
+    #[test]
+    fn find_tests() {
+        let messages = vec![
+            message("0x01", "500", Status::PENDING),
+            message("0x02", "1", Status::APPROVED),
+        ];
+        let amount = U256::from(1);
+        assert_eq!(
+            Some("0x02"),
+            find(&messages, amount, None).map(|m| m.id.as_str())
+        );
+        let followed = "0x01".to_string();
+        assert_eq!(
+            Some("0x01"),
+            find(&messages, amount, Some(&followed)).map(|m| m.id.as_str())
+        );
+        assert!(find(&messages, U256::from(2), None).is_none());
+    }
+}
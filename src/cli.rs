@@ -0,0 +1,161 @@
+use web3::types::H256;
+
+use std::env;
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Runs the validator, the default when no command is given.
+    Run,
+    /// Prints the transactions this validator sent for a message.
+    Submissions(H256),
+    /// Prints the bridge state of both chains as JSON.
+    Snapshot,
+    /// Checks the signatures in an attestation log, needs no configuration.
+    VerifyAttestations(String),
+    /// Asks the running validator to finish in-flight work and hold new
+    /// transfers, then waits until it is safe to stop.
+    Drain,
+    /// Prunes what the retention allows while the validator is stopped, the
+    /// days override `RETENTION_AUDIT_DAYS`.
+    Prune { audit_days: Option<u64> },
+    /// Bootstraps the embedded indexer's store and the database from the
+    /// subgraph, for validators upgraded from the graph node listener.
+    Migrate,
+}
+
+impl Default for Command {
+    fn default() -> Self {
+        Command::Run
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    pub profile: Option<String>,
+    pub command: Command,
+}
+
+impl Args {
+    pub fn from_env() -> Result<Self, String> {
+        let mut args = Self::parse(env::args().skip(1))?;
+        if args.profile.is_none() {
+            args.profile = env::var("VALIDATOR_PROFILE").ok();
+        }
+        Ok(args)
+    }
+
+    pub fn parse<I>(args: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--profile" {
+                parsed.profile = Some(args.next().ok_or("--profile requires a value")?);
+            } else if arg.starts_with("--profile=") {
+                parsed.profile = Some(arg["--profile=".len()..].to_string());
+            } else if arg == "submissions" {
+                let message_id = args.next().ok_or("usage: submissions <message_id>")?;
+                parsed.command = Command::Submissions(
+                    message_id
+                        .trim_start_matches("0x")
+                        .parse()
+                        .map_err(|_| format!("invalid message id {:?}", message_id))?,
+                );
+            } else if arg == "verify-attestations" {
+                let path = args.next().ok_or("usage: verify-attestations <path>")?;
+                parsed.command = Command::VerifyAttestations(path);
+            } else if arg == "snapshot" {
+                parsed.command = Command::Snapshot;
+            } else if arg == "drain" {
+                parsed.command = Command::Drain;
+            } else if arg == "migrate" {
+                parsed.command = Command::Migrate;
+            } else if arg == "prune" {
+                parsed.command = parse_prune(args.by_ref().collect())?;
+            } else {
+                return Err(format!("unknown argument {:?}", arg));
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// `prune [--audit-days <days>]`.
+fn parse_prune(args: Vec<String>) -> Result<Command, String> {
+    const USAGE: &str = "usage: prune [--audit-days <days>]";
+    let mut audit_days = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg != "--audit-days" {
+            return Err(USAGE.to_string());
+        }
+        let value = args.next().ok_or(USAGE)?;
+        audit_days = Some(
+            value
+                .parse()
+                .map_err(|_| format!("{} must be a number of days, got {:?}", arg, value))?,
+        );
+    }
+    Ok(Command::Prune { audit_days })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parse_profile_tests() {
+        assert_eq!(Ok(Args::default()), parse(&[]));
+        assert_eq!(
+            Some("mainnet".to_string()),
+            parse(&["--profile", "mainnet"]).unwrap().profile
+        );
+        assert_eq!(
+            Some("staging".to_string()),
+            parse(&["--profile=staging"]).unwrap().profile
+        );
+        assert!(parse(&["--profile"]).is_err());
+        assert!(parse(&["--unknown"]).is_err());
+    }
+
+    #[test]
+    fn parse_submissions_test() {
+        let message_id = format!("0x{}", "01".repeat(32));
+        assert_eq!(
+            Command::Submissions(H256::from_slice(&[1; 32])),
+            parse(&["submissions", &message_id]).unwrap().command
+        );
+        assert!(parse(&["submissions"]).is_err());
+        assert!(parse(&["submissions", "0x01"]).is_err());
+        assert_eq!(Command::Drain, parse(&["drain"]).unwrap().command);
+        assert_eq!(Command::Snapshot, parse(&["snapshot"]).unwrap().command);
+        assert_eq!(Command::Migrate, parse(&["migrate"]).unwrap().command);
+        assert_eq!(
+            Command::VerifyAttestations("attestations.jsonl".to_string()),
+            parse(&["verify-attestations", "attestations.jsonl"])
+                .unwrap()
+                .command
+        );
+        assert!(parse(&["verify-attestations"]).is_err());
+    }
+
+    #[test]
+    fn parse_prune_test() {
+        assert_eq!(
+            Command::Prune {
+                audit_days: Some(30)
+            },
+            parse(&["prune", "--audit-days", "30"]).unwrap().command
+        );
+        assert!(parse(&["prune", "--audit-days"]).is_err());
+        assert!(parse(&["prune", "--audit-days", "-1"]).is_err());
+        assert!(parse(&["prune", "30"]).is_err());
+        assert!(parse(&["prune", "--transfer-days", "30"]).is_err());
+    }
+}
@@ -0,0 +1,114 @@
+use log;
+use web3::{
+    futures::Future,
+    transports::WebSocket,
+    types::{BlockId, BlockNumber},
+};
+
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::{thread, time::Duration};
+
+use crate::config::Config;
+use crate::substrate_transactions;
+
+/// Seconds added to the local clock, set when `USE_CHAIN_TIME` is on and the
+/// local clock is too far from the Ethereum one.
+static OFFSET: AtomicI64 = AtomicI64::new(0);
+
+/// Unix time in seconds, corrected towards chain time when that is enabled.
+pub fn now() -> u64 {
+    (local_now() + OFFSET.load(Ordering::Relaxed)) as u64
+}
+
+pub fn begin_of_this_day() -> u64 {
+    const SECONDS_IN_DAY: u64 = 24 * 60 * 60;
+    now() / SECONDS_IN_DAY * SECONDS_IN_DAY
+}
+
+pub fn spawn(config: Config) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("clock".to_string())
+        .spawn(move || loop {
+            thread::sleep(Duration::from_secs(config.clock_check_interval_secs));
+            check(&config);
+        })
+        .expect("can not started clock")
+}
+
+/// Compares the local clock with the latest Ethereum and Substrate block
+/// timestamps. Block timestamps trail real time by up to a block interval,
+/// which `MAX_CLOCK_SKEW_SECS` has to allow for.
+pub fn check(config: &Config) {
+    match get_ethereum_timestamp(&config.eth_api_url) {
+        Ok(timestamp) => {
+            let skew = skew(local_now(), timestamp);
+            let too_far = report("ethereum", skew, config.max_clock_skew_secs);
+            if config.use_chain_time {
+                let offset = if too_far { -skew } else { 0 };
+                if OFFSET.swap(offset, Ordering::Relaxed) != offset {
+                    log::warn!("correcting local clock by {}s", offset);
+                }
+            }
+        }
+        Err(e) => log::warn!("can not get ethereum block timestamp: {}", e),
+    }
+    match substrate_transactions::get_timestamp(config.sub_api_url.clone()) {
+        Ok(timestamp) => {
+            report(
+                "substrate",
+                skew(local_now(), timestamp),
+                config.max_clock_skew_secs,
+            );
+        }
+        Err(e) => log::warn!("can not get substrate block timestamp: {}", e),
+    }
+}
+
+/// Logs the skew and returns whether it exceeds `max_skew`.
+fn report(chain: &str, skew: i64, max_skew: u64) -> bool {
+    if skew.abs() as u64 > max_skew {
+        log::error!(
+            "local clock is {}s {} the latest {} block, day boundaries may be wrong",
+            skew.abs(),
+            if skew > 0 { "ahead of" } else { "behind" },
+            chain
+        );
+        true
+    } else {
+        log::debug!("local clock skew against {}: {}s", chain, skew);
+        false
+    }
+}
+
+fn skew(local: i64, chain: u64) -> i64 {
+    local - chain as i64
+}
+
+fn get_ethereum_timestamp(eth_api_url: &str) -> Result<u64, String> {
+    let (_eloop, transport) = WebSocket::new(eth_api_url).map_err(|e| format!("{:?}", e))?;
+    web3::Web3::new(transport)
+        .eth()
+        .block(BlockId::Number(BlockNumber::Latest))
+        .wait()
+        .map_err(|e| format!("{:?}", e))?
+        .map(|block| block.timestamp.low_u64())
+        .ok_or_else(|| "no latest block".to_string())
+}
+
+fn local_now() -> i64 {
+    time::now_utc().to_timespec().sec
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_tests() {
+        assert_eq!(-30, skew(1_000, 1_030));
+        assert!(!report("ethereum", -30, 60));
+        assert!(!report("ethereum", 60, 60));
+        assert!(report("ethereum", 61, 60));
+        assert!(report("ethereum", -90, 60));
+    }
+}
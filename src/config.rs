@@ -1,117 +1,1380 @@
 use primitives::{crypto::Pair, sr25519};
 use rustc_hex::FromHex;
-use web3::types::Address;
+use serde::Deserialize;
+use url::Url;
+use web3::types::{Address, U256};
 
-use raw_transaction_builder::Bip32ECKeyPair;
+use raw_transaction_builder::{AccessListItem, Bip32ECKeyPair};
 
-use std::env;
+#[cfg(feature = "admin-api")]
+use crate::admin_api::Tokens as AdminApiTokens;
+#[cfg(feature = "graph-node")]
+use crate::canary::Canary;
+use crate::coordination::Coordinator;
+use crate::eth_indexer::Indexer;
+use crate::ethereum_transactions::NonceGap;
+use crate::executor;
+use crate::host_chain::HostChain;
+use crate::net;
+use crate::policy::{self, Kind as PolicyKind, Timelock};
+use crate::retention::Retention;
+use crate::secret::{Backend, Secret};
+#[cfg(feature = "secret-backends")]
+use crate::secret_backend;
+use crate::urgency::Urgency;
+
+use std::{collections::HashMap, env, fmt, fs, net::SocketAddr, str::FromStr, time::Duration};
 
-const DEFAULT_GAS_PRICE: u64 = 24_000_000_000;
 const DEFAULT_GAS: u64 = 5_000_000;
+const DEFAULT_INSTANCE_CHECK_SECS: u64 = 15;
+const DEFAULT_TIMELOCK_SECS: u64 = 1800;
+const DEFAULT_GRAPH_NODE_MAX_LAG_BLOCKS: u64 = 20;
+const DEFAULT_GRAPH_NODE_WATCHDOG_SECS: u64 = 300;
+const DEFAULT_EXECUTOR_WORKERS: usize = 4;
+const DEFAULT_DATA_DIR: &str = "data";
+const DEFAULT_QUEUE_MEMORY_CAP: usize = 10_000;
+const DEFAULT_MAX_CLOCK_SKEW_SECS: u64 = 60;
+const DEFAULT_CLOCK_CHECK_INTERVAL_SECS: u64 = 600;
+const DEFAULT_GAS_RUNWAY_ALERT_HOURS: u64 = 24;
+const DEFAULT_GAS_RUNWAY_WINDOW_SECS: u64 = 6 * 3600;
+const DEFAULT_SUB_SYNC_CHECK_SECS: u64 = 30;
+const DEFAULT_SUB_SYNC_ALERT_SECS: u64 = 600;
+const DEFAULT_ETH_MAX_LAG_BLOCKS: u64 = 5;
+const DEFAULT_ETH_SYNC_CHECK_SECS: u64 = 30;
+const DEFAULT_ETH_SYNC_ALERT_SECS: u64 = 600;
+const DEFAULT_STARTUP_TIMEOUT_SECS: u64 = 120;
+const DEFAULT_INDEXER_BATCH_BLOCKS: u64 = 1000;
+const DEFAULT_INDEXER_POLL_SECS: u64 = 15;
+const DEFAULT_RETENTION_INTERVAL_SECS: u64 = 3600;
+#[cfg(feature = "graph-node")]
+const DEFAULT_CANARY_SLA_SECS: u64 = 3600;
+const DEFAULT_REPLAY_CHUNK_SIZE: usize = 100;
+const DEFAULT_REPLAY_CHUNK_PAUSE_SECS: u64 = 5;
+const DEFAULT_COORDINATION_GRACE_SECS: u64 = 300;
+const DEFAULT_DIVERGENCE_CHECK_SECS: u64 = 600;
+const DEFAULT_DIVERGENCE_SAMPLE_SIZE: usize = 20;
+const DEFAULT_ORPHAN_SWEEP_SECS: u64 = 900;
+const DEFAULT_ORPHAN_MIN_AGE_BLOCKS: u64 = 240;
+const DEFAULT_ORPHAN_MAX_REDRIVES: u32 = 3;
+const DEFAULT_AUTH_FAILURE_LIMIT: u32 = 5;
+#[cfg(feature = "otlp")]
+const DEFAULT_OTLP_SERVICE_NAME: &str = "validator";
+const DEFAULT_UNFINALIZED_STATUSES: &str = "PENDING,WITHDRAW,APPROVED,CANCELED";
+/// Message statuses of the subgraph schema in `res/graph_node_schema.graphql`.
+const KNOWN_STATUSES: [&str; 5] = ["PENDING", "WITHDRAW", "APPROVED", "CANCELED", "CONFIRMED"];
+
+const HTTP_SCHEMES: [&str; 2] = ["http://", "https://"];
+const WS_SCHEMES: [&str; 2] = ["ws://", "wss://"];
+const MNEMONIC_WORD_COUNTS: [usize; 5] = [12, 15, 18, 21, 24];
+
+/// A token bridge contract and the ABI calls to it are encoded with. The
+/// executor, the contract reads and the canary all take both from here.
+#[derive(Clone, Debug)]
+pub struct TokenBridge {
+    pub address: Address,
+    pub abi: ethabi::Contract,
+    /// The ERC20 the contract holds, transfers relayed from Ethereum are
+    /// approved on the contract of their token.
+    pub token: Option<Address>,
+}
+
+/// Stands in while `Config::load` collects problems.
+impl Default for TokenBridge {
+    fn default() -> Self {
+        TokenBridge {
+            address: Address::zero(),
+            abi: executor::get_contract_abi(),
+            token: None,
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct Config {
+    /// Empty when the embedded indexer replaces the graph node.
     pub graph_node_api_url: String,
+    pub graph_node_max_lag_blocks: u64,
+    pub graph_node_hold_on_lag: bool,
+    pub graph_node_watchdog_secs: u64,
+    pub strict_mode: bool,
     pub eth_api_url: String,
+    pub host_chain: HostChain,
     pub eth_validator_address: Address,
-    pub eth_validator_private_key: String,
-    pub token_bridge_address: Address,
+    pub eth_validator_private_key: Secret<String>,
+    /// For tokens that are not listed in `token_bridges`.
+    pub token_bridge: TokenBridge,
+    pub token_bridges: HashMap<U256, TokenBridge>,
     pub eth_gas_price: u64,
     pub eth_gas: u64,
+    pub nonce_gap: NonceGap,
+    pub eth_confirmations: u64,
+    pub eth_chain_id: u64,
+    /// Where the embedded indexer starts a new store, e.g. the block the
+    /// contracts were deployed in. Unset, it starts at the head.
+    pub eth_start_block: Option<u64>,
     pub sub_token_index: u32,
     pub sub_api_url: String,
-    pub sub_validator_mnemonic_phrase: String,
+    pub sub_validator_mnemonic_phrase: Secret<String>,
+    pub unfinalized_statuses: Vec<String>,
+    pub unfinalized_min_block: u64,
+    pub unfinalized_max_age_blocks: u64,
+    pub replay_chunk_size: usize,
+    pub replay_chunk_pause_secs: u64,
+    pub divergence_check_secs: u64,
+    pub divergence_sample_size: usize,
+    pub orphan_sweep_secs: u64,
+    pub orphan_min_age_blocks: u64,
+    pub orphan_max_redrives: u32,
+    pub observe_only: bool,
+    pub observe_only_if_unregistered: bool,
+    /// No keys are configured, the validator only mirrors the bridge state.
+    pub follower: bool,
+    pub force_start: bool,
+    pub auth_failure_limit: u32,
+    pub instance_check_secs: u64,
+    pub executor_workers: usize,
+    pub max_pending_transfers: Option<usize>,
+    pub account_pause_grace_secs: u64,
+    pub data_dir: String,
+    pub queue_memory_cap: usize,
+    #[cfg(feature = "otlp")]
+    pub otlp_endpoint: Option<String>,
+    #[cfg(feature = "otlp")]
+    pub otlp_service_name: String,
+    pub metrics_address: Option<SocketAddr>,
+    pub rate_limits: HashMap<String, u32>,
+    pub call_urgency: HashMap<String, Urgency>,
+    pub access_lists: HashMap<String, Vec<AccessListItem>>,
+    pub policies: Vec<PolicyKind>,
+    pub timelock: Timelock,
+    pub approval_threshold: U256,
+    #[cfg(feature = "admin-api")]
+    pub admin_api_address: Option<SocketAddr>,
+    #[cfg(feature = "admin-api")]
+    pub admin_api_tokens: AdminApiTokens,
+    /// Serves the admin API over gRPC as well, with the same tokens.
+    #[cfg(feature = "grpc")]
+    pub grpc_api_address: Option<SocketAddr>,
+    /// Block explorers the admin API links transactions to.
+    #[cfg(feature = "admin-api")]
+    pub eth_explorer_url: Option<String>,
+    #[cfg(feature = "admin-api")]
+    pub sub_explorer_url: Option<String>,
+    pub max_clock_skew_secs: u64,
+    pub use_chain_time: bool,
+    pub clock_check_interval_secs: u64,
+    pub gas_runway_alert_hours: u64,
+    pub gas_runway_window_secs: u64,
+    pub sub_sync_check_secs: u64,
+    pub sub_sync_alert_secs: u64,
+    /// Another node whose head `ETH_API_URL` is compared against.
+    pub eth_secondary_api_url: Option<String>,
+    pub eth_max_lag_blocks: u64,
+    pub eth_sync_check_secs: u64,
+    pub eth_sync_alert_secs: u64,
+    /// How long each subsystem may take to get ready at startup.
+    pub startup_timeout_secs: u64,
+    #[cfg(feature = "graph-node")]
+    pub canary: Option<Canary>,
+    pub coordinator: Option<Coordinator>,
+    /// Scans Ethereum logs itself instead of querying the graph node.
+    pub indexer: Option<Indexer>,
+    /// How long confirmed transfers and audit entries are kept.
+    pub retention: Retention,
 }
 
-impl Config {
-    pub fn load() -> Result<Self, &'static str> {
-        Ok(Config {
-            graph_node_api_url: parse_graph_node_api_url()?,
-            eth_api_url: parse_eth_api_url()?,
-            eth_validator_address: parse_eth_validator_address()?,
-            eth_validator_private_key: parse_eth_validator_private_key()?,
-            token_bridge_address: parse_token_bridge_address()?,
-            eth_gas_price: parse_eth_gas_price()?,
-            eth_gas: parse_eth_gas()?,
-            sub_token_index: parse_sub_token_index()?,
-            sub_api_url: parse_sub_api_url()?,
-            sub_validator_mnemonic_phrase: parse_sub_validator_mnemonic_phrase()?,
+/// All problems found while loading the config, reported together so
+/// operators can fix their environment in one go.
+#[derive(Debug, PartialEq)]
+pub struct Error {
+    problems: Vec<String>,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "invalid configuration:")?;
+        for problem in &self.problems {
+            writeln!(f, "  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error {
+    fn new() -> Self {
+        Error { problems: vec![] }
+    }
+
+    /// Records the problem, if any, and substitutes a default value so the
+    /// remaining fields can still be checked.
+    fn check<T: Default>(&mut self, result: Result<T, String>) -> T {
+        result.unwrap_or_else(|problem| {
+            self.problems.push(problem);
+            T::default()
         })
     }
 }
 
-fn parse_graph_node_api_url() -> Result<String, &'static str> {
-    env::var("GRAPH_NODE_API_URL").map_err(|_| "can not read GRAPH_NODE_API_URL")
+/// Loads `.env.<profile>` and then the shared `.env`. dotenv never overrides
+/// variables that are already set, so the process environment wins over the
+/// profile file, which wins over the shared file.
+pub fn load_env_files(profile: Option<&str>) -> Result<(), String> {
+    if let Some(profile) = profile {
+        let file_name = format!(".env.{}", profile);
+        dotenv::from_filename(&file_name).map_err(|e| {
+            format!(
+                "can not load profile {:?} from {}: {}",
+                profile, file_name, e
+            )
+        })?;
+    }
+    dotenv::dotenv().ok();
+    Ok(())
 }
 
-fn parse_eth_api_url() -> Result<String, &'static str> {
-    env::var("ETH_API_URL").map_err(|_| "can not read ETH_API_URL")
+impl Config {
+    pub fn load() -> Result<Self, Error> {
+        let mut error = Error::new();
+        // net reads the proxy on its own, as secrets are fetched before the
+        // config exists; this only reports a malformed proxy url
+        error.check(net::Proxy::from_env());
+        let secret_backend = error.check(parse_secret_backend());
+        let follower = error.check(parse_follower());
+        let (eth_validator_private_key, eth_validator_address) = if follower {
+            (Secret::new(String::new()), Address::zero())
+        } else {
+            error.check(parse_eth_validator_private_key(&secret_backend))
+        };
+        let sub_validator_mnemonic_phrase = if follower {
+            Secret::new(String::new())
+        } else {
+            error.check(parse_sub_validator_mnemonic_phrase(&secret_backend))
+        };
+        let host_chain = error.check(parse_host_chain());
+        let strict_mode = error.check(parse_strict_mode());
+        error.check(check_features());
+        #[cfg(feature = "grpc")]
+        let grpc_api_address = error.check(parse_grpc_api_address());
+        #[cfg(all(feature = "admin-api", not(feature = "grpc")))]
+        let grpc_api_address: Option<SocketAddr> = None;
+        #[cfg(feature = "admin-api")]
+        let (admin_api_address, admin_api_tokens) =
+            error.check(parse_admin_api(&secret_backend, grpc_api_address.is_some()));
+        let token_bridge = error.check(parse_token_bridge());
+        let token_bridges = error.check(parse_token_bridges(&token_bridge.abi));
+        let indexer = error.check(parse_indexer());
+        let policies = error.check(parse_policies());
+        let config = Config {
+            graph_node_api_url: error.check(parse_graph_node_api_url(
+                indexer.as_ref().map_or(false, |indexer| !indexer.failover),
+            )),
+            graph_node_max_lag_blocks: error.check(parse_graph_node_max_lag_blocks()),
+            graph_node_hold_on_lag: error.check(parse_graph_node_hold_on_lag()),
+            graph_node_watchdog_secs: error.check(parse_graph_node_watchdog_secs()),
+            strict_mode,
+            eth_api_url: error.check(parse_eth_api_url()),
+            host_chain,
+            eth_validator_address,
+            eth_validator_private_key,
+            token_bridge,
+            token_bridges,
+            eth_gas_price: error.check(parse_eth_gas_price(&host_chain)),
+            eth_gas: error.check(parse_eth_gas()),
+            nonce_gap: error.check(parse_nonce_gap()),
+            eth_confirmations: error.check(parse_eth_confirmations(&host_chain)),
+            eth_chain_id: error.check(parse_eth_chain_id(&host_chain)),
+            eth_start_block: error.check(parse_eth_start_block()),
+            sub_token_index: error.check(parse_sub_token_index()),
+            sub_api_url: error.check(parse_sub_api_url()),
+            sub_validator_mnemonic_phrase,
+            unfinalized_statuses: error.check(parse_unfinalized_statuses(strict_mode)),
+            unfinalized_min_block: error.check(parse_unfinalized_min_block()),
+            unfinalized_max_age_blocks: error.check(parse_unfinalized_max_age_blocks()),
+            replay_chunk_size: error.check(parse_replay_chunk_size()),
+            replay_chunk_pause_secs: error.check(parse_replay_chunk_pause_secs()),
+            divergence_check_secs: error.check(parse_divergence_check_secs()),
+            divergence_sample_size: error.check(parse_divergence_sample_size()),
+            orphan_sweep_secs: error.check(parse_orphan_sweep_secs()),
+            orphan_min_age_blocks: error.check(parse_orphan_min_age_blocks()),
+            orphan_max_redrives: error.check(parse_orphan_max_redrives()),
+            observe_only: follower || error.check(parse_observe_only()),
+            observe_only_if_unregistered: error.check(parse_observe_only_if_unregistered()),
+            follower,
+            force_start: error.check(parse_force_start()),
+            auth_failure_limit: error.check(parse_auth_failure_limit()),
+            instance_check_secs: error.check(parse_instance_check_secs()),
+            executor_workers: error.check(parse_executor_workers()),
+            max_pending_transfers: error.check(parse_max_pending_transfers()),
+            account_pause_grace_secs: error.check(parse_account_pause_grace_secs()),
+            data_dir: error.check(parse_data_dir()),
+            queue_memory_cap: error.check(parse_queue_memory_cap()),
+            #[cfg(feature = "otlp")]
+            otlp_endpoint: error.check(parse_otlp_endpoint()),
+            #[cfg(feature = "otlp")]
+            otlp_service_name: error.check(parse_otlp_service_name()),
+            metrics_address: error.check(parse_metrics_address()),
+            rate_limits: error.check(parse_rate_limits()),
+            call_urgency: error.check(parse_call_urgency()),
+            access_lists: error.check(parse_access_lists()),
+            timelock: error.check(parse_timelock(&policies)),
+            approval_threshold: error.check(parse_threshold(
+                "APPROVAL_THRESHOLD",
+                &policies,
+                "manual_approval",
+            )),
+            policies,
+            #[cfg(feature = "admin-api")]
+            admin_api_address,
+            #[cfg(feature = "admin-api")]
+            admin_api_tokens,
+            #[cfg(feature = "grpc")]
+            grpc_api_address,
+            #[cfg(feature = "admin-api")]
+            eth_explorer_url: error.check(parse_explorer_url("ETH_EXPLORER_URL")),
+            #[cfg(feature = "admin-api")]
+            sub_explorer_url: error.check(parse_explorer_url("SUB_EXPLORER_URL")),
+            max_clock_skew_secs: error.check(parse_max_clock_skew_secs()),
+            use_chain_time: error.check(parse_use_chain_time()),
+            clock_check_interval_secs: error.check(parse_clock_check_interval_secs()),
+            gas_runway_alert_hours: error.check(parse_gas_runway_alert_hours()),
+            gas_runway_window_secs: error.check(parse_gas_runway_window_secs()),
+            sub_sync_check_secs: error.check(parse_sub_sync_check_secs()),
+            sub_sync_alert_secs: error.check(parse_sub_sync_alert_secs()),
+            eth_secondary_api_url: error.check(parse_eth_secondary_api_url()),
+            eth_max_lag_blocks: error.check(parse_eth_max_lag_blocks()),
+            eth_sync_check_secs: error.check(parse_eth_sync_check_secs()),
+            eth_sync_alert_secs: error.check(parse_eth_sync_alert_secs()),
+            startup_timeout_secs: error.check(parse_startup_timeout_secs()),
+            #[cfg(feature = "graph-node")]
+            canary: error.check(parse_canary(&secret_backend, eth_validator_address)),
+            coordinator: error.check(parse_coordinator(eth_validator_address)),
+            indexer,
+            retention: error.check(parse_retention()),
+        };
+
+        if error.problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(error)
+        }
+    }
+
+    /// The graph node listener runs unless the embedded indexer replaces it.
+    #[cfg(feature = "graph-node")]
+    pub fn has_graph_node_listener(&self) -> bool {
+        self.indexer
+            .as_ref()
+            .map_or(true, |indexer| indexer.failover)
+    }
 }
 
-fn parse_eth_validator_address() -> Result<Address, &'static str> {
-    let address =
-        env::var("ETH_VALIDATOR_ADDRESS").map_err(|_| "can not read ETH_VALIDATOR_ADDRESS")?;
-    address[2..]
-        .parse()
-        .map_err(|_| "can not parse validator address")
+/// Optional when the embedded indexer replaces the graph node.
+fn parse_graph_node_api_url(replaced_by_indexer: bool) -> Result<String, String> {
+    if replaced_by_indexer && env::var("GRAPH_NODE_API_URL").is_err() {
+        return Ok(String::new());
+    }
+    parse_url("GRAPH_NODE_API_URL", &HTTP_SCHEMES)
 }
 
-fn parse_eth_validator_private_key() -> Result<String, &'static str> {
-    let private_key = env::var("ETH_VALIDATOR_PRIVATE_KEY")
-        .map_err(|_| "can not read ETH_VALIDATOR_PRIVATE_KEY")?;
-    let private_key = private_key[2..].to_string();
-    try_convert_to_bip32_key_pair(&private_key)?;
+fn parse_graph_node_max_lag_blocks() -> Result<u64, String> {
+    parse_number(
+        "GRAPH_NODE_MAX_LAG_BLOCKS",
+        DEFAULT_GRAPH_NODE_MAX_LAG_BLOCKS,
+    )
+}
 
-    Ok(private_key)
+fn parse_graph_node_hold_on_lag() -> Result<bool, String> {
+    parse_bool("GRAPH_NODE_HOLD_ON_LAG", false)
 }
 
-fn parse_token_bridge_address() -> Result<Address, &'static str> {
-    let address = env::var("TOKEN_BRIDGE_ADDRESS").map_err(|_| "can not read TOKEN_BRIDGE_ADDRESS")?;
-    address[2..]
-        .parse()
-        .map_err(|_| "can not parse contract address")
+fn parse_graph_node_watchdog_secs() -> Result<u64, String> {
+    match parse_number("GRAPH_NODE_WATCHDOG_SECS", DEFAULT_GRAPH_NODE_WATCHDOG_SECS)? {
+        0 => Err("GRAPH_NODE_WATCHDOG_SECS must be at least 1".to_string()),
+        secs => Ok(secs),
+    }
 }
 
-fn parse_eth_gas_price() -> Result<u64, &'static str> {
-    env::var("ETH_GAS_PRICE")
-    .or_else(|_| Ok(DEFAULT_GAS_PRICE.to_string()))
-    .map(|x| x.parse().expect("can not parse ETH_GAS_PRICE"))
+fn parse_eth_api_url() -> Result<String, String> {
+    parse_url("ETH_API_URL", &WS_SCHEMES)
 }
 
-fn parse_eth_gas() -> Result<u64, &'static str> {
-    env::var("ETH_GAS")
-    .or_else(|_| Ok(DEFAULT_GAS.to_string()))
-    .map(|x| x.parse().expect("can not parse ETH_GAS"))
+/// Returns the private key together with the address derived from it, so the
+/// nonce is always looked up for the account that actually signs.
+fn parse_eth_validator_private_key(backend: &Backend) -> Result<(Secret<String>, Address), String> {
+    const NAME: &str = "ETH_VALIDATOR_PRIVATE_KEY";
+
+    let private_key = read_secret(NAME, backend)?;
+    let private_key = check_hex(NAME, &private_key, 32)?.to_string();
+    let address = try_convert_to_bip32_key_pair(&private_key)?.address();
+
+    if env::var("ETH_VALIDATOR_ADDRESS").is_ok() {
+        let configured = parse_address("ETH_VALIDATOR_ADDRESS")?;
+        if configured != address {
+            return Err(format!(
+                "ETH_VALIDATOR_ADDRESS {:?} does not match ETH_VALIDATOR_PRIVATE_KEY address {:?}",
+                configured, address
+            ));
+        }
+        log::warn!(
+            "ETH_VALIDATOR_ADDRESS is derived from ETH_VALIDATOR_PRIVATE_KEY and can be removed"
+        );
+    }
+
+    Ok((Secret::new(private_key), address))
+}
+
+/// `TOKEN_BRIDGE_ADDRESS` with the ABI in `TOKEN_BRIDGE_ABI_FILE`, or the
+/// bundled one.
+fn parse_token_bridge() -> Result<TokenBridge, String> {
+    let address = parse_address("TOKEN_BRIDGE_ADDRESS")?;
+    let abi = match env::var("TOKEN_BRIDGE_ABI_FILE") {
+        Ok(path) => load_abi(&path).map_err(|e| format!("TOKEN_BRIDGE_ABI_FILE: {}", e))?,
+        Err(_) => executor::get_contract_abi(),
+    };
+    Ok(TokenBridge {
+        address,
+        abi,
+        token: None,
+    })
+}
+
+fn load_abi(path: &str) -> Result<ethabi::Contract, String> {
+    fs::File::open(path)
+        .map_err(|e| e.to_string())
+        .and_then(|file| ethabi::Contract::load(file).map_err(|e| e.to_string()))
+        .map_err(|e| format!("can not read ABI {:?}: {}", path, e))
+}
+
+/// `TOKEN_BRIDGES_FILE` maps token ids to their contract and, optionally,
+/// its ABI file and ERC20; tokens that are not listed use
+/// `TOKEN_BRIDGE_ADDRESS`. Entries without an ABI share the one of
+/// `TOKEN_BRIDGE_ADDRESS`.
+fn parse_token_bridges(
+    default_abi: &ethabi::Contract,
+) -> Result<HashMap<U256, TokenBridge>, String> {
+    let path = match env::var("TOKEN_BRIDGES_FILE") {
+        Ok(path) => path,
+        Err(_) => return Ok(HashMap::new()),
+    };
+    let json = fs::read_to_string(&path)
+        .map_err(|e| format!("can not read TOKEN_BRIDGES_FILE {:?}: {}", path, e))?;
+    read_token_bridges(&json, default_abi)
+        .map_err(|e| format!("TOKEN_BRIDGES_FILE {:?}: {}", path, e))
+}
+
+fn read_token_bridges(
+    json: &str,
+    default_abi: &ethabi::Contract,
+) -> Result<HashMap<U256, TokenBridge>, String> {
+    #[derive(Deserialize)]
+    struct Entry {
+        address: Address,
+        abi: Option<String>,
+        token: Option<Address>,
+    }
+
+    let entries: HashMap<String, Entry> =
+        serde_json::from_str(json).map_err(|e| format!("is not valid: {}", e))?;
+    let mut token_bridges = HashMap::new();
+    for (token_id, entry) in entries {
+        let token_id = U256::from_dec_str(&token_id)
+            .map_err(|_| format!("invalid token id {:?}", token_id))?;
+        let abi = match entry.abi {
+            Some(abi) => load_abi(&abi).map_err(|e| format!("token {}: {}", token_id, e))?,
+            None => default_abi.clone(),
+        };
+        token_bridges.insert(
+            token_id,
+            TokenBridge {
+                address: entry.address,
+                abi,
+                token: entry.token,
+            },
+        );
+    }
+    let mut tokens = HashMap::new();
+    for (token_id, token_bridge) in &token_bridges {
+        if let Some(token) = token_bridge.token {
+            if let Some(other) = tokens.insert(token, token_id) {
+                return Err(format!(
+                    "tokens {} and {} both hold {:?}",
+                    other.min(token_id),
+                    other.max(token_id),
+                    token
+                ));
+            }
+        }
+    }
+    Ok(token_bridges)
+}
+
+fn parse_host_chain() -> Result<HostChain, String> {
+    match env::var("HOST_CHAIN") {
+        Ok(name) => name.parse().map_err(|e| format!("HOST_CHAIN: {}", e)),
+        Err(_) => Ok(HostChain::default()),
+    }
+}
+
+fn parse_eth_gas_price(host_chain: &HostChain) -> Result<u64, String> {
+    parse_number("ETH_GAS_PRICE", host_chain.gas_price)
+}
+
+fn parse_eth_gas() -> Result<u64, String> {
+    parse_number("ETH_GAS", DEFAULT_GAS)
+}
+
+fn parse_nonce_gap() -> Result<NonceGap, String> {
+    match env::var("NONCE_GAP") {
+        Ok(value) => value.parse().map_err(|e| format!("NONCE_GAP: {}", e)),
+        Err(_) => Ok(NonceGap::default()),
+    }
+}
+
+fn parse_eth_confirmations(host_chain: &HostChain) -> Result<u64, String> {
+    parse_number("ETH_CONFIRMATIONS", host_chain.confirmations)
+}
+
+fn parse_eth_chain_id(host_chain: &HostChain) -> Result<u64, String> {
+    match parse_number("ETH_CHAIN_ID", host_chain.chain_id)? {
+        0 => Err("ETH_CHAIN_ID must be at least 1".to_string()),
+        chain_id => Ok(chain_id),
+    }
+}
+
+/// `INDEXER_START_BLOCK` is the deprecated name, read when
+/// `ETH_START_BLOCK` is unset.
+fn parse_eth_start_block() -> Result<Option<u64>, String> {
+    if env::var("ETH_START_BLOCK").is_ok() {
+        return parse_number("ETH_START_BLOCK", 0).map(Some);
+    }
+    if env::var("INDEXER_START_BLOCK").is_ok() {
+        log::warn!("INDEXER_START_BLOCK is deprecated, set ETH_START_BLOCK instead");
+        return parse_number("INDEXER_START_BLOCK", 0).map(Some);
+    }
+    Ok(None)
 }
 
-fn parse_sub_token_index() -> Result<u32, &'static str> {
-    env::var("SUB_TOKEN_INDEX")
-    .or_else(|_| Ok("0".into()))
-    .map(|x| x.parse().expect("can not parse SUB_TOKEN_INDEX"))
+fn parse_sub_token_index() -> Result<u32, String> {
+    parse_number("SUB_TOKEN_INDEX", 0)
 }
 
-fn parse_sub_api_url() -> Result<String, &'static str> {
-    env::var("SUB_API_URL").map_err(|_| "can not read SUB_API_URL")
+fn parse_sub_api_url() -> Result<String, String> {
+    parse_url("SUB_API_URL", &WS_SCHEMES)
 }
 
-fn parse_sub_validator_mnemonic_phrase() -> Result<String, &'static str> {
-    let mnemonic_phrase = env::var("SUB_VALIDATOR_MNEMONIC_PHRASE")
-        .map_err(|_| "can not read SUB_VALIDATOR_MNEMONIC_PHRASE")?;
+fn parse_sub_validator_mnemonic_phrase(backend: &Backend) -> Result<Secret<String>, String> {
+    let mnemonic_phrase = read_secret("SUB_VALIDATOR_MNEMONIC_PHRASE", backend)?;
     try_convert_to_sr25519_key_pair(&mnemonic_phrase)?;
 
-    Ok(mnemonic_phrase)
+    Ok(Secret::new(mnemonic_phrase))
+}
+
+fn parse_strict_mode() -> Result<bool, String> {
+    parse_bool("STRICT_MODE", false)
+}
+
+fn parse_unfinalized_statuses(strict_mode: bool) -> Result<Vec<String>, String> {
+    let statuses = env::var("UNFINALIZED_STATUSES")
+        .unwrap_or_else(|_| DEFAULT_UNFINALIZED_STATUSES.to_string());
+    let statuses = statuses
+        .split(',')
+        .map(|status| status.trim().to_uppercase())
+        .filter(|status| !status.is_empty())
+        .collect::<Vec<_>>();
+    if strict_mode {
+        check_statuses(&statuses)?;
+    }
+    Ok(statuses)
+}
+
+fn parse_unfinalized_min_block() -> Result<u64, String> {
+    parse_number("UNFINALIZED_MIN_BLOCK", 0)
+}
+
+fn parse_unfinalized_max_age_blocks() -> Result<u64, String> {
+    parse_number("UNFINALIZED_MAX_AGE_BLOCKS", 0)
+}
+
+fn parse_replay_chunk_size() -> Result<usize, String> {
+    parse_number("REPLAY_CHUNK_SIZE", DEFAULT_REPLAY_CHUNK_SIZE)
+}
+
+fn parse_replay_chunk_pause_secs() -> Result<u64, String> {
+    parse_number("REPLAY_CHUNK_PAUSE_SECS", DEFAULT_REPLAY_CHUNK_PAUSE_SECS)
+}
+
+/// 0 turns the divergence check off.
+fn parse_divergence_check_secs() -> Result<u64, String> {
+    parse_number("DIVERGENCE_CHECK_SECS", DEFAULT_DIVERGENCE_CHECK_SECS)
+}
+
+/// The graph node returns at most 100 messages per query.
+fn parse_divergence_sample_size() -> Result<usize, String> {
+    match parse_number("DIVERGENCE_SAMPLE_SIZE", DEFAULT_DIVERGENCE_SAMPLE_SIZE)? {
+        size @ 1..=100 => Ok(size),
+        size => Err(format!(
+            "DIVERGENCE_SAMPLE_SIZE must be between 1 and 100, got {}",
+            size
+        )),
+    }
+}
+
+/// 0 turns the orphan sweep off.
+fn parse_orphan_sweep_secs() -> Result<u64, String> {
+    parse_number("ORPHAN_SWEEP_SECS", DEFAULT_ORPHAN_SWEEP_SECS)
+}
+
+fn parse_orphan_min_age_blocks() -> Result<u64, String> {
+    parse_number("ORPHAN_MIN_AGE_BLOCKS", DEFAULT_ORPHAN_MIN_AGE_BLOCKS)
+}
+
+fn parse_orphan_max_redrives() -> Result<u32, String> {
+    parse_number("ORPHAN_MAX_REDRIVES", DEFAULT_ORPHAN_MAX_REDRIVES)
+}
+
+fn check_statuses(statuses: &[String]) -> Result<(), String> {
+    match statuses
+        .iter()
+        .find(|status| !KNOWN_STATUSES.contains(&status.as_str()))
+    {
+        Some(status) => Err(format!(
+            "UNFINALIZED_STATUSES has {:?}, in STRICT_MODE only {:?} are allowed",
+            status, KNOWN_STATUSES
+        )),
+        None => Ok(()),
+    }
+}
+
+fn parse_observe_only() -> Result<bool, String> {
+    parse_bool("OBSERVE_ONLY", false)
+}
+
+fn parse_observe_only_if_unregistered() -> Result<bool, String> {
+    parse_bool("OBSERVE_ONLY_IF_UNREGISTERED", false)
+}
+
+/// A follower runs every listener and the controller and serves metrics and
+/// the admin API, but has no keys and never submits anything.
+fn parse_follower() -> Result<bool, String> {
+    parse_bool("FOLLOWER", false)
+}
+
+fn parse_force_start() -> Result<bool, String> {
+    parse_bool("FORCE_START", false)
+}
+
+/// 0 never halts submissions.
+fn parse_auth_failure_limit() -> Result<u32, String> {
+    parse_number("AUTH_FAILURE_LIMIT", DEFAULT_AUTH_FAILURE_LIMIT)
+}
+
+fn parse_instance_check_secs() -> Result<u64, String> {
+    parse_number("INSTANCE_CHECK_SECS", DEFAULT_INSTANCE_CHECK_SECS)
+}
+
+fn parse_executor_workers() -> Result<usize, String> {
+    match parse_number("EXECUTOR_WORKERS", DEFAULT_EXECUTOR_WORKERS)? {
+        0 => Err("EXECUTOR_WORKERS must be at least 1".to_string()),
+        workers => Ok(workers),
+    }
+}
+
+fn parse_max_pending_transfers() -> Result<Option<usize>, String> {
+    match env::var("MAX_PENDING_TRANSFERS") {
+        Ok(_) => parse_number("MAX_PENDING_TRANSFERS", 0).map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+/// 0 holds transfers of a paused account right away.
+fn parse_account_pause_grace_secs() -> Result<u64, String> {
+    parse_number("ACCOUNT_PAUSE_GRACE_SECS", 0)
+}
+
+fn parse_data_dir() -> Result<String, String> {
+    Ok(env::var("DATA_DIR").unwrap_or_else(|_| DEFAULT_DATA_DIR.to_string()))
+}
+
+fn parse_queue_memory_cap() -> Result<usize, String> {
+    match parse_number("QUEUE_MEMORY_CAP", DEFAULT_QUEUE_MEMORY_CAP)? {
+        0 => Err("QUEUE_MEMORY_CAP must be at least 1".to_string()),
+        cap => Ok(cap),
+    }
+}
+
+#[cfg(feature = "otlp")]
+fn parse_otlp_endpoint() -> Result<Option<String>, String> {
+    match env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+        Ok(_) => parse_url("OTEL_EXPORTER_OTLP_ENDPOINT", &HTTP_SCHEMES).map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(feature = "otlp")]
+fn parse_otlp_service_name() -> Result<String, String> {
+    Ok(env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| DEFAULT_OTLP_SERVICE_NAME.to_string()))
+}
+
+fn parse_metrics_address() -> Result<Option<SocketAddr>, String> {
+    match env::var("METRICS_ADDRESS") {
+        Ok(address) => address.trim().parse().map(Some).map_err(|_| {
+            format!(
+                "METRICS_ADDRESS must be an address with a port, got {:?}",
+                address
+            )
+        }),
+        Err(_) => Ok(None),
+    }
+}
+
+/// `RATE_LIMITS="approveTransfer=10,multi_signed_mint=10"`, calls per minute.
+fn parse_rate_limits() -> Result<HashMap<String, u32>, String> {
+    env::var("RATE_LIMITS")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|limit| !limit.trim().is_empty())
+        .map(|limit| {
+            let mut parts = limit.trim().splitn(2, '=');
+            match (parts.next(), parts.next().map(str::parse::<u32>)) {
+                (Some(method), Some(Ok(per_minute))) if per_minute > 0 => {
+                    Ok((method.to_string(), per_minute))
+                }
+                _ => Err(format!(
+                    "RATE_LIMITS entries must be <method>=<calls per minute>, got {:?}",
+                    limit
+                )),
+            }
+        })
+        .collect()
+}
+
+/// `CALL_URGENCY="confirmCancelTransfer=critical"`, token bridge methods not
+/// listed are `normal`.
+fn parse_call_urgency() -> Result<HashMap<String, Urgency>, String> {
+    env::var("CALL_URGENCY")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            let mut parts = entry.trim().splitn(2, '=');
+            match (parts.next(), parts.next().map(str::parse::<Urgency>)) {
+                (Some(method), Some(Ok(urgency))) => Ok((method.to_string(), urgency)),
+                (_, Some(Err(e))) => Err(format!("CALL_URGENCY: {}", e)),
+                _ => Err(format!(
+                    "CALL_URGENCY entries must be <method>=<urgency>, got {:?}",
+                    entry
+                )),
+            }
+        })
+        .collect()
+}
+
+/// `ACCESS_LISTS_FILE` maps token bridge methods to EIP-2930 access lists,
+/// in the format `eth_createAccessList` returns them.
+fn parse_access_lists() -> Result<HashMap<String, Vec<AccessListItem>>, String> {
+    let path = match env::var("ACCESS_LISTS_FILE") {
+        Ok(path) => path,
+        Err(_) => return Ok(HashMap::new()),
+    };
+    let json = fs::read_to_string(&path)
+        .map_err(|e| format!("can not read ACCESS_LISTS_FILE {:?}: {}", path, e))?;
+    serde_json::from_str(&json)
+        .map_err(|e| format!("ACCESS_LISTS_FILE {:?} is not valid: {}", path, e))
+}
+
+fn parse_timelock(policies: &[PolicyKind]) -> Result<Timelock, String> {
+    Ok(Timelock {
+        threshold: parse_threshold("TIMELOCK_THRESHOLD", policies, "timelock")?,
+        delay: Duration::from_secs(parse_number("TIMELOCK_SECS", DEFAULT_TIMELOCK_SECS)?),
+    })
+}
+
+/// The canary is enabled by a non-zero `CANARY_INTERVAL_SECS`.
+#[cfg(feature = "graph-node")]
+fn parse_canary(backend: &Backend, validator_address: Address) -> Result<Option<Canary>, String> {
+    const NAME: &str = "CANARY_PRIVATE_KEY";
+
+    let interval = parse_number("CANARY_INTERVAL_SECS", 0)?;
+    if interval == 0 {
+        return Ok(None);
+    }
+    let private_key = read_secret(NAME, backend)?;
+    let private_key = check_hex(NAME, &private_key, 32)?.to_string();
+    let address = try_convert_to_bip32_key_pair(&private_key)?.address();
+    if address == validator_address {
+        return Err(format!(
+            "{} must not be the validator key, the canary would take its nonces",
+            NAME
+        ));
+    }
+    let amount = parse_amount("CANARY_AMOUNT")?;
+    if amount.is_zero() {
+        return Err("CANARY_AMOUNT must be set when the canary is enabled".to_string());
+    }
+    Ok(Some(Canary {
+        private_key: Secret::new(private_key),
+        address,
+        token: parse_address("CANARY_TOKEN_ADDRESS")?,
+        amount,
+        interval: Duration::from_secs(interval),
+        sla: Duration::from_secs(parse_number("CANARY_SLA_SECS", DEFAULT_CANARY_SLA_SECS)?),
+    }))
+}
+
+/// Coordination is enabled by `COORDINATION_REDIS_ADDRESS` and applies to the
+/// token bridge methods in `COORDINATED_CALLS`.
+fn parse_coordinator(validator: Address) -> Result<Option<Coordinator>, String> {
+    let address = env::var("COORDINATION_REDIS_ADDRESS").unwrap_or_default();
+    let address = match address.trim() {
+        "" => return Ok(None),
+        address => address.to_string(),
+    };
+    let calls = env::var("COORDINATED_CALLS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|call| !call.is_empty())
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    if calls.is_empty() {
+        return Err("COORDINATED_CALLS must be set when coordination is enabled".to_string());
+    }
+    let grace = parse_number("COORDINATION_GRACE_SECS", DEFAULT_COORDINATION_GRACE_SECS)?;
+    Ok(Some(Coordinator {
+        address,
+        calls,
+        grace: Duration::from_secs(grace),
+        validator,
+    }))
+}
+
+/// The embedded indexer is enabled by `INDEXER_ENABLED`, it scans the logs
+/// of the token bridge contracts. With `INDEXER_FAILOVER` it stands by for
+/// the graph node listener, which needs the graph-node feature.
+fn parse_indexer() -> Result<Option<Indexer>, String> {
+    if !parse_bool("INDEXER_ENABLED", false)? {
+        return Ok(None);
+    }
+    let failover = parse_bool("INDEXER_FAILOVER", false)?;
+    if failover && !cfg!(feature = "graph-node") {
+        return Err(
+            "INDEXER_FAILOVER is set, but the validator was built without the graph-node feature"
+                .to_string(),
+        );
+    }
+    let batch_blocks = match parse_number("INDEXER_BATCH_BLOCKS", DEFAULT_INDEXER_BATCH_BLOCKS)? {
+        0 => return Err("INDEXER_BATCH_BLOCKS must be at least 1".to_string()),
+        blocks => blocks,
+    };
+    let poll_secs = parse_number("INDEXER_POLL_SECS", DEFAULT_INDEXER_POLL_SECS)?;
+    Ok(Some(Indexer {
+        batch_blocks,
+        poll_interval: Duration::from_secs(poll_secs),
+        failover,
+    }))
+}
+
+/// `RETENTION_TRANSFER_DAYS` and `RETENTION_AUDIT_DAYS` are off by default,
+/// everything is kept.
+fn parse_retention() -> Result<Retention, String> {
+    let interval = match parse_number("RETENTION_INTERVAL_SECS", DEFAULT_RETENTION_INTERVAL_SECS)? {
+        0 => return Err("RETENTION_INTERVAL_SECS must be at least 1".to_string()),
+        interval => interval,
+    };
+    Ok(Retention {
+        transfer_days: parse_number("RETENTION_TRANSFER_DAYS", 0)?,
+        audit_days: parse_number("RETENTION_AUDIT_DAYS", 0)?,
+        interval: Duration::from_secs(interval),
+    })
+}
+
+/// The operator token is only needed, and then required, when the API or
+/// its gRPC service is enabled. The observer token is optional and must
+/// differ from it.
+#[cfg(feature = "admin-api")]
+fn parse_admin_api(
+    backend: &Backend,
+    grpc_enabled: bool,
+) -> Result<(Option<SocketAddr>, AdminApiTokens), String> {
+    let address = match env::var("ADMIN_API_ADDRESS") {
+        Ok(address) => Some(address.trim().parse::<SocketAddr>().map_err(|_| {
+            format!(
+                "ADMIN_API_ADDRESS must be an address with a port, got {:?}",
+                address
+            )
+        })?),
+        Err(_) if grpc_enabled => None,
+        Err(_) => return Ok((None, AdminApiTokens::default())),
+    };
+    let operator = read_secret("ADMIN_API_TOKEN", backend)?;
+    let observer = if is_set("ADMIN_API_OBSERVER_TOKEN", backend) {
+        Some(read_secret("ADMIN_API_OBSERVER_TOKEN", backend)?)
+    } else {
+        None
+    };
+    if operator.is_empty() || observer.as_ref().map_or(false, String::is_empty) {
+        return Err("admin API tokens must not be empty".to_string());
+    }
+    if observer.as_ref() == Some(&operator) {
+        return Err("ADMIN_API_OBSERVER_TOKEN must differ from ADMIN_API_TOKEN".to_string());
+    }
+    let tokens = AdminApiTokens {
+        operator: Secret::new(operator),
+        observer: observer.map(Secret::new),
+    };
+    Ok((address, tokens))
+}
+
+#[cfg(feature = "grpc")]
+fn parse_grpc_api_address() -> Result<Option<SocketAddr>, String> {
+    match env::var("GRPC_API_ADDRESS") {
+        Ok(address) => address.trim().parse().map(Some).map_err(|_| {
+            format!(
+                "GRPC_API_ADDRESS must be an address with a port, got {:?}",
+                address
+            )
+        }),
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(feature = "admin-api")]
+fn parse_explorer_url(name: &str) -> Result<Option<String>, String> {
+    match env::var(name) {
+        Ok(_) => {
+            parse_url(name, &HTTP_SCHEMES).map(|url| Some(url.trim_end_matches('/').to_string()))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+/// Settings of a subsystem this binary was built without are refused, so a
+/// slim build never ignores them silently.
+fn check_features() -> Result<(), String> {
+    let features = [
+        (
+            "graph-node",
+            cfg!(feature = "graph-node"),
+            "CANARY_INTERVAL_SECS",
+        ),
+        (
+            "admin-api",
+            cfg!(feature = "admin-api"),
+            "ADMIN_API_ADDRESS",
+        ),
+        (
+            "metrics-exporter",
+            cfg!(feature = "metrics-exporter"),
+            "METRICS_ADDRESS",
+        ),
+        ("grpc", cfg!(feature = "grpc"), "GRPC_API_ADDRESS"),
+        (
+            "otlp",
+            cfg!(feature = "otlp"),
+            "OTEL_EXPORTER_OTLP_ENDPOINT",
+        ),
+    ];
+    for (feature, built, name) in features.iter() {
+        let set = env::var(name).map_or(false, |value| !["", "0"].contains(&value.trim()));
+        if set && !built {
+            return Err(format!(
+                "{} is set, but the validator was built without the {} feature",
+                name, feature
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn parse_policies() -> Result<Vec<PolicyKind>, String> {
+    env::var("POLICIES")
+        .unwrap_or_else(|_| policy::DEFAULT_POLICIES.to_string())
+        .split(',')
+        .filter(|name| !name.trim().is_empty())
+        .map(|name| name.parse().map_err(|e| format!("POLICIES: {}", e)))
+        .collect()
+}
+
+fn parse_max_clock_skew_secs() -> Result<u64, String> {
+    parse_number("MAX_CLOCK_SKEW_SECS", DEFAULT_MAX_CLOCK_SKEW_SECS)
+}
+
+fn parse_use_chain_time() -> Result<bool, String> {
+    parse_bool("USE_CHAIN_TIME", false)
+}
+
+fn parse_clock_check_interval_secs() -> Result<u64, String> {
+    match parse_number(
+        "CLOCK_CHECK_INTERVAL_SECS",
+        DEFAULT_CLOCK_CHECK_INTERVAL_SECS,
+    )? {
+        0 => Err("CLOCK_CHECK_INTERVAL_SECS must be at least 1".to_string()),
+        interval => Ok(interval),
+    }
+}
+
+/// 0 turns the gas runway alert off.
+fn parse_gas_runway_alert_hours() -> Result<u64, String> {
+    parse_number("GAS_RUNWAY_ALERT_HOURS", DEFAULT_GAS_RUNWAY_ALERT_HOURS)
+}
+
+fn parse_gas_runway_window_secs() -> Result<u64, String> {
+    match parse_number("GAS_RUNWAY_WINDOW_SECS", DEFAULT_GAS_RUNWAY_WINDOW_SECS)? {
+        0 => Err("GAS_RUNWAY_WINDOW_SECS must be at least 1".to_string()),
+        window => Ok(window),
+    }
+}
+
+fn parse_sub_sync_check_secs() -> Result<u64, String> {
+    match parse_number("SUB_SYNC_CHECK_SECS", DEFAULT_SUB_SYNC_CHECK_SECS)? {
+        0 => Err("SUB_SYNC_CHECK_SECS must be at least 1".to_string()),
+        interval => Ok(interval),
+    }
+}
+
+fn parse_sub_sync_alert_secs() -> Result<u64, String> {
+    parse_number("SUB_SYNC_ALERT_SECS", DEFAULT_SUB_SYNC_ALERT_SECS)
+}
+
+fn parse_eth_secondary_api_url() -> Result<Option<String>, String> {
+    match env::var("ETH_SECONDARY_API_URL") {
+        Ok(_) => parse_url("ETH_SECONDARY_API_URL", &WS_SCHEMES).map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+fn parse_eth_max_lag_blocks() -> Result<u64, String> {
+    parse_number("ETH_MAX_LAG_BLOCKS", DEFAULT_ETH_MAX_LAG_BLOCKS)
+}
+
+fn parse_eth_sync_check_secs() -> Result<u64, String> {
+    match parse_number("ETH_SYNC_CHECK_SECS", DEFAULT_ETH_SYNC_CHECK_SECS)? {
+        0 => Err("ETH_SYNC_CHECK_SECS must be at least 1".to_string()),
+        interval => Ok(interval),
+    }
+}
+
+fn parse_eth_sync_alert_secs() -> Result<u64, String> {
+    parse_number("ETH_SYNC_ALERT_SECS", DEFAULT_ETH_SYNC_ALERT_SECS)
+}
+
+fn parse_startup_timeout_secs() -> Result<u64, String> {
+    match parse_number("STARTUP_TIMEOUT_SECS", DEFAULT_STARTUP_TIMEOUT_SECS)? {
+        0 => Err("STARTUP_TIMEOUT_SECS must be at least 1".to_string()),
+        timeout => Ok(timeout),
+    }
+}
+
+fn parse_secret_backend() -> Result<Backend, String> {
+    let backend = match env::var("SECRET_BACKEND") {
+        Ok(backend) => backend
+            .parse()
+            .map_err(|e| format!("SECRET_BACKEND: {}", e))?,
+        Err(_) => Backend::Env,
+    };
+    if backend != Backend::Env && !cfg!(feature = "secret-backends") {
+        return Err(format!(
+            "SECRET_BACKEND is {:?}, but the validator was built without the secret-backends \
+             feature",
+            backend
+        ));
+    }
+    Ok(backend)
+}
+
+fn read_var(name: &str) -> Result<String, String> {
+    env::var(name).map_err(|_| format!("{} is not set", name))
+}
+
+/// Reads the secret from `name` itself with the env backend, otherwise
+/// fetches it from the backend by the reference stored in `<name>_SECRET`.
+fn read_secret(name: &str, backend: &Backend) -> Result<String, String> {
+    if *backend == Backend::Env {
+        return read_var(name);
+    }
+    let reference = read_var(&format!("{}_SECRET", name))?;
+    #[cfg(feature = "secret-backends")]
+    let secret = secret_backend::fetch(backend, &reference);
+    // refused by `parse_secret_backend` already
+    #[cfg(not(feature = "secret-backends"))]
+    let secret: Result<String, String> = Err(format!("no backend to resolve {:?} with", reference));
+    secret
+        .map(|secret| secret.trim().to_string())
+        .map_err(|e| format!("can not fetch {} from {:?}: {}", name, backend, e))
+}
+
+/// Required and above 0 when `policy` is in `POLICIES`, a policy holding
+/// every transfer above 0 would hold them all.
+fn parse_threshold(name: &str, policies: &[PolicyKind], policy: &str) -> Result<U256, String> {
+    if !policies.contains(&policy.parse().expect("known policy")) {
+        return parse_amount(name);
+    }
+    if env::var(name).is_err() {
+        return Err(format!("{} must be set with the {} policy", name, policy));
+    }
+    match parse_amount(name)? {
+        threshold if threshold.is_zero() => Err(format!(
+            "{} must be above 0 with the {} policy",
+            name, policy
+        )),
+        threshold => Ok(threshold),
+    }
+}
+
+/// An amount in base units, zero when the variable is not set.
+fn parse_amount(name: &str) -> Result<U256, String> {
+    match env::var(name) {
+        Ok(amount) => U256::from_dec_str(amount.trim())
+            .map_err(|_| format!("{} must be an amount in base units, got {:?}", name, amount)),
+        Err(_) => Ok(U256::zero()),
+    }
+}
+
+/// Whether the secret `name` is configured, see `read_secret`.
+#[cfg(feature = "admin-api")]
+fn is_set(name: &str, backend: &Backend) -> bool {
+    if *backend == Backend::Env {
+        env::var(name).is_ok()
+    } else {
+        env::var(format!("{}_SECRET", name)).is_ok()
+    }
+}
+
+fn parse_number<T>(name: &str, default: T) -> Result<T, String>
+where
+    T: FromStr,
+{
+    match env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map_err(|_| format!("{} must be a non-negative integer, got {:?}", name, value)),
+        Err(_) => Ok(default),
+    }
+}
+
+fn parse_bool(name: &str, default: bool) -> Result<bool, String> {
+    match env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map_err(|_| format!("{} must be \"true\" or \"false\", got {:?}", name, value)),
+        Err(_) => Ok(default),
+    }
+}
+
+fn parse_url(name: &str, schemes: &[&str]) -> Result<String, String> {
+    let url = read_var(name)?;
+    check_url(name, &url, schemes)?;
+    Ok(url)
 }
 
-fn try_convert_to_sr25519_key_pair(mnemonic_phrase: &str) -> Result<(), &'static str> {
-    sr25519::Pair::from_phrase(&mnemonic_phrase, None)
-        .map_err(|_| "invalid SUB_VALIDATOR_MNEMONIC_PHRASE")?;
+fn parse_address(name: &str) -> Result<Address, String> {
+    let address = read_var(name)?;
+    check_hex(name, &address, 20)?
+        .parse()
+        .map_err(|_| format!("{} is not a valid address", name))
+}
+
+/// IPv6 hosts are written in brackets, e.g. `ws://[::1]:9944`.
+fn check_url(name: &str, url: &str, schemes: &[&str]) -> Result<(), String> {
+    if !schemes.iter().any(|scheme| url.starts_with(scheme)) {
+        return Err(format!(
+            "{} must start with one of {:?}, got {:?}",
+            name, schemes, url
+        ));
+    }
+    match Url::parse(url) {
+        Ok(ref parsed) if parsed.host().is_some() => Ok(()),
+        _ => Err(format!("{} is not a valid url, got {:?}", name, url)),
+    }
+}
+
+/// Checks that `value` is a 0x-prefixed hex string of `bytes` bytes and
+/// returns it without the prefix. The value itself is not echoed back as
+/// it may be a secret.
+fn check_hex<'a>(name: &str, value: &'a str, bytes: usize) -> Result<&'a str, String> {
+    if !value.starts_with("0x") {
+        return Err(format!("{} must start with 0x", name));
+    }
+    let hex = &value[2..];
+    if hex.len() != bytes * 2 {
+        return Err(format!(
+            "{} must be {} hex digits after 0x, got {}",
+            name,
+            bytes * 2,
+            hex.len()
+        ));
+    }
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("{} contains non-hex characters", name));
+    }
+    Ok(hex)
+}
+
+fn try_convert_to_sr25519_key_pair(mnemonic_phrase: &str) -> Result<(), String> {
+    let words = mnemonic_phrase.split_whitespace().count();
+    if !MNEMONIC_WORD_COUNTS.contains(&words) {
+        return Err(format!(
+            "SUB_VALIDATOR_MNEMONIC_PHRASE must have one of {:?} words, got {}",
+            MNEMONIC_WORD_COUNTS, words
+        ));
+    }
+    sr25519::Pair::from_phrase(&mnemonic_phrase, None).map_err(|_| {
+        "SUB_VALIDATOR_MNEMONIC_PHRASE is not a valid BIP39 phrase (unknown word or bad checksum)"
+            .to_string()
+    })?;
     Ok(())
 }
 
-fn try_convert_to_bip32_key_pair(private_key: &str) -> Result<(), &'static str> {
+fn try_convert_to_bip32_key_pair(private_key: &str) -> Result<Bip32ECKeyPair, String> {
     let private_key = private_key
         .from_hex::<Vec<_>>()
-        .map_err(|_| "can not parse validator private key")?;
-    Bip32ECKeyPair::from_raw_secret(&private_key).map_err(|_| "invalid validator private key")?;
-    Ok(())
+        .map_err(|_| "can not parse ETH_VALIDATOR_PRIVATE_KEY".to_string())?;
+    Bip32ECKeyPair::from_raw_secret(&private_key)
+        .map_err(|_| "ETH_VALIDATOR_PRIVATE_KEY is not a valid secp256k1 key".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_url_tests() {
+        assert_eq!(Ok(()), check_url("URL", "ws://localhost:9944", &WS_SCHEMES));
+        assert_eq!(
+            Ok(()),
+            check_url("URL", "https://example.com", &HTTP_SCHEMES)
+        );
+        assert!(check_url("URL", "http://localhost:9944", &WS_SCHEMES).is_err());
+        assert_eq!(Ok(()), check_url("URL", "ws://[::1]:9944", &WS_SCHEMES));
+        assert_eq!(
+            Ok(()),
+            check_url("URL", "https://[2001:db8::1]:8000/subgraphs", &HTTP_SCHEMES)
+        );
+        assert!(check_url("URL", "ws://[::1:9944", &WS_SCHEMES).is_err());
+        assert!(check_url("URL", "ws://", &WS_SCHEMES).is_err());
+    }
+
+    #[test]
+    fn check_hex_tests() {
+        let address = "0xC6870AED119d01CE3f7A377775bD489839c51815";
+        assert_eq!(Ok(&address[2..]), check_hex("ADDRESS", address, 20));
+        assert!(check_hex("ADDRESS", &address[2..], 20).is_err());
+        assert!(check_hex("ADDRESS", "0x", 20).is_err());
+        assert!(check_hex("ADDRESS", "0xZZ870AED119d01CE3f7A377775bD489839c51815", 20).is_err());
+    }
+
+    #[test]
+    fn check_statuses_tests() {
+        let statuses = |statuses: &[&str]| {
+            statuses
+                .iter()
+                .map(|status| status.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(Ok(()), check_statuses(&statuses(&["PENDING", "CONFIRMED"])));
+        assert!(check_statuses(&statuses(&["PENDING", "REFUNDED"])).is_err());
+    }
+
+    #[test]
+    fn error_collects_all_problems() {
+        let mut error = Error::new();
+        let value: u64 = error.check(Err("first".to_string()));
+        assert_eq!(0, value);
+        assert_eq!(7, error.check::<u64>(Ok(7)));
+        error.check::<String>(Err("second".to_string()));
+        assert_eq!(
+            "invalid configuration:\n  - first\n  - second\n",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn read_token_bridges_tests() {
+        let default_abi = executor::get_contract_abi();
+        let address = |byte: u8| format!("0x{}", format!("{:02x}", byte).repeat(20));
+        let json = format!(
+            r#"{{"1": {{"address": "{}", "token": "{}"}},
+                "2": {{"address": "{}", "abi": "res/Contract.json"}}}}"#,
+            address(1),
+            address(0xaa),
+            address(2)
+        );
+        let token_bridges = read_token_bridges(&json, &default_abi).unwrap();
+        assert_eq!(2, token_bridges.len());
+        let first = &token_bridges[&U256::from(1)];
+        assert_eq!(Address::from_slice(&[1; 20]), first.address);
+        assert_eq!(Some(Address::from_slice(&[0xaa; 20])), first.token);
+        let second = &token_bridges[&U256::from(2)];
+        assert_eq!(Address::from_slice(&[2; 20]), second.address);
+        assert_eq!(None, second.token);
+
+        let invalid = |json: &str| read_token_bridges(json, &default_abi).unwrap_err();
+        assert!(invalid("[]").starts_with("is not valid"));
+        let entry = format!(r#"{{"address": "{}"}}"#, address(1));
+        assert_eq!(
+            r#"invalid token id "one""#,
+            invalid(&format!(r#"{{"one": {}}}"#, entry))
+        );
+        let missing_abi = format!(r#"{{"address": "{}", "abi": "missing.json"}}"#, address(1));
+        assert!(invalid(&format!(r#"{{"3": {}}}"#, missing_abi)).starts_with("token 3: "));
+        let holding = |byte: u8| {
+            format!(
+                r#"{{"address": "{}", "token": "{}"}}"#,
+                address(byte),
+                address(0xaa)
+            )
+        };
+        assert_eq!(
+            format!(
+                "tokens 1 and 2 both hold {:?}",
+                Address::from_slice(&[0xaa; 20])
+            ),
+            invalid(&format!(r#"{{"1": {}, "2": {}}}"#, holding(1), holding(2)))
+        );
+    }
+
+    #[test]
+    fn parse_threshold_tests() {
+        let timelock = [PolicyKind::Timelock];
+        env::remove_var("THRESHOLD_TEST_UNSET");
+        env::set_var("THRESHOLD_TEST_ZERO", "0");
+        env::set_var("THRESHOLD_TEST_SET", "1000");
+        assert_eq!(
+            Ok(U256::zero()),
+            parse_threshold("THRESHOLD_TEST_UNSET", &[], "timelock")
+        );
+        assert_eq!(
+            Err("THRESHOLD_TEST_UNSET must be set with the timelock policy".to_string()),
+            parse_threshold("THRESHOLD_TEST_UNSET", &timelock, "timelock")
+        );
+        assert_eq!(
+            Err("THRESHOLD_TEST_ZERO must be above 0 with the timelock policy".to_string()),
+            parse_threshold("THRESHOLD_TEST_ZERO", &timelock, "timelock")
+        );
+        assert_eq!(
+            Ok(U256::from(1000)),
+            parse_threshold("THRESHOLD_TEST_SET", &timelock, "timelock")
+        );
+        // another policy's threshold is not required
+        assert_eq!(
+            Ok(U256::zero()),
+            parse_threshold("THRESHOLD_TEST_UNSET", &timelock, "manual_approval")
+        );
+    }
+
+    #[test]
+    fn eth_validator_address_is_derived_from_private_key() {
+        let key_pair = try_convert_to_bip32_key_pair(
+            "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318",
+        )
+        .expect("valid private key");
+        assert_eq!(
+            "2c7536e3605d9c16a7a3d7b1898e529396a65c23"
+                .parse::<Address>()
+                .unwrap(),
+            key_pair.address()
+        );
+    }
 }
@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use web3::types::H256;
+
+use crate::jsonl::OnceLog;
+
+pub const FILE_NAME: &str = "confirmed_transfers.jsonl";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Confirmation {
+    message_id: H256,
+    confirmed_at: i64,
+}
+
+/// Messages whose `confirm_transfer` this validator got finalized, kept in
+/// `<data_dir>/confirmed_transfers.jsonl`. WITHDRAW messages are replayed on
+/// every start, confirming them again only makes the pallet return errors.
+#[derive(Debug)]
+pub struct ConfirmedTransfers {
+    confirmed: OnceLog<H256>,
+}
+
+impl ConfirmedTransfers {
+    pub fn open(data_dir: &str) -> Self {
+        ConfirmedTransfers {
+            confirmed: OnceLog::open(
+                data_dir,
+                FILE_NAME,
+                "confirmed transfers are only kept in memory",
+                |confirmation: Confirmation| confirmation.message_id,
+            ),
+        }
+    }
+
+    pub fn contains(&self, message_id: &H256) -> bool {
+        self.confirmed.contains(message_id)
+    }
+
+    pub fn record(&self, message_id: H256) {
+        self.confirmed.record(message_id, || Confirmation {
+            message_id,
+            confirmed_at: time::now_utc().to_timespec().sec,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use std::fs;
+
+    #[test]
+    fn record_test() {
+        let dir = TestDir::new("confirmed_transfers");
+        let data_dir = dir.path();
+        let message_id = H256::from_slice(&[1; 32]);
+        let confirmed = ConfirmedTransfers::open(data_dir);
+        assert!(!confirmed.contains(&message_id));
+        confirmed.record(message_id);
+        confirmed.record(message_id);
+        assert!(confirmed.contains(&message_id));
+
+        // a restarted validator remembers what it confirmed
+        let confirmed = ConfirmedTransfers::open(data_dir);
+        assert!(confirmed.contains(&message_id));
+        assert!(!confirmed.contains(&H256::from_slice(&[2; 32])));
+        let lines = fs::read_to_string(dir.join(FILE_NAME)).unwrap();
+        assert_eq!(1, lines.lines().count());
+    }
+}
@@ -1,11 +1,34 @@
 use web3::types::{H160, H256, U256};
 
 use log;
-use std::sync::mpsc::{Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant};
 
+use crate::approvals::{Approvals, Decision};
+use crate::attestations::AttestationLog;
+use crate::auth_guard::{self, AuthGuard};
+use crate::bus::{Bus, Topic, OBSERVED};
+use crate::clock;
 use crate::config::Config;
-use crate::controller_storage::ControllerStorage;
+use crate::controller_storage::{self, ControllerStorage};
+use crate::debug_state;
+#[cfg(feature = "graph-node")]
+use crate::divergence;
+use crate::fatal;
+use crate::metrics;
+use crate::policy::{self, Policy, Verdict};
+use crate::startup::Ready;
+use crate::submissions::{Chain, SubmissionLog};
+use crate::substrate_transactions;
+
+/// How often the controller looks for timelocked events that are due and
+/// for transfers operators have decided on.
+const TICK: Duration = Duration::from_secs(1);
 
 type MessageId = H256;
 type EthAddress = H160;
@@ -15,262 +38,1176 @@ type TokenId = U256;
 type BlockNumber = u128;
 type Timestamp = u64;
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Address {
     Eth(EthAddress),
     Sub(SubAddress),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Direction of a transfer, from the chain where it was started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    EthToSub,
+    SubToEth,
+}
+
+/// Where an event came from. Events released from the controller's queues
+/// after being held back are re-sent as `Replay`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Source {
+    GraphNode,
+    /// The embedded indexer, which stands in for the graph node.
+    Indexer,
+    SubstrateWs,
+    Replay,
+    Executor,
+    /// Re-driven by the orphan sweep, dispatched again even though seen before.
+    OrphanSweep,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Event {
-    EthBridgePausedMessage(MessageId, BlockNumber),
-    EthBridgeResumedMessage(MessageId, BlockNumber),
-    EthBridgeStartedMessage(MessageId, EthAddress, BlockNumber),
-    EthBridgeStoppedMessage(MessageId, EthAddress, BlockNumber),
-
-    EthRelayMessage(
-        MessageId,
-        EthAddress,
-        SubAddress,
-        Amount,
-        BlockNumber,
-    ),
-    EthApprovedRelayMessage(
-        MessageId,
-        EthAddress,
-        SubAddress,
-        Amount,
-        BlockNumber,
-    ),
-    EthRevertMessage(MessageId, EthAddress, Amount, BlockNumber),
-    EthWithdrawMessage(MessageId, BlockNumber),
-
-    EthHostAccountPausedMessage(MessageId, EthAddress, Timestamp, BlockNumber),
-    EthHostAccountResumedMessage(MessageId, EthAddress, Timestamp, BlockNumber),
-    EthGuestAccountPausedMessage(MessageId, SubAddress, Timestamp, BlockNumber),
-    EthGuestAccountResumedMessage(MessageId, SubAddress, Timestamp, BlockNumber),
-
-    EthSetNewLimits(
-        MessageId,
-        Amount,
-        Amount,
-        Amount,
-        Amount,
-        Amount,
-        Amount,
-        Amount,
-        Amount,
-        Amount,
-        Amount,
-        BlockNumber,
-    ),
-
-    EthValidatorsListMessage(MessageId, Vec<SubAddress>, Amount, BlockNumber),
-
-    SubRelayMessage(MessageId, BlockNumber),
-    SubApprovedRelayMessage(
-        MessageId,
-        SubAddress,
-        EthAddress,
-        Amount,
-        TokenId,
-        BlockNumber,
-    ),
-    SubBurnedMessage(
-        MessageId,
-        SubAddress,
-        EthAddress,
-        Amount,
-        TokenId,
-        BlockNumber,
-    ),
-    SubMintedMessage(MessageId, TokenId, BlockNumber),
-    SubCancellationConfirmedMessage(MessageId, TokenId, BlockNumber),
-
-    SubAccountPausedMessage(MessageId, SubAddress, Timestamp, TokenId, BlockNumber),
-    SubAccountResumedMessage(MessageId, SubAddress, Timestamp, TokenId, BlockNumber),
+    EthBridgePausedMessage {
+        message_id: MessageId,
+        block_number: BlockNumber,
+    },
+    EthBridgeResumedMessage {
+        message_id: MessageId,
+        block_number: BlockNumber,
+    },
+    /// The subgraph does not record who started or stopped the bridge, an
+    /// emergency stop must be relayed all the same.
+    EthBridgeStartedMessage {
+        message_id: MessageId,
+        sender: Option<EthAddress>,
+        block_number: BlockNumber,
+    },
+    EthBridgeStoppedMessage {
+        message_id: MessageId,
+        sender: Option<EthAddress>,
+        block_number: BlockNumber,
+    },
+
+    EthRelayMessage {
+        message_id: MessageId,
+        sender: EthAddress,
+        recipient: SubAddress,
+        amount: Amount,
+        /// The ERC20 the transfer locks, it picks the token bridge to approve
+        /// it on. Events stored before it was recorded have none.
+        token: Option<EthAddress>,
+        block_number: BlockNumber,
+    },
+    EthApprovedRelayMessage {
+        message_id: MessageId,
+        sender: EthAddress,
+        recipient: SubAddress,
+        amount: Amount,
+        block_number: BlockNumber,
+    },
+    EthRevertMessage {
+        message_id: MessageId,
+        sender: EthAddress,
+        amount: Amount,
+        block_number: BlockNumber,
+    },
+    EthWithdrawMessage {
+        message_id: MessageId,
+        block_number: BlockNumber,
+    },
+
+    EthHostAccountPausedMessage {
+        message_id: MessageId,
+        account: EthAddress,
+        timestamp: Timestamp,
+        block_number: BlockNumber,
+    },
+    EthHostAccountResumedMessage {
+        message_id: MessageId,
+        account: EthAddress,
+        timestamp: Timestamp,
+        block_number: BlockNumber,
+    },
+    EthGuestAccountPausedMessage {
+        message_id: MessageId,
+        account: SubAddress,
+        timestamp: Timestamp,
+        block_number: BlockNumber,
+    },
+    EthGuestAccountResumedMessage {
+        message_id: MessageId,
+        account: SubAddress,
+        timestamp: Timestamp,
+        block_number: BlockNumber,
+    },
+
+    EthSetNewLimits {
+        message_id: MessageId,
+        limits: EthLimits,
+        block_number: BlockNumber,
+    },
+
+    EthValidatorsListMessage {
+        message_id: MessageId,
+        validators: Vec<SubAddress>,
+        /// How many validators decide.
+        quorum: Amount,
+        block_number: BlockNumber,
+    },
+
+    SubRelayMessage {
+        message_id: MessageId,
+        block_number: BlockNumber,
+    },
+    SubApprovedRelayMessage {
+        message_id: MessageId,
+        sender: SubAddress,
+        recipient: EthAddress,
+        amount: Amount,
+        token_id: TokenId,
+        block_number: BlockNumber,
+    },
+    SubBurnedMessage {
+        message_id: MessageId,
+        sender: SubAddress,
+        recipient: EthAddress,
+        amount: Amount,
+        token_id: TokenId,
+        block_number: BlockNumber,
+    },
+    SubMintedMessage {
+        message_id: MessageId,
+        token_id: TokenId,
+        block_number: BlockNumber,
+    },
+    SubCancellationConfirmedMessage {
+        message_id: MessageId,
+        token_id: TokenId,
+        block_number: BlockNumber,
+    },
+
+    SubAccountPausedMessage {
+        message_id: MessageId,
+        account: SubAddress,
+        timestamp: Timestamp,
+        token_id: TokenId,
+        block_number: BlockNumber,
+    },
+    SubAccountResumedMessage {
+        message_id: MessageId,
+        account: SubAddress,
+        timestamp: Timestamp,
+        token_id: TokenId,
+        block_number: BlockNumber,
+    },
+
+    /// Guest limits the Bridge pallet holds, read when they change, as
+    /// governance can change them without an Ethereum event. The message id
+    /// is the hash of the limits. Kept by the controller, never executed.
+    SubSetNewLimits {
+        message_id: MessageId,
+        limits: GuestLimits,
+        block_number: BlockNumber,
+    },
+
+    /// A message the listeners do not know how to convert, with the query
+    /// it came from and its raw payload. Counted and logged, never acted on.
+    Unknown {
+        message_id: MessageId,
+        origin: String,
+        payload: String,
+        block_number: BlockNumber,
+    },
+
+    /// What became of a call the executor made for a message, reported back
+    /// to the controller. Never sent to the executor.
+    Outcome {
+        message_id: MessageId,
+        outcome: Outcome,
+    },
+}
+
+/// Transaction limits of the guest side, the Substrate chain.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GuestLimits {
+    pub min_transaction_value: Amount,
+    pub max_transaction_value: Amount,
+    pub day_max_limit: Amount,
+    pub day_max_limit_for_one_address: Amount,
+    pub max_pending_transaction_limit: Amount,
+}
+
+/// Transaction limits the token bridge sets for both sides.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EthLimits {
+    pub min_host_transaction_value: Amount,
+    pub max_host_transaction_value: Amount,
+    pub day_host_max_limit: Amount,
+    pub day_host_max_limit_for_one_address: Amount,
+    pub max_host_pending_transaction_limit: Amount,
+    pub guest: GuestLimits,
+}
+
+impl From<substrate_transactions::Limits> for GuestLimits {
+    fn from(limits: substrate_transactions::Limits) -> Self {
+        GuestLimits {
+            min_transaction_value: limits.min_tx_value.into(),
+            max_transaction_value: limits.max_tx_value.into(),
+            day_max_limit: limits.day_max_limit.into(),
+            day_max_limit_for_one_address: limits.day_max_limit_for_one_address.into(),
+            max_pending_transaction_limit: limits.max_pending_tx_limit.into(),
+        }
+    }
+}
+
+/// Outcome of a token bridge call or an extrinsic, by the name of the
+/// contract method or extrinsic.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Outcome {
+    Submitted(Chain, String, H256),
+    /// Has `ETH_CONFIRMATIONS` blocks on top, or was finalized on Substrate.
+    Confirmed(Chain, String, H256),
+    Failed(Chain, String, String),
+}
+
+impl Outcome {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Submitted(..) => "submitted",
+            Self::Confirmed(..) => "confirmed",
+            Self::Failed(..) => "failed",
+        }
+    }
+}
+
+/// The subgraph entity an event was converted from, so operators can look at
+/// what the graph node indexed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entity {
+    /// The query field of the entity type, e.g. `message` or `account`.
+    pub kind: String,
+    pub id: String,
+}
+
+impl Entity {
+    pub fn new(kind: &str, id: &str) -> Self {
+        Entity {
+            kind: kind.to_string(),
+            id: id.to_string(),
+        }
+    }
+}
+
+/// An event together with the tracing span it is processed in. The span is
+/// created where the event enters the validator and entered again by every
+/// thread that works on the event, so all of that work lands in one trace.
+#[derive(Debug, Clone)]
+pub struct Envelope {
+    pub event: Event,
+    pub source: Source,
+    pub span: tracing::Span,
+    pub received_at: Instant,
+    /// Set for events from the graph node.
+    pub entity: Option<Entity>,
+}
+
+impl Envelope {
+    pub fn new(event: Event, source: Source) -> Self {
+        let span = tracing::info_span!(
+            "event",
+            message_id = ?event.message_id(),
+            block_number = event.block_number() as u64,
+            source = ?source
+        );
+        Envelope {
+            event,
+            source,
+            span,
+            received_at: Instant::now(),
+            entity: None,
+        }
+    }
+
+    pub fn with_entity(self, entity: Entity) -> Self {
+        Envelope {
+            entity: Some(entity),
+            ..self
+        }
+    }
+
+    /// Reports an outcome in the span it happened in, so it joins the trace
+    /// of the event that caused the call.
+    pub fn outcome(message_id: MessageId, outcome: Outcome) -> Self {
+        Envelope {
+            event: Event::outcome(message_id, outcome),
+            source: Source::Executor,
+            span: tracing::Span::current(),
+            received_at: Instant::now(),
+            entity: None,
+        }
+    }
+
+    /// Records how long the event has been in the validator when it reaches
+    /// `stage`, to tell controller gating apart from executor backlog.
+    pub fn observe_wait(&self, stage: &str) {
+        metrics::observe_seconds(
+            "validator_event_wait_seconds",
+            &[
+                ("stage", stage),
+                ("variant", self.event.name()),
+                ("source", &format!("{:?}", self.source)),
+            ],
+            self.received_at.elapsed().as_secs_f64(),
+        );
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
-enum EventType {
+pub enum EventType {
     Transfer,
     Other,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-enum Status {
+pub enum Status {
     NotReady,
     Active,
     Paused,
     Stopped,
+    /// A chain kept rejecting this validator's calls. Only a restart resumes.
+    Halted,
 }
 
 #[derive(Debug)]
 struct Controller {
     config: Config,
     status: Status,
-    controller_rx: Receiver<Event>,
-    executor_tx: Sender<Event>,
+    controller_rx: Receiver<Envelope>,
+    bus: Arc<Bus>,
     storage: ControllerStorage,
+    policies: Vec<Box<dyn Policy>>,
+    submissions: SubmissionLog,
+    attestations: AttestationLog,
+    divergence_tx: Option<Sender<Vec<Event>>>,
+    auth_guard: AuthGuard,
 }
 
+/// Subscribes to everything the validator observes before the thread starts,
+/// so no event published meanwhile is missed. Events that pass the policies
+/// are published on `Topic::Dispatched`. Reports ready once its storage,
+/// policies and logs are loaded.
 pub fn spawn(
     config: Config,
-    controller_rx: Receiver<Event>,
-    executor_tx: Sender<Event>,
+    bus: Arc<Bus>,
+    approvals: Arc<Approvals>,
+    ready: Ready,
 ) -> thread::JoinHandle<()> {
+    let controller_rx = bus.subscribe_essential(&OBSERVED);
     thread::Builder::new()
         .name("controller".to_string())
         .spawn(move || {
-            let mut controller = Controller::new(config, controller_rx, executor_tx);
+            let mut controller = Controller::new(config, controller_rx, bus, approvals);
+            ready.ok();
             controller.start();
         })
         .expect("can not started controller")
 }
 
+/// Constructors, one per variant, taking the fields in the order they are
+/// declared in.
+impl Event {
+    pub fn eth_bridge_paused_message(message_id: MessageId, block_number: BlockNumber) -> Self {
+        Self::EthBridgePausedMessage {
+            message_id,
+            block_number,
+        }
+    }
+
+    pub fn eth_bridge_resumed_message(message_id: MessageId, block_number: BlockNumber) -> Self {
+        Self::EthBridgeResumedMessage {
+            message_id,
+            block_number,
+        }
+    }
+
+    pub fn eth_bridge_started_message(
+        message_id: MessageId,
+        sender: Option<EthAddress>,
+        block_number: BlockNumber,
+    ) -> Self {
+        Self::EthBridgeStartedMessage {
+            message_id,
+            sender,
+            block_number,
+        }
+    }
+
+    pub fn eth_bridge_stopped_message(
+        message_id: MessageId,
+        sender: Option<EthAddress>,
+        block_number: BlockNumber,
+    ) -> Self {
+        Self::EthBridgeStoppedMessage {
+            message_id,
+            sender,
+            block_number,
+        }
+    }
+
+    pub fn eth_relay_message(
+        message_id: MessageId,
+        sender: EthAddress,
+        recipient: SubAddress,
+        amount: Amount,
+        token: Option<EthAddress>,
+        block_number: BlockNumber,
+    ) -> Self {
+        Self::EthRelayMessage {
+            message_id,
+            sender,
+            recipient,
+            amount,
+            token,
+            block_number,
+        }
+    }
+
+    pub fn eth_approved_relay_message(
+        message_id: MessageId,
+        sender: EthAddress,
+        recipient: SubAddress,
+        amount: Amount,
+        block_number: BlockNumber,
+    ) -> Self {
+        Self::EthApprovedRelayMessage {
+            message_id,
+            sender,
+            recipient,
+            amount,
+            block_number,
+        }
+    }
+
+    pub fn eth_revert_message(
+        message_id: MessageId,
+        sender: EthAddress,
+        amount: Amount,
+        block_number: BlockNumber,
+    ) -> Self {
+        Self::EthRevertMessage {
+            message_id,
+            sender,
+            amount,
+            block_number,
+        }
+    }
+
+    pub fn eth_withdraw_message(message_id: MessageId, block_number: BlockNumber) -> Self {
+        Self::EthWithdrawMessage {
+            message_id,
+            block_number,
+        }
+    }
+
+    pub fn eth_host_account_paused_message(
+        message_id: MessageId,
+        account: EthAddress,
+        timestamp: Timestamp,
+        block_number: BlockNumber,
+    ) -> Self {
+        Self::EthHostAccountPausedMessage {
+            message_id,
+            account,
+            timestamp,
+            block_number,
+        }
+    }
+
+    pub fn eth_host_account_resumed_message(
+        message_id: MessageId,
+        account: EthAddress,
+        timestamp: Timestamp,
+        block_number: BlockNumber,
+    ) -> Self {
+        Self::EthHostAccountResumedMessage {
+            message_id,
+            account,
+            timestamp,
+            block_number,
+        }
+    }
+
+    pub fn eth_guest_account_paused_message(
+        message_id: MessageId,
+        account: SubAddress,
+        timestamp: Timestamp,
+        block_number: BlockNumber,
+    ) -> Self {
+        Self::EthGuestAccountPausedMessage {
+            message_id,
+            account,
+            timestamp,
+            block_number,
+        }
+    }
+
+    pub fn eth_guest_account_resumed_message(
+        message_id: MessageId,
+        account: SubAddress,
+        timestamp: Timestamp,
+        block_number: BlockNumber,
+    ) -> Self {
+        Self::EthGuestAccountResumedMessage {
+            message_id,
+            account,
+            timestamp,
+            block_number,
+        }
+    }
+
+    pub fn eth_set_new_limits(
+        message_id: MessageId,
+        limits: EthLimits,
+        block_number: BlockNumber,
+    ) -> Self {
+        Self::EthSetNewLimits {
+            message_id,
+            limits,
+            block_number,
+        }
+    }
+
+    pub fn eth_validators_list_message(
+        message_id: MessageId,
+        validators: Vec<SubAddress>,
+        quorum: Amount,
+        block_number: BlockNumber,
+    ) -> Self {
+        Self::EthValidatorsListMessage {
+            message_id,
+            validators,
+            quorum,
+            block_number,
+        }
+    }
+
+    pub fn sub_relay_message(message_id: MessageId, block_number: BlockNumber) -> Self {
+        Self::SubRelayMessage {
+            message_id,
+            block_number,
+        }
+    }
+
+    pub fn sub_approved_relay_message(
+        message_id: MessageId,
+        sender: SubAddress,
+        recipient: EthAddress,
+        amount: Amount,
+        token_id: TokenId,
+        block_number: BlockNumber,
+    ) -> Self {
+        Self::SubApprovedRelayMessage {
+            message_id,
+            sender,
+            recipient,
+            amount,
+            token_id,
+            block_number,
+        }
+    }
+
+    pub fn sub_burned_message(
+        message_id: MessageId,
+        sender: SubAddress,
+        recipient: EthAddress,
+        amount: Amount,
+        token_id: TokenId,
+        block_number: BlockNumber,
+    ) -> Self {
+        Self::SubBurnedMessage {
+            message_id,
+            sender,
+            recipient,
+            amount,
+            token_id,
+            block_number,
+        }
+    }
+
+    pub fn sub_minted_message(
+        message_id: MessageId,
+        token_id: TokenId,
+        block_number: BlockNumber,
+    ) -> Self {
+        Self::SubMintedMessage {
+            message_id,
+            token_id,
+            block_number,
+        }
+    }
+
+    pub fn sub_cancellation_confirmed_message(
+        message_id: MessageId,
+        token_id: TokenId,
+        block_number: BlockNumber,
+    ) -> Self {
+        Self::SubCancellationConfirmedMessage {
+            message_id,
+            token_id,
+            block_number,
+        }
+    }
+
+    pub fn sub_account_paused_message(
+        message_id: MessageId,
+        account: SubAddress,
+        timestamp: Timestamp,
+        token_id: TokenId,
+        block_number: BlockNumber,
+    ) -> Self {
+        Self::SubAccountPausedMessage {
+            message_id,
+            account,
+            timestamp,
+            token_id,
+            block_number,
+        }
+    }
+
+    pub fn sub_account_resumed_message(
+        message_id: MessageId,
+        account: SubAddress,
+        timestamp: Timestamp,
+        token_id: TokenId,
+        block_number: BlockNumber,
+    ) -> Self {
+        Self::SubAccountResumedMessage {
+            message_id,
+            account,
+            timestamp,
+            token_id,
+            block_number,
+        }
+    }
+
+    pub fn sub_set_new_limits(
+        message_id: MessageId,
+        limits: GuestLimits,
+        block_number: BlockNumber,
+    ) -> Self {
+        Self::SubSetNewLimits {
+            message_id,
+            limits,
+            block_number,
+        }
+    }
+
+    pub fn unknown(
+        message_id: MessageId,
+        origin: &str,
+        payload: &str,
+        block_number: BlockNumber,
+    ) -> Self {
+        Self::Unknown {
+            message_id,
+            origin: origin.to_string(),
+            payload: payload.to_string(),
+            block_number,
+        }
+    }
+
+    pub fn outcome(message_id: MessageId, outcome: Outcome) -> Self {
+        Self::Outcome {
+            message_id,
+            outcome,
+        }
+    }
+}
+
 impl Event {
     pub fn message_id(&self) -> &H256 {
         match self {
             // Transfers
-            Self::EthRelayMessage(message_id, _, _, _, _) => message_id,
-            Self::EthApprovedRelayMessage(message_id, _, _, _, _) => message_id,
-            Self::EthRevertMessage(message_id, _, _, _) => message_id,
-            Self::EthWithdrawMessage(message_id, _) => message_id,
-            Self::SubRelayMessage(message_id, _) => message_id,
-            Self::SubApprovedRelayMessage(message_id, _, _, _, _, _) => message_id,
-            Self::SubBurnedMessage(message_id, _, _, _, _, _) => message_id,
-            Self::SubMintedMessage(message_id, _, _) => message_id,
-            Self::SubCancellationConfirmedMessage(message_id, _, _) => message_id,
+            Self::EthRelayMessage { message_id, .. }
+            | Self::EthApprovedRelayMessage { message_id, .. }
+            | Self::EthRevertMessage { message_id, .. }
+            | Self::EthWithdrawMessage { message_id, .. }
+            | Self::SubRelayMessage { message_id, .. }
+            | Self::SubApprovedRelayMessage { message_id, .. }
+            | Self::SubBurnedMessage { message_id, .. }
+            | Self::SubMintedMessage { message_id, .. }
+            | Self::SubCancellationConfirmedMessage { message_id, .. }
+            // Bridge management
+            | Self::EthBridgePausedMessage { message_id, .. }
+            | Self::EthBridgeResumedMessage { message_id, .. }
+            | Self::EthBridgeStartedMessage { message_id, .. }
+            | Self::EthBridgeStoppedMessage { message_id, .. }
+            | Self::EthSetNewLimits { message_id, .. }
+            | Self::EthValidatorsListMessage { message_id, .. }
+            // Account management
+            | Self::EthHostAccountPausedMessage { message_id, .. }
+            | Self::EthHostAccountResumedMessage { message_id, .. }
+            | Self::EthGuestAccountPausedMessage { message_id, .. }
+            | Self::EthGuestAccountResumedMessage { message_id, .. }
+            | Self::SubAccountPausedMessage { message_id, .. }
+            | Self::SubAccountResumedMessage { message_id, .. }
+            | Self::SubSetNewLimits { message_id, .. }
+            | Self::Unknown { message_id, .. }
+            | Self::Outcome { message_id, .. } => message_id,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            // Transfers
+            Self::EthRelayMessage { .. } => "EthRelayMessage",
+            Self::EthApprovedRelayMessage { .. } => "EthApprovedRelayMessage",
+            Self::EthRevertMessage { .. } => "EthRevertMessage",
+            Self::EthWithdrawMessage { .. } => "EthWithdrawMessage",
+            Self::SubRelayMessage { .. } => "SubRelayMessage",
+            Self::SubApprovedRelayMessage { .. } => "SubApprovedRelayMessage",
+            Self::SubBurnedMessage { .. } => "SubBurnedMessage",
+            Self::SubMintedMessage { .. } => "SubMintedMessage",
+            Self::SubCancellationConfirmedMessage { .. } => "SubCancellationConfirmedMessage",
             // Bridge management
-            Self::EthBridgePausedMessage(message_id, _) => message_id,
-            Self::EthBridgeResumedMessage(message_id, _) => message_id,
-            Self::EthBridgeStartedMessage(message_id, _, _) => message_id,
-            Self::EthBridgeStoppedMessage(message_id, _, _) => message_id,
-            Self::EthSetNewLimits(message_id, _, _, _, _, _, _, _, _, _, _, _) => message_id,
-            Self::EthValidatorsListMessage(message_id, _, _, _) => message_id,
+            Self::EthBridgePausedMessage { .. } => "EthBridgePausedMessage",
+            Self::EthBridgeResumedMessage { .. } => "EthBridgeResumedMessage",
+            Self::EthBridgeStartedMessage { .. } => "EthBridgeStartedMessage",
+            Self::EthBridgeStoppedMessage { .. } => "EthBridgeStoppedMessage",
+            Self::EthSetNewLimits { .. } => "EthSetNewLimits",
+            Self::EthValidatorsListMessage { .. } => "EthValidatorsListMessage",
             // Account management
-            Self::EthHostAccountPausedMessage(message_id, _, _, _) => message_id,
-            Self::EthHostAccountResumedMessage(message_id, _, _, _) => message_id,
-            Self::EthGuestAccountPausedMessage(message_id, _, _, _) => message_id,
-            Self::EthGuestAccountResumedMessage(message_id, _, _, _) => message_id,
-            Self::SubAccountPausedMessage(message_id, _, _, _, _) => message_id,
-            Self::SubAccountResumedMessage(message_id, _, _, _, _) => message_id,
+            Self::EthHostAccountPausedMessage { .. } => "EthHostAccountPausedMessage",
+            Self::EthHostAccountResumedMessage { .. } => "EthHostAccountResumedMessage",
+            Self::EthGuestAccountPausedMessage { .. } => "EthGuestAccountPausedMessage",
+            Self::EthGuestAccountResumedMessage { .. } => "EthGuestAccountResumedMessage",
+            Self::SubAccountPausedMessage { .. } => "SubAccountPausedMessage",
+            Self::SubAccountResumedMessage { .. } => "SubAccountResumedMessage",
+            Self::SubSetNewLimits { .. } => "SubSetNewLimits",
+            Self::Unknown { .. } => "Unknown",
+            Self::Outcome { .. } => "Outcome",
         }
     }
 
     pub fn block_number(&self) -> u128 {
         match self {
             // Transfers
-            Self::EthRelayMessage(_, _, _, _, block_number) => *block_number,
-            Self::EthApprovedRelayMessage(_, _, _, _, block_number) => *block_number,
-            Self::EthWithdrawMessage(_, block_number) => *block_number,
-            Self::EthRevertMessage(_, _, _, block_number) => *block_number,
-            Self::SubRelayMessage(_, block_number) => *block_number,
-            Self::SubApprovedRelayMessage(_, _, _, _, _, block_number) => *block_number,
-            Self::SubBurnedMessage(_, _, _, _, _, block_number) => *block_number,
-            Self::SubMintedMessage(_, _, block_number) => *block_number,
-            Self::SubCancellationConfirmedMessage(_, _, block_number) => *block_number,
+            Self::EthRelayMessage { block_number, .. }
+            | Self::EthApprovedRelayMessage { block_number, .. }
+            | Self::EthWithdrawMessage { block_number, .. }
+            | Self::EthRevertMessage { block_number, .. }
+            | Self::SubRelayMessage { block_number, .. }
+            | Self::SubApprovedRelayMessage { block_number, .. }
+            | Self::SubBurnedMessage { block_number, .. }
+            | Self::SubMintedMessage { block_number, .. }
+            | Self::SubCancellationConfirmedMessage { block_number, .. }
             // Bridge management
-            Self::EthSetNewLimits(_, _, _, _, _, _, _, _, _, _, _, block_number) => *block_number,
-            Self::EthBridgePausedMessage(_, block_number) => *block_number,
-            Self::EthBridgeResumedMessage(_, block_number) => *block_number,
-            Self::EthBridgeStartedMessage(_, _, block_number) => *block_number,
-            Self::EthBridgeStoppedMessage(_, _, block_number) => *block_number,
-            Self::EthValidatorsListMessage(_, _, _, block_number) => *block_number,
+            | Self::EthSetNewLimits { block_number, .. }
+            | Self::EthBridgePausedMessage { block_number, .. }
+            | Self::EthBridgeResumedMessage { block_number, .. }
+            | Self::EthBridgeStartedMessage { block_number, .. }
+            | Self::EthBridgeStoppedMessage { block_number, .. }
+            | Self::EthValidatorsListMessage { block_number, .. }
             // Account management
-            Self::EthHostAccountPausedMessage(_, _, _, block_number) => *block_number,
-            Self::EthHostAccountResumedMessage(_, _, _, block_number) => *block_number,
-            Self::EthGuestAccountPausedMessage(_, _, _, block_number) => *block_number,
-            Self::EthGuestAccountResumedMessage(_, _, _, block_number) => *block_number,
-            Self::SubAccountPausedMessage(_, _, _, _, block_number) => *block_number,
-            Self::SubAccountResumedMessage(_, _, _, _, block_number) => *block_number,
+            | Self::EthHostAccountPausedMessage { block_number, .. }
+            | Self::EthHostAccountResumedMessage { block_number, .. }
+            | Self::EthGuestAccountPausedMessage { block_number, .. }
+            | Self::EthGuestAccountResumedMessage { block_number, .. }
+            | Self::SubAccountPausedMessage { block_number, .. }
+            | Self::SubAccountResumedMessage { block_number, .. }
+            | Self::SubSetNewLimits { block_number, .. }
+            | Self::Unknown { block_number, .. } => *block_number,
+            // happened in the validator, not in a block
+            Self::Outcome { .. } => 0,
         }
     }
 
-    fn event_type(&self) -> EventType {
+    pub fn event_type(&self) -> EventType {
         match self {
-            Self::EthRelayMessage(..) => EventType::Transfer,
-            Self::EthApprovedRelayMessage(..) => EventType::Transfer,
-            Self::EthRevertMessage(..) => EventType::Other,
-            Self::EthWithdrawMessage(..) => EventType::Transfer,
-            Self::SubRelayMessage(..) => EventType::Transfer,
-            Self::SubApprovedRelayMessage(..) => EventType::Transfer,
-            Self::SubBurnedMessage(..) => EventType::Transfer,
-            Self::SubMintedMessage(..) => EventType::Transfer,
-            Self::SubCancellationConfirmedMessage(..) => EventType::Other,
+            Self::EthRelayMessage { .. } => EventType::Transfer,
+            Self::EthApprovedRelayMessage { .. } => EventType::Transfer,
+            Self::EthRevertMessage { .. } => EventType::Other,
+            Self::EthWithdrawMessage { .. } => EventType::Transfer,
+            Self::SubRelayMessage { .. } => EventType::Transfer,
+            Self::SubApprovedRelayMessage { .. } => EventType::Transfer,
+            Self::SubBurnedMessage { .. } => EventType::Transfer,
+            Self::SubMintedMessage { .. } => EventType::Transfer,
+            Self::SubCancellationConfirmedMessage { .. } => EventType::Other,
             _ => EventType::Other,
         }
     }
 
-    pub fn sender(&self) -> Option<Address> {
+    /// The direction of the transfer this event starts.
+    pub fn starts_transfer(&self) -> Option<Direction> {
         match self {
-            Self::EthRelayMessage(_, eth_address, _, _, _) => Some(Address::Eth(*eth_address)),
-            Self::EthApprovedRelayMessage(_, eth_address, _, _, _) => {
-                Some(Address::Eth(*eth_address))
+            Self::EthRelayMessage { .. } => Some(Direction::EthToSub),
+            Self::SubRelayMessage { .. } => Some(Direction::SubToEth),
+            _ => None,
+        }
+    }
+
+    /// The direction of the transfer this event is a later stage of, other
+    /// than the last one.
+    fn continues_transfer(&self) -> Option<Direction> {
+        match self {
+            Self::EthApprovedRelayMessage { .. } | Self::EthRevertMessage { .. } => {
+                Some(Direction::EthToSub)
             }
-            Self::EthRevertMessage(_, eth_address, _, _) => Some(Address::Eth(*eth_address)),
-            Self::EthWithdrawMessage(_, _) => None,
-            Self::SubRelayMessage(_, _) => None,
-            Self::SubApprovedRelayMessage(_, sub_address, _, _, _, _) => {
-                Some(Address::Sub(*sub_address))
+            Self::SubApprovedRelayMessage { .. } | Self::EthWithdrawMessage { .. } => {
+                Some(Direction::SubToEth)
             }
-            Self::SubBurnedMessage(_, sub_address, _, _, _, _) => Some(Address::Sub(*sub_address)),
-            Self::SubMintedMessage(_, _, _) => None,
-            Self::SubCancellationConfirmedMessage(_, _, _) => None,
             _ => None,
         }
     }
+
+    /// Whether this event is the last stage of a transfer.
+    fn finishes_transfer(&self) -> bool {
+        match self {
+            Self::SubMintedMessage { .. }
+            | Self::SubCancellationConfirmedMessage { .. }
+            | Self::SubBurnedMessage { .. } => true,
+            _ => false,
+        }
+    }
+
+    pub fn sender(&self) -> Option<Address> {
+        match self {
+            Self::EthRelayMessage { sender, .. }
+            | Self::EthApprovedRelayMessage { sender, .. }
+            | Self::EthRevertMessage { sender, .. } => Some(Address::Eth(*sender)),
+            Self::SubApprovedRelayMessage { sender, .. }
+            | Self::SubBurnedMessage { sender, .. } => Some(Address::Sub(*sender)),
+            _ => None,
+        }
+    }
+
+    pub fn recipient(&self) -> Option<Address> {
+        match self {
+            Self::EthRelayMessage { recipient, .. }
+            | Self::EthApprovedRelayMessage { recipient, .. } => Some(Address::Sub(*recipient)),
+            Self::SubApprovedRelayMessage { recipient, .. }
+            | Self::SubBurnedMessage { recipient, .. } => Some(Address::Eth(*recipient)),
+            _ => None,
+        }
+    }
+
+    pub fn amount(&self) -> Option<Amount> {
+        match self {
+            Self::EthRelayMessage { amount, .. }
+            | Self::EthApprovedRelayMessage { amount, .. }
+            | Self::EthRevertMessage { amount, .. }
+            | Self::SubApprovedRelayMessage { amount, .. }
+            | Self::SubBurnedMessage { amount, .. } => Some(*amount),
+            _ => None,
+        }
+    }
+
+    /// Two events with the same message id collide when they disagree on any
+    /// part of the transfer payload both of them carry.
+    pub fn collides_with(&self, other: &Event) -> bool {
+        fn differs<T: PartialEq>(a: Option<T>, b: Option<T>) -> bool {
+            match (a, b) {
+                (Some(a), Some(b)) => a != b,
+                _ => false,
+            }
+        }
+
+        self.message_id() == other.message_id()
+            && (differs(self.sender(), other.sender())
+                || differs(self.recipient(), other.recipient())
+                || differs(self.amount(), other.amount()))
+    }
 }
 
 impl Controller {
-    fn new(config: Config, controller_rx: Receiver<Event>, executor_tx: Sender<Event>) -> Self {
+    fn new(
+        config: Config,
+        controller_rx: Receiver<Envelope>,
+        bus: Arc<Bus>,
+        approvals: Arc<Approvals>,
+    ) -> Self {
+        let spill_dir = Path::new(&config.data_dir).join("queues");
+        let mut storage = match fs::create_dir_all(&spill_dir) {
+            Ok(()) => ControllerStorage::with_spill_dir(spill_dir, config.queue_memory_cap),
+            Err(e) => {
+                log::error!(
+                    "can not create {:?}, keeping queues in memory: {}",
+                    spill_dir,
+                    e
+                );
+                ControllerStorage::new()
+            }
+        };
+        storage.set_max_pending_transfers(Direction::EthToSub, config.max_pending_transfers);
+        storage.set_max_pending_transfers(Direction::SubToEth, config.max_pending_transfers);
+        storage.set_approvals(approvals);
+        storage.set_pause_grace(Duration::from_secs(config.account_pause_grace_secs));
+        if let Err(e) = block_paused_substrate_accounts(&config, &mut storage) {
+            // without them transfers of paused accounts would go through
+            fatal::stop(&format!("can not read the paused substrate accounts: {}", e));
+        }
+        let policies = policy::chain(
+            &config.policies,
+            &config.data_dir,
+            config.timelock,
+            config.approval_threshold,
+        );
+        let submissions = SubmissionLog::open(&config.data_dir);
+        let attestations =
+            AttestationLog::open(&config.data_dir, &config.sub_validator_mnemonic_phrase);
+        let auth_guard = AuthGuard::new(config.auth_failure_limit);
+        #[cfg(feature = "graph-node")]
+        let divergence_tx = if config.divergence_check_secs > 0 && config.has_graph_node_listener()
+        {
+            Some(divergence::spawn(config.clone()))
+        } else {
+            None
+        };
+        #[cfg(not(feature = "graph-node"))]
+        let divergence_tx = None;
         Controller {
             config,
             status: Status::Active,
             controller_rx,
-            executor_tx,
-            storage: ControllerStorage::new(),
+            bus,
+            storage,
+            policies,
+            submissions,
+            attestations,
+            divergence_tx,
+            auth_guard,
         }
     }
 
     fn start(&mut self) {
         log::info!("current status: {:?}", self.status);
+        let config = &self.config;
         let storage = &mut self.storage;
         let controller_rx = &self.controller_rx;
         let status = &mut self.status;
-        let executor_tx = &self.executor_tx;
-        controller_rx
-            .iter()
-            .for_each(|event| match storage.put_event(&event) {
-                Ok(()) => {
-                    log::info!("received event: {:?}", event);
-                    change_status(status, &event);
-                    match status {
-                        Status::Active => {
-                            handle_account_control_events(storage, &event);
-                            let deferred_events =
-                            storage.iter_events_queue().cloned().collect::<Vec<_>>();
-                            deferred_events.iter().cloned().for_each(|event| {
-                                handle_account_control_events(storage, &event);
-                                executor_tx.send(event).expect("can not sent event")
-                            });
-                            storage.clear_events_queue();
-                            if event.event_type() == EventType::Transfer
-                            && storage.is_account_blocked(event.sender())
-                            {
-                                log::info!("putting event in a queue: {:?}", event);
-                                storage.put_event_to_account_queue(event)
-                            } else {
-                                executor_tx.send(event).expect("can not sent event")
-                            }
-                        }
-                        Status::NotReady | Status::Paused | Status::Stopped => {
-                            storage.put_event_to_queue(event)
-                        }
+        let bus = &*self.bus;
+        let policies = &self.policies;
+        let submissions = &self.submissions;
+        let attestations = &self.attestations;
+        let divergence_tx = &self.divergence_tx;
+        let auth_guard = &mut self.auth_guard;
+        let divergence_check = Duration::from_secs(config.divergence_check_secs);
+        let mut published_at = Instant::now() - TICK;
+        let mut sampled_at = Instant::now();
+        for event in storage.take_drain_held() {
+            log::info!("releasing transfer held by the drain before the restart: {:?}", event);
+            let envelope = Envelope::new(event, Source::Replay);
+            handle_envelope(config, policies, status, storage, bus, attestations, envelope)
+        }
+        loop {
+            match controller_rx.recv_timeout(TICK) {
+                Ok(Envelope {
+                    event: Event::Outcome {
+                        message_id,
+                        outcome,
+                    },
+                    span,
+                    ..
+                }) => {
+                    let _enter = span.enter();
+                    let _context = fatal::message_context(message_id);
+                    handle_outcome(submissions, message_id, &outcome);
+                    if let Some(chain) = auth_guard.observe(&outcome) {
+                        log::error!(
+                            "{:?} rejected {} calls in a row, is this validator still in the \
+                             validator set with the right key? Not submitting anything until \
+                             restarted",
+                            chain,
+                            config.auth_failure_limit
+                        );
+                        metrics::set_gauge("validator_halted", &[], 1.0);
+                        auth_guard::halt();
+                        *status = Status::Halted;
                     }
                 }
-                Err(e) => log::debug!("controller storage error: {:?}", e),
-            })
+                Ok(envelope) => {
+                    handle_envelope(
+                        config,
+                        policies,
+                        status,
+                        storage,
+                        bus,
+                        attestations,
+                        envelope,
+                    )
+                }
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+            for event in storage.release_timelocked(Instant::now()) {
+                log::info!("timelock is over, releasing event: {:?}", event);
+                dispatch_event(
+                    policies,
+                    status,
+                    storage,
+                    bus,
+                    attestations,
+                    Envelope::new(event, Source::Replay),
+                )
+            }
+            for (event, decision) in storage.take_decided_approvals() {
+                match decision {
+                    Decision::Approved => {
+                        log::info!("transfer approved, releasing event: {:?}", event);
+                        dispatch_event(
+                            policies,
+                            status,
+                            storage,
+                            bus,
+                            attestations,
+                            Envelope::new(event, Source::Replay),
+                        )
+                    }
+                    Decision::Rejected => {
+                        log::warn!("transfer rejected, dropping event: {:?}", event)
+                    }
+                }
+            }
+            // at most once per tick, walking the queues is not free
+            if published_at.elapsed() >= TICK {
+                let mut state = storage.debug_state();
+                state["status"] = serde_json::json!(format!("{:?}", status));
+                debug_state::publish("controller", state);
+                published_at = Instant::now();
+            }
+            if let Some(divergence_tx) = divergence_tx {
+                if sampled_at.elapsed() >= divergence_check {
+                    let sample = storage.sample_transfers_in_flight(config.divergence_sample_size);
+                    let _ = divergence_tx.send(sample);
+                    sampled_at = Instant::now();
+                }
+            }
+        }
+    }
+}
+
+/// Records submissions in `DATA_DIR/submissions.jsonl`, counts outcomes per
+/// call and alerts on failed calls.
+fn handle_outcome(submissions: &SubmissionLog, message_id: H256, outcome: &Outcome) {
+    let (chain, call) = match outcome {
+        Outcome::Submitted(chain, call, _)
+        | Outcome::Confirmed(chain, call, _)
+        | Outcome::Failed(chain, call, _) => (chain, call),
+    };
+    metrics::increment(
+        "validator_outcomes_total",
+        &[
+            ("outcome", outcome.name()),
+            ("chain", &format!("{:?}", chain)),
+            ("call", call),
+        ],
+    );
+    match outcome {
+        Outcome::Submitted(chain, call, tx_hash) => {
+            submissions.record(message_id, *chain, call, *tx_hash)
+        }
+        Outcome::Confirmed(chain, call, tx_hash) => log::info!(
+            "{:?} {} {:?} of {:?} is confirmed",
+            chain,
+            call,
+            tx_hash,
+            message_id
+        ),
+        Outcome::Failed(chain, call, reason) => log::error!(
+            "{:?} {} of {:?} failed: {}",
+            chain,
+            call,
+            message_id,
+            reason
+        ),
+    }
+}
+
+/// Records the event and, if it is new, dispatches it together with the
+/// events queued while the bridge was not active.
+fn handle_envelope(
+    config: &Config,
+    policies: &[Box<dyn Policy>],
+    status: &mut Status,
+    storage: &mut ControllerStorage,
+    bus: &Bus,
+    attestations: &AttestationLog,
+    envelope: Envelope,
+) {
+    let _enter = envelope.span.enter();
+    let _context = fatal::message_context(*envelope.event.message_id());
+    envelope.observe_wait("controller");
+    let event = &envelope.event;
+    if let Event::Unknown {
+        message_id,
+        origin,
+        payload,
+        ..
+    } = event
+    {
+        if !storage.put_unknown_event(*message_id, payload) {
+            return;
+        }
+        log::warn!(
+            "ignoring message {:?} from {} that matches no known event: {}",
+            message_id,
+            origin,
+            payload
+        );
+        metrics::increment("validator_unknown_events_total", &[("origin", origin)]);
+        return;
+    }
+    if let Event::SubSetNewLimits { .. } = event {
+        log::info!("substrate limits changed: {:?}", event);
+        handle_limit_events(config, storage, event);
+        return;
+    }
+    match storage.put_event(event) {
+        Ok(()) => {
+            log::info!("received event from {:?}: {:?}", envelope.source, event);
+            change_status(status, event);
+            if *status == Status::Active {
+                handle_account_control_events(storage, event);
+                handle_limit_events(config, storage, event);
+                // events held back again go to the end, take each one once
+                for _ in 0..storage.queued_events() {
+                    let event = match storage.pop_event_from_queue() {
+                        Some(event) => event,
+                        None => break,
+                    };
+                    handle_account_control_events(storage, &event);
+                    dispatch_event(
+                        policies,
+                        status,
+                        storage,
+                        bus,
+                        attestations,
+                        Envelope::new(event, Source::Replay),
+                    )
+                }
+            }
+            dispatch_event(policies, status, storage, bus, attestations, envelope.clone())
+        }
+        Err(controller_storage::Error::Duplicate) if envelope.source == Source::OrphanSweep => {
+            log::warn!("re-driving orphaned message: {:?}", event);
+            dispatch_event(policies, status, storage, bus, attestations, envelope.clone())
+        }
+        // the graph node listener fetches the last block again every poll, a
+        // steady trickle from it is normal, a burst is not
+        Err(controller_storage::Error::Duplicate) => {
+            log::debug!("dropping duplicate event from {:?}: {:?}", envelope.source, event);
+            metrics::increment(
+                "validator_duplicate_events_total",
+                &[
+                    ("variant", event.name()),
+                    ("source", &format!("{:?}", envelope.source)),
+                ],
+            );
+        }
+        Err(controller_storage::Error::Collision) => log::error!(
+            "message id collision, possible replay attack, refusing to process: {:?}",
+            event
+        ),
     }
 }
 
@@ -278,50 +1215,191 @@ fn change_status(status: &mut Status, event: &Event) {
     let mut status_changed = false;
     match status {
         Status::Active => match event {
-            Event::EthBridgePausedMessage(..) => {
+            Event::EthBridgePausedMessage { .. } => {
                 *status = Status::Paused;
                 status_changed = true;
             }
-            Event::EthBridgeStoppedMessage(..) => {
+            Event::EthBridgeStoppedMessage { .. } => {
                 *status = Status::Stopped;
                 status_changed = true;
             }
             _ => (),
         },
         Status::NotReady | Status::Paused => match event {
-            Event::EthBridgeResumedMessage(..) | Event::EthBridgeStartedMessage(..) => {
+            Event::EthBridgeResumedMessage { .. } | Event::EthBridgeStartedMessage { .. } => {
                 *status = Status::Active;
                 status_changed = true;
             }
             _ => (),
         },
         Status::Stopped => {
-            if let Event::EthBridgeStartedMessage(..) = event {
+            if let Event::EthBridgeStartedMessage { .. } = event {
                 *status = Status::Active;
                 status_changed = true;
             }
         }
+        Status::Halted => (),
     }
     if status_changed {
         log::info!("current status: {:?}", status);
     }
 }
 
+/// Runs the event through the policies, attests the verdict and either
+/// forwards the event or holds it in the queue the verdict asks for.
+fn dispatch_event(
+    policies: &[Box<dyn Policy>],
+    status: &Status,
+    storage: &mut ControllerStorage,
+    bus: &Bus,
+    attestations: &AttestationLog,
+    envelope: Envelope,
+) {
+    let verdict = policy::evaluate(policies, status, storage, &envelope.event);
+    attestations.attest(&envelope.event, envelope.entity.as_ref(), verdict.name());
+    match verdict {
+        Verdict::Forward => forward_event(status, storage, bus, envelope),
+        Verdict::Queue => storage.put_event_to_queue(envelope.event),
+        Verdict::HoldForAccount => {
+            log::info!("putting event in a queue: {:?}", envelope.event);
+            storage.put_event_to_account_queue(envelope.event)
+        }
+        Verdict::Defer(direction) => {
+            log::info!(
+                "{} {:?} transfers in flight, deferring event: {:?}",
+                storage.pending_transfers(direction),
+                direction,
+                envelope.event
+            );
+            storage.defer_transfer(direction, envelope.event)
+        }
+        Verdict::Timelock(delay) => {
+            log::info!("holding event for {:?}: {:?}", delay, envelope.event);
+            storage.timelock(envelope.event, Instant::now() + delay)
+        }
+        Verdict::AwaitApproval => {
+            log::warn!("transfer needs manual approval: {:?}", envelope.event);
+            storage.put_event_to_approval_queue(envelope.event)
+        }
+        Verdict::HoldForDrain => {
+            log::info!("draining, holding new transfer: {:?}", envelope.event);
+            storage.hold_for_drain(envelope.event)
+        }
+    }
+}
+
+/// Publishes the event to the executor and keeps track of transfers in flight.
+/// A transfer first seen at a later stage, as unfinalized messages are after a
+/// restart, is pending on its chain all the same and counts towards the limit.
+/// Deferred transfers are sent as earlier ones finish, while the bridge is
+/// active.
+fn forward_event(
+    status: &Status,
+    storage: &mut ControllerStorage,
+    bus: &Bus,
+    envelope: Envelope,
+) {
+    let event = &envelope.event;
+    if event.finishes_transfer() {
+        storage.finish_transfer(event.message_id());
+    } else if let Some(direction) = event.continues_transfer() {
+        storage.resume_transfer(direction, *event.message_id());
+    }
+    if let Some(direction) = event.starts_transfer() {
+        storage.start_transfer(direction, *event.message_id());
+    }
+    bus.publish_to(Topic::Dispatched, envelope);
+    if *status != Status::Active {
+        return;
+    }
+    storage
+        .release_deferred_transfers()
+        .into_iter()
+        .for_each(|event| bus.publish_to(Topic::Dispatched, Envelope::new(event, Source::Replay)));
+}
+
+/// The bridge's own pending transaction limits tighten the configured ones.
+fn handle_limit_events(config: &Config, storage: &mut ControllerStorage, event: &Event) {
+    match event {
+        Event::EthSetNewLimits { limits, .. } => {
+            storage.set_max_pending_transfers(
+                Direction::EthToSub,
+                pending_limit(config, &limits.max_host_pending_transaction_limit),
+            );
+            storage.set_max_pending_transfers(
+                Direction::SubToEth,
+                pending_limit(config, &limits.guest.max_pending_transaction_limit),
+            );
+        }
+        Event::SubSetNewLimits { limits, .. } => {
+            storage.set_max_pending_transfers(
+                Direction::SubToEth,
+                pending_limit(config, &limits.max_pending_transaction_limit),
+            );
+        }
+        _ => (),
+    }
+}
+
+/// The bridge's pending transaction limit, capped by `MAX_PENDING_TRANSFERS`.
+fn pending_limit(config: &Config, bridge_limit: &Amount) -> Option<usize> {
+    // zero means the bridge sets no limit
+    let bridge_limit = if bridge_limit.is_zero() {
+        return config.max_pending_transfers;
+    } else if *bridge_limit > U256::from(usize::max_value()) {
+        usize::max_value()
+    } else {
+        bridge_limit.as_usize()
+    };
+    Some(
+        config
+            .max_pending_transfers
+            .map_or(bridge_limit, |l| l.min(bridge_limit)),
+    )
+}
+
+/// Guest-side pauses that never reached Ethereum are unknown to the graph
+/// node, so the paused accounts are also read from the Bridge pallet.
+fn block_paused_substrate_accounts(
+    config: &Config,
+    storage: &mut ControllerStorage,
+) -> Result<(), String> {
+    let accounts = substrate_transactions::get_paused_accounts(
+        config.sub_api_url.clone(),
+        clock::begin_of_this_day(),
+    )?;
+    for account in accounts {
+        let sub_address: [u8; 32] = account.into();
+        let sub_address = H256::from(sub_address);
+        storage.block_account(Address::Sub(sub_address));
+        log::info!("substrate account {:?} is paused in the Bridge pallet", sub_address);
+    }
+    Ok(())
+}
+
 fn handle_account_control_events(storage: &mut ControllerStorage, event: &Event) {
     match event {
-        Event::EthHostAccountPausedMessage(_, eth_address, _, _) => {
+        Event::EthHostAccountPausedMessage { account: eth_address, .. } => {
             storage.block_account(Address::Eth(*eth_address));
             log::info!("ethereum account {:?} is blocked", eth_address);
         }
-        Event::EthHostAccountResumedMessage(_, eth_address, _, _) => {
+        Event::EthHostAccountResumedMessage { account: eth_address, .. } => {
             storage.unblock_account(Address::Eth(*eth_address));
             log::info!("ethereum account {:?} is unblocked", eth_address);
         }
-        Event::EthGuestAccountPausedMessage(_, sub_address, _, _) => {
+        Event::EthGuestAccountPausedMessage { account: sub_address, .. } => {
+            storage.block_account(Address::Sub(*sub_address));
+            log::info!("substrate account {:?} is blocked", sub_address);
+        }
+        Event::EthGuestAccountResumedMessage { account: sub_address, .. } => {
+            storage.unblock_account(Address::Sub(*sub_address));
+            log::info!("substrate account {:?} is unblocked", sub_address);
+        }
+        Event::SubAccountPausedMessage { account: sub_address, .. } => {
             storage.block_account(Address::Sub(*sub_address));
             log::info!("substrate account {:?} is blocked", sub_address);
         }
-        Event::EthGuestAccountResumedMessage(_, sub_address, _, _) => {
+        Event::SubAccountResumedMessage { account: sub_address, .. } => {
             storage.unblock_account(Address::Sub(*sub_address));
             log::info!("substrate account {:?} is unblocked", sub_address);
         }
@@ -1,36 +1,93 @@
 use log;
+use serde_json::{json, Value};
 use web3::types::H256;
 
-use std::collections::HashMap;
-use std::iter::Iterator;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::controller::{Address, Event};
+use crate::approvals::{Approvals, Decision};
+use crate::controller::{Address, Direction, Event};
+use crate::spill_queue::SpillQueue;
+
+/// New transfers held by a drain, in the spill dir.
+const DRAIN_HELD_FILE: &str = "drain_held.jsonl";
 
 #[derive(Debug)]
 pub struct ControllerStorage {
     events: HashMap<H256, Event>,
-    events_queue: Vec<Event>,
-    events_of_blocked_accounts: HashMap<Address, Vec<Event>>,
+    events_queue: SpillQueue<Event>,
+    events_of_blocked_accounts: HashMap<Address, SpillQueue<Event>>,
+    /// When each blocked account was blocked, for the pause grace period.
+    blocked_at: HashMap<Address, Instant>,
+    pause_grace: Duration,
+    /// Queues keep up to `queue_memory_cap` events in memory and spill the
+    /// rest to files in `spill_dir`.
+    spill_dir: Option<PathBuf>,
+    queue_memory_cap: usize,
+    collisions: HashMap<H256, Vec<Event>>,
+    transfers_in_flight: HashMap<H256, Direction>,
+    /// Transfers past their last stage, so a late event does not count them again.
+    finished_transfers: HashSet<H256>,
+    deferred_transfers: HashMap<Direction, VecDeque<Event>>,
+    max_pending_transfers: HashMap<Direction, usize>,
+    timelocked: Vec<(Instant, Event)>,
+    drain_held: Vec<Event>,
+    /// Messages whose timelock is over, their events are not held again.
+    timelock_released: HashSet<H256>,
+    /// Message ids and payloads of `Event::Unknown` already reported.
+    unknown_events: HashSet<(H256, String)>,
+    /// Shared with the admin API, where operators decide on flagged events.
+    approvals: Arc<Approvals>,
 }
 
 #[derive(Debug, PartialEq)]
 pub enum Error {
     Duplicate,
+    Collision,
 }
 
 impl ControllerStorage {
     pub fn new() -> Self {
         ControllerStorage {
             events: HashMap::new(),
-            events_queue: Vec::new(),
+            events_queue: SpillQueue::in_memory(),
             events_of_blocked_accounts: HashMap::new(),
+            blocked_at: HashMap::new(),
+            pause_grace: Duration::from_secs(0),
+            spill_dir: None,
+            queue_memory_cap: usize::max_value(),
+            collisions: HashMap::new(),
+            transfers_in_flight: HashMap::new(),
+            finished_transfers: HashSet::new(),
+            deferred_transfers: HashMap::new(),
+            max_pending_transfers: HashMap::new(),
+            timelocked: vec![],
+            drain_held: vec![],
+            timelock_released: HashSet::new(),
+            unknown_events: HashSet::new(),
+            approvals: Arc::new(Approvals::in_memory()),
         }
     }
 
     pub fn put_event(&mut self, event: &Event) -> Result<(), Error> {
         let message_id = event.message_id();
+        if let Some(versions) = self.collisions.get_mut(message_id) {
+            if !versions.contains(event) {
+                versions.push(event.clone());
+            }
+            return Err(Error::Collision);
+        }
         match self.events.get(message_id) {
             Some(e) if e == event => Err(Error::Duplicate),
+            Some(e) if e.collides_with(event) => {
+                self.collisions
+                    .insert(*message_id, vec![e.clone(), event.clone()]);
+                Err(Error::Collision)
+            }
             _ => {
                 self.events.insert(*message_id, event.clone());
                 Ok(())
@@ -38,37 +95,107 @@ impl ControllerStorage {
         }
     }
 
+    /// Returns true the first time an unknown message is seen. The graph
+    /// node listener fetches the messages of the last block again every poll.
+    pub fn put_unknown_event(&mut self, message_id: H256, payload: &str) -> bool {
+        self.unknown_events.insert((message_id, payload.to_string()))
+    }
+
+    pub fn collisions(&self, message_id: &H256) -> Option<&Vec<Event>> {
+        self.collisions.get(message_id)
+    }
+
+    pub fn with_spill_dir(spill_dir: PathBuf, queue_memory_cap: usize) -> Self {
+        let mut storage = ControllerStorage::new();
+        storage.events_queue =
+            SpillQueue::with_spill_file(spill_dir.join("events_queue.jsonl"), queue_memory_cap);
+        storage.spill_dir = Some(spill_dir);
+        storage.queue_memory_cap = queue_memory_cap;
+        storage
+    }
+
+    /// Keeps a new transfer until the drained validator is restarted. A
+    /// drain ends with the restart, so the transfer is written to the spill
+    /// dir for the next start to take it from there.
+    pub fn hold_for_drain(&mut self, event: Event) {
+        if let Some(spill_dir) = &self.spill_dir {
+            let path = spill_dir.join(DRAIN_HELD_FILE);
+            let line = serde_json::to_string(&event).expect("event serializes");
+            let written = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .and_then(|mut file| writeln!(file, "{}", line));
+            if let Err(e) = written {
+                log::error!("can not write {:?} to {:?}: {}", event, path, e);
+            }
+        }
+        self.drain_held.push(event);
+    }
+
+    /// The transfers a drain held before the restart, the file is removed
+    /// once they are taken.
+    pub fn take_drain_held(&mut self) -> Vec<Event> {
+        let path = match &self.spill_dir {
+            Some(spill_dir) => spill_dir.join(DRAIN_HELD_FILE),
+            None => return vec![],
+        };
+        let events = fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        let _ = fs::remove_file(&path);
+        events
+    }
+
     pub fn put_event_to_queue(&mut self, event: Event) {
-        self.events_queue.push(event)
+        self.events_queue.push_back(event)
     }
 
-    pub fn iter_events_queue(&self) -> impl Iterator<Item = &Event> {
-        self.events_queue.iter()
+    pub fn queued_events(&self) -> usize {
+        self.events_queue.len()
     }
 
-    pub fn clear_events_queue(&mut self) {
-        self.events_queue.clear();
+    pub fn pop_event_from_queue(&mut self) -> Option<Event> {
+        self.events_queue.pop_front()
     }
 
     pub fn block_account(&mut self, address: Address) {
         if !self.events_of_blocked_accounts.contains_key(&address) {
-            self.events_of_blocked_accounts.insert(address, vec![]);
+            let queue = self.new_account_queue(&address);
+            self.events_of_blocked_accounts.insert(address, queue);
+            self.blocked_at.insert(address, Instant::now());
         } else {
             log::info!("account {:?} is already blocked", address);
         }
     }
 
     pub fn unblock_account(&mut self, address: Address) {
-        match self.events_of_blocked_accounts.get(&address) {
-            Some(queue) => {
-                let mut queue = queue.to_vec();
-                self.events_queue.append(queue.as_mut());
-                self.events_of_blocked_accounts.remove(&address);
+        self.blocked_at.remove(&address);
+        match self.events_of_blocked_accounts.remove(&address) {
+            Some(mut queue) => {
+                while let Some(event) = queue.pop_front() {
+                    self.events_queue.push_back(event);
+                }
             }
             None => log::warn!("can not found account queue for {:?}", address),
         }
     }
 
+    fn new_account_queue(&self, address: &Address) -> SpillQueue<Event> {
+        match &self.spill_dir {
+            Some(spill_dir) => {
+                let file_name = match address {
+                    Address::Eth(address) => format!("account_eth_{:x}.jsonl", address),
+                    Address::Sub(address) => format!("account_sub_{:x}.jsonl", address),
+                };
+                SpillQueue::with_spill_file(spill_dir.join(file_name), self.queue_memory_cap)
+            }
+            None => SpillQueue::in_memory(),
+        }
+    }
+
     pub fn is_account_blocked(&self, address: Option<Address>) -> bool {
         match address {
             None => false,
@@ -76,24 +203,215 @@ impl ControllerStorage {
         }
     }
 
+    /// How long transfers of a just paused account that are already in
+    /// flight may go on.
+    pub fn set_pause_grace(&mut self, pause_grace: Duration) {
+        self.pause_grace = pause_grace;
+    }
+
+    /// Whether the event continues a transfer that was in flight when its
+    /// sender got paused less than the pause grace period before `now`.
+    pub fn is_in_pause_grace(&self, event: &Event, now: Instant) -> bool {
+        let blocked_at = match event.sender().and_then(|sender| self.blocked_at.get(&sender)) {
+            Some(blocked_at) => *blocked_at,
+            None => return false,
+        };
+        now < blocked_at + self.pause_grace
+            && self.transfers_in_flight.contains_key(event.message_id())
+    }
+
+    /// Limits how many transfers in `direction` may be in flight, `None` lifts the limit.
+    pub fn set_max_pending_transfers(&mut self, direction: Direction, limit: Option<usize>) {
+        match limit {
+            Some(limit) => self.max_pending_transfers.insert(direction, limit),
+            None => self.max_pending_transfers.remove(&direction),
+        };
+    }
+
+    pub fn pending_transfers(&self, direction: Direction) -> usize {
+        self.transfers_in_flight
+            .values()
+            .filter(|d| **d == direction)
+            .count()
+    }
+
+    /// Whether the transfer is already in flight or there is room for it
+    /// within the limit.
+    pub fn can_start_transfer(&self, direction: Direction, message_id: &H256) -> bool {
+        self.transfers_in_flight.contains_key(message_id) || self.has_room_for_transfer(direction)
+    }
+
+    pub fn start_transfer(&mut self, direction: Direction, message_id: H256) {
+        self.transfers_in_flight.insert(message_id, direction);
+    }
+
+    /// Counts a transfer seen past its first stage as in flight, unless it
+    /// already finished.
+    pub fn resume_transfer(&mut self, direction: Direction, message_id: H256) {
+        if !self.finished_transfers.contains(&message_id) {
+            self.transfers_in_flight.entry(message_id).or_insert(direction);
+        }
+    }
+
+    pub fn finish_transfer(&mut self, message_id: &H256) {
+        self.transfers_in_flight.remove(message_id);
+        self.finished_transfers.insert(*message_id);
+    }
+
+    /// The latest event of up to `count` transfers in flight, lowest block
+    /// number first: the longer a transfer is in flight, the likelier it
+    /// missed an event.
+    pub fn sample_transfers_in_flight(&self, count: usize) -> Vec<Event> {
+        let mut sample = self
+            .transfers_in_flight
+            .keys()
+            .filter_map(|message_id| self.events.get(message_id))
+            .cloned()
+            .collect::<Vec<_>>();
+        sample.sort_by_key(Event::block_number);
+        sample.truncate(count);
+        sample
+    }
+
+    pub fn defer_transfer(&mut self, direction: Direction, event: Event) {
+        self.deferred_transfers
+            .entry(direction)
+            .or_insert_with(VecDeque::new)
+            .push_back(event);
+    }
+
+    /// Takes deferred transfers, oldest first, while there is room for them
+    /// and records them as in flight.
+    pub fn release_deferred_transfers(&mut self) -> Vec<Event> {
+        let mut released = vec![];
+        for direction in &[Direction::EthToSub, Direction::SubToEth] {
+            while self.has_room_for_transfer(*direction) {
+                let event = match self
+                    .deferred_transfers
+                    .get_mut(direction)
+                    .and_then(VecDeque::pop_front)
+                {
+                    Some(event) => event,
+                    None => break,
+                };
+                self.transfers_in_flight
+                    .insert(*event.message_id(), *direction);
+                released.push(event);
+            }
+        }
+        released
+    }
+
+    fn has_room_for_transfer(&self, direction: Direction) -> bool {
+        match self.max_pending_transfers.get(&direction) {
+            Some(limit) => self.pending_transfers(direction) < *limit,
+            None => true,
+        }
+    }
+
+    pub fn timelock(&mut self, event: Event, until: Instant) {
+        self.timelocked.push((until, event));
+    }
+
+    /// Takes the timelocked events that are due at `now`.
+    pub fn release_timelocked(&mut self, now: Instant) -> Vec<Event> {
+        let (due, held): (Vec<_>, Vec<_>) = self
+            .timelocked
+            .drain(..)
+            .partition(|(until, _)| *until <= now);
+        self.timelocked = held;
+        due.into_iter()
+            .map(|(_, event)| {
+                self.timelock_released.insert(*event.message_id());
+                event
+            })
+            .collect()
+    }
+
+    pub fn is_timelock_released(&self, message_id: &H256) -> bool {
+        self.timelock_released.contains(message_id)
+    }
+
+    pub fn set_approvals(&mut self, approvals: Arc<Approvals>) {
+        self.approvals = approvals;
+    }
+
+    pub fn put_event_to_approval_queue(&mut self, event: Event) {
+        self.approvals.flag(event)
+    }
+
+    pub fn is_approved(&self, message_id: &H256) -> bool {
+        self.approvals.is_approved(message_id)
+    }
+
+    pub fn take_decided_approvals(&mut self) -> Vec<(Event, Decision)> {
+        self.approvals.take_decided()
+    }
+
     pub fn put_event_to_account_queue(&mut self, event: Event) {
         let sender = event
             .sender()
             .expect("called put_event_to_account_queue for invalid event");
-        match self.events_of_blocked_accounts.get(&sender) {
-            Some(queue) => {
-                let mut queue = queue.to_vec();
-                queue.push(event);
-                self.events_of_blocked_accounts.insert(sender, queue);
-            }
+        match self.events_of_blocked_accounts.get_mut(&sender) {
+            Some(queue) => queue.push_back(event),
             None => log::warn!("can not found account queue for {:?}", sender),
         }
     }
+
+    /// Message ids held in each queue, for `/debug/state`. Spilled events
+    /// are only counted.
+    pub fn debug_state(&self) -> Value {
+        let blocked_accounts = self
+            .events_of_blocked_accounts
+            .iter()
+            .map(|(address, queue)| {
+                json!({ "account": format!("{:?}", address), "queue": queue_state(queue) })
+            })
+            .collect::<Vec<_>>();
+        let in_flight = self
+            .transfers_in_flight
+            .iter()
+            .map(|(message_id, direction)| {
+                json!({ "message_id": message_id, "direction": format!("{:?}", direction) })
+            })
+            .collect::<Vec<_>>();
+        let deferred = self
+            .deferred_transfers
+            .iter()
+            .map(|(direction, events)| {
+                let message_ids = events.iter().map(Event::message_id).collect::<Vec<_>>();
+                (format!("{:?}", direction), json!(message_ids))
+            })
+            .collect::<serde_json::Map<_, _>>();
+        let timelocked = self
+            .timelocked
+            .iter()
+            .map(|(_, event)| event.message_id())
+            .collect::<Vec<_>>();
+        json!({
+            "events": self.events.len(),
+            "queue": queue_state(&self.events_queue),
+            "blocked_accounts": blocked_accounts,
+            "transfers_in_flight": in_flight,
+            "deferred_transfers": deferred,
+            "timelocked": timelocked,
+            "drain_held": self.drain_held.iter().map(Event::message_id).collect::<Vec<_>>(),
+            "collisions": self.collisions.keys().collect::<Vec<_>>(),
+        })
+    }
+}
+
+fn queue_state(queue: &SpillQueue<Event>) -> Value {
+    json!({
+        "message_ids": queue.iter_in_memory().map(Event::message_id).collect::<Vec<_>>(),
+        "spilled": queue.spilled(),
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_dir::TestDir;
     use web3::types::H160;
 
     const MESSAGE_ID: [u8; 32] = [0; 32];
@@ -103,65 +421,105 @@ mod tests {
     const AMOUNT: u128 = 0;
     const BLOCK_NUMBER: u128 = 0;
 
+    fn drain_events_queue(storage: &mut ControllerStorage) -> Vec<Event> {
+        std::iter::from_fn(|| storage.pop_event_from_queue()).collect()
+    }
+
     #[test]
     fn put_event_tests() {
         let mut storage = ControllerStorage::new();
-        let event = Event::EthBridgePausedMessage(H256::from_slice(&MESSAGE_ID), BLOCK_NUMBER);
+        let event = Event::eth_bridge_paused_message(H256::from_slice(&MESSAGE_ID), BLOCK_NUMBER);
         assert_eq!(Ok(()), storage.put_event(&event));
         assert_eq!(Err(Error::Duplicate), storage.put_event(&event));
     }
 
+    #[test]
+    fn put_unknown_event_tests() {
+        let mut storage = ControllerStorage::new();
+        let message_id = H256::from_slice(&MESSAGE_ID);
+        assert!(storage.put_unknown_event(message_id, "CONFIRMED"));
+        assert!(!storage.put_unknown_event(message_id, "CONFIRMED"));
+        assert!(storage.put_unknown_event(message_id, "REFUNDED"));
+    }
+
+    #[test]
+    fn message_id_collision_tests() {
+        let mut storage = ControllerStorage::new();
+        let message_id = H256::from_slice(&MESSAGE_ID);
+        let address = H160::from_slice(&ETH_ADDRESS);
+        let event = Event::eth_relay_message(
+            message_id,
+            address,
+            H256::from_slice(&SUB_ADDRESS),
+            AMOUNT.into(),
+            None,
+            BLOCK_NUMBER,
+        );
+        let approved_event = Event::eth_approved_relay_message(
+            message_id,
+            address,
+            H256::from_slice(&SUB_ADDRESS),
+            AMOUNT.into(),
+            BLOCK_NUMBER,
+        );
+        let forged_event = Event::eth_approved_relay_message(
+            message_id,
+            address,
+            H256::from_slice(&SUB_ADDRESS),
+            (AMOUNT + 1).into(),
+            BLOCK_NUMBER,
+        );
+
+        assert_eq!(Ok(()), storage.put_event(&event));
+        assert_eq!(Ok(()), storage.put_event(&approved_event));
+        assert_eq!(Err(Error::Collision), storage.put_event(&forged_event));
+        assert_eq!(
+            Some(&vec![approved_event, forged_event]),
+            storage.collisions(&message_id)
+        );
+        assert_eq!(Err(Error::Collision), storage.put_event(&event));
+    }
+
     #[test]
     fn event_queue_tests() {
         let mut storage = ControllerStorage::new();
-        let event = Event::EthBridgePausedMessage(H256::from_slice(&MESSAGE_ID), BLOCK_NUMBER);
-        let event2 = Event::EthBridgePausedMessage(H256::from_slice(&MESSAGE_ID2), BLOCK_NUMBER);
+        let event = Event::eth_bridge_paused_message(H256::from_slice(&MESSAGE_ID), BLOCK_NUMBER);
+        let event2 = Event::eth_bridge_paused_message(H256::from_slice(&MESSAGE_ID2), BLOCK_NUMBER);
 
         let empty_vec: Vec<Event> = vec![];
         let vec_with_events = vec![event.clone(), event2.clone()];
 
-        assert_eq!(
-            empty_vec,
-            storage.iter_events_queue().cloned().collect::<Vec<_>>()
-        );
+        assert_eq!(empty_vec, drain_events_queue(&mut storage));
         storage.put_event_to_queue(event);
         storage.put_event_to_queue(event2);
-        assert_eq!(
-            vec_with_events,
-            storage.iter_events_queue().cloned().collect::<Vec<_>>()
-        );
-        storage.clear_events_queue();
-        assert_eq!(
-            empty_vec,
-            storage.iter_events_queue().cloned().collect::<Vec<_>>()
-        );
+        assert_eq!(vec_with_events, drain_events_queue(&mut storage));
+        assert_eq!(empty_vec, drain_events_queue(&mut storage));
     }
 
     #[test]
     fn blocking_and_unblocking_account_tests() {
         let mut storage = ControllerStorage::new();
         let address = H160::from_slice(&ETH_ADDRESS);
-        let event = Event::EthRelayMessage(
+        let event = Event::eth_relay_message(
             H256::from_slice(&MESSAGE_ID),
             address,
             H256::from_slice(&SUB_ADDRESS),
             AMOUNT.into(),
+            None,
             BLOCK_NUMBER,
         );
-        let event2 = Event::EthRelayMessage(
+        let event2 = Event::eth_relay_message(
             H256::from_slice(&MESSAGE_ID2),
             address,
             H256::from_slice(&SUB_ADDRESS),
             AMOUNT.into(),
+            None,
             BLOCK_NUMBER,
         );
         let empty_vec: Vec<Event> = vec![];
         let vec_with_events = vec![event.clone(), event2.clone()];
 
-        assert_eq!(
-            empty_vec,
-            storage.iter_events_queue().cloned().collect::<Vec<_>>()
-        );
+        assert_eq!(empty_vec, drain_events_queue(&mut storage));
         assert_eq!(
             false,
             storage.is_account_blocked(Some(Address::Eth(address)))
@@ -174,18 +532,169 @@ mod tests {
 
         storage.put_event_to_account_queue(event);
         storage.put_event_to_account_queue(event2);
-        assert_eq!(
-            empty_vec,
-            storage.iter_events_queue().cloned().collect::<Vec<_>>()
-        );
+        assert_eq!(empty_vec, drain_events_queue(&mut storage));
         storage.unblock_account(Address::Eth(address));
         assert_eq!(
             false,
             storage.is_account_blocked(Some(Address::Eth(address)))
         );
+        assert_eq!(vec_with_events, drain_events_queue(&mut storage));
+    }
+
+    #[test]
+    fn pause_grace_tests() {
+        let mut storage = ControllerStorage::new();
+        storage.set_pause_grace(Duration::from_secs(60));
+        let address = H160::from_slice(&ETH_ADDRESS);
+        let approved = |message_id| {
+            Event::eth_approved_relay_message(
+                H256::from_slice(message_id),
+                address,
+                H256::from_slice(&SUB_ADDRESS),
+                AMOUNT.into(),
+                BLOCK_NUMBER,
+            )
+        };
+        storage.start_transfer(Direction::EthToSub, H256::from_slice(&MESSAGE_ID));
+        storage.block_account(Address::Eth(address));
+
+        let now = Instant::now();
+        assert!(storage.is_in_pause_grace(&approved(&MESSAGE_ID), now));
+        // not in flight when the account was paused
+        assert!(!storage.is_in_pause_grace(&approved(&MESSAGE_ID2), now));
+        let later = now + Duration::from_secs(61);
+        assert!(!storage.is_in_pause_grace(&approved(&MESSAGE_ID), later));
+    }
+
+    #[test]
+    fn pending_transfers_limit_tests() {
+        let mut storage = ControllerStorage::new();
+        let message_id = H256::from_slice(&MESSAGE_ID);
+        let message_id2 = H256::from_slice(&MESSAGE_ID2);
+        let event2 = Event::sub_relay_message(message_id2, BLOCK_NUMBER);
+        storage.set_max_pending_transfers(Direction::SubToEth, Some(1));
+
+        assert!(storage.can_start_transfer(Direction::SubToEth, &message_id));
+        storage.start_transfer(Direction::SubToEth, message_id);
+        assert!(storage.can_start_transfer(Direction::SubToEth, &message_id));
+        assert!(!storage.can_start_transfer(Direction::SubToEth, &message_id2));
+        assert!(storage.can_start_transfer(Direction::EthToSub, &H256::from_slice(&[2; 32])));
+        assert_eq!(1, storage.pending_transfers(Direction::SubToEth));
+
+        storage.defer_transfer(Direction::SubToEth, event2.clone());
+        assert_eq!(Vec::<Event>::new(), storage.release_deferred_transfers());
+        storage.finish_transfer(&message_id);
+        assert_eq!(vec![event2], storage.release_deferred_transfers());
+        assert_eq!(1, storage.pending_transfers(Direction::SubToEth));
+
+        storage.set_max_pending_transfers(Direction::SubToEth, None);
+        assert!(storage.can_start_transfer(Direction::SubToEth, &message_id));
+    }
+
+    #[test]
+    fn resume_transfer_tests() {
+        let mut storage = ControllerStorage::new();
+        let message_id = H256::from_slice(&MESSAGE_ID);
+        let message_id2 = H256::from_slice(&MESSAGE_ID2);
+        storage.set_max_pending_transfers(Direction::EthToSub, Some(1));
+
+        // approved before a restart, the approval of another one waits
+        storage.resume_transfer(Direction::EthToSub, message_id);
+        assert_eq!(1, storage.pending_transfers(Direction::EthToSub));
+        assert!(!storage.can_start_transfer(Direction::EthToSub, &message_id2));
+
+        storage.finish_transfer(&message_id);
+        storage.resume_transfer(Direction::EthToSub, message_id);
+        assert_eq!(0, storage.pending_transfers(Direction::EthToSub));
+        assert!(storage.can_start_transfer(Direction::EthToSub, &message_id2));
+    }
+
+    #[test]
+    fn timelock_tests() {
+        let mut storage = ControllerStorage::new();
+        let message_id = H256::from_slice(&MESSAGE_ID);
+        let event = Event::eth_withdraw_message(message_id, BLOCK_NUMBER);
+        let now = Instant::now();
+        storage.timelock(event.clone(), now + std::time::Duration::from_secs(60));
+        assert_eq!(Vec::<Event>::new(), storage.release_timelocked(now));
+        assert!(!storage.is_timelock_released(&message_id));
+        assert_eq!(
+            vec![event],
+            storage.release_timelocked(now + std::time::Duration::from_secs(60))
+        );
+        assert!(storage.is_timelock_released(&message_id));
+    }
+
+    #[test]
+    fn spilled_queues_tests() {
+        let dir = TestDir::new("controller_storage");
+        let mut storage = ControllerStorage::with_spill_dir(dir.path().into(), 1);
+        let address = H160::from_slice(&ETH_ADDRESS);
+        let events = (0..3u8)
+            .map(|i| {
+                Event::eth_relay_message(
+                    H256::from_slice(&[i; 32]),
+                    address,
+                    H256::from_slice(&SUB_ADDRESS),
+                    AMOUNT.into(),
+                    None,
+                    BLOCK_NUMBER,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        storage.put_event_to_queue(events[0].clone());
+        storage.block_account(Address::Eth(address));
+        storage.put_event_to_account_queue(events[1].clone());
+        storage.put_event_to_account_queue(events[2].clone());
+        storage.unblock_account(Address::Eth(address));
+        assert_eq!(events, drain_events_queue(&mut storage));
+
+        // transfers held by a drain are there for the next start
+        storage.hold_for_drain(events[0].clone());
+        storage.hold_for_drain(events[1].clone());
+        let mut restarted = ControllerStorage::with_spill_dir(dir.path().into(), 1);
+        assert_eq!(events[..2].to_vec(), restarted.take_drain_held());
+        assert!(restarted.take_drain_held().is_empty());
+    }
+
+    #[test]
+    fn sample_transfers_in_flight_tests() {
+        let mut storage = ControllerStorage::new();
+        let events = (0..3u8)
+            .map(|i| Event::eth_withdraw_message(H256::from_slice(&[i; 32]), 3 - i as u128))
+            .collect::<Vec<_>>();
+        for event in &events {
+            storage.put_event(event).unwrap();
+            storage.start_transfer(Direction::EthToSub, *event.message_id());
+        }
+        storage.finish_transfer(events[2].message_id());
+        assert_eq!(
+            vec![events[1].clone()],
+            storage.sample_transfers_in_flight(1)
+        );
+        assert_eq!(2, storage.sample_transfers_in_flight(5).len());
+    }
+
+    #[test]
+    fn debug_state_tests() {
+        let mut storage = ControllerStorage::new();
+        let address = H160::from_slice(&ETH_ADDRESS);
+        let message_id = H256::from_slice(&MESSAGE_ID);
+        storage.put_event_to_queue(Event::eth_withdraw_message(message_id, BLOCK_NUMBER));
+        storage.block_account(Address::Eth(address));
+        storage.start_transfer(Direction::EthToSub, H256::from_slice(&MESSAGE_ID2));
+
+        let state = storage.debug_state();
+        assert_eq!(json!([message_id]), state["queue"]["message_ids"]);
+        assert_eq!(json!(0), state["queue"]["spilled"]);
+        assert_eq!(
+            json!(format!("Eth({:?})", address)),
+            state["blocked_accounts"][0]["account"]
+        );
         assert_eq!(
-            vec_with_events,
-            storage.iter_events_queue().cloned().collect::<Vec<_>>()
+            json!("EthToSub"),
+            state["transfers_in_flight"][0]["direction"]
         );
     }
 }
@@ -0,0 +1,168 @@
+use log;
+use web3::types::{H160, H256};
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Claims on token bridge calls in a Redis shared by cooperating validators.
+/// A multi-signed call needs the votes of a quorum, so each call of a message
+/// has one slot per needed vote: the first validators to claim a slot submit
+/// the call, the others check on-chain once the claims expired after `grace`
+/// and submit it then if it is still needed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coordinator {
+    /// `host:port` of the Redis server.
+    pub address: String,
+    pub calls: Vec<String>,
+    pub grace: Duration,
+    pub validator: H160,
+}
+
+impl Coordinator {
+    /// Whether this validator should submit `call` for the message, which
+    /// needs `quorum` submissions. Fails open: when Redis can not be reached
+    /// or the quorum is not known, 0, the call is submitted.
+    pub fn claim(&self, message_id: H256, call: &str, quorum: u64) -> bool {
+        if !self.calls.iter().any(|coordinated| coordinated == call) {
+            return true;
+        }
+        if quorum == 0 {
+            log::warn!("quorum is not known, submitting {} uncoordinated", call);
+            return true;
+        }
+        let key = format!("validator:submitting:{:?}:{}", message_id, call);
+        match self.claim_slot(&key, quorum) {
+            Ok(claimed) => claimed,
+            Err(e) => {
+                log::warn!(
+                    "can not claim {} in {}, submitting: {}",
+                    key,
+                    self.address,
+                    e
+                );
+                true
+            }
+        }
+    }
+
+    fn claim_slot(&self, key: &str, quorum: u64) -> io::Result<bool> {
+        let validator = format!("{:?}", self.validator);
+        let grace = self.grace.as_millis().to_string();
+        for slot in 0..quorum {
+            let slot = format!("{}:{}", key, slot);
+            let claimed = match self.command(&["SET", &slot, &validator, "NX", "PX", &grace])? {
+                Some(_) => true,
+                // claimed before, e.g. the event is replayed after a restart
+                None => self.command(&["GET", &slot])?.as_ref() == Some(&validator),
+            };
+            if claimed {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Sends one command and returns its reply, `None` for a nil reply.
+    fn command(&self, args: &[&str]) -> io::Result<Option<String>> {
+        let address = self
+            .address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "no address"))?;
+        let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        stream.write_all(&encode(args))?;
+        read_reply(&mut BufReader::new(stream))
+    }
+}
+
+/// A command as a RESP array of bulk strings.
+fn encode(args: &[&str]) -> Vec<u8> {
+    let mut command = format!("*{}\r\n", args.len());
+    for arg in args {
+        command.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+    }
+    command.into_bytes()
+}
+
+/// Reads a simple string, error or bulk string reply.
+fn read_reply<R: BufRead>(reader: &mut R) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let line = line.trim_end();
+    let error = |message: String| Err(io::Error::new(io::ErrorKind::Other, message));
+    match line.chars().next() {
+        Some('+') => Ok(Some(line[1..].to_string())),
+        Some('-') => error(format!("redis error: {}", &line[1..])),
+        Some('$') if line == "$-1" => Ok(None),
+        Some('$') => {
+            let mut value = String::new();
+            reader.read_line(&mut value)?;
+            Ok(Some(value.trim_end().to_string()))
+        }
+        _ => error(format!("unexpected reply {:?}", line)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn resp_tests() {
+        assert_eq!(
+            b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n".to_vec(),
+            encode(&["GET", "key"])
+        );
+        let reply = |bytes: &[u8]| read_reply(&mut BufReader::new(bytes)).ok();
+        assert_eq!(Some(Some("OK".to_string())), reply(b"+OK\r\n"));
+        assert_eq!(Some(None), reply(b"$-1\r\n"));
+        assert_eq!(Some(Some("0x01".to_string())), reply(b"$4\r\n0x01\r\n"));
+        assert_eq!(None, reply(b"-ERR wrong type\r\n"));
+    }
+
+    #[test]
+    fn claim_tests() {
+        // answers as if another validator claimed the first of two slots,
+        // then as if two others claimed both
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let taken = ["$-1\r\n", "$4\r\n0x02\r\n"];
+            let replies = taken.iter().chain(&["+OK\r\n"]).chain(&taken).chain(&taken);
+            for reply in replies {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut command = [0; 512];
+                let _ = io::Read::read(&mut stream, &mut command).unwrap();
+                stream.write_all(reply.as_bytes()).unwrap();
+            }
+        });
+        let coordinator = Coordinator {
+            address,
+            calls: vec!["confirmTransfer".to_string()],
+            grace: Duration::from_secs(60),
+            validator: H160::from_slice(&[1; 20]),
+        };
+        let message_id = H256::from_slice(&[1; 32]);
+        assert!(coordinator.claim(message_id, "approveTransfer", 2));
+        assert!(coordinator.claim(message_id, "confirmTransfer", 2));
+        assert!(!coordinator.claim(message_id, "confirmTransfer", 2));
+        server.join().unwrap();
+
+        // every validator submits while the quorum is not known
+        assert!(coordinator.claim(message_id, "confirmTransfer", 0));
+
+        // fails open
+        let unreachable = Coordinator {
+            address: "127.0.0.1:1".to_string(),
+            ..coordinator
+        };
+        assert!(unreachable.claim(message_id, "confirmTransfer", 2));
+    }
+}
@@ -0,0 +1,970 @@
+use lazy_static::lazy_static;
+use log;
+use rusqlite::{params, types::Type, Connection, Row, TransactionBehavior, NO_PARAMS};
+use serde::Serialize;
+use web3::types::{H160, H256, U256};
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::attestations::{self, Attestation};
+#[cfg(feature = "admin-api")]
+use crate::controller::{Entity, Outcome};
+use crate::fees::{self, Fee};
+use crate::submissions::{self, Chain, Submission};
+#[cfg(feature = "admin-api")]
+use crate::transfer_index::{Filter, Transfer};
+
+const FILE_NAME: &str = "validator.sqlite3";
+
+/// How long a writer waits for another connection to finish.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Each entry upgrades the schema by one version, `PRAGMA user_version` is
+/// the number of entries applied.
+const SCHEMA: &[&str] = &[
+    "
+    CREATE TABLE messages (
+        message_id TEXT PRIMARY KEY,
+        direction TEXT NOT NULL,
+        status TEXT NOT NULL,
+        eth_address TEXT NOT NULL,
+        sub_address TEXT NOT NULL,
+        token TEXT NOT NULL,
+        amount TEXT NOT NULL,
+        eth_block_number INTEGER NOT NULL
+    );
+    CREATE INDEX messages_status ON messages (status, eth_block_number);
+    CREATE INDEX messages_eth_address ON messages (eth_address);
+    CREATE INDEX messages_sub_address ON messages (sub_address);
+    CREATE TABLE accounts (
+        id TEXT PRIMARY KEY,
+        message_id TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        status TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        eth_block_number INTEGER NOT NULL
+    );
+    CREATE TABLE submissions (
+        message_id TEXT NOT NULL,
+        chain TEXT NOT NULL,
+        call TEXT NOT NULL,
+        tx_hash TEXT NOT NULL,
+        submitted_at INTEGER NOT NULL
+    );
+    CREATE INDEX submissions_message_id ON submissions (message_id);
+    CREATE TABLE fees (
+        message_id TEXT NOT NULL,
+        chain TEXT NOT NULL,
+        call TEXT NOT NULL,
+        tx_hash TEXT NOT NULL,
+        amount TEXT NOT NULL,
+        recorded_at INTEGER NOT NULL
+    );
+    CREATE INDEX fees_message_id ON fees (message_id);
+    CREATE INDEX fees_recorded_at ON fees (chain, recorded_at);
+    CREATE TABLE offsets (
+        name TEXT PRIMARY KEY,
+        block_number INTEGER NOT NULL
+    );
+",
+    "
+    CREATE TABLE prices (
+        chain TEXT NOT NULL,
+        call TEXT NOT NULL,
+        tx_hash TEXT NOT NULL,
+        price TEXT NOT NULL,
+        recorded_at INTEGER NOT NULL
+    );
+    CREATE INDEX prices_recorded_at ON prices (chain, recorded_at);
+    CREATE TABLE attestations (
+        message_id TEXT NOT NULL,
+        action TEXT NOT NULL,
+        payload_hash TEXT NOT NULL,
+        attested_at INTEGER NOT NULL,
+        signer TEXT NOT NULL,
+        signature TEXT NOT NULL,
+        entity_kind TEXT,
+        entity_id TEXT
+    );
+    CREATE INDEX attestations_message_id ON attestations (message_id);
+    CREATE TABLE transfers (
+        message_id TEXT PRIMARY KEY,
+        sender TEXT,
+        recipient TEXT,
+        amount TEXT,
+        stage TEXT NOT NULL,
+        block_number INTEGER NOT NULL,
+        first_seen INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL,
+        outcome TEXT,
+        confirmed INTEGER NOT NULL
+    );
+    CREATE INDEX transfers_sender ON transfers (sender);
+    CREATE INDEX transfers_recipient ON transfers (recipient);
+    CREATE INDEX transfers_first_seen ON transfers (first_seen);
+",
+];
+
+/// A connection shared by the components of the process.
+pub type Shared = Arc<Mutex<Database>>;
+
+lazy_static! {
+    static ref SHARED: Mutex<HashMap<String, Result<Shared, String>>> = Mutex::new(HashMap::new());
+}
+
+/// The connection of this process to the database in `data_dir`, opened and
+/// upgraded once. When that fails every caller gets the error, which is
+/// logged once, and records and reads only the JSON lines files.
+pub fn shared(data_dir: &str) -> Result<Shared, String> {
+    let mut shared = SHARED.lock().expect("shared database lock poisoned");
+    shared
+        .entry(data_dir.to_string())
+        .or_insert_with(|| {
+            Database::open(data_dir)
+                .map(|database| Arc::new(Mutex::new(database)))
+                .map_err(|e| {
+                    log::error!("{}, falling back to the JSON lines files", e);
+                    e
+                })
+        })
+        .clone()
+}
+
+/// A transfer as the embedded indexer keeps it. Statuses and directions are
+/// named as in the subgraph schema.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MessageRow {
+    pub message_id: H256,
+    pub direction: String,
+    pub status: String,
+    pub eth_address: H160,
+    pub sub_address: H256,
+    pub token: H160,
+    pub amount: U256,
+    pub eth_block_number: u64,
+}
+
+/// A paused or resumed account, by its address in `0x` hex.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AccountRow {
+    pub id: String,
+    pub message_id: H256,
+    pub kind: String,
+    pub status: String,
+    pub timestamp: u64,
+    pub eth_block_number: u64,
+}
+
+/// Which indexed messages to list, every part that is set has to match.
+#[cfg(feature = "admin-api")]
+#[derive(Debug, Default, PartialEq)]
+pub struct MessageFilter {
+    pub status: Option<String>,
+    /// Ethereum or Substrate address of either side.
+    pub address: Option<String>,
+    pub from_block: Option<u64>,
+    pub to_block: Option<u64>,
+}
+
+/// The query layer over what the validator records, in
+/// `<data_dir>/validator.sqlite3`. The JSON lines files stay the audit
+/// trail, the database is what the admin API, exports and reconciliations
+/// query. Components share one connection, see `shared`.
+#[derive(Debug)]
+pub struct Database {
+    connection: Connection,
+}
+
+impl Database {
+    /// Creates or upgrades the schema. Tables new to the database are filled
+    /// with what the JSON lines files recorded before they existed.
+    pub fn open(data_dir: &str) -> Result<Self, String> {
+        let path = Path::new(data_dir).join(FILE_NAME);
+        let error = |e: rusqlite::Error| format!("can not open {:?}: {}", path, e);
+        fs::create_dir_all(data_dir).map_err(|e| format!("can not open {:?}: {}", path, e))?;
+        let mut connection = Connection::open(&path).map_err(error)?;
+        connection.busy_timeout(BUSY_TIMEOUT).map_err(error)?;
+        connection
+            .query_row("PRAGMA journal_mode = WAL", NO_PARAMS, |_| Ok(()))
+            .map_err(error)?;
+        migrate(&mut connection, data_dir)
+            .map_err(|e| format!("can not open {:?}: {}", path, e))?;
+        Ok(Database { connection })
+    }
+
+    pub fn record_submission(&self, submission: &Submission) -> Result<(), String> {
+        insert_submission(&self.connection, submission)
+    }
+
+    /// Oldest first.
+    pub fn submissions(&self, message_id: &H256) -> Result<Vec<Submission>, String> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT message_id, chain, call, tx_hash, submitted_at FROM submissions
+                 WHERE message_id = ?1 ORDER BY rowid",
+            )
+            .map_err(query_error)?;
+        let rows = statement
+            .query_map(params![hex(message_id)], |row| {
+                Ok(Submission {
+                    message_id: column(row, 0)?,
+                    chain: chain_column(row, 1)?,
+                    call: row.get(2)?,
+                    tx_hash: column(row, 3)?,
+                    submitted_at: row.get(4)?,
+                })
+            })
+            .map_err(query_error)?;
+        rows.collect::<Result<_, _>>().map_err(query_error)
+    }
+
+    /// Records the fee, and the price per unit in `prices` if it has one.
+    pub fn record_fee(&mut self, fee: &Fee) -> Result<(), String> {
+        let transaction = self.connection.transaction().map_err(query_error)?;
+        insert_fee(&transaction, fee)?;
+        transaction.commit().map_err(query_error)
+    }
+
+    /// The fees recorded since the unix time `since`, on `chain` or on both,
+    /// ordered by message.
+    pub fn fees(&self, chain: Option<Chain>, since: i64) -> Result<Vec<Fee>, String> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT fees.message_id, fees.chain, fees.call, fees.tx_hash, fees.amount,
+                 fees.recorded_at, prices.price FROM fees
+                 LEFT JOIN prices ON prices.chain = fees.chain AND prices.tx_hash = fees.tx_hash
+                 WHERE (?1 IS NULL OR fees.chain = ?1) AND fees.recorded_at >= ?2
+                 ORDER BY fees.message_id, fees.rowid",
+            )
+            .map_err(query_error)?;
+        let rows = statement
+            .query_map(params![chain.map(chain_name), since], |row| {
+                Ok(Fee {
+                    message_id: column(row, 0)?,
+                    chain: chain_column(row, 1)?,
+                    call: row.get(2)?,
+                    tx_hash: column(row, 3)?,
+                    amount: amount(row, 4)?,
+                    price: match row.get::<_, Option<String>>(6)? {
+                        Some(_) => Some(amount(row, 6)?),
+                        None => None,
+                    },
+                    recorded_at: row.get(5)?,
+                })
+            })
+            .map_err(query_error)?;
+        rows.collect::<Result<_, _>>().map_err(query_error)
+    }
+
+    /// The average price per unit paid on `chain` since the unix time
+    /// `since`, none if nothing was paid.
+    pub fn average_price(&self, chain: Chain, since: i64) -> Result<Option<U256>, String> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT price FROM prices WHERE chain = ?1 AND recorded_at >= ?2")
+            .map_err(query_error)?;
+        let prices = statement
+            .query_map(params![chain_name(chain), since], |row| amount(row, 0))
+            .map_err(query_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(query_error)?;
+        Ok(fees::average(&prices))
+    }
+
+    pub fn record_attestation(&self, attestation: &Attestation) -> Result<(), String> {
+        insert_attestation(&self.connection, attestation)
+    }
+
+    /// Oldest first.
+    #[cfg(feature = "admin-api")]
+    pub fn attestations(&self, message_id: &H256) -> Result<Vec<Attestation>, String> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT message_id, action, payload_hash, attested_at, signer, signature,
+                 entity_kind, entity_id FROM attestations
+                 WHERE message_id = ?1 ORDER BY rowid",
+            )
+            .map_err(query_error)?;
+        let rows = statement
+            .query_map(params![hex(message_id)], |row| {
+                let entity = match (row.get(6)?, row.get::<_, Option<String>>(7)?) {
+                    (Some(kind), Some(id)) => Some(Entity { kind, id }),
+                    _ => None,
+                };
+                Ok(Attestation {
+                    message_id: column(row, 0)?,
+                    action: row.get(1)?,
+                    payload_hash: column(row, 2)?,
+                    attested_at: row.get(3)?,
+                    signer: row.get(4)?,
+                    signature: row.get(5)?,
+                    entity,
+                })
+            })
+            .map_err(query_error)?;
+        rows.collect::<Result<_, _>>().map_err(query_error)
+    }
+
+    #[cfg(feature = "admin-api")]
+    pub fn transfer(&self, message_id: &H256) -> Result<Option<Transfer>, String> {
+        let mut statement = self
+            .connection
+            .prepare(&format!("{} WHERE message_id = ?1", SELECT_TRANSFERS))
+            .map_err(query_error)?;
+        let mut rows = statement
+            .query_map(params![hex(message_id)], transfer_row)
+            .map_err(query_error)?;
+        rows.next().transpose().map_err(query_error)
+    }
+
+    #[cfg(feature = "admin-api")]
+    pub fn save_transfer(&self, transfer: &Transfer) -> Result<(), String> {
+        let outcome = transfer
+            .outcome
+            .as_ref()
+            .map(|outcome| serde_json::to_string(outcome).expect("outcome serializes"));
+        let confirmed = match transfer.outcome {
+            Some(Outcome::Confirmed(..)) => 1,
+            _ => 0,
+        };
+        self.connection
+            .execute(
+                "INSERT OR REPLACE INTO transfers (message_id, sender, recipient, amount, stage,
+                 block_number, first_seen, updated_at, outcome, confirmed)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    hex(&transfer.message_id),
+                    transfer.sender,
+                    transfer.recipient,
+                    transfer.amount.map(|amount| amount.to_string()),
+                    transfer.stage,
+                    transfer.block_number as i64,
+                    transfer.first_seen as i64,
+                    transfer.updated_at as i64,
+                    outcome,
+                    confirmed
+                ],
+            )
+            .map(|_| ())
+            .map_err(|e| format!("can not save {:?}: {}", transfer, e))
+    }
+
+    /// Oldest first.
+    #[cfg(feature = "admin-api")]
+    pub fn transfers(&self, filter: &Filter) -> Result<Vec<Transfer>, String> {
+        let mut statement = self
+            .connection
+            .prepare(&format!(
+                "{} WHERE (?1 IS NULL OR sender = ?1 OR recipient = ?1)
+                 AND (?2 IS NULL OR first_seen >= ?2) AND (?3 IS NULL OR first_seen <= ?3)
+                 AND (?4 IS NULL OR block_number >= ?4) AND (?5 IS NULL OR block_number <= ?5)
+                 ORDER BY first_seen, block_number",
+                SELECT_TRANSFERS
+            ))
+            .map_err(query_error)?;
+        let address = filter
+            .address
+            .as_ref()
+            .map(|address| address.to_lowercase());
+        let rows = statement
+            .query_map(
+                params![
+                    address,
+                    filter.since.map(|since| since as i64),
+                    filter.until.map(|until| until as i64),
+                    filter.from_block.map(|block| block as i64),
+                    filter.to_block.map(|block| block as i64)
+                ],
+                transfer_row,
+            )
+            .map_err(query_error)?;
+        rows.collect::<Result<_, _>>().map_err(query_error)
+    }
+
+    /// Deletes the transfers whose latest call was confirmed before the unix
+    /// time `before`, returns how many.
+    #[cfg(feature = "admin-api")]
+    pub fn prune_transfers(&self, before: u64) -> Result<usize, String> {
+        self.connection
+            .execute(
+                "DELETE FROM transfers WHERE confirmed = 1 AND updated_at < ?1",
+                params![before as i64],
+            )
+            .map_err(query_error)
+    }
+
+    /// Deletes the submissions and attestations older than the unix time
+    /// `before` and the fees and prices older than `fees_before`, returns
+    /// how many submissions, fees and attestations.
+    pub fn prune(&self, before: i64, fees_before: i64) -> Result<(usize, usize, usize), String> {
+        let submissions = self
+            .connection
+            .execute(
+                "DELETE FROM submissions WHERE submitted_at < ?1",
+                params![before],
+            )
+            .map_err(query_error)?;
+        let attestations = self
+            .connection
+            .execute(
+                "DELETE FROM attestations WHERE attested_at < ?1",
+                params![before],
+            )
+            .map_err(query_error)?;
+        let fees = self
+            .connection
+            .execute(
+                "DELETE FROM fees WHERE recorded_at < ?1",
+                params![fees_before],
+            )
+            .map_err(query_error)?;
+        self.connection
+            .execute(
+                "DELETE FROM prices WHERE recorded_at < ?1",
+                params![fees_before],
+            )
+            .map_err(query_error)?;
+        Ok((submissions, fees, attestations))
+    }
+
+    /// Stores what the indexer changed and where it continues, all or
+    /// nothing.
+    pub fn save_index(
+        &mut self,
+        messages: &[MessageRow],
+        accounts: &[AccountRow],
+        offset: (&str, u64),
+    ) -> Result<(), String> {
+        let transaction = self.connection.transaction().map_err(query_error)?;
+        for message in messages {
+            transaction
+                .execute(
+                    "INSERT OR REPLACE INTO messages (message_id, direction, status, eth_address,
+                     sub_address, token, amount, eth_block_number)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    params![
+                        hex(&message.message_id),
+                        message.direction,
+                        message.status,
+                        hex(&message.eth_address),
+                        hex(&message.sub_address),
+                        hex(&message.token),
+                        message.amount.to_string(),
+                        message.eth_block_number as i64
+                    ],
+                )
+                .map_err(query_error)?;
+        }
+        for account in accounts {
+            transaction
+                .execute(
+                    "INSERT OR REPLACE INTO accounts (id, message_id, kind, status, timestamp,
+                     eth_block_number) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        account.id,
+                        hex(&account.message_id),
+                        account.kind,
+                        account.status,
+                        account.timestamp as i64,
+                        account.eth_block_number as i64
+                    ],
+                )
+                .map_err(query_error)?;
+        }
+        transaction
+            .execute(
+                "INSERT OR REPLACE INTO offsets (name, block_number) VALUES (?1, ?2)",
+                params![offset.0, offset.1 as i64],
+            )
+            .map_err(query_error)?;
+        transaction.commit().map_err(query_error)
+    }
+
+    pub fn offset(&self, name: &str) -> Result<Option<u64>, String> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT block_number FROM offsets WHERE name = ?1")
+            .map_err(query_error)?;
+        let mut rows = statement
+            .query_map(params![name], |row| row.get::<_, i64>(0))
+            .map_err(query_error)?;
+        rows.next()
+            .transpose()
+            .map(|block_number| block_number.map(|block_number| block_number as u64))
+            .map_err(query_error)
+    }
+
+    /// Oldest first.
+    #[cfg(feature = "admin-api")]
+    pub fn messages(&self, filter: &MessageFilter) -> Result<Vec<MessageRow>, String> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT message_id, direction, status, eth_address, sub_address, token, amount,
+                 eth_block_number FROM messages
+                 WHERE (?1 IS NULL OR status = ?1)
+                 AND (?2 IS NULL OR eth_address = ?2 OR sub_address = ?2)
+                 AND (?3 IS NULL OR eth_block_number >= ?3)
+                 AND (?4 IS NULL OR eth_block_number <= ?4)
+                 ORDER BY eth_block_number, message_id",
+            )
+            .map_err(query_error)?;
+        let address = filter
+            .address
+            .as_ref()
+            .map(|address| address.to_lowercase());
+        let rows = statement
+            .query_map(
+                params![
+                    filter.status,
+                    address,
+                    filter.from_block.map(|block| block as i64),
+                    filter.to_block.map(|block| block as i64)
+                ],
+                |row| {
+                    Ok(MessageRow {
+                        message_id: column(row, 0)?,
+                        direction: row.get(1)?,
+                        status: row.get(2)?,
+                        eth_address: column(row, 3)?,
+                        sub_address: column(row, 4)?,
+                        token: column(row, 5)?,
+                        amount: amount(row, 6)?,
+                        eth_block_number: row.get::<_, i64>(7)? as u64,
+                    })
+                },
+            )
+            .map_err(query_error)?;
+        rows.collect::<Result<_, _>>().map_err(query_error)
+    }
+}
+
+#[cfg(feature = "admin-api")]
+const SELECT_TRANSFERS: &str = "SELECT message_id, sender, recipient, amount, stage,
+    block_number, first_seen, updated_at, outcome FROM transfers";
+
+#[cfg(feature = "admin-api")]
+fn transfer_row(row: &Row) -> rusqlite::Result<Transfer> {
+    let outcome = match row.get::<_, Option<String>>(8)? {
+        Some(outcome) => Some(serde_json::from_str(&outcome).map_err(|_| invalid(8, &outcome))?),
+        None => None,
+    };
+    Ok(Transfer {
+        message_id: column(row, 0)?,
+        sender: row.get(1)?,
+        recipient: row.get(2)?,
+        amount: match row.get::<_, Option<String>>(3)? {
+            Some(_) => Some(amount(row, 3)?),
+            None => None,
+        },
+        stage: row.get(4)?,
+        block_number: row.get::<_, i64>(5)? as u128,
+        first_seen: row.get::<_, i64>(6)? as u64,
+        updated_at: row.get::<_, i64>(7)? as u64,
+        outcome,
+    })
+}
+
+/// Applies the schema versions the database does not have yet, and fills
+/// the tables they add from the JSON lines files, all or nothing. A
+/// connection opened at the same time waits and finds the upgrade done.
+fn migrate(connection: &mut Connection, data_dir: &str) -> Result<(), String> {
+    let transaction = connection
+        .transaction_with_behavior(TransactionBehavior::Immediate)
+        .map_err(query_error)?;
+    let version = transaction
+        .query_row("PRAGMA user_version", NO_PARAMS, |row| row.get::<_, i64>(0))
+        .map_err(query_error)? as usize;
+    if version >= SCHEMA.len() {
+        return Ok(());
+    }
+    log::info!(
+        "upgrading the database from version {} to {}",
+        version,
+        SCHEMA.len()
+    );
+    for schema in SCHEMA.iter().skip(version) {
+        transaction.execute_batch(schema).map_err(query_error)?;
+    }
+    import(&transaction, data_dir, version)?;
+    transaction
+        .execute_batch(&format!("PRAGMA user_version = {}", SCHEMA.len()))
+        .map_err(query_error)?;
+    transaction.commit().map_err(query_error)
+}
+
+/// Imports what was recorded before the tables of schema versions after
+/// `version` existed.
+fn import(connection: &Connection, data_dir: &str, version: usize) -> Result<(), String> {
+    if version < 1 {
+        let submissions = submissions::read(data_dir)?;
+        let fees = fees::read(data_dir)?;
+        log::info!(
+            "importing {} submissions and {} fees into the database",
+            submissions.len(),
+            fees.len()
+        );
+        for submission in &submissions {
+            insert_submission(connection, submission)?;
+        }
+        for fee in &fees {
+            insert_fee(connection, fee)?;
+        }
+    }
+    if version < 2 {
+        let attestations = attestations::read(data_dir)?;
+        log::info!(
+            "importing {} attestations into the database",
+            attestations.len()
+        );
+        for attestation in &attestations {
+            insert_attestation(connection, attestation)?;
+        }
+    }
+    Ok(())
+}
+
+fn insert_submission(connection: &Connection, submission: &Submission) -> Result<(), String> {
+    connection
+        .execute(
+            "INSERT INTO submissions (message_id, chain, call, tx_hash, submitted_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                hex(&submission.message_id),
+                chain_name(submission.chain),
+                submission.call,
+                hex(&submission.tx_hash),
+                submission.submitted_at
+            ],
+        )
+        .map(|_| ())
+        .map_err(|e| format!("can not record {:?}: {}", submission, e))
+}
+
+fn insert_fee(connection: &Connection, fee: &Fee) -> Result<(), String> {
+    let error = |e| format!("can not record {:?}: {}", fee, e);
+    connection
+        .execute(
+            "INSERT INTO fees (message_id, chain, call, tx_hash, amount, recorded_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                hex(&fee.message_id),
+                chain_name(fee.chain),
+                fee.call,
+                hex(&fee.tx_hash),
+                fee.amount.to_string(),
+                fee.recorded_at
+            ],
+        )
+        .map_err(error)?;
+    if let Some(price) = fee.price {
+        connection
+            .execute(
+                "INSERT INTO prices (chain, call, tx_hash, price, recorded_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    chain_name(fee.chain),
+                    fee.call,
+                    hex(&fee.tx_hash),
+                    price.to_string(),
+                    fee.recorded_at
+                ],
+            )
+            .map_err(error)?;
+    }
+    Ok(())
+}
+
+fn insert_attestation(connection: &Connection, attestation: &Attestation) -> Result<(), String> {
+    let entity = attestation.entity.as_ref();
+    connection
+        .execute(
+            "INSERT INTO attestations (message_id, action, payload_hash, attested_at, signer,
+             signature, entity_kind, entity_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                hex(&attestation.message_id),
+                attestation.action,
+                hex(&attestation.payload_hash),
+                attestation.attested_at,
+                attestation.signer,
+                attestation.signature,
+                entity.map(|entity| entity.kind.clone()),
+                entity.map(|entity| entity.id.clone())
+            ],
+        )
+        .map(|_| ())
+        .map_err(|e| format!("can not record {:?}: {}", attestation, e))
+}
+
+/// `0x` and lower case hex, as addresses are compared in queries.
+fn hex(value: &impl std::fmt::Debug) -> String {
+    format!("{:?}", value)
+}
+
+/// A hash or an address stored by `hex`.
+fn column<T: FromStr>(row: &Row, index: usize) -> rusqlite::Result<T> {
+    let value = row.get::<_, String>(index)?;
+    value
+        .trim_start_matches("0x")
+        .parse()
+        .map_err(|_| invalid(index, &value))
+}
+
+/// Amounts are stored in decimal, they do not fit an integer column.
+fn amount(row: &Row, index: usize) -> rusqlite::Result<U256> {
+    let value = row.get::<_, String>(index)?;
+    U256::from_dec_str(&value).map_err(|_| invalid(index, &value))
+}
+
+fn chain_name(chain: Chain) -> &'static str {
+    match chain {
+        Chain::Ethereum => "Ethereum",
+        Chain::Substrate => "Substrate",
+    }
+}
+
+fn chain_column(row: &Row, index: usize) -> rusqlite::Result<Chain> {
+    match row.get::<_, String>(index)?.as_str() {
+        "Ethereum" => Ok(Chain::Ethereum),
+        "Substrate" => Ok(Chain::Substrate),
+        chain => Err(invalid(index, chain)),
+    }
+}
+
+fn invalid(index: usize, value: &str) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(
+        index,
+        Type::Text,
+        format!("invalid value {:?}", value).into(),
+    )
+}
+
+fn query_error(e: rusqlite::Error) -> String {
+    format!("database query failed: {}", e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn submissions_and_fees_test() {
+        let dir = TestDir::new("database");
+        let data_dir = dir.path();
+        let message_id = H256::from_slice(&[1; 32]);
+        // recorded before the database existed
+        let submission = Submission {
+            message_id,
+            chain: Chain::Ethereum,
+            call: "approveTransfer".to_string(),
+            tx_hash: H256::from_slice(&[2; 32]),
+            submitted_at: 100,
+        };
+        fs::write(
+            dir.join("submissions.jsonl"),
+            format!("{}\n", serde_json::to_string(&submission).unwrap()),
+        )
+        .unwrap();
+        let mut database = Database::open(data_dir).unwrap();
+        database
+            .record_fee(&Fee {
+                message_id,
+                chain: Chain::Substrate,
+                call: "approve_transfer".to_string(),
+                tx_hash: H256::zero(),
+                amount: 7.into(),
+                price: None,
+                recorded_at: 100,
+            })
+            .unwrap();
+        database
+            .record_fee(&Fee {
+                message_id,
+                chain: Chain::Ethereum,
+                call: "approveTransfer".to_string(),
+                tx_hash: H256::from_slice(&[2; 32]),
+                amount: 42_000.into(),
+                price: Some(2.into()),
+                recorded_at: 100,
+            })
+            .unwrap();
+
+        let found = database.submissions(&message_id).unwrap();
+        assert_eq!(1, found.len());
+        assert_eq!("approveTransfer", found[0].call);
+        assert!(database.submissions(&H256::zero()).unwrap().is_empty());
+        assert_eq!(2, database.fees(None, 0).unwrap().len());
+        assert_eq!(1, database.fees(Some(Chain::Substrate), 100).unwrap().len());
+        assert_eq!(
+            Some(U256::from(2)),
+            database.fees(Some(Chain::Ethereum), 0).unwrap()[0].price
+        );
+        assert!(database.fees(None, 101).unwrap().is_empty());
+        assert_eq!(
+            Ok(Some(U256::from(2))),
+            database.average_price(Chain::Ethereum, 0)
+        );
+        assert_eq!(Ok(None), database.average_price(Chain::Substrate, 0));
+        // opened again, nothing is imported twice
+        let database = Database::open(data_dir).unwrap();
+        assert_eq!(1, database.submissions(&message_id).unwrap().len());
+        assert_eq!(Ok((0, 2, 0)), database.prune(100, 101));
+        assert_eq!(Ok(None), database.average_price(Chain::Ethereum, 0));
+        assert_eq!(Ok((1, 0, 0)), database.prune(101, 101));
+        assert!(database.submissions(&message_id).unwrap().is_empty());
+    }
+
+    #[cfg(feature = "admin-api")]
+    #[test]
+    fn upgrade_imports_attestations_test() {
+        let dir = TestDir::new("database_upgrade");
+        let data_dir = dir.path();
+        // a database of the first version
+        let connection = Connection::open(dir.join(FILE_NAME)).unwrap();
+        connection.execute_batch(SCHEMA[0]).unwrap();
+        connection.execute_batch("PRAGMA user_version = 1").unwrap();
+        drop(connection);
+        let attestation = Attestation {
+            message_id: H256::from_slice(&[1; 32]),
+            action: "forward".to_string(),
+            payload_hash: H256::from_slice(&[2; 32]),
+            attested_at: 100,
+            signer: "0x01".to_string(),
+            signature: "0x02".to_string(),
+            entity: Some(Entity::new("message", "0x01")),
+        };
+        fs::write(
+            dir.join(attestations::FILE_NAME),
+            format!("{}\n", serde_json::to_string(&attestation).unwrap()),
+        )
+        .unwrap();
+
+        let database = Database::open(data_dir).unwrap();
+        assert_eq!(
+            Ok(vec![attestation.clone()]),
+            database.attestations(&attestation.message_id)
+        );
+        let attestation = Attestation {
+            entity: None,
+            ..attestation
+        };
+        database.record_attestation(&attestation).unwrap();
+        let database = Database::open(data_dir).unwrap();
+        let found = database.attestations(&attestation.message_id).unwrap();
+        assert_eq!(2, found.len());
+        assert_eq!(attestation, found[1]);
+    }
+
+    #[cfg(feature = "admin-api")]
+    #[test]
+    fn transfers_test() {
+        let dir = TestDir::new("database_transfers");
+        let data_dir = dir.path();
+        let database = Database::open(data_dir).unwrap();
+        let transfer = |id: u8, first_seen: u64, outcome: Option<Outcome>| Transfer {
+            message_id: H256::from_slice(&[id; 32]),
+            sender: Some(format!("{:?}", H160::from_slice(&[id; 20]))),
+            recipient: None,
+            amount: Some(100.into()),
+            stage: "EthRelayMessage".to_string(),
+            block_number: 10 + id as u128,
+            first_seen,
+            updated_at: first_seen,
+            outcome,
+        };
+        let confirmed =
+            Outcome::Confirmed(Chain::Ethereum, "approveTransfer".to_string(), H256::zero());
+        database.save_transfer(&transfer(1, 100, None)).unwrap();
+        database
+            .save_transfer(&transfer(1, 100, Some(confirmed.clone())))
+            .unwrap();
+        database.save_transfer(&transfer(2, 200, None)).unwrap();
+
+        assert_eq!(
+            Ok(Some(transfer(1, 100, Some(confirmed.clone())))),
+            database.transfer(&H256::from_slice(&[1; 32]))
+        );
+        assert_eq!(Ok(None), database.transfer(&H256::zero()));
+        assert_eq!(2, database.transfers(&Filter::default()).unwrap().len());
+        let by_sender = Filter {
+            address: Some(format!("0x{}", "02".repeat(20))),
+            ..Filter::default()
+        };
+        assert_eq!(
+            Ok(vec![transfer(2, 200, None)]),
+            database.transfers(&by_sender)
+        );
+        let window = Filter {
+            since: Some(50),
+            until: Some(150),
+            to_block: Some(11),
+            ..Filter::default()
+        };
+        assert_eq!(1, database.transfers(&window).unwrap().len());
+        // only confirmed transfers are pruned
+        assert_eq!(Ok(1), database.prune_transfers(300));
+        assert_eq!(
+            Ok(vec![transfer(2, 200, None)]),
+            database.transfers(&Filter::default())
+        );
+    }
+
+    #[cfg(feature = "admin-api")]
+    #[test]
+    fn save_index_and_messages_test() {
+        let dir = TestDir::new("database_index");
+        let data_dir = dir.path();
+        let mut database = Database::open(data_dir).unwrap();
+        let message = |id: u8, status: &str, eth_block_number: u64| MessageRow {
+            message_id: H256::from_slice(&[id; 32]),
+            direction: "ETH2SUB".to_string(),
+            status: status.to_string(),
+            eth_address: H160::from_slice(&[id; 20]),
+            sub_address: H256::from_slice(&[0xab; 32]),
+            token: H160::zero(),
+            amount: 100.into(),
+            eth_block_number,
+        };
+        database
+            .save_index(
+                &[message(1, "PENDING", 10), message(2, "APPROVED", 20)],
+                &[],
+                ("eth_indexer", 21),
+            )
+            .unwrap();
+        database
+            .save_index(&[message(1, "APPROVED", 10)], &[], ("eth_indexer", 30))
+            .unwrap();
+
+        assert_eq!(Ok(Some(30)), database.offset("eth_indexer"));
+        assert_eq!(Ok(None), database.offset("other"));
+        let approved = MessageFilter {
+            status: Some("APPROVED".to_string()),
+            ..MessageFilter::default()
+        };
+        assert_eq!(
+            vec![message(1, "APPROVED", 10), message(2, "APPROVED", 20)],
+            database.messages(&approved).unwrap()
+        );
+        let by_address = MessageFilter {
+            address: Some(format!("0x{}", "AB".repeat(32))),
+            from_block: Some(15),
+            ..MessageFilter::default()
+        };
+        assert_eq!(1, database.messages(&by_address).unwrap().len());
+        let by_sender = MessageFilter {
+            address: Some(format!("{:?}", H160::from_slice(&[1; 20]))),
+            ..MessageFilter::default()
+        };
+        assert_eq!(1, database.messages(&by_sender).unwrap().len());
+    }
+}
@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use web3::types::H256;
+
+use crate::jsonl::OnceLog;
+
+pub const FILE_NAME: &str = "dead_letters.jsonl";
+
+/// A message refused because it matches no known event.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub message_id: H256,
+    pub origin: String,
+    pub payload: String,
+    pub block_number: u128,
+    pub recorded_at: i64,
+}
+
+/// Append-only record of refused messages in `<data_dir>/dead_letters.jsonl`.
+/// Each message is written once, also across restarts.
+#[derive(Debug)]
+pub struct DeadLetters {
+    recorded: OnceLog<(H256, String)>,
+}
+
+impl DeadLetters {
+    pub fn open(data_dir: &str) -> Self {
+        DeadLetters {
+            recorded: OnceLog::open(
+                data_dir,
+                FILE_NAME,
+                "dead letters are only logged",
+                |letter: DeadLetter| (letter.message_id, letter.payload),
+            ),
+        }
+    }
+
+    /// Returns false when the message was recorded before.
+    pub fn record(
+        &self,
+        message_id: H256,
+        origin: &str,
+        payload: &str,
+        block_number: u128,
+    ) -> bool {
+        self.recorded
+            .record((message_id, payload.to_string()), || DeadLetter {
+                message_id,
+                origin: origin.to_string(),
+                payload: payload.to_string(),
+                block_number,
+                recorded_at: time::now_utc().to_timespec().sec,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use std::fs;
+
+    #[test]
+    fn record_once_test() {
+        let dir = TestDir::new("dead_letters");
+        let data_dir = dir.path();
+        let message_id = H256::from_slice(&[1; 32]);
+        let dead_letters = DeadLetters::open(data_dir);
+        assert!(dead_letters.record(message_id, "all_messages", "REFUNDED", 10));
+        assert!(!dead_letters.record(message_id, "all_messages", "REFUNDED", 10));
+
+        // a restarted listener remembers what was recorded
+        let dead_letters = DeadLetters::open(data_dir);
+        assert!(!dead_letters.record(message_id, "all_messages", "REFUNDED", 10));
+        assert!(dead_letters.record(message_id, "all_messages", "EXPIRED", 11));
+        let lines = fs::read_to_string(dir.join(FILE_NAME)).unwrap();
+        assert_eq!(2, lines.lines().count());
+    }
+}
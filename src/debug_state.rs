@@ -0,0 +1,45 @@
+use lazy_static::lazy_static;
+#[cfg(feature = "admin-api")]
+use serde_json::json;
+use serde_json::Value;
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+lazy_static! {
+    static ref SECTIONS: Mutex<BTreeMap<&'static str, Value>> = Mutex::new(BTreeMap::new());
+}
+
+/// Replaces what `section` reports in the `/debug/state` dump. Threads
+/// publish their own state, so the dump never blocks them.
+pub fn publish(section: &'static str, state: Value) {
+    SECTIONS
+        .lock()
+        .expect("debug state lock poisoned")
+        .insert(section, state);
+}
+
+/// Every published section, as last reported by its thread.
+#[cfg(feature = "admin-api")]
+pub fn dump() -> Value {
+    let sections = SECTIONS.lock().expect("debug state lock poisoned");
+    let mut dump = json!({ "taken_at": time::now_utc().to_timespec().sec });
+    for (section, state) in sections.iter() {
+        dump[*section] = state.clone();
+    }
+    dump
+}
+
+#[cfg(all(test, feature = "admin-api"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn publish_test() {
+        publish("debug_state_test", json!({ "offset": 1 }));
+        publish("debug_state_test", json!({ "offset": 2 }));
+        let dump = dump();
+        assert_eq!(json!({ "offset": 2 }), dump["debug_state_test"]);
+        assert!(dump["taken_at"].is_i64());
+    }
+}
@@ -0,0 +1,120 @@
+use graphql_client::{GraphQLQuery, Response};
+use log;
+
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+
+use crate::config::Config;
+use crate::controller::Event;
+use crate::metrics;
+use crate::net;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "res/graph_node_schema.graphql",
+    query_path = "res/graph_node_messages_by_id.graphql",
+    response_derives = "Debug"
+)]
+struct MessagesById;
+
+use messages_by_id::Status;
+
+/// Compares samples of the transfers the controller holds in flight, as the
+/// latest event of each, with their status as the graph node indexed it from
+/// the contracts. A transfer the chain already finished, or one it never saw,
+/// usually means a missed event or a listener bug.
+pub fn spawn(config: Config) -> Sender<Vec<Event>> {
+    let (sample_tx, sample_rx) = channel::<Vec<Event>>();
+    thread::Builder::new()
+        .name("divergence".to_string())
+        .spawn(move || {
+            for sample in sample_rx {
+                match check(&config, &sample) {
+                    Ok(divergent) => {
+                        metrics::set_gauge("validator_divergent_messages", &[], divergent as f64)
+                    }
+                    Err(e) => log::warn!("can not check messages for divergence: {}", e),
+                }
+            }
+        })
+        .expect("can not started divergence monitor");
+    sample_tx
+}
+
+/// Alerts on each divergent message of the sample and returns their count.
+fn check(config: &Config, sample: &[Event]) -> Result<usize, String> {
+    if sample.is_empty() {
+        return Ok(0);
+    }
+    let request_body = MessagesById::build_query(messages_by_id::Variables {
+        ids: sample
+            .iter()
+            .map(|event| format!("{:?}", event.message_id()))
+            .collect(),
+    });
+    let response: Response<messages_by_id::ResponseData> = net::http_client()
+        .post(&config.graph_node_api_url)
+        .json(&request_body)
+        .send()
+        .and_then(|mut response| response.json())
+        .map_err(|e| format!("can not query the graph node: {}", e))?;
+    let statuses = response
+        .data
+        .ok_or_else(|| format!("no data from the graph node, errors: {:?}", response.errors))?
+        .messages
+        .into_iter()
+        .map(|message| (message.id.to_lowercase(), message.status))
+        .collect::<HashMap<_, _>>();
+    let mut divergent = 0;
+    for event in sample {
+        let message_id = format!("{:?}", event.message_id());
+        if let Some(reason) = diverges(event, statuses.get(&message_id)) {
+            divergent += 1;
+            log::error!(
+                "message {} diverged, local stage {}: {}",
+                message_id,
+                event.name(),
+                reason
+            );
+            metrics::increment("validator_divergences_total", &[("stage", event.name())]);
+        }
+    }
+    Ok(divergent)
+}
+
+/// Why the chain disagrees with a transfer being in flight at `event`, if it
+/// does. Transfers started on Substrate reach the graph node only once they
+/// are relayed, so only those started on Ethereum must be known to it.
+fn diverges(event: &Event, status: Option<&Status>) -> Option<String> {
+    match status {
+        Some(status @ Status::CONFIRMED) | Some(status @ Status::CANCELED) => Some(format!(
+            "the chain finished it as {:?}, it is still in flight here",
+            status
+        )),
+        Some(_) => None,
+        None if event.name().starts_with("Eth") => {
+            Some("the graph node does not know the message".to_string())
+        }
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use web3::types::{H160, H256};
+
+    #[test]
+    fn diverges_tests() {
+        let message_id = H256::from_slice(&[1; 32]);
+        let eth =
+            Event::eth_relay_message(message_id, H160::zero(), H256::zero(), 1.into(), None, 10);
+        let sub = Event::sub_relay_message(message_id, 10);
+        assert!(diverges(&eth, Some(&Status::PENDING)).is_none());
+        assert!(diverges(&eth, Some(&Status::CONFIRMED)).is_some());
+        assert!(diverges(&sub, Some(&Status::CANCELED)).is_some());
+        assert!(diverges(&eth, None).is_some());
+        assert!(diverges(&sub, None).is_none());
+    }
+}
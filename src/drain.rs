@@ -0,0 +1,84 @@
+use log;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::{thread, time::Duration};
+
+use crate::scheduler::Activity;
+
+const REQUEST_FILE: &str = "drain";
+const DRAINED_FILE: &str = "drained";
+
+/// Drain requests go through files in the data dir, so the CLI can reach a
+/// validator running as another process.
+#[derive(Debug, Clone)]
+pub struct Drain {
+    request: PathBuf,
+    drained: PathBuf,
+}
+
+impl Drain {
+    pub fn new(data_dir: &str) -> Self {
+        Drain {
+            request: Path::new(data_dir).join(REQUEST_FILE),
+            drained: Path::new(data_dir).join(DRAINED_FILE),
+        }
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.request.exists()
+    }
+
+    pub fn request(&self) -> Result<(), String> {
+        let _ = fs::remove_file(&self.drained);
+        fs::write(&self.request, "")
+            .map_err(|e| format!("can not create {:?}: {}", self.request, e))
+    }
+
+    /// Forgets a drain left over from before a restart.
+    pub fn clear(&self) {
+        let _ = fs::remove_file(&self.request);
+        let _ = fs::remove_file(&self.drained);
+    }
+
+    pub fn is_drained(&self) -> bool {
+        self.drained.exists()
+    }
+
+    /// Reports the validator drained once a drain is requested and the
+    /// executor has no submissions left.
+    pub fn spawn_monitor<K>(self, executor: Activity<K>) -> thread::JoinHandle<()>
+    where
+        K: Send + 'static,
+    {
+        thread::Builder::new()
+            .name("drain_monitor".to_string())
+            .spawn(move || loop {
+                if self.is_requested() && !self.is_drained() && executor.is_idle() {
+                    match fs::write(&self.drained, "") {
+                        Ok(()) => log::info!("drained, it is safe to stop the validator"),
+                        Err(e) => log::error!("can not create {:?}: {}", self.drained, e),
+                    }
+                }
+                thread::sleep(Duration::from_secs(1));
+            })
+            .expect("can not started drain monitor")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn request_and_clear_test() {
+        let dir = TestDir::new("drain");
+        let drain = Drain::new(dir.path());
+        assert!(!drain.is_requested());
+        drain.request().unwrap();
+        assert!(drain.is_requested());
+        drain.clear();
+        assert!(!drain.is_requested());
+    }
+}
@@ -0,0 +1,1601 @@
+use ethabi::Token;
+#[cfg(all(feature = "graph-node", feature = "sqlite"))]
+use graphql_client::{GraphQLQuery, Response};
+use log;
+use rustc_hex::FromHex;
+use serde::{Deserialize, Serialize};
+use web3::{
+    futures::Future,
+    transports::WebSocket,
+    types::{BlockNumber, FilterBuilder, Log, H160, H256, U256},
+};
+
+#[cfg(feature = "sqlite")]
+use std::collections::BTreeSet;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::bus::Bus;
+use crate::config::Config;
+use crate::controller::{Entity, EthLimits, Event, GuestLimits, Source};
+#[cfg(feature = "sqlite")]
+use crate::database::{self, AccountRow, MessageRow, Shared};
+use crate::dead_letters::DeadLetters;
+use crate::listener::{self, Converted};
+use crate::metrics;
+#[cfg(all(feature = "graph-node", feature = "sqlite"))]
+use crate::net;
+use crate::orphans::Orphans;
+use crate::startup::Ready;
+use crate::versioned::{self, Migration};
+
+const FILE_NAME: &str = "indexer.json";
+
+/// How often the store is written to `indexer.json`. After a crash the
+/// blocks since are indexed again.
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The offset of the indexer in the database.
+#[cfg(feature = "sqlite")]
+const OFFSET: &str = "eth_indexer";
+
+/// How `indexer.json` is upgraded from one version to the next.
+const MIGRATIONS: &[Migration] = &[];
+
+/// Entities per list in one page of the `migrate` query.
+#[cfg(all(feature = "graph-node", feature = "sqlite"))]
+const PAGE: usize = 1000;
+
+#[cfg(all(feature = "graph-node", feature = "sqlite"))]
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "res/graph_node_schema.graphql",
+    query_path = "res/graph_node_indexing_status.graphql",
+    response_derives = "Debug"
+)]
+struct IndexingStatus;
+
+#[cfg(all(feature = "graph-node", feature = "sqlite"))]
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "res/graph_node_schema.graphql",
+    query_path = "res/graph_node_migrate.graphql",
+    response_derives = "Debug"
+)]
+struct Migrate;
+
+/// Scans the logs of the token bridge contracts itself instead of querying
+/// the graph node, enabled by `INDEXER_ENABLED`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Indexer {
+    /// How many blocks one `eth_getLogs` call covers at most.
+    pub batch_blocks: u64,
+    pub poll_interval: Duration,
+    /// Stands by for the graph node listener and only publishes while the
+    /// subgraph is unhealthy, `INDEXER_FAILOVER`.
+    pub failover: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum Status {
+    Pending,
+    Withdraw,
+    Approved,
+    Canceled,
+    Confirmed,
+    ConfirmedWithdraw,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Status::Pending => "PENDING",
+            Status::Withdraw => "WITHDRAW",
+            Status::Approved => "APPROVED",
+            Status::Canceled => "CANCELED",
+            Status::Confirmed => "CONFIRMED",
+            Status::ConfirmedWithdraw => "CONFIRMED_WITHDRAW",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum Direction {
+    #[serde(rename = "ETH2SUB")]
+    Eth2Sub,
+    #[serde(rename = "SUB2ETH")]
+    Sub2Eth,
+}
+
+#[cfg(feature = "sqlite")]
+impl Direction {
+    fn name(self) -> &'static str {
+        match self {
+            Direction::Eth2Sub => "ETH2SUB",
+            Direction::Sub2Eth => "SUB2ETH",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum Action {
+    Stop,
+    Start,
+    Pause,
+    Resume,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum AccountKind {
+    Eth,
+    Sub,
+}
+
+#[cfg(feature = "sqlite")]
+impl AccountKind {
+    fn name(self) -> &'static str {
+        match self {
+            AccountKind::Eth => "ETH",
+            AccountKind::Sub => "SUB",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum AccountStatus {
+    Active,
+    Blocked,
+}
+
+#[cfg(feature = "sqlite")]
+impl AccountStatus {
+    fn name(self) -> &'static str {
+        match self {
+            AccountStatus::Active => "ACTIVE",
+            AccountStatus::Blocked => "BLOCKED",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Message {
+    eth_address: H160,
+    sub_address: H256,
+    token: H160,
+    amount: U256,
+    status: Status,
+    direction: Direction,
+    eth_block_number: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct BridgeMessage {
+    action: Action,
+    /// The contracts do not log one, as in the subgraph it is never set.
+    sender: Option<H160>,
+    eth_block_number: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct AccountMessage {
+    action: Action,
+    direction: Direction,
+    eth_address: Option<H160>,
+    sub_address: Option<H256>,
+    timestamp: u64,
+    eth_block_number: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Account {
+    message_id: H256,
+    kind: AccountKind,
+    status: AccountStatus,
+    timestamp: u64,
+    eth_block_number: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct LimitMessage {
+    limits: EthLimits,
+    eth_block_number: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CandidateValidator {
+    sub_address: H256,
+    active: bool,
+    eth_block_number: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ValidatorsListMessage {
+    new_validators: Vec<H256>,
+    new_how_many_validators_decide: U256,
+    eth_block_number: u64,
+}
+
+/// The entities the subgraph keeps, built by the same rules from the same
+/// logs, and the next block to scan. Kept in `<data_dir>/indexer.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Store {
+    /// Where the store was started, unknown for stores written before it
+    /// was kept.
+    #[serde(default)]
+    start_block: Option<u64>,
+    next_block: u64,
+    messages: BTreeMap<H256, Message>,
+    bridge_messages: BTreeMap<H256, BridgeMessage>,
+    account_messages: BTreeMap<H256, AccountMessage>,
+    /// By address, `0x` and lower case hex as the subgraph ids.
+    accounts: BTreeMap<String, Account>,
+    limit_messages: BTreeMap<H256, LimitMessage>,
+    candidate_validators: BTreeMap<H160, CandidateValidator>,
+    validators_list_messages: BTreeMap<H256, ValidatorsListMessage>,
+    #[cfg(feature = "sqlite")]
+    #[serde(skip)]
+    changed: Changed,
+}
+
+/// Messages and accounts changed since the database was last written.
+#[cfg(feature = "sqlite")]
+#[derive(Debug, Default)]
+struct Changed {
+    messages: BTreeSet<H256>,
+    accounts: BTreeSet<String>,
+}
+
+/// What `migrate` copied from the subgraph.
+#[cfg(all(feature = "graph-node", feature = "sqlite"))]
+#[derive(Debug, Default, PartialEq)]
+pub struct Migrated {
+    pub block_number: u64,
+    pub messages: usize,
+    pub accounts: usize,
+    pub limit_messages: usize,
+    pub candidate_validators: usize,
+}
+
+struct EventIndexer {
+    config: Config,
+    indexer: Indexer,
+    bus: Arc<Bus>,
+    dead_letters: DeadLetters,
+    web3: web3::Web3<WebSocket>,
+    addresses: Vec<H160>,
+    abi: HashMap<H256, ethabi::Event>,
+    store: Store,
+    path: PathBuf,
+    saved_at: Instant,
+    orphans: Orphans,
+    swept_at: Instant,
+    /// Whether the events are published, see `publishes`.
+    publishing: bool,
+    /// When the indexer started to stand by, in seconds since the epoch.
+    standby_since: u64,
+    #[cfg(feature = "sqlite")]
+    database: Shared,
+}
+
+pub fn spawn(
+    config: Config,
+    indexer: Indexer,
+    bus: Arc<Bus>,
+    ready: Ready,
+) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("eth_indexer".to_string())
+        .spawn(move || {
+            let (_eloop, transport) = match WebSocket::new(&config.eth_api_url) {
+                Ok(connection) => connection,
+                Err(e) => return ready.fail(format!("can not connect to ethereum node: {:?}", e)),
+            };
+            let web3 = web3::Web3::new(transport);
+            let path = Path::new(&config.data_dir).join(FILE_NAME);
+            let head = || confirmed_head(&web3, &config);
+            let store = match open_store(&path, config.eth_start_block, head) {
+                Ok(store) => store,
+                Err(e) => return ready.fail(e),
+            };
+            #[cfg(feature = "sqlite")]
+            let database = match sync_database(&config.data_dir, &store) {
+                Ok(database) => database,
+                Err(e) => return ready.fail(e),
+            };
+            log::info!("indexing ethereum logs from block {}", store.next_block);
+            let (addresses, abi) = contracts(&config);
+            let mut event_indexer = EventIndexer {
+                dead_letters: DeadLetters::open(&config.data_dir),
+                orphans: Orphans::open(&config.data_dir, config.orphan_max_redrives),
+                config,
+                indexer,
+                bus,
+                web3,
+                addresses,
+                abi,
+                store,
+                path,
+                saved_at: Instant::now(),
+                swept_at: Instant::now(),
+                publishing: false,
+                standby_since: listener::now(),
+                #[cfg(feature = "sqlite")]
+                database,
+            };
+            ready.ok();
+            event_indexer.start();
+        })
+        .expect("can not started eth_indexer")
+}
+
+impl EventIndexer {
+    /// Blocks up to the head at startup are indexed without sending their
+    /// events, as the graph node listener starts at the latest offsets. What
+    /// is still unfinalized in them is replayed once the store caught up and
+    /// the indexer publishes.
+    fn start(&mut self) {
+        let head = loop {
+            match confirmed_head(&self.web3, &self.config) {
+                Ok(head) => break head,
+                Err(e) => log::warn!("{}", e),
+            }
+            thread::sleep(self.indexer.poll_interval);
+        };
+        if self.store.next_block <= head {
+            log::info!(
+                "catching up on blocks {} to {} without sending their events",
+                self.store.next_block,
+                head
+            );
+        }
+        self.run(false, head);
+        if let Err(e) = self.save() {
+            log::warn!("{}", e);
+        }
+        self.run(true, u64::max_value());
+    }
+
+    /// Indexes up to block `until`, forever if there is no end to it.
+    fn run(&mut self, send: bool, until: u64) {
+        loop {
+            let send = send && self.publishes();
+            match self.index(send, until) {
+                // the next batch is waiting already
+                Ok(true) => continue,
+                Ok(false) if self.store.next_block > until => return,
+                Ok(false) => (),
+                Err(e) => log::warn!("can not index ethereum logs: {}", e),
+            }
+            let sweep = Duration::from_secs(self.config.orphan_sweep_secs);
+            if send && self.config.orphan_sweep_secs > 0 && self.swept_at.elapsed() >= sweep {
+                self.sweep_orphans();
+                self.swept_at = Instant::now();
+            }
+            thread::sleep(self.indexer.poll_interval);
+        }
+    }
+
+    /// Always, or with `INDEXER_FAILOVER` while the graph node listener has
+    /// not found the subgraph healthy for `GRAPH_NODE_WATCHDOG_SECS`, the
+    /// time after which the watchdog restarts a stalled listener. Taking over
+    /// replays what the listener may have missed; the listener keeps polling
+    /// and the controller drops the events it got from both.
+    fn publishes(&mut self) -> bool {
+        let unhealthy_secs = listener::graph_node_unhealthy_secs(self.standby_since);
+        let publishes =
+            !self.indexer.failover || unhealthy_secs > self.config.graph_node_watchdog_secs;
+        if publishes && !self.publishing {
+            if self.indexer.failover {
+                log::error!(
+                    "graph node is not healthy for {}s, the indexer takes over",
+                    unhealthy_secs
+                );
+            }
+            self.replay();
+        } else if !publishes && self.publishing {
+            log::info!("graph node is healthy again, the indexer stands by");
+            self.standby_since = listener::now();
+        }
+        if self.indexer.failover {
+            let failover = if publishes { 1.0 } else { 0.0 };
+            metrics::set_gauge("validator_indexer_failover", &[], failover);
+        }
+        self.publishing = publishes;
+        publishes
+    }
+
+    /// Re-drives transfers stuck for `ORPHAN_MIN_AGE_BLOCKS` behind the
+    /// indexed head, see `Orphans`.
+    fn sweep_orphans(&mut self) {
+        let indexed = self.store.next_block.saturating_sub(1);
+        let before = indexed.saturating_sub(self.config.orphan_min_age_blocks);
+        let stuck = self.store.orphaned(before);
+        self.orphans.sweep(stuck, &self.bus, &self.dead_letters);
+    }
+
+    /// Blocked accounts and unfinalized messages are sent again after a
+    /// restart, as the graph node listener does. Messages older than
+    /// `UNFINALIZED_MIN_BLOCK` or `UNFINALIZED_MAX_AGE_BLOCKS` are left out.
+    fn replay(&self) {
+        let from = listener::unfinalized_from(&self.web3, &self.config);
+        let mut events = self.store.blocked_accounts();
+        events.extend(
+            self.store
+                .unfinalized(&self.config.unfinalized_statuses, from),
+        );
+        events.sort_by(|(a, _), (b, _)| a.block_number().cmp(&b.block_number()));
+        log::info!("replaying {} indexed events", events.len());
+        self.send_events(events);
+    }
+
+    /// Indexes the next batch of blocks with `ETH_CONFIRMATIONS` up to
+    /// `until`, returns whether more are waiting. Events are sent before the
+    /// store is saved, after a crash the blocks since the last save are
+    /// indexed again without sending them.
+    fn index(&mut self, send: bool, until: u64) -> Result<bool, String> {
+        let confirmed = confirmed_head(&self.web3, &self.config)?.min(until);
+        let from = self.store.next_block;
+        if from > confirmed {
+            return Ok(false);
+        }
+        let to = confirmed.min(from + self.indexer.batch_blocks - 1);
+        let filter = FilterBuilder::default()
+            .address(self.addresses.clone())
+            .from_block(BlockNumber::Number(from.into()))
+            .to_block(BlockNumber::Number(to.into()))
+            .build();
+        let mut logs = self
+            .web3
+            .eth()
+            .logs(filter)
+            .wait()
+            .map_err(|e| format!("can not get logs of blocks {} to {}: {:?}", from, to, e))?;
+        logs.sort_by_key(|entry| {
+            (
+                entry.block_number.map(|number| number.low_u64()),
+                entry.log_index.map(|index| index.low_u64()),
+            )
+        });
+
+        let mut events = vec![];
+        for entry in logs.into_iter().filter(|entry| entry.removed != Some(true)) {
+            events.extend(apply(&self.abi, &mut self.store, entry));
+        }
+        if send {
+            self.send_events(events);
+        }
+        self.store.next_block = to + 1;
+        #[cfg(feature = "sqlite")]
+        {
+            let (messages, accounts) = self.store.changed_rows();
+            self.database
+                .lock()
+                .expect("database lock poisoned")
+                .save_index(&messages, &accounts, (OFFSET, self.store.next_block))?;
+            self.store.changed = Changed::default();
+        }
+        if self.saved_at.elapsed() >= SAVE_INTERVAL {
+            self.save()?;
+        }
+        metrics::set_gauge("validator_indexed_block", &[], to as f64);
+        log::debug!("indexed blocks {} to {}", from, to);
+        Ok(to < confirmed)
+    }
+
+    fn save(&mut self) -> Result<(), String> {
+        self.store.save(&self.path)?;
+        self.saved_at = Instant::now();
+        Ok(())
+    }
+
+    fn send_events(&self, events: Vec<Converted>) {
+        let source = Source::Indexer;
+        listener::send_events(&self.config, &self.bus, &self.dead_letters, source, events);
+    }
+}
+
+/// The latest block with `ETH_CONFIRMATIONS`.
+fn confirmed_head(web3: &web3::Web3<WebSocket>, config: &Config) -> Result<u64, String> {
+    let head = web3
+        .eth()
+        .block_number()
+        .wait()
+        .map_err(|e| format!("can not get ethereum block number: {:?}", e))?
+        .low_u64();
+    Ok(head.saturating_sub(config.eth_confirmations))
+}
+
+/// The token bridge contracts of `TOKEN_BRIDGE_ADDRESS` and
+/// `TOKEN_BRIDGES_FILE` and the events of their ABIs by topic.
+fn contracts(config: &Config) -> (Vec<H160>, HashMap<H256, ethabi::Event>) {
+    let bridges = std::iter::once(&config.token_bridge).chain(config.token_bridges.values());
+    let mut addresses = vec![];
+    let mut abis = vec![];
+    for bridge in bridges {
+        if !addresses.contains(&bridge.address) {
+            addresses.push(bridge.address);
+        }
+        abis.push(&bridge.abi);
+    }
+    (addresses, load_abi(abis))
+}
+
+/// A log that can not be decoded is logged and skipped, where the subgraph
+/// would stop indexing.
+fn apply(abi: &HashMap<H256, ethabi::Event>, store: &mut Store, entry: Log) -> Vec<Converted> {
+    let block_number = entry.block_number.map_or(0, |number| number.low_u64());
+    decode(abi, entry.topics.clone(), entry.data.0.clone())
+        .and_then(|decoded| match decoded {
+            Some((name, params)) => store.apply(&name, &params, block_number),
+            None => Ok(vec![]),
+        })
+        .unwrap_or_else(|e| {
+            log::error!("skipping log {:?}: {}", entry, e);
+            metrics::increment("validator_indexer_skipped_logs_total", &[]);
+            vec![]
+        })
+}
+
+/// A new store starts at `ETH_START_BLOCK`, or at the head without one. A
+/// kept store continues where it stopped, also when it was started at
+/// another block.
+fn open_store(
+    path: &Path,
+    start_block: Option<u64>,
+    head: impl FnOnce() -> Result<u64, String>,
+) -> Result<Store, String> {
+    let store = match Store::open(path)? {
+        Some(store) => store,
+        None => return start_block.map_or_else(head, Ok).map(Store::new),
+    };
+    match (start_block, store.start_block) {
+        (Some(configured), Some(started)) if configured != started => log::warn!(
+            "ETH_START_BLOCK is {} but {:?} was started at block {}, continuing from block {}; \
+             remove it to index from {}",
+            configured,
+            path,
+            started,
+            store.next_block,
+            configured
+        ),
+        _ => (),
+    }
+    Ok(store)
+}
+
+/// Opens the database and brings it up to the store, in full when it was
+/// created or is not at the same block, e.g. after a crash between the two.
+#[cfg(feature = "sqlite")]
+fn sync_database(data_dir: &str, store: &Store) -> Result<Shared, String> {
+    let shared = database::shared(data_dir)?;
+    {
+        let mut database = shared.lock().expect("database lock poisoned");
+        if database.offset(OFFSET)? != Some(store.next_block) {
+            log::info!("copying the indexer store into the database");
+            let (messages, accounts) = store.rows(store.messages.keys(), store.accounts.keys());
+            database.save_index(&messages, &accounts, (OFFSET, store.next_block))?;
+        }
+    }
+    Ok(shared)
+}
+
+/// Bootstraps the store and the database of a validator upgraded from the
+/// graph node listener with what the subgraph indexed, so the indexer starts
+/// after that block instead of scanning from `ETH_START_BLOCK` with an empty
+/// store. Opening the database imports the JSON lines files as well. The
+/// messages, accounts, limits and candidate validators are copied up to the
+/// block the subgraph indexed with `ETH_CONFIRMATIONS`; the bridge, account
+/// and validator list messages are only a history the indexer appends to.
+/// A kept store is not replaced.
+#[cfg(all(feature = "graph-node", feature = "sqlite"))]
+pub fn migrate(config: &Config) -> Result<Migrated, String> {
+    let path = Path::new(&config.data_dir).join(FILE_NAME);
+    if Store::open(&path)?.is_some() {
+        return Err(format!(
+            "{:?} exists already, remove it to migrate again",
+            path
+        ));
+    }
+    let (_eloop, transport) = WebSocket::new(&config.eth_api_url)
+        .map_err(|e| format!("can not connect to ethereum node: {:?}", e))?;
+    let web3 = web3::Web3::new(transport);
+    let block_number = indexed_block(config)?.min(confirmed_head(&web3, config)?);
+    let mut store = Store {
+        start_block: config.eth_start_block,
+        next_block: block_number + 1,
+        ..Store::default()
+    };
+    let mut skip = 0;
+    loop {
+        let page = query_subgraph(config, block_number, skip)?;
+        let more = [
+            page.messages.len(),
+            page.accounts.len(),
+            page.limit_messages.len(),
+            page.candidate_validators.len(),
+        ]
+        .iter()
+        .any(|len| *len == PAGE);
+        store.insert_subgraph(page)?;
+        if !more {
+            break;
+        }
+        skip += PAGE;
+    }
+    store.save(&path)?;
+    sync_database(&config.data_dir, &store)?;
+    Ok(Migrated {
+        block_number,
+        messages: store.messages.len(),
+        accounts: store.accounts.len(),
+        limit_messages: store.limit_messages.len(),
+        candidate_validators: store.candidate_validators.len(),
+    })
+}
+
+/// The last block the subgraph indexed.
+#[cfg(all(feature = "graph-node", feature = "sqlite"))]
+fn indexed_block(config: &Config) -> Result<u64, String> {
+    let request_body = IndexingStatus::build_query(indexing_status::Variables);
+    let response: Response<indexing_status::ResponseData> = net::http_client()
+        .post(&config.graph_node_api_url)
+        .json(&request_body)
+        .send()
+        .and_then(|mut response| response.json())
+        .map_err(|e| format!("can not query the graph node: {}", e))?;
+    response
+        .data
+        .and_then(|data| data.meta)
+        .map(|meta| meta.block.number as u64)
+        .ok_or_else(|| {
+            format!(
+                "the graph node has no indexed block, errors: {:?}",
+                response.errors
+            )
+        })
+}
+
+#[cfg(all(feature = "graph-node", feature = "sqlite"))]
+fn query_subgraph(
+    config: &Config,
+    block_number: u64,
+    skip: usize,
+) -> Result<migrate::ResponseData, String> {
+    let request_body = Migrate::build_query(migrate::Variables {
+        block_number: block_number as i64,
+        skip: skip as i64,
+    });
+    let response: Response<migrate::ResponseData> = net::http_client()
+        .post(&config.graph_node_api_url)
+        .json(&request_body)
+        .send()
+        .and_then(|mut response| response.json())
+        .map_err(|e| format!("can not query the graph node: {}", e))?;
+    response
+        .data
+        .ok_or_else(|| format!("no data from the graph node, errors: {:?}", response.errors))
+}
+
+impl Store {
+    fn new(start_block: u64) -> Self {
+        Store {
+            start_block: Some(start_block),
+            next_block: start_block,
+            ..Store::default()
+        }
+    }
+
+    /// `None` if there is no store yet.
+    fn open(path: &Path) -> Result<Option<Self>, String> {
+        match fs::read_to_string(path) {
+            Ok(json) => versioned::from_str(&json, MIGRATIONS)
+                .map(Some)
+                .map_err(|e| format!("can not parse {:?}: {}", path, e)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Written to a temporary file first, a crash while saving must not
+    /// leave a truncated store.
+    fn save(&self, path: &Path) -> Result<(), String> {
+        let json = versioned::to_string(self, MIGRATIONS).expect("indexer store serializes");
+        let temporary = path.with_extension("json.tmp");
+        path.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&temporary, json))
+            .and_then(|()| fs::rename(&temporary, path))
+            .map_err(|e| format!("can not save the indexer store to {:?}: {}", path, e))
+    }
+
+    /// The messages and accounts changed since the database was written.
+    #[cfg(feature = "sqlite")]
+    fn changed_rows(&self) -> (Vec<MessageRow>, Vec<AccountRow>) {
+        self.rows(self.changed.messages.iter(), self.changed.accounts.iter())
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn rows<'a>(
+        &self,
+        message_ids: impl Iterator<Item = &'a H256>,
+        account_ids: impl Iterator<Item = &'a String>,
+    ) -> (Vec<MessageRow>, Vec<AccountRow>) {
+        let messages = message_ids
+            .filter_map(|id| self.messages.get(id).map(|message| (id, message)))
+            .map(|(id, message)| MessageRow {
+                message_id: *id,
+                direction: message.direction.name().to_string(),
+                status: message.status.name().to_string(),
+                eth_address: message.eth_address,
+                sub_address: message.sub_address,
+                token: message.token,
+                amount: message.amount,
+                eth_block_number: message.eth_block_number,
+            })
+            .collect();
+        let accounts = account_ids
+            .filter_map(|id| self.accounts.get(id).map(|account| (id, account)))
+            .map(|(id, account)| AccountRow {
+                id: id.clone(),
+                message_id: account.message_id,
+                kind: account.kind.name().to_string(),
+                status: account.status.name().to_string(),
+                timestamp: account.timestamp,
+                eth_block_number: account.eth_block_number,
+            })
+            .collect();
+        (messages, accounts)
+    }
+
+    /// Updates the entities the way the subgraph mapping in
+    /// `subgraph/src/mapping.ts` does and returns the events of those that
+    /// changed.
+    fn apply(
+        &mut self,
+        name: &str,
+        params: &Params,
+        block_number: u64,
+    ) -> Result<Vec<Converted>, String> {
+        let converted = match name {
+            "RelayMessage" => {
+                let message_id = params.h256("messageID")?;
+                let message = Message {
+                    eth_address: params.h160("sender")?,
+                    sub_address: params.h256("recipient")?,
+                    token: params.h160("token")?,
+                    amount: params.u256("amount")?,
+                    status: Status::Pending,
+                    direction: Direction::Eth2Sub,
+                    eth_block_number: block_number,
+                };
+                self.messages.insert(message_id, message);
+                self.message_event(message_id)
+            }
+            "WithdrawMessage" => {
+                let message_id = params.h256("messageID")?;
+                let message = Message {
+                    eth_address: params.h160("recepient")?,
+                    sub_address: params.h256("sender")?,
+                    token: params.h160("token")?,
+                    amount: params.u256("amount")?,
+                    status: Status::Withdraw,
+                    direction: Direction::Sub2Eth,
+                    eth_block_number: block_number,
+                };
+                self.messages.insert(message_id, message);
+                self.message_event(message_id)
+            }
+            "ApprovedRelayMessage" => self.change_status(params, Status::Approved)?,
+            "RevertMessage" | "ConfirmCancelMessage" => {
+                self.change_status(params, Status::Canceled)?
+            }
+            "ConfirmMessage" => self.change_status(params, Status::Confirmed)?,
+            "ConfirmWithdrawMessage" => self.change_status(params, Status::ConfirmedWithdraw)?,
+            "BridgeStarted"
+            | "BridgeStopped"
+            | "BridgePaused"
+            | "BridgePausedByVolume"
+            | "BridgeResumed"
+            | "BridgeStartedByVolume" => {
+                let action = match name {
+                    "BridgeStarted" => Action::Start,
+                    "BridgeStopped" => Action::Stop,
+                    "BridgePaused" | "BridgePausedByVolume" => Action::Pause,
+                    _ => Action::Resume,
+                };
+                let message_id = params.h256("messageID")?;
+                let message = BridgeMessage {
+                    action,
+                    sender: None,
+                    eth_block_number: block_number,
+                };
+                let event = message.event(message_id);
+                self.bridge_messages.insert(message_id, message);
+                vec![(event, entity("bridgeMessage", &message_id))]
+            }
+            "HostAccountPausedMessage" | "HostAccountResumedMessage" => {
+                let action = if name == "HostAccountPausedMessage" {
+                    Action::Pause
+                } else {
+                    Action::Resume
+                };
+                let account = params.h160("sender")?;
+                let message = AccountMessage {
+                    action,
+                    direction: Direction::Eth2Sub,
+                    eth_address: Some(account),
+                    sub_address: None,
+                    timestamp: params.u256("timestamp")?.low_u64(),
+                    eth_block_number: block_number,
+                };
+                self.account_message(
+                    params.h256("messageID")?,
+                    &format!("{:?}", account),
+                    message,
+                )
+            }
+            "GuestAccountPausedMessage" | "GuestAccountResumedMessage" => {
+                let action = if name == "GuestAccountPausedMessage" {
+                    Action::Pause
+                } else {
+                    Action::Resume
+                };
+                let account = params.h256("recipient")?;
+                let message = AccountMessage {
+                    action,
+                    direction: Direction::Sub2Eth,
+                    eth_address: None,
+                    sub_address: Some(account),
+                    timestamp: params.u256("timestamp")?.low_u64(),
+                    eth_block_number: block_number,
+                };
+                self.account_message(
+                    params.h256("messageID")?,
+                    &format!("{:?}", account),
+                    message,
+                )
+            }
+            "SetNewLimits" => {
+                let message_id = limit_message_id(block_number);
+                let limits = EthLimits {
+                    min_host_transaction_value: params.u256("minHostTransactionValue")?,
+                    max_host_transaction_value: params.u256("maxHostTransactionValue")?,
+                    day_host_max_limit: params.u256("dayHostMaxLimit")?,
+                    day_host_max_limit_for_one_address: params
+                        .u256("dayHostMaxLimitForOneAddress")?,
+                    max_host_pending_transaction_limit: params
+                        .u256("maxHostPendingTransactionLimit")?,
+                    guest: GuestLimits {
+                        min_transaction_value: params.u256("minGuestTransactionValue")?,
+                        max_transaction_value: params.u256("maxGuestTransactionValue")?,
+                        day_max_limit: params.u256("dayGuestMaxLimit")?,
+                        day_max_limit_for_one_address: params
+                            .u256("dayGuestMaxLimitForOneAddress")?,
+                        max_pending_transaction_limit: params
+                            .u256("maxGuestPendingTransactionLimit")?,
+                    },
+                };
+                let event =
+                    Event::eth_set_new_limits(message_id, limits.clone(), u128::from(block_number));
+                self.limit_messages.insert(
+                    message_id,
+                    LimitMessage {
+                        limits,
+                        eth_block_number: block_number,
+                    },
+                );
+                vec![(event, entity("limitMessage", &message_id))]
+            }
+            "AddCandidateValidator" | "RemoveCandidateValidator" => {
+                self.candidate_validators.insert(
+                    params.h160("host")?,
+                    CandidateValidator {
+                        sub_address: params.h256("guest")?,
+                        active: name == "AddCandidateValidator",
+                        eth_block_number: block_number,
+                    },
+                );
+                vec![]
+            }
+            "ChangeValidatorsList" => self.validators_list_message(params, block_number)?,
+            _ => vec![],
+        };
+        Ok(converted)
+    }
+
+    fn change_status(&mut self, params: &Params, status: Status) -> Result<Vec<Converted>, String> {
+        let message_id = params.h256("messageID")?;
+        match self.messages.get_mut(&message_id) {
+            Some(message) if message.status != status => message.status = status,
+            // seen before, or a message the store does not have, which the
+            // subgraph ignores as well
+            _ => return Ok(vec![]),
+        }
+        Ok(self.message_event(message_id))
+    }
+
+    /// The event of a message that changed.
+    fn message_event(&mut self, message_id: H256) -> Vec<Converted> {
+        #[cfg(feature = "sqlite")]
+        self.changed.messages.insert(message_id);
+        self.messages
+            .get(&message_id)
+            .and_then(|message| message.event(message_id))
+            .map(|event| (event, entity("message", &message_id)))
+            .into_iter()
+            .collect()
+    }
+
+    fn account_message(
+        &mut self,
+        message_id: H256,
+        account_id: &str,
+        message: AccountMessage,
+    ) -> Vec<Converted> {
+        let event = message.event(message_id);
+        self.accounts.insert(
+            account_id.to_string(),
+            Account {
+                message_id,
+                kind: match message.direction {
+                    Direction::Eth2Sub => AccountKind::Eth,
+                    Direction::Sub2Eth => AccountKind::Sub,
+                },
+                status: match message.action {
+                    Action::Pause => AccountStatus::Blocked,
+                    _ => AccountStatus::Active,
+                },
+                timestamp: message.timestamp,
+                eth_block_number: message.eth_block_number,
+            },
+        );
+        #[cfg(feature = "sqlite")]
+        self.changed.accounts.insert(account_id.to_string());
+        self.account_messages.insert(message_id, message);
+        vec![(event, entity("accountMessage", &message_id))]
+    }
+
+    /// Validators are listed by their substrate accounts, which they
+    /// registered as candidates. A list with a validator that is not an
+    /// active candidate is dropped, as by the subgraph.
+    fn validators_list_message(
+        &mut self,
+        params: &Params,
+        block_number: u64,
+    ) -> Result<Vec<Converted>, String> {
+        let message_id = params.h256("messageID")?;
+        let new_validators = params
+            .h160s("newvalidators")?
+            .iter()
+            .map(|validator| match self.candidate_validators.get(validator) {
+                Some(candidate) if candidate.active => Ok(candidate.sub_address),
+                _ => Err(format!(
+                    "{:?} of validator list {:?} is not an active candidate",
+                    validator, message_id
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>();
+        let new_validators = match new_validators {
+            Ok(new_validators) => new_validators,
+            Err(e) => {
+                log::error!("dropping invalid validator list: {}", e);
+                return Ok(vec![]);
+            }
+        };
+        let message = ValidatorsListMessage {
+            new_validators,
+            new_how_many_validators_decide: params.u256("newHowManyValidatorsDecide")?,
+            eth_block_number: block_number,
+        };
+        let event = Event::eth_validators_list_message(
+            message_id,
+            message.new_validators.clone(),
+            message.new_how_many_validators_decide,
+            u128::from(block_number),
+        );
+        self.validators_list_messages.insert(message_id, message);
+        Ok(vec![(event, entity("validatorsListMessage", &message_id))])
+    }
+
+    fn blocked_accounts(&self) -> Vec<Converted> {
+        self.accounts
+            .iter()
+            .filter(|(_, account)| account.status == AccountStatus::Blocked)
+            .filter_map(|(id, account)| {
+                account
+                    .paused_event(id)
+                    .map(|event| (event, Entity::new("account", id)))
+            })
+            .collect()
+    }
+
+    fn unfinalized(&self, statuses: &[String], from_block: u64) -> Vec<Converted> {
+        self.messages
+            .iter()
+            .filter(|(_, message)| message.eth_block_number >= from_block)
+            .filter(|(_, message)| statuses.iter().any(|s| s == message.status.name()))
+            .filter_map(|(message_id, message)| {
+                message
+                    .event(*message_id)
+                    .map(|event| (event, entity("message", message_id)))
+            })
+            .collect()
+    }
+
+    /// Transfers approved or withdrawn on one chain up to block `before` and
+    /// not finished on the other.
+    fn orphaned(&self, before: u64) -> Vec<Converted> {
+        self.messages
+            .iter()
+            .filter(|(_, message)| message.eth_block_number <= before)
+            .filter(|(_, message)| match message.status {
+                Status::Approved | Status::Withdraw => true,
+                _ => false,
+            })
+            .filter_map(|(message_id, message)| {
+                message
+                    .event(*message_id)
+                    .map(|event| (event, entity("message", message_id)))
+            })
+            .collect()
+    }
+}
+
+/// The entities of the subgraph, whose enums are named as those of the
+/// store and whose numbers are in decimal.
+#[cfg(all(feature = "graph-node", feature = "sqlite"))]
+impl Store {
+    fn insert_subgraph(&mut self, page: migrate::ResponseData) -> Result<(), String> {
+        for message in page.messages {
+            self.messages.insert(
+                parse_hex(&message.id)?,
+                Message {
+                    eth_address: parse_hex(&message.eth_address)?,
+                    sub_address: parse_hex(&message.sub_address)?,
+                    token: parse_hex(&message.token)?,
+                    amount: parse_decimal(&message.amount)?,
+                    status: same_name(&message.status)?,
+                    direction: same_name(&message.direction)?,
+                    eth_block_number: parse_number(&message.eth_block_number)?,
+                },
+            );
+        }
+        for account in page.accounts {
+            let kind = same_name(&account.kind)?;
+            // keyed as the indexer formats addresses
+            let id = match kind {
+                AccountKind::Eth => format!("{:?}", parse_hex::<H160>(&account.id)?),
+                AccountKind::Sub => format!("{:?}", parse_hex::<H256>(&account.id)?),
+            };
+            self.accounts.insert(
+                id,
+                Account {
+                    message_id: parse_hex(&account.message_id)?,
+                    kind,
+                    status: same_name(&account.status)?,
+                    timestamp: parse_number(&account.timestamp)?,
+                    eth_block_number: parse_number(&account.eth_block_number)?,
+                },
+            );
+        }
+        for message in page.limit_messages {
+            let limits = EthLimits {
+                min_host_transaction_value: parse_decimal(&message.min_host_transaction_value)?,
+                max_host_transaction_value: parse_decimal(&message.max_host_transaction_value)?,
+                day_host_max_limit: parse_decimal(&message.day_host_max_limit)?,
+                day_host_max_limit_for_one_address: parse_decimal(
+                    &message.day_host_max_limit_for_one_address,
+                )?,
+                max_host_pending_transaction_limit: parse_decimal(
+                    &message.max_host_pending_transaction_limit,
+                )?,
+                guest: GuestLimits {
+                    min_transaction_value: parse_decimal(&message.min_guest_transaction_value)?,
+                    max_transaction_value: parse_decimal(&message.max_guest_transaction_value)?,
+                    day_max_limit: parse_decimal(&message.day_guest_max_limit)?,
+                    day_max_limit_for_one_address: parse_decimal(
+                        &message.day_guest_max_limit_for_one_address,
+                    )?,
+                    max_pending_transaction_limit: parse_decimal(
+                        &message.max_guest_pending_transaction_limit,
+                    )?,
+                },
+            };
+            self.limit_messages.insert(
+                parse_hex(&message.id)?,
+                LimitMessage {
+                    limits,
+                    eth_block_number: parse_number(&message.eth_block_number)?,
+                },
+            );
+        }
+        for candidate in page.candidate_validators {
+            self.candidate_validators.insert(
+                parse_hex(&candidate.id)?,
+                CandidateValidator {
+                    sub_address: parse_hex(&candidate.sub_address)?,
+                    active: candidate.active,
+                    eth_block_number: parse_number(&candidate.eth_block_number)?,
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Message {
+    /// Confirmed messages are done, they have no event.
+    fn event(&self, message_id: H256) -> Option<Event> {
+        let block_number = u128::from(self.eth_block_number);
+        let event = match (self.status, self.direction) {
+            (Status::Pending, Direction::Eth2Sub) => Event::eth_relay_message(
+                message_id,
+                self.eth_address,
+                self.sub_address,
+                self.amount,
+                Some(self.token),
+                block_number,
+            ),
+            (Status::Approved, Direction::Eth2Sub) => Event::eth_approved_relay_message(
+                message_id,
+                self.eth_address,
+                self.sub_address,
+                self.amount,
+                block_number,
+            ),
+            (Status::Canceled, Direction::Eth2Sub) => {
+                Event::eth_revert_message(message_id, self.eth_address, self.amount, block_number)
+            }
+            (Status::Withdraw, Direction::Sub2Eth) => {
+                Event::eth_withdraw_message(message_id, block_number)
+            }
+            _ => return None,
+        };
+        Some(event)
+    }
+}
+
+impl BridgeMessage {
+    fn event(&self, message_id: H256) -> Event {
+        let block_number = u128::from(self.eth_block_number);
+        let sender = self.sender;
+        match self.action {
+            Action::Pause => Event::eth_bridge_paused_message(message_id, block_number),
+            Action::Resume => Event::eth_bridge_resumed_message(message_id, block_number),
+            Action::Start => Event::eth_bridge_started_message(message_id, sender, block_number),
+            Action::Stop => Event::eth_bridge_stopped_message(message_id, sender, block_number),
+        }
+    }
+}
+
+impl AccountMessage {
+    fn event(&self, message_id: H256) -> Event {
+        let timestamp = self.timestamp;
+        let block_number = u128::from(self.eth_block_number);
+        match (self.action, self.eth_address, self.sub_address) {
+            (Action::Pause, Some(account), _) => {
+                Event::eth_host_account_paused_message(message_id, account, timestamp, block_number)
+            }
+            (Action::Resume, Some(account), _) => Event::eth_host_account_resumed_message(
+                message_id,
+                account,
+                timestamp,
+                block_number,
+            ),
+            (Action::Pause, None, Some(account)) => Event::eth_guest_account_paused_message(
+                message_id,
+                account,
+                timestamp,
+                block_number,
+            ),
+            (Action::Resume, None, Some(account)) => Event::eth_guest_account_resumed_message(
+                message_id,
+                account,
+                timestamp,
+                block_number,
+            ),
+            _ => missing_address("accountMessage", "address", message_id, self, block_number),
+        }
+    }
+}
+
+impl Account {
+    fn paused_event(&self, id: &str) -> Option<Event> {
+        let message_id = self.message_id;
+        let timestamp = self.timestamp;
+        let block_number = u128::from(self.eth_block_number);
+        let address = id.trim_start_matches("0x");
+        let event = match self.kind {
+            AccountKind::Eth => Event::eth_host_account_paused_message(
+                message_id,
+                address.parse().ok()?,
+                timestamp,
+                block_number,
+            ),
+            AccountKind::Sub => Event::eth_guest_account_paused_message(
+                message_id,
+                address.parse().ok()?,
+                timestamp,
+                block_number,
+            ),
+        };
+        Some(event)
+    }
+}
+
+/// The parameters of a decoded log by name.
+#[derive(Debug, Default)]
+struct Params(HashMap<String, Token>);
+
+impl Params {
+    fn get(&self, name: &str) -> Result<&Token, String> {
+        self.0
+            .get(name)
+            .ok_or_else(|| format!("the log has no {}", name))
+    }
+
+    fn h256(&self, name: &str) -> Result<H256, String> {
+        match self.get(name)? {
+            Token::FixedBytes(bytes) if bytes.len() == 32 => Ok(H256::from_slice(bytes)),
+            token => Err(format!("{} is not a bytes32: {:?}", name, token)),
+        }
+    }
+
+    fn h160(&self, name: &str) -> Result<H160, String> {
+        match self.get(name)? {
+            Token::Address(address) => Ok(*address),
+            token => Err(format!("{} is not an address: {:?}", name, token)),
+        }
+    }
+
+    fn h160s(&self, name: &str) -> Result<Vec<H160>, String> {
+        match self.get(name)? {
+            Token::Array(tokens) => tokens
+                .iter()
+                .map(|token| match token {
+                    Token::Address(address) => Ok(*address),
+                    token => Err(format!("{} has a non address: {:?}", name, token)),
+                })
+                .collect(),
+            token => Err(format!("{} is not an address list: {:?}", name, token)),
+        }
+    }
+
+    fn u256(&self, name: &str) -> Result<U256, String> {
+        match self.get(name)? {
+            Token::Uint(value) => Ok(*value),
+            token => Err(format!("{} is not a uint256: {:?}", name, token)),
+        }
+    }
+}
+
+/// The events of the contracts by their topic. Those the subgraph does not
+/// map are ignored by `Store::apply`.
+fn load_abi<'a>(
+    abis: impl IntoIterator<Item = &'a ethabi::Contract>,
+) -> HashMap<H256, ethabi::Event> {
+    abis.into_iter()
+        .flat_map(|abi| abi.events())
+        .map(|event| (event.signature(), event.clone()))
+        .collect()
+}
+
+/// Logs of events the subgraph does not map, e.g. `OwnershipTransferred`,
+/// are `None`.
+fn decode(
+    abi: &HashMap<H256, ethabi::Event>,
+    topics: Vec<H256>,
+    data: Vec<u8>,
+) -> Result<Option<(String, Params)>, String> {
+    let event = match topics.first().and_then(|topic| abi.get(topic)) {
+        Some(event) => event,
+        None => return Ok(None),
+    };
+    let log = event
+        .parse_log(ethabi::RawLog { topics, data })
+        .map_err(|e| format!("can not decode {}: {}", event.name, e))?;
+    let params = log
+        .params
+        .into_iter()
+        .map(|param| (param.name, param.value))
+        .collect();
+    Ok(Some((event.name.clone(), Params(params))))
+}
+
+/// Limit messages have no id of their own, the subgraph derives it from the
+/// block: the keccak hash of `0` followed by the block number in hex.
+fn limit_message_id(block_number: u64) -> H256 {
+    let mut hex = format!("0{:x}", block_number);
+    if hex.len() % 2 == 1 {
+        hex.insert(0, '0');
+    }
+    let bytes: Vec<u8> = hex.from_hex().expect("formatted as hex");
+    H256::from_slice(&primitives::hashing::keccak_256(&bytes))
+}
+
+#[cfg(all(feature = "graph-node", feature = "sqlite"))]
+fn parse_hex<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .trim_start_matches("0x")
+        .parse()
+        .map_err(|_| format!("invalid hex {:?}", value))
+}
+
+#[cfg(all(feature = "graph-node", feature = "sqlite"))]
+fn parse_decimal(value: &str) -> Result<U256, String> {
+    U256::from_dec_str(value).map_err(|_| format!("invalid number {:?}", value))
+}
+
+#[cfg(all(feature = "graph-node", feature = "sqlite"))]
+fn parse_number(value: &str) -> Result<u64, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid number {:?}", value))
+}
+
+#[cfg(all(feature = "graph-node", feature = "sqlite"))]
+fn same_name<T: Serialize, U: serde::de::DeserializeOwned>(value: &T) -> Result<U, String> {
+    serde_json::to_value(value)
+        .and_then(serde_json::from_value)
+        .map_err(|e| format!("unknown value in the subgraph: {}", e))
+}
+
+fn entity(kind: &str, id: &impl std::fmt::Debug) -> Entity {
+    Entity::new(kind, &format!("{:?}", id))
+}
+
+/// An entity without the address its event needs, kept as an unknown event.
+fn missing_address<T: std::fmt::Debug>(
+    origin: &str,
+    field: &str,
+    message_id: H256,
+    entity: &T,
+    block_number: u128,
+) -> Event {
+    log::error!(
+        "{} {:?} has no {}, skipping it: {:?}",
+        origin,
+        message_id,
+        field,
+        entity
+    );
+    metrics::increment(
+        "validator_missing_address_total",
+        &[("origin", origin), ("field", field)],
+    );
+    Event::unknown(message_id, origin, &format!("{:?}", entity), block_number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor;
+    use crate::test_dir::TestDir;
+
+    fn apply(store: &mut Store, name: &str, tokens: &[Token], block_number: u64) -> Vec<Event> {
+        let abi = load_abi(vec![&executor::get_contract_abi()]);
+        let topic = abi
+            .values()
+            .find(|event| event.name == name)
+            .map(|event| event.signature())
+            .unwrap();
+        let (name, params) = decode(&abi, vec![topic], ethabi::encode(tokens))
+            .unwrap()
+            .unwrap();
+        store
+            .apply(&name, &params, block_number)
+            .unwrap()
+            .into_iter()
+            .map(|(event, _)| event)
+            .collect()
+    }
+
+    #[test]
+    fn open_store_test() {
+        let dir = TestDir::new("eth_indexer");
+        let path = dir.join(FILE_NAME);
+        let head = || Ok(500);
+        assert_eq!(500, open_store(&path, None, head).unwrap().next_block);
+        let mut store = open_store(&path, Some(100), head).unwrap();
+        assert_eq!((Some(100), 100), (store.start_block, store.next_block));
+        store.next_block = 200;
+        store.save(&path).unwrap();
+
+        // the kept store wins over another start block
+        let store = open_store(&path, Some(50), || Err("no head".to_string())).unwrap();
+        assert_eq!((Some(100), 200), (store.start_block, store.next_block));
+    }
+
+    #[test]
+    fn apply_tests() {
+        let mut store = Store::default();
+        let message_id = H256::from_slice(&[1; 32]);
+        let sender = H160::from_slice(&[2; 20]);
+        let recipient = H256::from_slice(&[3; 32]);
+        let transfer = [
+            Token::FixedBytes(message_id.as_bytes().to_vec()),
+            Token::Address(sender),
+            Token::FixedBytes(recipient.as_bytes().to_vec()),
+            Token::Uint(100.into()),
+            Token::Address(H160::zero()),
+        ];
+        assert_eq!(
+            vec![Event::eth_relay_message(
+                message_id,
+                sender,
+                recipient,
+                100.into(),
+                Some(H160::zero()),
+                10
+            )],
+            apply(&mut store, "RelayMessage", &transfer, 10)
+        );
+        let approved = apply(&mut store, "ApprovedRelayMessage", &transfer, 12);
+        match &approved[..] {
+            [Event::EthApprovedRelayMessage { block_number, .. }] => assert_eq!(10, *block_number),
+            events => panic!("expected an approval, got {:?}", events),
+        }
+        // replayed while approved, not once confirmed
+        assert_eq!(1, store.unfinalized(&["APPROVED".to_string()], 0).len());
+        assert_eq!(1, store.orphaned(10).len());
+        assert!(store.orphaned(9).is_empty());
+        assert!(apply(&mut store, "ConfirmMessage", &transfer, 13).is_empty());
+        assert!(apply(&mut store, "ConfirmMessage", &transfer, 13).is_empty());
+        assert_eq!(Status::Confirmed, store.messages[&message_id].status);
+        assert!(store.unfinalized(&["APPROVED".to_string()], 0).is_empty());
+        assert!(store.orphaned(13).is_empty());
+        // a confirmation has no event, the database still learns of it
+        #[cfg(feature = "sqlite")]
+        match &store.changed_rows().0[..] {
+            [row] => assert_eq!("CONFIRMED", row.status),
+            rows => panic!("expected the confirmed message, got {:?}", rows),
+        }
+
+        let host = H160::from_slice(&[4; 20]);
+        let guest = H256::from_slice(&[5; 32]);
+        let candidate = [
+            Token::FixedBytes(vec![6; 32]),
+            Token::Address(host),
+            Token::FixedBytes(guest.as_bytes().to_vec()),
+        ];
+        assert!(apply(&mut store, "AddCandidateValidator", &candidate, 14).is_empty());
+        let validators = |validators: Vec<H160>| {
+            [
+                Token::FixedBytes(vec![7; 32]),
+                Token::Array(vec![]),
+                Token::Uint(0.into()),
+                Token::Array(validators.into_iter().map(Token::Address).collect()),
+                Token::Uint(1.into()),
+            ]
+        };
+        assert!(apply(
+            &mut store,
+            "ChangeValidatorsList",
+            &validators(vec![sender]),
+            15
+        )
+        .is_empty());
+        match &apply(
+            &mut store,
+            "ChangeValidatorsList",
+            &validators(vec![host]),
+            15,
+        )[..]
+        {
+            [Event::EthValidatorsListMessage { validators, .. }] => {
+                assert_eq!(&vec![guest], validators)
+            }
+            events => panic!("expected a validator list, got {:?}", events),
+        }
+
+        let paused = [
+            Token::FixedBytes(vec![8; 32]),
+            Token::Address(sender),
+            Token::Uint(1_600_000_000u64.into()),
+        ];
+        apply(&mut store, "HostAccountPausedMessage", &paused, 16);
+        match &store.blocked_accounts()[..] {
+            [(Event::EthHostAccountPausedMessage { account, .. }, _)] => {
+                assert_eq!(sender, *account)
+            }
+            events => panic!("expected a paused account, got {:?}", events),
+        }
+        apply(&mut store, "HostAccountResumedMessage", &paused, 17);
+        assert!(store.blocked_accounts().is_empty());
+
+        let started = [Token::FixedBytes(vec![9; 32])];
+        match &apply(&mut store, "BridgeStarted", &started, 18)[..] {
+            [Event::EthBridgeStartedMessage { sender: None, .. }] => (),
+            events => panic!("expected the bridge to start, got {:?}", events),
+        }
+        let stopped = [Token::FixedBytes(vec![10; 32])];
+        match &apply(&mut store, "BridgeStopped", &stopped, 19)[..] {
+            [Event::EthBridgeStoppedMessage { sender: None, .. }] => (),
+            events => panic!("expected the bridge to stop, got {:?}", events),
+        }
+    }
+
+    #[cfg(all(feature = "graph-node", feature = "sqlite"))]
+    #[test]
+    fn insert_subgraph_test() {
+        let message_id = H256::from_slice(&[1; 32]);
+        let sender = H160::from_slice(&[2; 20]);
+        let host = H160::from_slice(&[4; 20]);
+        let guest = H256::from_slice(&[5; 32]);
+        let page = |status: &str| -> migrate::ResponseData {
+            serde_json::from_value(serde_json::json!({
+                "messages": [{
+                    "id": format!("{:?}", message_id),
+                    "ethAddress": format!("{:?}", sender),
+                    "subAddress": format!("{:?}", H256::from_slice(&[3; 32])),
+                    "token": format!("{:?}", H160::zero()),
+                    "amount": "100",
+                    "status": status,
+                    "direction": "ETH2SUB",
+                    "ethBlockNumber": "10",
+                }],
+                "accounts": [{
+                    "id": format!("{:?}", sender),
+                    "messageID": format!("{:?}", H256::from_slice(&[8; 32])),
+                    "kind": "ETH",
+                    "status": "BLOCKED",
+                    "timestamp": "1600000000",
+                    "ethBlockNumber": "16",
+                }],
+                "limitMessages": [],
+                "candidateValidators": [{
+                    "id": format!("{:?}", host),
+                    "subAddress": format!("{:?}", guest),
+                    "active": true,
+                    "ethBlockNumber": "14",
+                }],
+            }))
+            .unwrap()
+        };
+        let mut store = Store::default();
+        store.insert_subgraph(page("APPROVED")).unwrap();
+        // replayed and re-driven as if the indexer had seen the logs
+        assert_eq!(1, store.unfinalized(&["APPROVED".to_string()], 0).len());
+        assert_eq!(1, store.orphaned(10).len());
+        match &store.blocked_accounts()[..] {
+            [(Event::EthHostAccountPausedMessage { account, .. }, _)] => {
+                assert_eq!(sender, *account)
+            }
+            events => panic!("expected a paused account, got {:?}", events),
+        }
+        let validators = [
+            Token::FixedBytes(vec![7; 32]),
+            Token::Array(vec![]),
+            Token::Uint(0.into()),
+            Token::Array(vec![Token::Address(host)]),
+            Token::Uint(1.into()),
+        ];
+        match &apply(&mut store, "ChangeValidatorsList", &validators, 15)[..] {
+            [Event::EthValidatorsListMessage { validators, .. }] => {
+                assert_eq!(&vec![guest], validators)
+            }
+            events => panic!("expected a validator list, got {:?}", events),
+        }
+        assert!(Store::default().insert_subgraph(page("LOST")).is_err());
+    }
+
+    #[test]
+    fn limit_message_id_test() {
+        // 0x10 is "010", made even as the subgraph does
+        assert_eq!(
+            H256::from_slice(&primitives::hashing::keccak_256(&[0x00, 0x10])),
+            limit_message_id(16)
+        );
+        assert_eq!(
+            H256::from_slice(&primitives::hashing::keccak_256(&[0x01, 0x00])),
+            limit_message_id(256)
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use log;
+use web3::{futures::Future, transports::WebSocket, types::SyncState};
+
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::metrics;
+
+/// How long a held call waits before it looks again whether a node caught up.
+pub const RECHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The current `Route` of token bridge calls.
+static ROUTE: AtomicU8 = AtomicU8::new(Route::Primary as u8);
+
+/// Where token bridge calls go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    /// `ETH_API_URL`, it is synced.
+    Primary,
+    /// `ETH_SECONDARY_API_URL`, the primary is behind and the secondary is
+    /// synced.
+    Secondary,
+    /// Both are behind, calls wait until one caught up.
+    Hold,
+}
+
+impl Route {
+    fn from_u8(route: u8) -> Self {
+        match route {
+            0 => Route::Primary,
+            1 => Route::Secondary,
+            _ => Route::Hold,
+        }
+    }
+}
+
+/// What the endpoints report and how far the head of the primary one trails
+/// the secondary one, if that is configured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Observation {
+    syncing: bool,
+    lag: Option<u64>,
+    secondary_syncing: bool,
+}
+
+impl Observation {
+    fn is_lagging(&self, max_lag_blocks: u64) -> bool {
+        self.syncing || self.lag.map_or(false, |lag| lag > max_lag_blocks)
+    }
+
+    fn route(&self, max_lag_blocks: u64) -> Route {
+        if !self.is_lagging(max_lag_blocks) {
+            Route::Primary
+        } else if self.lag.is_some() && !self.secondary_syncing {
+            Route::Secondary
+        } else {
+            Route::Hold
+        }
+    }
+}
+
+struct Endpoints {
+    _eloops: Vec<web3::transports::EventLoopHandle>,
+    primary: web3::Web3<WebSocket>,
+    secondary: Option<web3::Web3<WebSocket>>,
+}
+
+impl Endpoints {
+    fn connect(config: &Config) -> Result<Self, String> {
+        let connect = |url: &str| {
+            WebSocket::new(url)
+                .map(|(eloop, transport)| (eloop, web3::Web3::new(transport)))
+                .map_err(|e| format!("can not connect to {}: {:?}", url, e))
+        };
+        let (eloop, primary) = connect(&config.eth_api_url)?;
+        let mut eloops = vec![eloop];
+        let secondary = match &config.eth_secondary_api_url {
+            Some(url) => {
+                let (eloop, secondary) = connect(url)?;
+                eloops.push(eloop);
+                Some(secondary)
+            }
+            None => None,
+        };
+        Ok(Endpoints {
+            _eloops: eloops,
+            primary,
+            secondary,
+        })
+    }
+
+    fn observe(&self) -> Result<Observation, String> {
+        let syncing = |web3: &web3::Web3<WebSocket>| match web3.eth().syncing().wait() {
+            Ok(SyncState::Syncing(_)) => Ok(true),
+            Ok(SyncState::NotSyncing) => Ok(false),
+            Err(e) => Err(format!("can not get eth_syncing: {:?}", e)),
+        };
+        let head = |web3: &web3::Web3<WebSocket>| {
+            web3.eth()
+                .block_number()
+                .wait()
+                .map(|head| head.low_u64())
+                .map_err(|e| format!("can not get ethereum block number: {:?}", e))
+        };
+        let primary_syncing = syncing(&self.primary)?;
+        let (lag, secondary_syncing) = match &self.secondary {
+            Some(secondary) => (
+                Some(head(secondary)?.saturating_sub(head(&self.primary)?)),
+                syncing(secondary)?,
+            ),
+            None => (None, false),
+        };
+        Ok(Observation {
+            syncing: primary_syncing,
+            lag,
+            secondary_syncing,
+        })
+    }
+}
+
+/// Routes token bridge calls by whether the endpoints can be trusted with
+/// calls whose effect depends on the current head. When they can not be
+/// asked the last route stands. Returns whether the primary is synced.
+fn check(endpoints: &Endpoints, config: &Config) -> bool {
+    match endpoints.observe() {
+        Ok(observation) => record(&observation, config.eth_max_lag_blocks) == Route::Primary,
+        Err(e) => {
+            log::warn!("can not check the ethereum node: {}", e);
+            route() == Route::Primary
+        }
+    }
+}
+
+fn record(observation: &Observation, max_lag_blocks: u64) -> Route {
+    let route = observation.route(max_lag_blocks);
+    let previous = Route::from_u8(ROUTE.swap(route as u8, Ordering::Relaxed));
+    if previous != route {
+        match route {
+            Route::Primary => log::info!("ethereum node caught up: {:?}", observation),
+            Route::Secondary => log::warn!(
+                "ethereum node is behind, sending token bridge calls to the secondary one: {:?}",
+                observation
+            ),
+            Route::Hold => log::warn!(
+                "ethereum nodes are behind, holding token bridge calls: {:?}",
+                observation
+            ),
+        }
+    }
+    let lagging = route != Route::Primary;
+    metrics::set_gauge(
+        "validator_ethereum_lagging",
+        &[],
+        if lagging { 1.0 } else { 0.0 },
+    );
+    metrics::set_gauge(
+        "validator_ethereum_failover",
+        &[],
+        if route == Route::Secondary { 1.0 } else { 0.0 },
+    );
+    if let Some(lag) = observation.lag {
+        metrics::set_gauge("validator_ethereum_lag_blocks", &[], lag as f64);
+    }
+    route
+}
+
+/// Checks at startup and every `ETH_SYNC_CHECK_SECS`, and alerts once the
+/// node has been behind for `ETH_SYNC_ALERT_SECS`.
+pub fn spawn(config: Config) -> thread::JoinHandle<()> {
+    let endpoints = Endpoints::connect(&config).unwrap_or_else(|e| {
+        log::error!("{}", e);
+        std::process::exit(1)
+    });
+    if !check(&endpoints, &config) {
+        log::warn!("starting while the ethereum node is behind: {:?}", route());
+    }
+    thread::Builder::new()
+        .name("eth_sync".to_string())
+        .spawn(move || {
+            let mut lagging_since = None;
+            loop {
+                thread::sleep(Duration::from_secs(config.eth_sync_check_secs));
+                if check(&endpoints, &config) {
+                    lagging_since = None;
+                    continue;
+                }
+                let since = *lagging_since.get_or_insert_with(Instant::now);
+                let lagging = since.elapsed();
+                metrics::set_gauge(
+                    "validator_ethereum_lagging_seconds",
+                    &[],
+                    lagging.as_secs_f64(),
+                );
+                if lagging >= Duration::from_secs(config.eth_sync_alert_secs) {
+                    log::error!(
+                        "ethereum node {} has been behind for {}s, token bridge calls: {:?}",
+                        config.eth_api_url,
+                        lagging.as_secs(),
+                        route()
+                    );
+                }
+            }
+        })
+        .expect("can not started ethereum sync monitor")
+}
+
+/// Where token bridge calls go now. Held calls are handled again after
+/// `RECHECK_INTERVAL` instead of waiting on a worker.
+pub fn route() -> Route {
+    Route::from_u8(ROUTE.load(Ordering::Relaxed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observation(syncing: bool, lag: Option<u64>) -> Observation {
+        Observation {
+            syncing,
+            lag,
+            secondary_syncing: false,
+        }
+    }
+
+    #[test]
+    fn is_lagging_tests() {
+        assert!(!observation(false, None).is_lagging(5));
+        assert!(observation(true, None).is_lagging(5));
+        assert!(!observation(false, Some(5)).is_lagging(5));
+        assert!(observation(false, Some(6)).is_lagging(5));
+    }
+
+    #[test]
+    fn route_tests() {
+        assert_eq!(Route::Primary, observation(false, Some(5)).route(5));
+        // fails over to a synced secondary
+        assert_eq!(Route::Secondary, observation(false, Some(6)).route(5));
+        assert_eq!(Route::Secondary, observation(true, Some(0)).route(5));
+        // holds without one
+        assert_eq!(Route::Hold, observation(true, None).route(5));
+        let both_syncing = Observation {
+            secondary_syncing: true,
+            ..observation(true, Some(0))
+        };
+        assert_eq!(Route::Hold, both_syncing.route(5));
+
+        // calls follow the last observation
+        assert_eq!(Route::Hold, record(&both_syncing, 5));
+        assert_eq!(Route::Hold, route());
+        record(&observation(false, Some(6)), 5);
+        assert_eq!(Route::Secondary, route());
+        record(&observation(false, None), 5);
+        assert_eq!(Route::Primary, route());
+    }
+}
@@ -1,33 +1,149 @@
-use raw_transaction_builder::{Bip32ECKeyPair, RawTransaction};
+use raw_transaction_builder::{AccessListItem, Bip32ECKeyPair, RawTransaction};
 use rustc_hex::FromHex;
-use web3::{self, contract::tokens::Tokenize, types::U256};
+use web3::{
+    self,
+    contract::tokens::Tokenize,
+    futures::Future,
+    types::{BlockNumber, H160, U256},
+    Transport, Web3,
+};
 
-const CHAIN_ID: u8 = 4;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
 
-pub fn build_transaction_data<P>(abi: &ethabi::Contract, function_name: &str, params: P) -> Vec<u8>
+use crate::secret::Secret;
+
+/// Why calldata could not be built, usually because the ABI changed and
+/// no longer matches the arguments the handler passes.
+#[derive(Debug, PartialEq)]
+pub enum CalldataError {
+    UnknownFunction(String),
+    ArgumentCount {
+        function: String,
+        expected: Vec<String>,
+        provided: usize,
+    },
+    ArgumentType {
+        function: String,
+        index: usize,
+        expected: String,
+        provided: String,
+    },
+    Encoding(String),
+}
+
+impl fmt::Display for CalldataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalldataError::UnknownFunction(function) => {
+                write!(f, "ABI has no function {:?}", function)
+            }
+            CalldataError::ArgumentCount {
+                function,
+                expected,
+                provided,
+            } => write!(
+                f,
+                "{}({}) takes {} arguments, {} provided",
+                function,
+                expected.join(","),
+                expected.len(),
+                provided
+            ),
+            CalldataError::ArgumentType {
+                function,
+                index,
+                expected,
+                provided,
+            } => write!(
+                f,
+                "argument {} of {} must be {}, provided {}",
+                index, function, expected, provided
+            ),
+            CalldataError::Encoding(e) => write!(f, "can not encode calldata: {}", e),
+        }
+    }
+}
+
+pub fn build_transaction_data<P>(
+    abi: &ethabi::Contract,
+    function_name: &str,
+    params: P,
+) -> Result<Vec<u8>, CalldataError>
 where
     P: Tokenize,
 {
-    abi.function(function_name)
-        .and_then(|function| function.encode_input(&params.into_tokens()))
-        .unwrap_or_else(|error| {
-            log::warn!(
-                "can not build transaction data for {:?}: {:?}",
-                function_name,
-                error
-            );
-            vec![]
-        })
+    let function = abi
+        .function(function_name)
+        .map_err(|_| CalldataError::UnknownFunction(function_name.to_string()))?;
+    let tokens = params.into_tokens();
+    if tokens.len() != function.inputs.len() {
+        return Err(CalldataError::ArgumentCount {
+            function: function.name.clone(),
+            expected: function
+                .inputs
+                .iter()
+                .map(|param| param.kind.to_string())
+                .collect(),
+            provided: tokens.len(),
+        });
+    }
+    if let Some((index, (param, token))) = function
+        .inputs
+        .iter()
+        .zip(&tokens)
+        .enumerate()
+        .find(|(_, (param, token))| !token.type_check(&param.kind))
+    {
+        return Err(CalldataError::ArgumentType {
+            function: function.name.clone(),
+            index,
+            expected: param.kind.to_string(),
+            provided: format!("{:?}", token),
+        });
+    }
+    function
+        .encode_input(&tokens)
+        .map_err(|e| CalldataError::Encoding(format!("{:?}", e)))
 }
 
 pub fn build(
-    private_key: String,
+    private_key: Secret<String>,
     to: web3::types::H160,
     nonce: web3::types::U256,
     value: u64,
     gas_price: u64,
     gas: u64,
     data: Vec<u8>,
+    chain_id: u64,
+) -> Vec<u8> {
+    build_with_access_list(
+        private_key,
+        to,
+        nonce,
+        value,
+        gas_price,
+        gas,
+        data,
+        chain_id,
+        &[],
+    )
+}
+
+/// Builds an EIP-2930 transaction when `access_list` is not empty, a legacy
+/// one otherwise.
+pub fn build_with_access_list(
+    private_key: Secret<String>,
+    to: web3::types::H160,
+    nonce: web3::types::U256,
+    value: u64,
+    gas_price: u64,
+    gas: u64,
+    data: Vec<u8>,
+    chain_id: u64,
+    access_list: &[AccessListItem],
 ) -> Vec<u8> {
     let tx = RawTransaction {
         nonce,
@@ -40,9 +156,251 @@ pub fn build(
 
     let bip32ec_keypair = Bip32ECKeyPair::from_raw_secret(
         &private_key
+            .expose()
             .from_hex::<Vec<_>>()
             .expect("can not parse private key"),
     )
     .expect("invalid private key");
-    tx.sign(&bip32ec_keypair, CHAIN_ID)
+    if access_list.is_empty() {
+        tx.sign(&bip32ec_keypair, chain_id)
+    } else {
+        tx.sign_with_access_list(&bip32ec_keypair, chain_id, access_list)
+    }
+}
+
+/// What becomes of the nonce of a transaction the node refused. Later
+/// transactions already hold the nonces after it and wait on the gap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonceGap {
+    /// Read the nonce from the node again, the gap stays until then.
+    Reset,
+    /// Send the transaction again at the nonce, a few times, then fill it.
+    Reuse,
+    /// Send a zero value transfer to ourselves with the nonce right away.
+    Fill,
+}
+
+impl Default for NonceGap {
+    fn default() -> Self {
+        NonceGap::Reuse
+    }
+}
+
+impl FromStr for NonceGap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "reset" => Ok(NonceGap::Reset),
+            "reuse" => Ok(NonceGap::Reuse),
+            "fill" => Ok(NonceGap::Fill),
+            _ => Err(format!(
+                "unknown nonce gap handling {:?}, expected reset, reuse or fill",
+                s
+            )),
+        }
+    }
+}
+
+/// Whether the node refused a transaction because a pending one of the
+/// validator has its nonce, which is then no gap to close.
+pub fn is_nonce_taken(err: &web3::Error) -> bool {
+    format!("{:?}", err)
+        .to_lowercase()
+        .contains("replacement transaction underpriced")
+}
+
+#[derive(Debug, Default)]
+struct Sequence {
+    next: Option<U256>,
+    /// Nonces of refused transactions, handed out again lowest first.
+    released: BTreeSet<U256>,
+}
+
+/// Hands out nonces locally, so transactions sent concurrently don't all read
+/// the same transaction count from the node.
+#[derive(Debug)]
+pub struct Nonces {
+    address: H160,
+    sequence: Mutex<Sequence>,
+}
+
+impl Nonces {
+    pub fn new(address: H160) -> Self {
+        Nonces {
+            address,
+            sequence: Mutex::new(Sequence::default()),
+        }
+    }
+
+    pub fn next<T: Transport>(&self, web3: &Web3<T>) -> Result<U256, web3::Error> {
+        let mut sequence = self.sequence.lock().expect("nonce lock poisoned");
+        if let Some(nonce) = sequence.released.iter().next().copied() {
+            sequence.released.remove(&nonce);
+            return Ok(nonce);
+        }
+        let nonce = match sequence.next {
+            Some(nonce) => nonce,
+            None => web3
+                .eth()
+                .transaction_count(self.address, Some(BlockNumber::Pending))
+                .wait()?,
+        };
+        sequence.next = Some(nonce + 1);
+        Ok(nonce)
+    }
+
+    /// The nonce the next call returns, `None` until it is read from the node.
+    pub fn peek(&self) -> Option<U256> {
+        let sequence = self.sequence.lock().expect("nonce lock poisoned");
+        sequence.released.iter().next().copied().or(sequence.next)
+    }
+
+    /// Hands out the nonce of a refused transaction again before new ones.
+    pub fn release(&self, nonce: U256) {
+        let mut sequence = self.sequence.lock().expect("nonce lock poisoned");
+        if sequence.next.map_or(false, |next| nonce < next) {
+            sequence.released.insert(nonce);
+        }
+    }
+
+    /// Makes the next call read the nonce from the node again, used after a
+    /// send fails and the local sequence can no longer be trusted.
+    pub fn reset(&self) {
+        *self.sequence.lock().expect("nonce lock poisoned") = Sequence::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use primitives::hashing::keccak_256;
+    use rustc_hex::ToHex;
+    use web3::types::H256;
+
+    struct Vector {
+        private_key: &'static str,
+        nonce: u64,
+        gas_price: u64,
+        gas: u64,
+        to: &'static str,
+        value: u64,
+        data: &'static str,
+        chain_id: u64,
+        signed: &'static str,
+        tx_hash: &'static str,
+    }
+
+    /// The first vector is the EIP-155 example, the others were signed with an
+    /// independent implementation and cover calldata and other chain ids,
+    /// including Polygon's, whose v no longer fits in a byte.
+    const VECTORS: [Vector; 4] = [
+        Vector {
+            private_key: "4646464646464646464646464646464646464646464646464646464646464646",
+            nonce: 9,
+            gas_price: 20_000_000_000,
+            gas: 21_000,
+            to: "3535353535353535353535353535353535353535",
+            value: 1_000_000_000_000_000_000,
+            data: "",
+            chain_id: 1,
+            signed: "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83",
+            tx_hash: "33469b22e9f636356c4160a87eb19df52b7412e8eac32a4a55ffe88ea8350788",
+        },
+        Vector {
+            private_key: "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318",
+            nonce: 7,
+            gas_price: 24_000_000_000,
+            gas: 5_000_000,
+            to: "813a4658007ed3c7b31f02009e8699bef8539cd8",
+            value: 0,
+            data: "36a55b380101010101010101010101010101010101010101010101010101010101010101",
+            chain_id: 4,
+            signed: "f8890785059682f000834c4b4094813a4658007ed3c7b31f02009e8699bef8539cd880a436a55b3801010101010101010101010101010101010101010101010101010101010101012ba0fdd9324f70283549c06ce67ab5f652a8853b35f5a845803b6f37696c21ae9dd5a039eb1b35e2cacd1b4c31fff08b54b50d315e5caa3bd1096a72f3499f51f882ee",
+            tx_hash: "0101a28d78effae5920e5688c367380b66795ab310f907eba38ba3b6a0b60838",
+        },
+        Vector {
+            private_key: "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318",
+            nonce: 7,
+            gas_price: 24_000_000_000,
+            gas: 5_000_000,
+            to: "813a4658007ed3c7b31f02009e8699bef8539cd8",
+            value: 0,
+            data: "36a55b380101010101010101010101010101010101010101010101010101010101010101",
+            chain_id: 42,
+            signed: "f8890785059682f000834c4b4094813a4658007ed3c7b31f02009e8699bef8539cd880a436a55b38010101010101010101010101010101010101010101010101010101010101010178a0b021dc29bd77dfe37ea06802b6856e5d11356683edba5b90cd5052b8f0221ce5a03977abd1b35f200118f04b902a36b48aa30f602e0f8327080c1900634054d045",
+            tx_hash: "4bc6754b8ca04292ce8ba98c80bf997b5ece5e89bdbbd8c5a1fffab99b387cab",
+        },
+        Vector {
+            private_key: "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318",
+            nonce: 7,
+            gas_price: 30_000_000_000,
+            gas: 5_000_000,
+            to: "813a4658007ed3c7b31f02009e8699bef8539cd8",
+            value: 0,
+            data: "36a55b380101010101010101010101010101010101010101010101010101010101010101",
+            chain_id: 137,
+            signed: "f88b078506fc23ac00834c4b4094813a4658007ed3c7b31f02009e8699bef8539cd880a436a55b380101010101010101010101010101010101010101010101010101010101010101820136a08fd4f8a873db4faf1cc894227daf5e35b19453a2d019585c9e76081b76d15bd0a00bf43418194347d2d5b1980690c66b7eab820ded4a3313fad6d3b50a81c05411",
+            tx_hash: "f7a296019884d49137405c866a33a17cef116a7596bd0e2c3c08de0974becea1",
+        },
+    ];
+
+    #[test]
+    fn build_matches_golden_vectors() {
+        for vector in VECTORS.iter() {
+            let signed = build(
+                Secret::new(vector.private_key.to_string()),
+                vector.to.parse().unwrap(),
+                vector.nonce.into(),
+                vector.value,
+                vector.gas_price,
+                vector.gas,
+                vector.data.from_hex().unwrap(),
+                vector.chain_id,
+            );
+            assert_eq!(vector.signed, signed.to_hex::<String>());
+            assert_eq!(vector.tx_hash, keccak_256(&signed).to_hex::<String>());
+        }
+    }
+
+    #[test]
+    fn release_tests() {
+        let nonces = Nonces::new(H160::zero());
+        nonces.sequence.lock().unwrap().next = Some(5.into());
+        nonces.release(3.into());
+        nonces.release(2.into());
+        // not handed out yet
+        nonces.release(5.into());
+        assert_eq!(Some(2.into()), nonces.peek());
+        let web3 = Web3::new(web3::transports::Http::new("http://localhost:1").unwrap().1);
+        assert_eq!(U256::from(2), nonces.next(&web3).unwrap());
+        assert_eq!(U256::from(3), nonces.next(&web3).unwrap());
+        assert_eq!(U256::from(5), nonces.next(&web3).unwrap());
+        nonces.reset();
+        assert_eq!(None, nonces.peek());
+    }
+
+    #[test]
+    fn build_transaction_data_tests() {
+        let abi = ethabi::Contract::load(&include_bytes!("../res/Contract.json")[..]).unwrap();
+        let message_id = H256::from_slice(&[1; 32]);
+        assert_eq!(
+            4 + 32,
+            build_transaction_data(&abi, "confirmTransfer", (message_id,))
+                .unwrap()
+                .len()
+        );
+        assert_eq!(
+            Err(CalldataError::UnknownFunction("noSuchMethod".to_string())),
+            build_transaction_data(&abi, "noSuchMethod", (message_id,))
+        );
+        match build_transaction_data(&abi, "confirmTransfer", (message_id, message_id)) {
+            Err(CalldataError::ArgumentCount { provided: 2, .. }) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+        match build_transaction_data(&abi, "confirmTransfer", (U256::from(1),)) {
+            Err(CalldataError::ArgumentType { index: 0, .. }) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
 }
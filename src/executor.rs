@@ -1,808 +1,1212 @@
-use futures::future::{lazy, poll_fn};
 use log;
 use primitives::{self, crypto::Public};
-use tokio::runtime::{Runtime, TaskExecutor};
-use tokio_threadpool::blocking;
+use serde_json::json;
 use web3::{
+    contract::tokens::Tokenize,
     futures::Future,
-    types::{Bytes, H160, H256, U256},
+    transports::WebSocket,
+    types::{Bytes, CallRequest, H160, H256, U256},
 };
 
 use std::{
-    sync::{mpsc::Receiver, Arc},
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex, MutexGuard,
+    },
     thread,
+    time::{Duration, Instant},
 };
 
+#[cfg(feature = "admin-api")]
+use crate::admin_operations::{self, AdminOperation, Refused, Submitted};
+use crate::auth_guard;
+use crate::bridge_contract::BridgeContract;
+use crate::bus::{Bus, Topic};
 use crate::config::Config;
-use crate::controller::Event;
-use crate::ethereum_transactions;
-use crate::substrate_transactions;
+use crate::confirmed_transfers::ConfirmedTransfers;
+use crate::controller::{Envelope, Event, GuestLimits, Outcome, Source};
+use crate::debug_state;
+use crate::drain::Drain;
+use crate::eth_sync::{self, Route};
+use crate::ethereum_transactions::{self, NonceGap, Nonces};
+use crate::fatal;
+use crate::fees::FeeLog;
+use crate::metrics;
+use crate::rate_limit::RateLimiter;
+use crate::receipt_watcher::{self, SentTransaction};
+use crate::scheduler::{Job, Next, Scheduler};
+use crate::startup::Ready;
+use crate::submissions::Chain;
+use crate::substrate_sync;
+use crate::substrate_transactions::{self, Finalized};
+use crate::validator_set::ValidatorSetCache;
 
 const AMOUNT: u64 = 0;
+/// Gas of a plain transfer, what a nonce gap filler costs.
+const TRANSFER_GAS: u64 = 21_000;
+/// How often a refused call is sent again at its nonce with `NONCE_GAP=reuse`
+/// before the nonce is filled, and how long it waits in between.
+const RESEND_ATTEMPTS: u32 = 3;
+const RESEND_DELAY: Duration = Duration::from_secs(5);
+
+/// Whether a handler is done with its event or wants to handle it again.
+#[must_use]
+#[derive(Debug, PartialEq)]
+enum Handled {
+    Done,
+    RetryAt(Instant),
+}
+
+/// The guest limits last applied, read from the Bridge pallet until this
+/// validator applies some, and again after the pallet reports a change, e.g.
+/// by governance.
+#[derive(Debug, Default, Clone)]
+struct AppliedLimits(Arc<Mutex<Option<GuestLimits>>>);
+
+impl AppliedLimits {
+    /// Forgets the limits on every `SubSetNewLimits`, subscribed to before
+    /// the publishers start.
+    fn watch(bus: &Bus) -> Self {
+        let changes = bus.subscribe(&[Topic::Management]);
+        let applied = AppliedLimits::default();
+        let forget = applied.clone();
+        thread::Builder::new()
+            .name("executor_limits".to_string())
+            .spawn(move || {
+                changes.iter().for_each(|envelope| {
+                    if let Event::SubSetNewLimits { .. } = envelope.event {
+                        forget.forget();
+                    }
+                })
+            })
+            .expect("can not start executor_limits");
+        applied
+    }
+
+    /// The limits applied, or those `read` returns when they are not known.
+    /// The lock is not held while reading.
+    fn get(
+        &self,
+        read: impl FnOnce() -> Result<GuestLimits, String>,
+    ) -> Result<GuestLimits, String> {
+        if let Some(applied) = self.lock().clone() {
+            return Ok(applied);
+        }
+        let current = read()?;
+        Ok(self.lock().get_or_insert(current).clone())
+    }
+
+    /// Takes `limits` as applied while they are submitted, so a repeat in the
+    /// meantime is skipped. False if they were applied already.
+    fn claim(&self, limits: &GuestLimits) -> bool {
+        self.lock().replace(limits.clone()).as_ref() != Some(limits)
+    }
+
+    fn forget(&self) {
+        *self.lock() = None;
+    }
+
+    fn lock(&self) -> MutexGuard<Option<GuestLimits>> {
+        self.0.lock().expect("guest limits lock poisoned")
+    }
+}
 
 #[derive(Debug)]
 struct Executor {
     config: Config,
-    executor_rx: Receiver<Event>,
+    executor_rx: Receiver<Envelope>,
+    bus: Arc<Bus>,
+    validator_set: Arc<ValidatorSetCache>,
+    guest_limits: AppliedLimits,
+}
+
+/// Everything a handler needs, shared by the scheduler workers.
+struct Context {
+    config: Config,
+    web3: web3::Web3<WebSocket>,
+    /// `ETH_SECONDARY_API_URL`, token bridge calls go there while the
+    /// primary node is behind.
+    secondary: Option<web3::Web3<WebSocket>>,
+    bridge: BridgeContract<WebSocket>,
+    validator_set: Arc<ValidatorSetCache>,
+    nonces: Nonces,
+    confirmed_transfers: ConfirmedTransfers,
+    fees: Arc<FeeLog>,
+    rate_limiter: RateLimiter,
+    sent_tx: Mutex<Sender<SentTransaction>>,
+    bus: Arc<Bus>,
+    guest_limits: AppliedLimits,
+    /// Coordinated calls another validator claimed, checked on-chain once
+    /// the claims expire.
+    lost_claims: Mutex<HashSet<(H256, String)>>,
+    /// How often each call the node refused was sent again at its nonce.
+    resends: Mutex<HashMap<(H256, String), u32>>,
 }
 
-pub fn spawn(config: Config, executor_rx: Receiver<Event>) -> thread::JoinHandle<()> {
+impl Context {
+    /// The contract and ABI for calls on messages of `token_id`.
+    fn token_bridge(&self, token_id: Option<U256>) -> (H160, &ethabi::Contract) {
+        match token_id.and_then(|token_id| self.config.token_bridges.get(&token_id)) {
+            Some(token_bridge) => (token_bridge.address, &token_bridge.abi),
+            None => (
+                self.config.token_bridge.address,
+                &self.config.token_bridge.abi,
+            ),
+        }
+    }
+
+    /// The token id of the `TOKEN_BRIDGES_FILE` entry that holds the ERC20
+    /// `token`, none for the token of `TOKEN_BRIDGE_ADDRESS`.
+    fn token_id(&self, token: Option<H160>) -> Option<U256> {
+        let token = token?;
+        self.config
+            .token_bridges
+            .iter()
+            .find(|(_, token_bridge)| token_bridge.token == Some(token))
+            .map(|(token_id, _)| *token_id)
+    }
+
+    /// The node to send token bridge calls through, none while all are behind.
+    fn ethereum(&self) -> Option<&web3::Web3<WebSocket>> {
+        match eth_sync::route() {
+            Route::Primary => Some(&self.web3),
+            Route::Secondary => Some(self.secondary.as_ref().unwrap_or(&self.web3)),
+            Route::Hold => None,
+        }
+    }
+
+    fn report(&self, message_id: H256, outcome: Outcome) {
+        self.bus.publish(Envelope::outcome(message_id, outcome));
+    }
+
+    /// Defers the extrinsic while the Substrate node is syncing or its rate
+    /// limit is reached, returning when to handle it again.
+    fn acquire_extrinsic(&self, call: &str) -> Result<(), Instant> {
+        if !substrate_sync::accepts_extrinsics() {
+            log::info!(
+                "substrate node is syncing, deferring {} for {:?}",
+                call,
+                substrate_sync::RECHECK_INTERVAL
+            );
+            return Err(Instant::now() + substrate_sync::RECHECK_INTERVAL);
+        }
+        self.rate_limiter.acquire(call)
+    }
+
+    fn record_extrinsic(&self, message_id: H256, call: &str, finalized: Result<Finalized, String>) {
+        let finalized = match finalized {
+            Ok(finalized) => finalized,
+            Err(reason) => {
+                return self.report(
+                    message_id,
+                    Outcome::Failed(Chain::Substrate, call.to_string(), reason),
+                );
+            }
+        };
+        let tx_hash = H256::from(finalized.hash.to_fixed_bytes());
+        if let Some(fee) = finalized.fee {
+            self.fees.record(
+                message_id,
+                Chain::Substrate,
+                call,
+                tx_hash,
+                fee.into(),
+                None,
+            );
+        }
+        // the node only answers once the extrinsic is finalized
+        self.report(
+            message_id,
+            Outcome::Submitted(Chain::Substrate, call.to_string(), tx_hash),
+        );
+        self.report(
+            message_id,
+            Outcome::Confirmed(Chain::Substrate, call.to_string(), tx_hash),
+        );
+    }
+}
+
+/// Executes the events published on `Topic::Dispatched`, subscribed to before
+/// the thread starts. Outcomes of the calls are published back on the bus.
+/// Reports ready once connected to the ethereum node.
+pub fn spawn(
+    config: Config,
+    bus: Arc<Bus>,
+    validator_set: Arc<ValidatorSetCache>,
+    ready: Ready,
+) -> thread::JoinHandle<()> {
+    let executor_rx = bus.subscribe_essential(&[Topic::Dispatched]);
+    let guest_limits = AppliedLimits::watch(&bus);
     thread::Builder::new()
         .name("executor".to_string())
         .spawn(move || {
-            let executor = Executor::new(config, executor_rx);
-            executor.start()
+            let executor = Executor::new(config, executor_rx, bus, validator_set, guest_limits);
+            executor.start(ready)
         })
         .expect("can not started executor")
 }
 
 impl Executor {
-    fn new(config: Config, executor_rx: Receiver<Event>) -> Self {
+    fn new(
+        config: Config,
+        executor_rx: Receiver<Envelope>,
+        bus: Arc<Bus>,
+        validator_set: Arc<ValidatorSetCache>,
+        guest_limits: AppliedLimits,
+    ) -> Self {
         Executor {
             config,
             executor_rx,
+            bus,
+            validator_set,
+            guest_limits,
         }
     }
 
-    fn start(&self) {
-        let runtime = Runtime::new().expect("can not create tokio runtime");
-
-        let (_eloop, transport) =
-            web3::transports::WebSocket::new(&self.config.eth_api_url).unwrap();
+    fn start(&self, ready: Ready) {
+        let (_eloop, transport) = match WebSocket::new(&self.config.eth_api_url) {
+            Ok(connected) => connected,
+            Err(e) => {
+                return ready.fail(format!(
+                    "can not connect to {}: {:?}",
+                    self.config.eth_api_url, e
+                ))
+            }
+        };
+        let (_secondary_eloop, secondary) = match &self.config.eth_secondary_api_url {
+            Some(url) => match WebSocket::new(url) {
+                Ok((eloop, transport)) => (Some(eloop), Some(web3::Web3::new(transport))),
+                Err(e) => return ready.fail(format!("can not connect to {}: {:?}", url, e)),
+            },
+            None => (None, None),
+        };
+        let (sent_tx, sent_rx) = channel();
+        let fees = Arc::new(FeeLog::open(&self.config.data_dir));
+        let receipt_watcher =
+            receipt_watcher::spawn(self.config.clone(), sent_rx, self.bus.clone(), fees.clone());
         let web3 = web3::Web3::new(transport);
+        let context = Arc::new(Context {
+            config: self.config.clone(),
+            bridge: BridgeContract::new(&web3, &self.config.token_bridge),
+            web3,
+            secondary,
+            validator_set: self.validator_set.clone(),
+            nonces: Nonces::new(self.config.eth_validator_address),
+            confirmed_transfers: ConfirmedTransfers::open(&self.config.data_dir),
+            fees,
+            rate_limiter: RateLimiter::new(self.config.rate_limits.clone()),
+            sent_tx: Mutex::new(sent_tx),
+            bus: self.bus.clone(),
+            guest_limits: self.guest_limits.clone(),
+            lost_claims: Mutex::new(HashSet::new()),
+            resends: Mutex::new(HashMap::new()),
+        });
+        context
+            .validator_set
+            .refresh(&context.bridge, &self.config.sub_api_url);
+        ready.ok();
+        #[cfg(feature = "admin-api")]
+        let admin_operations = {
+            let context = context.clone();
+            admin_operations::serve(move |operation| execute_admin_operation(&context, operation))
+        };
 
-        let web3 = Arc::new(web3);
+        // stages of one message are serialized, different messages run in parallel
+        let scheduler = Scheduler::new("executor", self.config.executor_workers);
+        Drain::new(&self.config.data_dir).spawn_monitor(scheduler.activity());
 
-        self.executor_rx.iter().for_each(|event| {
-            log::info!("received event: {:?}", event);
-            match event {
-                Event::EthBridgePausedMessage(message_id, _block_number) => {
-                    handle_eth_bridge_paused_message(&self.config, runtime.executor(), message_id)
-                }
-                Event::EthBridgeResumedMessage(message_id, _block_number) => {
-                    handle_eth_bridge_resumed_message(&self.config, runtime.executor(), message_id)
-                }
-                Event::EthBridgeStartedMessage(message_id, _eth_address, _block_number) => {
-                    handle_eth_bridge_resumed_message(&self.config, runtime.executor(), message_id)
-                }
-                Event::EthBridgeStoppedMessage(message_id, _eth_address, _block_number) => {
-                    handle_eth_bridge_paused_message(&self.config, runtime.executor(), message_id)
-                }
-                Event::EthRelayMessage(
-                    message_id,
-                    eth_address,
-                    sub_address,
-                    amount,
-                    _block_number,
-                ) => {
-                    let abi = get_contract_abi();
-                    handle_eth_relay_message(
-                        &self.config,
-                        runtime.executor(),
-                        web3.clone(),
-                        abi,
-                        message_id,
-                        eth_address,
-                        sub_address,
-                        amount,
-                    )
-                }
-                Event::EthApprovedRelayMessage(
-                    message_id,
-                    eth_address,
-                    sub_address,
-                    amount,
-                    _block_number,
-                ) => handle_eth_approved_relay_message(
-                    &self.config,
-                    runtime.executor(),
-                    message_id,
-                    eth_address,
-                    sub_address,
-                    amount,
-                ),
-                Event::EthRevertMessage(message_id, _eth_address, _amount, _block_number) => {
-                    handle_eth_revert_message(&self.config, runtime.executor(), message_id)
-                }
-                Event::EthWithdrawMessage(message_id, _block_number) => {
-                    handle_eth_withdraw_message(&self.config, runtime.executor(), message_id)
-                }
-                Event::EthHostAccountPausedMessage(_, _, _, _) => (),
-                Event::EthHostAccountResumedMessage(_, _, _, _) => (),
-                Event::EthGuestAccountPausedMessage(_, _, _, _) => (),
-                Event::EthGuestAccountResumedMessage(_, _, _, _) => (),
-                Event::EthSetNewLimits(
-                    message_id,
-                    _min_host_transaction_value,
-                    _max_host_transaction_value,
-                    _day_host_max_limit,
-                    _day_host_max_limit_for_one_address,
-                    _max_host_pending_transaction_limit,
-                    min_guest_transaction_value,
-                    max_guest_transaction_value,
-                    day_guest_max_limit,
-                    day_guest_max_limit_for_one_address,
-                    max_guest_pending_transaction_limit,
-                    _block_number,
-                ) => handle_eth_set_new_limits(
-                    &self.config,
-                    runtime.executor(),
-                    message_id,
-                    min_guest_transaction_value,
-                    max_guest_transaction_value,
-                    day_guest_max_limit,
-                    day_guest_max_limit_for_one_address,
-                    max_guest_pending_transaction_limit,
-                ),
-                Event::EthValidatorsListMessage(
-                    message_id,
-                    new_validators,
-                    new_how_many_validators_decide,
-                    _block_number,
-                ) => handle_eth_validators_list_message(
-                    &self.config,
-                    runtime.executor(),
-                    message_id,
-                    new_validators,
-                    new_how_many_validators_decide,
-                ),
-                Event::SubRelayMessage(message_id, _block_number) => {
-                    handle_sub_relay_message(&self.config, runtime.executor(), message_id)
-                }
-                Event::SubApprovedRelayMessage(
-                    message_id,
-                    sub_address,
-                    eth_address,
-                    _token_id,
-                    amount,
-                    _block_number,
-                ) => handle_sub_approved_relay_message(
-                    &self.config,
-                    runtime.executor(),
-                    web3.clone(),
-                    get_contract_abi(),
-                    message_id,
-                    sub_address,
-                    eth_address,
-                    amount,
-                ),
-                Event::SubBurnedMessage(
-                    message_id,
-                    _sub_address,
-                    _eth_address,
-                    _amount,
-                    _token_id,
-                    _block_number,
-                ) => handle_sub_burned_message(
-                    &self.config,
-                    runtime.executor(),
-                    web3.clone(),
-                    get_contract_abi(),
-                    message_id,
-                ),
-                Event::SubMintedMessage(message_id, _token_id, _block_number) => {
-                    handle_sub_minted_message(
-                        &self.config,
-                        runtime.executor(),
-                        web3.clone(),
-                        get_contract_abi(),
-                        message_id,
-                    )
-                }
-                Event::SubCancellationConfirmedMessage(message_id, _token_id, _block_number) => {
-                    handle_sub_cancellation_confirmed_message(
-                        &self.config,
-                        runtime.executor(),
-                        web3.clone(),
-                        get_contract_abi(),
-                        message_id,
-                    )
-                }
-                Event::SubAccountPausedMessage(
-                    message_id,
-                    sub_address,
-                    _timestamp,
-                    _token_id,
-                    _block_number,
-                ) => handle_sub_account_paused_message(
-                    &self.config,
-                    runtime.executor(),
-                    web3.clone(),
-                    get_contract_abi(),
-                    message_id,
-                    sub_address,
-                ),
-                Event::SubAccountResumedMessage(
-                    message_id,
-                    sub_address,
-                    _timestamp,
-                    _token_id,
-                    _block_number,
-                ) => handle_sub_account_resumed_message(
-                    &self.config,
-                    runtime.executor(),
-                    web3.clone(),
-                    get_contract_abi(),
-                    message_id,
-                    sub_address,
-                ),
+        self.executor_rx.iter().for_each(|envelope| {
+            let span = envelope.span.clone();
+            let _enter = span.enter();
+            log::info!("received event: {:?}", envelope.event);
+            envelope.observe_wait("executor");
+            if self.config.observe_only {
+                log::info!("observe-only mode, not executing {:?}", envelope.event);
+                return;
             }
-        })
+            let message_id = *envelope.event.message_id();
+            scheduler.schedule(message_id, job(context.clone(), envelope));
+        });
+
+        scheduler.shutdown();
+        #[cfg(feature = "admin-api")]
+        {
+            admin_operations::stop();
+            let _ = admin_operations.join();
+        }
+        drop(context);
+        let _ = receipt_watcher.join();
     }
 }
 
-fn handle_eth_bridge_paused_message(
-    config: &Config,
-    task_executor: TaskExecutor,
-    message_id: H256,
-) {
-    let message_id = primitives::H256::from_slice(&message_id.to_fixed_bytes());
-    let sub_validator_mnemonic_phrase = config.sub_validator_mnemonic_phrase.clone();
-    let sub_api_url = config.sub_api_url.clone();
-
-    task_executor.spawn(lazy(move || {
-        poll_fn(move || {
-            blocking(|| {
-                substrate_transactions::pause_bridge(
-                    sub_api_url.clone(),
-                    sub_validator_mnemonic_phrase.clone(),
-                );
-                log::info!(
-                    "[substrate] called pause_bridge(), message_id: {:?}",
-                    message_id
-                );
-            })
-            .map_err(|_| panic!("the threadpool shut down"))
-        })
-    }));
+/// Handles the event on a scheduler worker, and again when the handler asks
+/// for it.
+fn job(context: Arc<Context>, envelope: Envelope) -> Job {
+    Box::new(move || {
+        let message_id = *envelope.event.message_id();
+        let handled = {
+            let _enter = envelope.span.enter();
+            let _context = fatal::message_context(message_id);
+            envelope.observe_wait("action");
+            handle_event(&context, envelope.event.clone(), envelope.source)
+        };
+        match handled {
+            Handled::Done => Next::Done,
+            Handled::RetryAt(at) => Next::RetryAt(at, job(context, envelope)),
+        }
+    })
 }
 
-fn handle_eth_bridge_resumed_message(
-    config: &Config,
-    task_executor: TaskExecutor,
+fn handle_event(context: &Context, event: Event, source: Source) -> Handled {
+    // the controller queues events once halted, this also stops those
+    // already scheduled or released past its policies
+    if auth_guard::is_halted() {
+        log::error!(
+            "validator is halted after repeated authorization failures, not executing {:?}",
+            event
+        );
+        return Handled::Done;
+    }
+    match event {
+        Event::EthBridgePausedMessage { message_id, .. } => {
+            handle_eth_bridge_paused_message(context, message_id)
+        }
+        Event::EthBridgeResumedMessage { message_id, .. } => {
+            handle_eth_bridge_resumed_message(context, message_id)
+        }
+        Event::EthBridgeStartedMessage { message_id, .. } => {
+            handle_eth_bridge_resumed_message(context, message_id)
+        }
+        Event::EthBridgeStoppedMessage { message_id, .. } => {
+            handle_eth_bridge_paused_message(context, message_id)
+        }
+        Event::EthRelayMessage {
+            message_id,
+            sender,
+            recipient,
+            amount,
+            token,
+            ..
+        } => call_token_bridge(
+            context,
+            message_id,
+            context.token_id(token),
+            "approveTransfer",
+            (message_id, sender, recipient, amount),
+        ),
+        Event::EthApprovedRelayMessage {
+            message_id,
+            sender,
+            recipient,
+            amount,
+            ..
+        } => handle_eth_approved_relay_message(context, message_id, sender, recipient, amount),
+        Event::EthRevertMessage { message_id, .. } => {
+            handle_eth_revert_message(context, message_id)
+        }
+        Event::EthWithdrawMessage { message_id, .. } => {
+            handle_eth_withdraw_message(context, message_id, source == Source::OrphanSweep)
+        }
+        Event::EthHostAccountPausedMessage { .. } => Handled::Done,
+        Event::EthHostAccountResumedMessage { .. } => Handled::Done,
+        Event::EthGuestAccountPausedMessage { .. } => Handled::Done,
+        Event::EthGuestAccountResumedMessage { .. } => Handled::Done,
+        Event::EthSetNewLimits {
+            message_id, limits, ..
+        } => handle_eth_set_new_limits(context, message_id, &limits.guest),
+        Event::EthValidatorsListMessage {
+            message_id,
+            validators,
+            quorum,
+            ..
+        } => handle_eth_validators_list_message(context, message_id, validators, quorum),
+        Event::SubRelayMessage { message_id, .. } => handle_sub_relay_message(context, message_id),
+        Event::SubApprovedRelayMessage {
+            message_id,
+            sender,
+            recipient,
+            amount,
+            token_id,
+            ..
+        } => call_token_bridge(
+            context,
+            message_id,
+            Some(token_id),
+            "withdrawTransfer",
+            (message_id, sender, recipient, amount),
+        ),
+        Event::SubBurnedMessage {
+            message_id,
+            token_id,
+            ..
+        } => call_token_bridge(
+            context,
+            message_id,
+            Some(token_id),
+            "confirmWithdrawTransfer",
+            (message_id,),
+        ),
+        Event::SubMintedMessage {
+            message_id,
+            token_id,
+            ..
+        } => call_token_bridge(
+            context,
+            message_id,
+            Some(token_id),
+            "confirmTransfer",
+            (message_id,),
+        ),
+        Event::SubCancellationConfirmedMessage {
+            message_id,
+            token_id,
+            ..
+        } => call_token_bridge(
+            context,
+            message_id,
+            Some(token_id),
+            "confirmCancelTransfer",
+            (message_id,),
+        ),
+        Event::SubAccountPausedMessage {
+            message_id,
+            account,
+            token_id,
+            ..
+        } => call_token_bridge(
+            context,
+            message_id,
+            Some(token_id),
+            "setPausedStatusForGuestAddress",
+            (account,),
+        ),
+        Event::SubAccountResumedMessage {
+            message_id,
+            account,
+            token_id,
+            ..
+        } => call_token_bridge(
+            context,
+            message_id,
+            Some(token_id),
+            "setResumedStatusForGuestAddress",
+            (account,),
+        ),
+        Event::Unknown {
+            message_id, origin, ..
+        } => {
+            log::error!(
+                "unknown message {:?} from {} reached the executor, not submitting it",
+                message_id,
+                origin
+            );
+            Handled::Done
+        }
+        Event::SubSetNewLimits { message_id, .. } => {
+            log::error!(
+                "substrate limits {:?} reached the executor, they belong to the controller",
+                message_id
+            );
+            Handled::Done
+        }
+        Event::Outcome {
+            message_id,
+            outcome,
+        } => {
+            log::error!(
+                "outcome {:?} of {:?} reached the executor, it belongs to the controller",
+                outcome,
+                message_id
+            );
+            Handled::Done
+        }
+    }
+}
+
+/// Signs and sends a token bridge call, waiting until the node accepts it so
+/// the next stage of the same message is not sent before this one. Calls on
+/// messages of a token with its own contract in `TOKEN_BRIDGES_FILE` go there.
+/// A coordinated call another validator claimed is handled again once the
+/// claims expire, and sent then if it would still go through.
+fn call_token_bridge<P>(
+    context: &Context,
     message_id: H256,
-) {
-    let message_id = primitives::H256::from_slice(&message_id.to_fixed_bytes());
-    let sub_validator_mnemonic_phrase = config.sub_validator_mnemonic_phrase.clone();
-    let sub_api_url = config.sub_api_url.clone();
-
-    task_executor.spawn(lazy(move || {
-        poll_fn(move || {
-            blocking(|| {
-                substrate_transactions::resume_bridge(
-                    sub_api_url.clone(),
-                    sub_validator_mnemonic_phrase.clone(),
-                );
+    token_id: Option<U256>,
+    function: &str,
+    args: P,
+) -> Handled
+where
+    P: Tokenize + Debug,
+{
+    let call = format!("{}{:?}", function, args);
+    let span = tracing::info_span!("ethereum_call", call = %call);
+    let _enter = span.enter();
+    let failed = |reason: String| {
+        context.report(
+            message_id,
+            Outcome::Failed(Chain::Ethereum, function.to_string(), reason),
+        );
+        Handled::Done
+    };
+    let (token_bridge, abi) = context.token_bridge(token_id);
+    let data = match ethereum_transactions::build_transaction_data(abi, function, args) {
+        Ok(data) => data,
+        Err(e) => return failed(format!("can not build {}: {}", call, e)),
+    };
+    let web3 = match context.ethereum() {
+        Some(web3) => web3,
+        None => {
+            log::info!(
+                "ethereum nodes are behind, holding {} for {:?}",
+                call,
+                eth_sync::RECHECK_INTERVAL
+            );
+            return Handled::RetryAt(Instant::now() + eth_sync::RECHECK_INTERVAL);
+        }
+    };
+    if let Err(at) = context.rate_limiter.acquire(function) {
+        return Handled::RetryAt(at);
+    }
+    if let Some(coordinator) = &context.config.coordinator {
+        let claim = (message_id, function.to_string());
+        let lost = context
+            .lost_claims
+            .lock()
+            .expect("lost claims lock poisoned")
+            .remove(&claim);
+        if lost {
+            if !is_still_needed(context, web3, token_bridge, &data) {
                 log::info!(
-                    "[substrate] called resume_bridge(), message_id: {:?}",
+                    "{} for {:?} went through without this validator",
+                    function,
                     message_id
                 );
-            })
-            .map_err(|_| panic!("the threadpool shut down"))
-        })
-    }));
-}
+                return Handled::Done;
+            }
+            log::warn!(
+                "{} for {:?} is still needed after the claims expired, sending it",
+                function,
+                message_id
+            );
+        } else {
+            let quorum = context.validator_set.current().ethereum_quorum.low_u64();
+            if !coordinator.claim(message_id, function, quorum) {
+                log::info!(
+                    "another validator claimed {} for {:?}, checking it again in {:?}",
+                    function,
+                    message_id,
+                    coordinator.grace
+                );
+                metrics::increment("validator_coordinated_skips_total", &[("call", function)]);
+                context
+                    .lost_claims
+                    .lock()
+                    .expect("lost claims lock poisoned")
+                    .insert(claim);
+                return Handled::RetryAt(Instant::now() + coordinator.grace);
+            }
+        }
+    }
+    let nonce = match context.nonces.next(web3) {
+        Ok(nonce) => nonce,
+        Err(e) => return failed(format!("can not get nonce for {}: {:?}", call, e)),
+    };
+    let config = &context.config;
+    let urgency = config
+        .call_urgency
+        .get(function)
+        .copied()
+        .unwrap_or_default();
+    let gas_price = urgency.gas_price(config.eth_gas_price);
+    let access_list = config
+        .access_lists
+        .get(function)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let tx = ethereum_transactions::build_with_access_list(
+        config.eth_validator_private_key.clone(),
+        token_bridge,
+        nonce,
+        AMOUNT,
+        gas_price,
+        config.eth_gas,
+        data.clone(),
+        config.eth_chain_id,
+        access_list,
+    );
+    log::debug!("raw {}: {:?}", function, tx);
 
-fn handle_eth_relay_message<T>(
-    config: &Config,
-    task_executor: TaskExecutor,
-    web3: Arc<web3::Web3<T>>,
-    abi: Arc<ethabi::Contract>,
-    message_id: H256,
-    eth_address: H160,
-    sub_address: H256,
-    amount: U256,
-) where
-    T: web3::Transport + Send + Sync + 'static,
-    T::Out: Send,
+    let handled = match web3
+        .eth()
+        .send_raw_transaction(Bytes::from(tx.clone()))
+        .wait()
     {
-    log::debug!("handle_eth_relay_message");
-    
-    let args = (message_id, eth_address, sub_address, amount);
-    let eth_validator_private_key = config.eth_validator_private_key.clone();
-    let bridge_address = config.token_bridge_address;
-    let eth_gas_price = config.eth_gas_price;
-    let eth_gas = config.eth_gas;
-
-    log::debug!("handle_eth_relay_message:args:message_id:{:?} eth_address:{:?}, sub_address:{:?}, amount:{:?}", message_id, eth_address, sub_address, amount);
-    
-    let data = ethereum_transactions::build_transaction_data(&abi, "approveTransfer", args);
-    let fut = web3.eth().transaction_count(config.eth_validator_address, None)
-        .and_then(move |nonce| {
-
-            log::debug!("approveTransfer input: bridge_address:{:?}, nonce:{:?}, AMOUNT:{:?}, eth gas price:{:?}, gas:{:?}, data:{:?}", bridge_address, nonce, AMOUNT, eth_gas_price, eth_gas, data);
-            let tx = ethereum_transactions::build(eth_validator_private_key, bridge_address, nonce, AMOUNT, eth_gas_price, eth_gas, data);
-            log::debug!("raw approveTransfer: {:?}", tx);
-            web3.eth().send_raw_transaction(Bytes::from(tx))
-                .then(move |res| {
-                    match res {
-                        Ok(tx_res) => {
-                            log::info!("[ethereum] called approveTransfer({:?}, {:?}, {:?}, {:?}), nonce: {:?}, result: {:?}",
-                                        message_id, eth_address, sub_address, amount, nonce, tx_res);
-                        },
-                        Err(err) => {
-                            log::warn!("[ethereum] can not send approveTransfer({:?}, {:?}, {:?}, {:?}), nonce: {:?}, reason: {:?}",
-                                        message_id, eth_address, sub_address, amount, nonce, err);
-                        }
-                    }
-                    Ok(())
-                })
+        Ok(tx_hash) => {
+            log::info!(
+                "[ethereum] called {}, nonce: {:?}, result: {:?}",
+                call,
+                nonce,
+                tx_hash
+            );
+            context
+                .resends
+                .lock()
+                .expect("resends lock poisoned")
+                .remove(&(message_id, function.to_string()));
+            context.report(
+                message_id,
+                Outcome::Submitted(Chain::Ethereum, function.to_string(), tx_hash),
+            );
+            let _ = context
+                .sent_tx
+                .lock()
+                .expect("sent transactions lock poisoned")
+                .send(SentTransaction {
+                    message_id,
+                    tx_hash,
+                    nonce,
+                    to: token_bridge,
+                    data,
+                    function: function.to_string(),
+                    call,
+                    gas_price: gas_price.into(),
+                    urgency,
+                    replaced: vec![],
+                });
+            Handled::Done
+        }
+        Err(err) => match recover_nonce(context, web3, (message_id, function), nonce, &err) {
+            Some(at) => {
+                log::warn!(
+                    "can not send {}, sending it again at nonce {:?}: {:?}",
+                    call,
+                    nonce,
+                    err
+                );
+                Handled::RetryAt(at)
+            }
+            None => failed(format!(
+                "can not send {}, nonce: {:?}, reason: {:?}",
+                call, nonce, err
+            )),
+        },
+    };
+    debug_state::publish(
+        "executor",
+        json!({ "address": config.eth_validator_address, "next_nonce": context.nonces.peek() }),
+    );
+    handled
+}
 
-        })
-        .map_err(|e| log::warn!("can not get nonce: {:?}", e));
-    task_executor.spawn(fut);
+/// Whether the call would still go through, simulated with `eth_call`. A
+/// call the quorum already made reverts. When the node can not tell, it is
+/// sent, as when coordination fails.
+fn is_still_needed(
+    context: &Context,
+    web3: &web3::Web3<WebSocket>,
+    token_bridge: H160,
+    data: &[u8],
+) -> bool {
+    let request = CallRequest {
+        from: Some(context.config.eth_validator_address),
+        to: token_bridge,
+        gas: None,
+        gas_price: None,
+        value: None,
+        data: Some(Bytes::from(data.to_vec())),
+    };
+    match web3.eth().call(request, None).wait() {
+        Ok(_) => true,
+        Err(e) => !format!("{:?}", e).to_lowercase().contains("revert"),
+    }
+}
+
+/// A nonce the node already has a transaction for is used up. The nonce of a
+/// refused transaction is dealt with as `NONCE_GAP` says, so the transactions
+/// after it do not wait for it forever. Returns when to send the call again.
+fn recover_nonce(
+    context: &Context,
+    web3: &web3::Web3<WebSocket>,
+    (message_id, function): (H256, &str),
+    nonce: U256,
+    err: &web3::Error,
+) -> Option<Instant> {
+    let config = &context.config;
+    let resends = {
+        let mut resends = context.resends.lock().expect("resends lock poisoned");
+        let resends = resends.remove(&(message_id, function.to_string()));
+        resends.unwrap_or(0)
+    };
+    if ethereum_transactions::is_nonce_taken(err) {
+        context.nonces.reset();
+        return None;
+    }
+    match config.nonce_gap {
+        NonceGap::Reset => context.nonces.reset(),
+        NonceGap::Reuse if resends < RESEND_ATTEMPTS => {
+            context.nonces.release(nonce);
+            context
+                .resends
+                .lock()
+                .expect("resends lock poisoned")
+                .insert((message_id, function.to_string()), resends + 1);
+            return Some(Instant::now() + RESEND_DELAY);
+        }
+        NonceGap::Reuse | NonceGap::Fill => {
+            let filler = ethereum_transactions::build(
+                config.eth_validator_private_key.clone(),
+                config.eth_validator_address,
+                nonce,
+                AMOUNT,
+                config.eth_gas_price,
+                TRANSFER_GAS,
+                vec![],
+                config.eth_chain_id,
+            );
+            match web3.eth().send_raw_transaction(Bytes::from(filler)).wait() {
+                Ok(tx_hash) => {
+                    log::warn!("filled nonce {} with a self-transfer {:?}", nonce, tx_hash);
+                    metrics::increment("validator_nonce_gaps_filled_total", &[]);
+                }
+                Err(e) => {
+                    log::error!("can not fill nonce {}: {:?}", nonce, e);
+                    context.nonces.reset();
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Sends an operator's admin operation at the next nonce, the receipt watcher
+/// confirms it and bumps its gas price like for the calls of event handlers.
+#[cfg(feature = "admin-api")]
+fn execute_admin_operation(
+    context: &Context,
+    operation: &AdminOperation,
+) -> Result<Submitted, Refused> {
+    let config = &context.config;
+    if config.observe_only {
+        return Err(Refused::Invalid(
+            "admin operations are disabled in observe-only mode".to_string(),
+        ));
+    }
+    if auth_guard::is_halted() {
+        return Err(Refused::Unavailable(
+            "validator is halted after repeated authorization failures".to_string(),
+        ));
+    }
+    let (to, function, data) = operation.call(config).map_err(Refused::Invalid)?;
+    let web3 = context
+        .ethereum()
+        .ok_or_else(|| Refused::Unavailable("ethereum nodes are behind".to_string()))?;
+    let nonce = context
+        .nonces
+        .next(web3)
+        .map_err(|e| Refused::Unavailable(format!("can not get nonce: {:?}", e)))?;
+    let urgency = config
+        .call_urgency
+        .get(&function)
+        .copied()
+        .unwrap_or_default();
+    let gas_price = urgency.gas_price(config.eth_gas_price);
+    let tx = ethereum_transactions::build(
+        config.eth_validator_private_key.clone(),
+        to,
+        nonce,
+        AMOUNT,
+        gas_price,
+        config.eth_gas,
+        data.clone(),
+        config.eth_chain_id,
+    );
+    match web3.eth().send_raw_transaction(Bytes::from(tx)).wait() {
+        Ok(tx_hash) => {
+            log::info!(
+                "[ethereum] sent {:?}, nonce: {:?}, tx hash: {:?}",
+                operation,
+                nonce,
+                tx_hash
+            );
+            context.report(
+                tx_hash,
+                Outcome::Submitted(Chain::Ethereum, function.clone(), tx_hash),
+            );
+            let _ = context
+                .sent_tx
+                .lock()
+                .expect("sent transactions lock poisoned")
+                .send(SentTransaction {
+                    message_id: tx_hash,
+                    tx_hash,
+                    nonce,
+                    to,
+                    data,
+                    call: format!("{:?}", operation),
+                    function,
+                    gas_price: gas_price.into(),
+                    urgency,
+                    replaced: vec![],
+                });
+            Ok(Submitted { tx_hash, nonce })
+        }
+        Err(err) => {
+            // the operator sends it again if need be, the nonce is dealt
+            // with as for any refused call
+            let _ = recover_nonce(context, web3, (H256::zero(), &function), nonce, &err);
+            Err(Refused::Unavailable(format!(
+                "can not send {:?}, nonce: {:?}, reason: {:?}",
+                operation, nonce, err
+            )))
+        }
+    }
+}
+
+fn handle_eth_bridge_paused_message(context: &Context, message_id: H256) -> Handled {
+    let config = &context.config;
+    if let Err(at) = context.acquire_extrinsic("pause_bridge") {
+        return Handled::RetryAt(at);
+    }
+    let tx_hash = substrate_transactions::pause_bridge(
+        config.sub_api_url.clone(),
+        config.sub_validator_mnemonic_phrase.clone(),
+    );
+    log::info!(
+        "[substrate] called pause_bridge(), message_id: {:?}",
+        message_id
+    );
+    context.record_extrinsic(message_id, "pause_bridge", tx_hash);
+    Handled::Done
+}
+
+fn handle_eth_bridge_resumed_message(context: &Context, message_id: H256) -> Handled {
+    let config = &context.config;
+    if let Err(at) = context.acquire_extrinsic("resume_bridge") {
+        return Handled::RetryAt(at);
+    }
+    let tx_hash = substrate_transactions::resume_bridge(
+        config.sub_api_url.clone(),
+        config.sub_validator_mnemonic_phrase.clone(),
+    );
+    log::info!(
+        "[substrate] called resume_bridge(), message_id: {:?}",
+        message_id
+    );
+    context.record_extrinsic(message_id, "resume_bridge", tx_hash);
+    Handled::Done
 }
 
 fn handle_eth_approved_relay_message(
-    config: &Config,
-    task_executor: TaskExecutor,
+    context: &Context,
     message_id: H256,
     eth_address: H160,
     sub_address: H256,
     amount: U256,
-) {
-    
+) -> Handled {
+    let config = &context.config;
     let message_id = primitives::H256::from_slice(&message_id.to_fixed_bytes());
     let eth_address = primitives::H160::from_slice(&eth_address.to_fixed_bytes());
     let sub_address = primitives::crypto::AccountId32::from(sub_address.to_fixed_bytes());
-    let token_id = config.sub_token_index;
     let amount = amount.low_u128();
-    let sub_validator_mnemonic_phrase = config.sub_validator_mnemonic_phrase.clone();
-    let sub_api_url = config.sub_api_url.clone();
-    log::debug!("handle_EthRelayMessage");
-
-    task_executor.spawn(lazy(move || {
-        poll_fn(move || {
-            blocking(|| {
-                substrate_transactions::mint(
-                    sub_api_url.clone(),
-                    sub_validator_mnemonic_phrase.clone(),
-                    message_id,
-                    eth_address,
-                    sub_address.clone(),
-                    token_id,
-                    amount,
-                );
-                log::info!(
-                    "[substrate] called multi_signed_mint({:?}, {:?}, {:?}, {:?})",
-                    message_id,
-                    eth_address,
-                    sub_address,
-                    amount
-                );
-            })
-            .map_err(|_| panic!("the threadpool shut down"))
-        })
-    }));
+
+    if let Err(at) = context.acquire_extrinsic("multi_signed_mint") {
+        return Handled::RetryAt(at);
+    }
+    let tx_hash = substrate_transactions::mint(
+        config.sub_api_url.clone(),
+        config.sub_validator_mnemonic_phrase.clone(),
+        message_id,
+        eth_address,
+        sub_address.clone(),
+        config.sub_token_index,
+        amount,
+    );
+    log::info!(
+        "[substrate] called multi_signed_mint({:?}, {:?}, {:?}, {:?})",
+        message_id,
+        eth_address,
+        sub_address,
+        amount
+    );
+    context.record_extrinsic(
+        H256::from(message_id.to_fixed_bytes()),
+        "multi_signed_mint",
+        tx_hash,
+    );
+    Handled::Done
 }
 
-fn handle_eth_revert_message(config: &Config, task_executor: TaskExecutor, message_id: H256) {
+fn handle_eth_revert_message(context: &Context, message_id: H256) -> Handled {
+    let config = &context.config;
     let message_id = primitives::H256::from_slice(&message_id.to_fixed_bytes());
-    let sub_validator_mnemonic_phrase = config.sub_validator_mnemonic_phrase.clone();
-    let sub_api_url = config.sub_api_url.clone();
-
-    task_executor.spawn(lazy(move || {
-        poll_fn(move || {
-            blocking(|| {
-                substrate_transactions::cancel_transfer(
-                    sub_api_url.clone(),
-                    sub_validator_mnemonic_phrase.clone(),
-                    message_id,
-                );
-                log::info!("[substrate] called cancel_transfer({:?})", message_id);
-            })
-            .map_err(|_| panic!("the threadpool shut down"))
-        })
-    }));
+    if let Err(at) = context.acquire_extrinsic("cancel_transfer") {
+        return Handled::RetryAt(at);
+    }
+    let tx_hash = substrate_transactions::cancel_transfer(
+        config.sub_api_url.clone(),
+        config.sub_validator_mnemonic_phrase.clone(),
+        message_id,
+    );
+    log::info!("[substrate] called cancel_transfer({:?})", message_id);
+    context.record_extrinsic(
+        H256::from(message_id.to_fixed_bytes()),
+        "cancel_transfer",
+        tx_hash,
+    );
+    Handled::Done
 }
 
-fn handle_eth_withdraw_message(config: &Config, task_executor: TaskExecutor, message_id: H256) {
-    let message_id = primitives::H256::from_slice(&message_id.to_fixed_bytes());
-    let sub_validator_mnemonic_phrase = config.sub_validator_mnemonic_phrase.clone();
-    let sub_api_url = config.sub_api_url.clone();
-
-    task_executor.spawn(lazy(move || {
-        poll_fn(move || {
-            blocking(|| {
-                substrate_transactions::confirm_transfer(
-                    sub_api_url.clone(),
-                    sub_validator_mnemonic_phrase.clone(),
-                    message_id,
-                );
-                log::info!("[substrate] called confirm_transfer({:?})", message_id);
-            })
-            .map_err(|_| panic!("the threadpool shut down"))
-        })
-    }));
+/// A transfer re-driven by the orphan sweep is confirmed again, it is still
+/// stuck although this validator confirmed it before.
+fn handle_eth_withdraw_message(context: &Context, message_id: H256, redriven: bool) -> Handled {
+    if !redriven && context.confirmed_transfers.contains(&message_id) {
+        log::debug!("already confirmed transfer {:?}, skipping", message_id);
+        metrics::increment(
+            "validator_redundant_calls_skipped_total",
+            &[("call", "confirm_transfer")],
+        );
+        return Handled::Done;
+    }
+    let config = &context.config;
+    let sub_message_id = primitives::H256::from_slice(&message_id.to_fixed_bytes());
+    if let Err(at) = context.acquire_extrinsic("confirm_transfer") {
+        return Handled::RetryAt(at);
+    }
+    let tx_hash = substrate_transactions::confirm_transfer(
+        config.sub_api_url.clone(),
+        config.sub_validator_mnemonic_phrase.clone(),
+        sub_message_id,
+    );
+    log::info!("[substrate] called confirm_transfer({:?})", sub_message_id);
+    // only once it dispatched, a failed confirmation is tried again on replay
+    if tx_hash.is_ok() {
+        context.confirmed_transfers.record(message_id);
+    }
+    context.record_extrinsic(message_id, "confirm_transfer", tx_hash);
+    Handled::Done
 }
 
-fn handle_eth_set_new_limits(
-    config: &Config,
-    task_executor: TaskExecutor,
-    message_id: H256,
-    min_guest_transaction_value: U256,
-    max_guest_transaction_value: U256,
-    day_guest_max_limit: U256,
-    day_guest_max_limit_for_one_address: U256,
-    max_guest_pending_transaction_limit: U256,
-) {
-    let sub_validator_mnemonic_phrase = config.sub_validator_mnemonic_phrase.clone();
-    let sub_api_url = config.sub_api_url.clone();
-
-    task_executor.spawn(lazy(move || {
-        poll_fn(move || {
-            blocking(|| {
-                substrate_transactions::update_limits(
-                    sub_api_url.clone(),
-                    sub_validator_mnemonic_phrase.clone(),
-                    min_guest_transaction_value.as_u128(),
-                    max_guest_transaction_value.as_u128(),
-                    day_guest_max_limit.as_u128(),
-                    day_guest_max_limit_for_one_address.as_u128(),
-                    max_guest_pending_transaction_limit.as_u128(),
-                );
-                log::info!(
-                    "[substrate] called update_limits({:?}, {:?}, {:?}, {:?}, {:?}), message_id: {:?}",
-                    min_guest_transaction_value,
-                    max_guest_transaction_value,
-                    day_guest_max_limit,
-                    day_guest_max_limit_for_one_address,
-                    max_guest_pending_transaction_limit,
-                    message_id
-                );
-            })
-            .map_err(|_| panic!("the threadpool shut down"))
-        })
-    }));
+/// Replayed limit messages repeat limits that are applied already, only a
+/// change is submitted. The limits count as applied while the update is
+/// submitted, a repeat meanwhile is skipped; they are read again from the
+/// pallet if it fails.
+fn handle_eth_set_new_limits(context: &Context, message_id: H256, limits: &GuestLimits) -> Handled {
+    let config = &context.config;
+    let applied = context
+        .guest_limits
+        .get(|| substrate_transactions::get_limits(config.sub_api_url.clone()).map(Into::into));
+    match &applied {
+        Ok(applied) if applied == limits => return limits_applied(message_id),
+        Ok(_) => (),
+        Err(e) => log::warn!("[substrate] {}, submitting the limits anyway", e),
+    }
+    if let Err(at) = context.acquire_extrinsic("update_limits") {
+        return Handled::RetryAt(at);
+    }
+    if !context.guest_limits.claim(limits) {
+        return limits_applied(message_id);
+    }
+    let tx_hash = substrate_transactions::update_limits(
+        config.sub_api_url.clone(),
+        config.sub_validator_mnemonic_phrase.clone(),
+        limits.min_transaction_value.as_u128(),
+        limits.max_transaction_value.as_u128(),
+        limits.day_max_limit.as_u128(),
+        limits.day_max_limit_for_one_address.as_u128(),
+        limits.max_pending_transaction_limit.as_u128(),
+    );
+    log::info!(
+        "[substrate] called update_limits({:?}), message_id: {:?}",
+        limits,
+        message_id
+    );
+    if tx_hash.is_err() {
+        context.guest_limits.forget();
+    }
+    context.record_extrinsic(message_id, "update_limits", tx_hash);
+    Handled::Done
+}
+
+fn limits_applied(message_id: H256) -> Handled {
+    log::info!(
+        "[substrate] limits of message {:?} are applied already, not calling update_limits",
+        message_id
+    );
+    metrics::increment("validator_limit_updates_coalesced_total", &[]);
+    Handled::Done
+}
+
+/// Checks a proposed validator set before it goes to the Bridge pallet and
+/// returns its quorum. With no quorum or one larger than the set no message
+/// could ever be decided.
+fn check_validator_list(validators: &[H256], quorum: U256) -> Result<u64, String> {
+    let distinct = validators.iter().collect::<HashSet<_>>().len();
+    if distinct < validators.len() {
+        return Err(format!(
+            "{} validators are listed twice",
+            validators.len() - distinct
+        ));
+    }
+    if quorum.is_zero() {
+        return Err("quorum is zero".to_string());
+    }
+    if quorum > U256::from(validators.len()) {
+        return Err(format!(
+            "quorum {} is larger than the {} validators",
+            quorum,
+            validators.len()
+        ));
+    }
+    Ok(quorum.as_u64())
 }
 
+/// An invalid validator set is refused loudly instead of relayed, it needs
+/// an operator to look at what the token bridge was told.
 fn handle_eth_validators_list_message(
-    config: &Config,
-    task_executor: TaskExecutor,
+    context: &Context,
     message_id: H256,
     new_validators: Vec<H256>,
     new_how_many_validators_decide: U256,
-) {
+) -> Handled {
+    let config = &context.config;
+    let quorum = match check_validator_list(&new_validators, new_how_many_validators_decide) {
+        Ok(quorum) => quorum,
+        Err(reason) => {
+            log::error!(
+                "refusing validator list of message {:?}, {}: {:?}",
+                message_id,
+                reason,
+                new_validators
+            );
+            metrics::increment("validator_invalid_validator_lists_total", &[]);
+            context.report(
+                message_id,
+                Outcome::Failed(
+                    Chain::Substrate,
+                    "update_validator_list".to_string(),
+                    reason,
+                ),
+            );
+            return Handled::Done;
+        }
+    };
     let message_id = primitives::H256::from_slice(&message_id.to_fixed_bytes());
     let new_validators = new_validators
         .iter()
         .map(|a| primitives::sr25519::Public::from_slice(&a.to_fixed_bytes()))
         .collect::<Vec<_>>();
-    let sub_validator_mnemonic_phrase = config.sub_validator_mnemonic_phrase.clone();
-    let sub_api_url = config.sub_api_url.clone();
-
-    task_executor.spawn(lazy(move || {
-        poll_fn(move || {
-            blocking(|| {
-                substrate_transactions::update_validator_list(
-                    sub_api_url.clone(),
-                    sub_validator_mnemonic_phrase.clone(),
-                    message_id,
-                    new_how_many_validators_decide.as_u64(),
-                    new_validators.clone(),
-                );
-                log::info!(
-                    "[substrate] called update_validator_list({:?}, {:?}, {:?})",
-                    message_id,
-                    new_how_many_validators_decide,
-                    new_validators,
-                );
-            })
-            .map_err(|_| panic!("the threadpool shut down"))
-        })
-    }));
-}
-
-fn handle_sub_relay_message(config: &Config, task_executor: TaskExecutor, message_id: H256) {
-    let message_id = primitives::H256::from_slice(&message_id.to_fixed_bytes());
-    let sub_validator_mnemonic_phrase = config.sub_validator_mnemonic_phrase.clone();
-    let sub_api_url = config.sub_api_url.clone();
-
-    task_executor.spawn(lazy(move || {
-        poll_fn(move || {
-            blocking(|| {
-                substrate_transactions::approve_transfer(
-                    sub_api_url.clone(),
-                    sub_validator_mnemonic_phrase.clone(),
-                    message_id,
-                );
-                log::info!("[substrate] called approve_transfer({:?})", message_id);
-            })
-            .map_err(|_| panic!("the threadpool shut down"))
-        })
-    }));
-}
-
-fn handle_sub_approved_relay_message<T>(
-    config: &Config,
-    task_executor: TaskExecutor,
-    web3: Arc<web3::Web3<T>>,
-    abi: Arc<ethabi::Contract>,
-    message_id: H256,
-    sub_address: H256,
-    eth_address: H160,
-    amount: U256,
-) where
-    T: web3::Transport + Send + Sync + 'static,
-    T::Out: Send,
-{
-    let args = (message_id, sub_address, eth_address, amount);
-    let eth_validator_private_key = config.eth_validator_private_key.clone();
-    let contract_address = config.token_bridge_address;
-    let eth_gas_price = config.eth_gas_price;
-    let eth_gas = config.eth_gas;
-    let data = ethereum_transactions::build_transaction_data(&abi, "withdrawTransfer", args);
-    let fut = web3.eth().transaction_count(config.eth_validator_address, None)
-        .and_then(move |nonce| {
-            let tx = ethereum_transactions::build(eth_validator_private_key, contract_address, nonce, AMOUNT, eth_gas_price, eth_gas, data);
-            log::debug!("raw withdrawTransfer: {:?}", tx);
-            web3.eth().send_raw_transaction(Bytes::from(tx))
-                .then(move |res| {
-                    match res {
-                        Ok(tx_res) => {
-                            log::info!("[ethereum] called withdrawTransfer({:?}, {:?}, {:?}, {:?}), nonce: {:?}, result: {:?}",
-                                       args.0, args.1, args.2, args.3, nonce, tx_res)
-                        },
-                        Err(err) => {
-                            log::warn!("can not send withdrawTransfer({:?}, {:?}, {:?}, {:?}), nonce: {:?}, reason: {:?}",
-                                       args.0, args.1, args.2, args.3, nonce, err);
-
-                        }
-                    }
 
-                    Ok(())
-                })
-        })
-        .or_else(|e| {
-            log::warn!("can not get nonce: {:?}", e);
-            Ok(())
-        });
-    task_executor.spawn(fut);
+    if let Err(at) = context.acquire_extrinsic("update_validator_list") {
+        return Handled::RetryAt(at);
+    }
+    let tx_hash = substrate_transactions::update_validator_list(
+        config.sub_api_url.clone(),
+        config.sub_validator_mnemonic_phrase.clone(),
+        message_id,
+        quorum,
+        new_validators.clone(),
+    );
+    log::info!(
+        "[substrate] called update_validator_list({:?}, {:?}, {:?})",
+        message_id,
+        quorum,
+        new_validators,
+    );
+    context.record_extrinsic(
+        H256::from(message_id.to_fixed_bytes()),
+        "update_validator_list",
+        tx_hash,
+    );
+    context
+        .validator_set
+        .refresh(&context.bridge, &config.sub_api_url);
+    Handled::Done
 }
 
-fn handle_sub_minted_message<T>(
-    config: &Config,
-    task_executor: TaskExecutor,
-    web3: Arc<web3::Web3<T>>,
-    abi: Arc<ethabi::Contract>,
-    message_id: H256,
-) where
-    T: web3::Transport + Send + Sync + 'static,
-    T::Out: Send,
-{
-    let args = (message_id,);
-    let eth_validator_private_key = config.eth_validator_private_key.clone();
-    let contract_address = config.token_bridge_address;
-    let eth_gas_price = config.eth_gas_price;
-    let eth_gas = config.eth_gas;
-    let data = ethereum_transactions::build_transaction_data(&abi, "confirmTransfer", args);
-    let fut = web3.eth().transaction_count(config.eth_validator_address, None)
-        .and_then(move |nonce| {
-            let tx = ethereum_transactions::build(eth_validator_private_key, contract_address, nonce, AMOUNT, eth_gas_price, eth_gas, data);
-            log::debug!("raw confirmTransfer: {:?}", tx);
-            web3.eth().send_raw_transaction(Bytes::from(tx))
-                .then(move |res| {
-                    match res {
-                        Ok(tx_res) => {
-                            log::info!("[ethereum] called confirmTransfer({:?}), nonce: {:?}, result: {:?}",
-                                       args.0, nonce, tx_res)
-                        },
-                        Err(err) => {
-                            log::info!("[ethereum] can not send confirmTransfer({:?}), nonce: {:?}, reason: {:?}",
-                                       args.0, nonce, err)
-                        }
-                    }
-
-                    Ok(())
-                })
-        })
-        .or_else(|e| {
-            log::warn!("can not get nonce: {:?}", e);
-            Ok(())
-        });
-    task_executor.spawn(fut);
+fn handle_sub_relay_message(context: &Context, message_id: H256) -> Handled {
+    let config = &context.config;
+    let message_id = primitives::H256::from_slice(&message_id.to_fixed_bytes());
+    if let Err(at) = context.acquire_extrinsic("approve_transfer") {
+        return Handled::RetryAt(at);
+    }
+    let tx_hash = substrate_transactions::approve_transfer(
+        config.sub_api_url.clone(),
+        config.sub_validator_mnemonic_phrase.clone(),
+        message_id,
+    );
+    log::info!("[substrate] called approve_transfer({:?})", message_id);
+    context.record_extrinsic(
+        H256::from(message_id.to_fixed_bytes()),
+        "approve_transfer",
+        tx_hash,
+    );
+    Handled::Done
 }
 
-fn handle_sub_burned_message<T>(
-    config: &Config,
-    task_executor: TaskExecutor,
-    web3: Arc<web3::Web3<T>>,
-    abi: Arc<ethabi::Contract>,
-    message_id: H256,
-) where
-    T: web3::Transport + Send + Sync + 'static,
-    T::Out: Send,
-{
-    let args = (message_id,);
-    let eth_validator_private_key = config.eth_validator_private_key.clone();
-    let contract_address = config.token_bridge_address;
-    let eth_gas_price = config.eth_gas_price;
-    let eth_gas = config.eth_gas;
-    let data = ethereum_transactions::build_transaction_data(&abi, "confirmWithdrawTransfer", args);
-    let fut = web3
-        .eth()
-        .transaction_count(config.eth_validator_address, None)
-        .and_then(move |nonce| {
-            let tx = ethereum_transactions::build(
-                eth_validator_private_key,
-                contract_address,
-                nonce,
-                AMOUNT,
-                eth_gas_price,
-                eth_gas,
-                data,
-            );
-            log::debug!("raw confirmTransfer: {:?}", tx);
-            web3.eth()
-                .send_raw_transaction(Bytes::from(tx))
-                .then(move |res| {
-                    match res {
-                        Ok(tx_res) => log::info!(
-                            "[ethereum] called confirmBurn({:?}), nonce: {:?}, result: {:?}",
-                            args.0,
-                            nonce,
-                            tx_res
-                        ),
-                        Err(err) => log::info!(
-                            "[ethereum] can not send confirmBurn({:?}), nonce: {:?}, reason: {:?}",
-                            args.0,
-                            nonce,
-                            err
-                        ),
-                    }
-
-                    Ok(())
-                })
-        })
-        .or_else(|e| {
-            log::warn!("can not get nonce: {:?}", e);
-            Ok(())
-        });
-    task_executor.spawn(fut);
+#[cfg(any(feature = "admin-api", feature = "graph-node"))]
+pub fn get_erc20_abi() -> ethabi::Contract {
+    ethabi::Contract::load(&include_bytes!("../res/ERC20.json")[..])
+        .expect("can not read ERC20 ABI")
 }
 
-fn handle_sub_cancellation_confirmed_message<T>(
-    config: &Config,
-    task_executor: TaskExecutor,
-    web3: Arc<web3::Web3<T>>,
-    abi: Arc<ethabi::Contract>,
-    message_id: H256,
-) where
-    T: web3::Transport + Send + Sync + 'static,
-    T::Out: Send,
-{
-    let args = (message_id,);
-    let eth_validator_private_key = config.eth_validator_private_key.clone();
-    let contract_address = config.token_bridge_address;
-    let eth_gas_price = config.eth_gas_price;
-    let eth_gas = config.eth_gas;
-    let data = ethereum_transactions::build_transaction_data(&abi, "confirmCancelTransfer", args);
-    let fut = web3.eth().transaction_count(config.eth_validator_address, None)
-        .and_then(move |nonce| {
-            let tx = ethereum_transactions::build(eth_validator_private_key, contract_address, nonce, AMOUNT, eth_gas_price, eth_gas, data);
-            log::debug!("raw confirmCancel: {:?}", tx);
-            web3.eth().send_raw_transaction(Bytes::from(tx))
-                .then(move |res| {
-                    match res {
-                        Ok(tx_res) => {
-                            log::info!("[ethereum] called confirmCancel({:?}), nonce: {:?}, result: {:?}",
-                                       args.0, nonce, tx_res)
-                        },
-                        Err(err) => {
-                            log::info!("[ethereum] can not send confirmCancel({:?}), nonce: {:?}, reason: {:?}",
-                                       args.0, nonce, err)
-                        }
-                    }
-
-                    Ok(())
-                })
-        })
-        .or_else(|e| {
-            log::warn!("can not get nonce: {:?}", e);
-            Ok(())
-        });
-    task_executor.spawn(fut);
+pub fn get_contract_abi() -> ethabi::Contract {
+    ethabi::Contract::load(get_abi_file()).expect("can not read ABI")
 }
 
-fn handle_sub_account_paused_message<T>(
-    config: &Config,
-    task_executor: TaskExecutor,
-    web3: Arc<web3::Web3<T>>,
-    abi: Arc<ethabi::Contract>,
-    message_id: H256,
-    sub_address: H256,
-) where
-    T: web3::Transport + Send + Sync + 'static,
-    T::Out: Send,
-{
-    let args = (sub_address,);
-    let eth_validator_private_key = config.eth_validator_private_key.clone();
-    let contract_address = config.token_bridge_address;
-    let eth_gas_price = config.eth_gas_price;
-    let eth_gas = config.eth_gas;
-    let data =
-        ethereum_transactions::build_transaction_data(&abi, "setPausedStatusForGuestAddress", args);
-    let fut = web3.eth().transaction_count(config.eth_validator_address, None)
-        .and_then(move |nonce| {
-            let tx = ethereum_transactions::build(eth_validator_private_key, contract_address, nonce, AMOUNT, eth_gas_price, eth_gas, data);
-            log::debug!("raw setPausedStatusForGuestAddress: {:?}", tx);
-            web3.eth().send_raw_transaction(Bytes::from(tx))
-                .then(move |res| {
-                    match res {
-                        Ok(tx_res) => {
-                            log::info!("[ethereum] called setPausedStatusForGuestAddress({:?}), message_id: {:?}, nonce: {:?}, result: {:?}",
-                                       args.0, message_id, nonce, tx_res)
-                        },
-                        Err(err) => {
-                            log::info!("[ethereum] can not send setPausedStatusForGuestAddress({:?}), message_id: {:?}, nonce: {:?}, reason: {:?}",
-                                       args.0, message_id, nonce, err)
-                        }
-                    }
-
-                    Ok(())
-                })
-        })
-        .or_else(|e| {
-            log::warn!("can not get nonce: {:?}", e);
-            Ok(())
-        });
-    task_executor.spawn(fut);
+fn get_abi_file() -> &'static [u8] {
+    include_bytes!("../res/Contract.json")
 }
 
-fn handle_sub_account_resumed_message<T>(
-    config: &Config,
-    task_executor: TaskExecutor,
-    web3: Arc<web3::Web3<T>>,
-    abi: Arc<ethabi::Contract>,
-    message_id: H256,
-    sub_address: H256,
-) where
-    T: web3::Transport + Send + Sync + 'static,
-    T::Out: Send,
-{
-    let args = (sub_address,);
-    let eth_validator_private_key = config.eth_validator_private_key.clone();
-    let contract_address = config.token_bridge_address;
-    let eth_gas_price = config.eth_gas_price;
-    let eth_gas = config.eth_gas;
-    let data = ethereum_transactions::build_transaction_data(
-        &abi,
-        "setResumedStatusForGuestAddress",
-        args,
-    );
-    let fut = web3.eth().transaction_count(config.eth_validator_address, None)
-        .and_then(move |nonce| {
-            let tx = ethereum_transactions::build(eth_validator_private_key, contract_address, nonce, AMOUNT, eth_gas_price, eth_gas, data);
-            log::debug!("raw setResumedStatusForGuestAddress: {:?}", tx);
-            web3.eth().send_raw_transaction(Bytes::from(tx))
-                .then(move |res| {
-                    match res {
-                        Ok(tx_res) => {
-                            log::info!("[ethereum] called setResumedStatusForGuestAddress({:?}), message_id: {:?}, nonce: {:?}, result: {:?}",
-                                       args.0, message_id, nonce, tx_res)
-                        },
-                        Err(err) => {
-                            log::info!("[ethereum] can not send setResumedStatusForGuestAddress({:?}), message_id: {:?}, nonce: {:?}, reason: {:?}",
-                                       args.0, message_id, nonce, err)
-                        }
-                    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                    Ok(())
-                })
-        })
-        .or_else(|e| {
-            log::warn!("can not get nonce: {:?}", e);
-            Ok(())
-        });
-    task_executor.spawn(fut);
-}
+    #[test]
+    fn applied_limits_tests() {
+        let limits = |value: u64| GuestLimits {
+            min_transaction_value: value.into(),
+            ..GuestLimits::default()
+        };
+        let applied = AppliedLimits::default();
+        assert_eq!(Ok(limits(1)), applied.get(|| Ok(limits(1))));
+        // read once, then cached
+        assert_eq!(Ok(limits(1)), applied.get(|| Err("not read".to_string())));
+        assert!(!applied.claim(&limits(1)));
+        assert!(applied.claim(&limits(2)));
+        // a repeat while the update is submitted
+        assert!(!applied.claim(&limits(2)));
+        assert_eq!(Ok(limits(2)), applied.get(|| Ok(limits(1))));
+        // a failed update or a change on the pallet
+        applied.forget();
+        assert_eq!(Ok(limits(3)), applied.get(|| Ok(limits(3))));
+    }
 
-fn get_contract_abi() -> Arc<ethabi::Contract> {
-    let abi = get_abi_file();
-    let abi = ethabi::Contract::load(abi.to_vec().as_slice()).expect("can not read ABI");
-    Arc::new(abi)
-}
+    #[test]
+    fn applied_limits_watch_test() {
+        let bus = Bus::new();
+        let applied = AppliedLimits::watch(&bus);
+        assert!(applied.claim(&GuestLimits::default()));
+        bus.publish(Envelope::new(
+            Event::sub_set_new_limits(H256::zero(), GuestLimits::default(), 0),
+            Source::SubstrateWs,
+        ));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while applied.lock().is_some() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(None, *applied.lock());
+    }
 
-fn get_abi_file() -> &'static [u8] {
-    include_bytes!("../res/Contract.json")
+    #[test]
+    fn check_validator_list_tests() {
+        let validators = vec![H256::from_slice(&[1; 32]), H256::from_slice(&[2; 32])];
+        assert_eq!(Ok(2), check_validator_list(&validators, 2.into()));
+        assert!(check_validator_list(&validators, 3.into()).is_err());
+        assert!(check_validator_list(&validators, U256::zero()).is_err());
+        assert!(check_validator_list(&[], 1.into()).is_err());
+        let twice = vec![validators[0], validators[0]];
+        assert!(check_validator_list(&twice, 2.into()).is_err());
+    }
 }
@@ -0,0 +1,106 @@
+use backtrace::Backtrace;
+use log;
+use web3::types::H256;
+
+use std::any::Any;
+use std::cell::Cell;
+use std::{panic, process, thread};
+
+/// Exit code of a validator stopped by a panic, the same as an unhandled one.
+const EXIT_CODE: i32 = 101;
+
+thread_local! {
+    static MESSAGE_ID: Cell<Option<H256>> = Cell::new(None);
+}
+
+/// Marks the message the current thread works on until dropped, so a panic
+/// can be reported together with it.
+pub struct MessageContext {
+    previous: Option<H256>,
+}
+
+impl Drop for MessageContext {
+    fn drop(&mut self) {
+        MESSAGE_ID.with(|message_id| message_id.set(self.previous));
+    }
+}
+
+pub fn message_context(message_id: H256) -> MessageContext {
+    MessageContext {
+        previous: MESSAGE_ID.with(|current| current.replace(Some(message_id))),
+    }
+}
+
+pub fn current_message_id() -> Option<H256> {
+    MESSAGE_ID.try_with(Cell::get).ok().flatten()
+}
+
+/// Stops the validator the way a panic would, for threads that find the
+/// rest of it gone and would otherwise carry on alone.
+pub fn stop(reason: &str) -> ! {
+    log::error!(
+        "fatal: thread={} {}",
+        thread::current().name().unwrap_or("<unnamed>"),
+        reason
+    );
+    process::exit(EXIT_CODE)
+}
+
+/// Reports a panic in any thread as a fatal error and stops the validator.
+/// Without this a panicking worker dies alone, and the executor pool even
+/// swallows panics of its tasks, leaving a validator that looks alive but
+/// no longer handles messages.
+pub fn install_panic_hook() {
+    panic::set_hook(Box::new(|info| {
+        let thread = thread::current();
+        let location = info
+            .location()
+            .map(|location| format!("{}:{}", location.file(), location.line()))
+            .unwrap_or_default();
+        log::error!(
+            "fatal: thread={} message_id={} location={} panic={:?}\n{:?}",
+            thread.name().unwrap_or("<unnamed>"),
+            current_message_id()
+                .map(|message_id| format!("{:?}", message_id))
+                .unwrap_or_else(|| "-".to_string()),
+            location,
+            panic_message(info.payload()),
+            Backtrace::new()
+        );
+        process::exit(EXIT_CODE);
+    }));
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "<non-string panic>"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_context_test() {
+        assert_eq!(None, current_message_id());
+        {
+            let _outer = message_context(H256::from_slice(&[1; 32]));
+            {
+                let _inner = message_context(H256::from_slice(&[2; 32]));
+                assert_eq!(Some(H256::from_slice(&[2; 32])), current_message_id());
+            }
+            assert_eq!(Some(H256::from_slice(&[1; 32])), current_message_id());
+        }
+        assert_eq!(None, current_message_id());
+
+        let payload: Box<dyn Any + Send> = Box::new(format!("{} failed", "send"));
+        assert_eq!("send failed", panic_message(payload.as_ref()));
+        let payload: Box<dyn Any + Send> = Box::new(1);
+        assert_eq!("<non-string panic>", panic_message(payload.as_ref()));
+    }
+}
@@ -0,0 +1,290 @@
+#[cfg(feature = "sqlite")]
+use log;
+use serde::{Deserialize, Serialize};
+use web3::types::{H256, U256};
+
+#[cfg(feature = "admin-api")]
+use std::collections::BTreeMap;
+#[cfg(feature = "admin-api")]
+use std::fmt::Write as _;
+
+#[cfg(feature = "sqlite")]
+use crate::database::{self, Shared};
+use crate::jsonl::{self, JsonLog};
+use crate::submissions::Chain;
+
+pub const FILE_NAME: &str = "fees.jsonl";
+
+/// What this validator paid for a transaction or extrinsic, in wei on
+/// Ethereum and in the smallest balance unit on Substrate.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fee {
+    pub message_id: H256,
+    pub chain: Chain,
+    pub call: String,
+    pub tx_hash: H256,
+    pub amount: U256,
+    /// Wei per gas, recorded for Ethereum transactions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price: Option<U256>,
+    pub recorded_at: i64,
+}
+
+#[cfg(feature = "admin-api")]
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct MessageFees {
+    pub message_id: H256,
+    pub ethereum: U256,
+    pub substrate: U256,
+    pub transactions: usize,
+}
+
+#[cfg(feature = "admin-api")]
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Report {
+    pub ethereum: U256,
+    pub substrate: U256,
+    pub messages: Vec<MessageFees>,
+}
+
+/// Append-only record of fees in `<data_dir>/fees.jsonl`. Ethereum fees are
+/// recorded once the transaction is confirmed, Substrate fees when the
+/// extrinsic is finalized.
+#[derive(Debug)]
+pub struct FeeLog {
+    log: JsonLog,
+    #[cfg(feature = "sqlite")]
+    database: Option<Shared>,
+}
+
+impl FeeLog {
+    pub fn open(data_dir: &str) -> Self {
+        FeeLog {
+            log: JsonLog::open(data_dir, FILE_NAME, "fees are not recorded"),
+            #[cfg(feature = "sqlite")]
+            database: database::shared(data_dir).ok(),
+        }
+    }
+
+    pub fn record(
+        &self,
+        message_id: H256,
+        chain: Chain,
+        call: &str,
+        tx_hash: H256,
+        amount: U256,
+        price: Option<U256>,
+    ) {
+        let fee = Fee {
+            message_id,
+            chain,
+            call: call.to_string(),
+            tx_hash,
+            amount,
+            price,
+            recorded_at: time::now_utc().to_timespec().sec,
+        };
+        self.log.append(&fee);
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = &self.database {
+            let recorded = database
+                .lock()
+                .expect("database lock poisoned")
+                .record_fee(&fee);
+            if let Err(e) = recorded {
+                log::error!("{}", e);
+            }
+        }
+    }
+}
+
+/// Every fee in `fees.jsonl`.
+pub fn read(data_dir: &str) -> Result<Vec<Fee>, String> {
+    jsonl::read(data_dir, FILE_NAME)
+}
+
+/// The fees recorded since the unix time `since`, on `chain` or on both.
+/// Read from `fees.jsonl` when there is no database.
+fn find(data_dir: &str, chain: Option<Chain>, since: i64) -> Result<Vec<Fee>, String> {
+    #[cfg(feature = "sqlite")]
+    {
+        if let Ok(database) = database::shared(data_dir) {
+            return database
+                .lock()
+                .expect("database lock poisoned")
+                .fees(chain, since);
+        }
+    }
+    Ok(read(data_dir)?
+        .into_iter()
+        .filter(|fee| chain.map_or(true, |chain| fee.chain == chain))
+        .filter(|fee| fee.recorded_at >= since)
+        .collect())
+}
+
+/// Sums the recorded fees per message and in total.
+#[cfg(feature = "admin-api")]
+pub fn report(data_dir: &str) -> Result<Report, String> {
+    let mut messages = BTreeMap::new();
+    for fee in find(data_dir, None, 0)? {
+        let message = messages
+            .entry(fee.message_id)
+            .or_insert_with(|| MessageFees {
+                message_id: fee.message_id,
+                ..MessageFees::default()
+            });
+        match fee.chain {
+            Chain::Ethereum => message.ethereum += fee.amount,
+            Chain::Substrate => message.substrate += fee.amount,
+        }
+        message.transactions += 1;
+    }
+    let messages = messages
+        .into_iter()
+        .map(|(_, fees)| fees)
+        .collect::<Vec<_>>();
+    Ok(Report {
+        ethereum: messages
+            .iter()
+            .fold(U256::zero(), |sum, fees| sum + fees.ethereum),
+        substrate: messages
+            .iter()
+            .fold(U256::zero(), |sum, fees| sum + fees.substrate),
+        messages,
+    })
+}
+
+/// The fees paid on `chain` since the unix time `since`.
+pub fn spent_since(data_dir: &str, chain: Chain, since: i64) -> Result<U256, String> {
+    Ok(find(data_dir, Some(chain), since)?
+        .iter()
+        .fold(U256::zero(), |sum, fee| sum + fee.amount))
+}
+
+/// The average price per unit paid on `chain` since the unix time `since`,
+/// none if nothing was paid.
+pub fn average_price(data_dir: &str, chain: Chain, since: i64) -> Result<Option<U256>, String> {
+    #[cfg(feature = "sqlite")]
+    {
+        if let Ok(database) = database::shared(data_dir) {
+            return database
+                .lock()
+                .expect("database lock poisoned")
+                .average_price(chain, since);
+        }
+    }
+    let prices = find(data_dir, Some(chain), since)?
+        .iter()
+        .filter_map(|fee| fee.price)
+        .collect::<Vec<_>>();
+    Ok(average(&prices))
+}
+
+pub fn average(prices: &[U256]) -> Option<U256> {
+    if prices.is_empty() {
+        return None;
+    }
+    let sum = prices.iter().fold(U256::zero(), |sum, price| sum + *price);
+    Some(sum / prices.len())
+}
+
+/// One line per message, amounts in decimal.
+#[cfg(feature = "admin-api")]
+pub fn to_csv(report: &Report) -> String {
+    let mut csv = "message_id,ethereum_wei,substrate_fee,transactions\n".to_string();
+    for fees in &report.messages {
+        writeln!(
+            csv,
+            "{:?},{},{},{}",
+            fees.message_id, fees.ethereum, fees.substrate, fees.transactions
+        )
+        .expect("write to string");
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    fn record_fees(data_dir: &str) -> (H256, H256) {
+        let first = H256::from_slice(&[1; 32]);
+        let second = H256::from_slice(&[2; 32]);
+        let fees = FeeLog::open(data_dir);
+        fees.record(
+            first,
+            Chain::Ethereum,
+            "approveTransfer",
+            H256::zero(),
+            100.into(),
+            Some(10.into()),
+        );
+        fees.record(
+            first,
+            Chain::Ethereum,
+            "confirmTransfer",
+            H256::zero(),
+            50.into(),
+            Some(20.into()),
+        );
+        fees.record(
+            second,
+            Chain::Substrate,
+            "approve_transfer",
+            H256::zero(),
+            7.into(),
+            None,
+        );
+        (first, second)
+    }
+
+    #[test]
+    fn record_test() {
+        let dir = TestDir::new("fees");
+        let data_dir = dir.path();
+        record_fees(data_dir);
+
+        assert_eq!(
+            Ok(U256::from(150)),
+            spent_since(data_dir, Chain::Ethereum, 0)
+        );
+        assert_eq!(
+            Ok(U256::zero()),
+            spent_since(data_dir, Chain::Ethereum, i64::max_value())
+        );
+        assert_eq!(
+            Ok(Some(U256::from(15))),
+            average_price(data_dir, Chain::Ethereum, 0)
+        );
+        assert_eq!(Ok(None), average_price(data_dir, Chain::Substrate, 0));
+    }
+
+    #[cfg(feature = "admin-api")]
+    #[test]
+    fn report_test() {
+        let dir = TestDir::new("fees_report");
+        let data_dir = dir.path();
+        let (first, second) = record_fees(data_dir);
+
+        let report = report(data_dir).unwrap();
+        assert_eq!(U256::from(150), report.ethereum);
+        assert_eq!(U256::from(7), report.substrate);
+        assert_eq!(
+            MessageFees {
+                message_id: first,
+                ethereum: 150.into(),
+                substrate: 0.into(),
+                transactions: 2,
+            },
+            report.messages[0]
+        );
+        assert_eq!(
+            format!(
+                "message_id,ethereum_wei,substrate_fee,transactions\n{:?},150,0,2\n{:?},0,7,1\n",
+                first, second
+            ),
+            to_csv(&report)
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use log;
+use web3::{futures::Future, transports::WebSocket, types::U256};
+
+use std::{thread, time::Duration};
+
+use crate::config::Config;
+use crate::fees;
+use crate::metrics;
+use crate::submissions::Chain;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Watches how long the validator's ETH balance lasts at the rate gas was
+/// spent over the last `GAS_RUNWAY_WINDOW_SECS`, and alerts when that drops
+/// below `GAS_RUNWAY_ALERT_HOURS`. Unlike a fixed balance threshold this
+/// catches volume spikes early and stays quiet while nothing is bridged.
+pub fn spawn(config: Config) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("gas_runway".to_string())
+        .spawn(move || {
+            let (_eloop, transport) =
+                WebSocket::new(&config.eth_api_url).expect("can not connect to ethereum node");
+            let web3 = web3::Web3::new(transport);
+            loop {
+                if let Err(e) = check(&web3, &config) {
+                    log::warn!("can not check gas runway: {}", e);
+                }
+                thread::sleep(CHECK_INTERVAL);
+            }
+        })
+        .expect("can not started gas runway monitor")
+}
+
+fn check(web3: &web3::Web3<WebSocket>, config: &Config) -> Result<(), String> {
+    let balance = web3
+        .eth()
+        .balance(config.eth_validator_address, None)
+        .wait()
+        .map_err(|e| format!("can not get validator balance: {:?}", e))?;
+    let window = config.gas_runway_window_secs;
+    let since = time::now_utc().to_timespec().sec - window as i64;
+    let spent = fees::spent_since(&config.data_dir, Chain::Ethereum, since)?;
+    let price = fees::average_price(&config.data_dir, Chain::Ethereum, since)?;
+    if let Some(price) = price {
+        metrics::set_gauge("validator_gas_price_average_wei", &[], to_f64(price));
+    }
+    match runway_hours(balance, spent, window) {
+        Some(hours) => {
+            metrics::set_gauge("validator_gas_runway_hours", &[], hours);
+            if hours < config.gas_runway_alert_hours as f64 {
+                log::error!(
+                    "validator {:?} has gas for about {:.1}h at the current rate, \
+                     balance: {} wei, spent in the last {}s: {} wei at {} wei per gas on average",
+                    config.eth_validator_address,
+                    hours,
+                    balance,
+                    window,
+                    spent,
+                    price.unwrap_or_default()
+                );
+            }
+        }
+        None => log::debug!("no gas spent in the last {}s", window),
+    }
+    Ok(())
+}
+
+/// How many hours `balance` lasts when `spent` was used over `window_secs`,
+/// `None` when nothing was spent.
+fn runway_hours(balance: U256, spent: U256, window_secs: u64) -> Option<f64> {
+    if spent.is_zero() || window_secs == 0 {
+        return None;
+    }
+    // wei do not fit in f64 exactly, the ratio is all that matters here
+    let ratio = to_f64(balance) / to_f64(spent);
+    Some(ratio * window_secs as f64 / 3600.0)
+}
+
+fn to_f64(value: U256) -> f64 {
+    value.0.iter().rev().fold(0.0, |sum, word| {
+        sum * 18_446_744_073_709_551_616.0 + *word as f64
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runway_hours_tests() {
+        let ether = U256::exp10(18);
+        // 1 ether spent in 6 hours, 4 ether left
+        assert_eq!(Some(24.0), runway_hours(ether * 4, ether, 6 * 3600));
+        assert_eq!(Some(0.0), runway_hours(U256::zero(), ether, 3600));
+        assert_eq!(None, runway_hours(ether, U256::zero(), 3600));
+        assert_eq!(1e30, to_f64(U256::exp10(30)));
+    }
+}
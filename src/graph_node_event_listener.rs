@@ -1,23 +1,58 @@
 use graphql_client::{GraphQLQuery, Response};
 use reqwest;
 use rustc_hex::FromHex;
-use web3::types::{H160, H256, U256};
-
-use std::{sync::mpsc::Sender, thread, time::Duration};
-
+use serde::Serialize;
+use web3::{
+    futures::Future,
+    transports::{EventLoopHandle, WebSocket},
+    types::{H160, H256, U256},
+};
+
+use std::path::Path;
+use std::time::Instant;
+use std::{sync::Arc, thread, time::Duration};
+
+use crate::bus::Bus;
+use crate::clock;
 use crate::config::Config;
-use crate::controller::Event;
+use crate::controller::{Entity, EthLimits, Event, GuestLimits, Source};
+use crate::dead_letters::DeadLetters;
+use crate::debug_state;
+use crate::listener::{self, Converted};
+use crate::metrics;
+use crate::net;
+use crate::orphans::Orphans;
+use crate::retention::AppendLog;
+use crate::startup::Ready;
+use crate::watchdog::Heartbeat;
+
+pub const SKIPPED_FILE_NAME: &str = "skipped_unfinalized.jsonl";
 
 struct EventListener {
     config: Config,
-    controller_tx: Sender<Event>,
+    bus: Arc<Bus>,
+    heartbeat: Heartbeat,
+    dead_letters: DeadLetters,
+    _eloop: EventLoopHandle,
+    web3: web3::Web3<WebSocket>,
+    is_lagging: bool,
     messages_offset: u64,
     bridge_messages_offset: u64,
     account_messages_offset: u64,
     limit_messages_offset: u64,
     validators_list_messages_offset: u64,
+    orphans: Orphans,
+    swept_at: Instant,
 }
 
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "res/graph_node_schema.graphql",
+    query_path = "res/graph_node_indexing_status.graphql",
+    response_derives = "Debug"
+)]
+struct IndexingStatus;
+
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "res/graph_node_schema.graphql",
@@ -114,11 +149,17 @@ struct MaxBlockNumberOfValidatorsListMessages;
 )]
 struct AllValidatorsListMessages;
 
-pub fn spawn(config: Config, controller_tx: Sender<Event>) -> thread::JoinHandle<()> {
+pub fn spawn(
+    config: Config,
+    bus: Arc<Bus>,
+    heartbeat: Heartbeat,
+    ready: Ready,
+) -> thread::JoinHandle<()> {
 thread::Builder::new()
     .name("graph_node_event_listener".to_string())
     .spawn(move || {
-        let mut event_listener = EventListener::new(config, controller_tx);
+        let mut event_listener = EventListener::new(config, bus, heartbeat);
+        ready.ok();
         event_listener.start();
     })
     .expect("can not started graph_node_listener")
@@ -126,15 +167,26 @@ thread::Builder::new()
 
 
 impl EventListener {
-    fn new(config: Config, controller_tx: Sender<Event>) -> Self {
+    fn new(config: Config, bus: Arc<Bus>, heartbeat: Heartbeat) -> Self {
+        let (_eloop, transport) =
+            WebSocket::new(&config.eth_api_url).expect("can not connect to ethereum node");
+        let dead_letters = DeadLetters::open(&config.data_dir);
+        let orphans = Orphans::open(&config.data_dir, config.orphan_max_redrives);
         EventListener {
             config,
-            controller_tx,
+            bus,
+            heartbeat,
+            dead_letters,
+            _eloop,
+            web3: web3::Web3::new(transport),
+            is_lagging: false,
             messages_offset: 0,
             bridge_messages_offset: 0,
             account_messages_offset: 0,
             limit_messages_offset: 0,
             validators_list_messages_offset: 0,
+            orphans,
+            swept_at: Instant::now(),
         }
     }
 
@@ -144,13 +196,81 @@ impl EventListener {
         self.set_offsets();
         self.handle_unfinalized_events();
 
-        loop {
-            self.handle_last_events();
+        while self.heartbeat.beat() {
+            if self.check_indexing_lag() && self.config.graph_node_hold_on_lag {
+                log::debug!("graph node is lagging, holding new events");
+            } else {
+                self.handle_last_events();
+            }
+            let sweep = Duration::from_secs(self.config.orphan_sweep_secs);
+            if self.config.orphan_sweep_secs > 0 && self.swept_at.elapsed() >= sweep {
+                self.sweep_orphans();
+                self.swept_at = Instant::now();
+            }
+            self.publish_debug_state();
             thread::sleep(Duration::from_millis(1000));
         }
+        log::warn!("graph listener was replaced by the watchdog, stopping");
+    }
+
+    /// Returns true when the subgraph is behind the ethereum head by more than
+    /// `graph_node_max_lag_blocks`. Failures to get either block number are
+    /// logged and keep the previous state. A subgraph that is reached and not
+    /// lagging is reported healthy, for an indexer standing by.
+    fn check_indexing_lag(&mut self) -> bool {
+        let indexed_block_number = match self.get_indexed_block_number() {
+            Ok(block_number) => block_number,
+            Err(err) => {
+                log::warn!("can not get indexed block number, reason: {:?}", err);
+                return self.is_lagging;
+            }
+        };
+        let head_block_number = match self.web3.eth().block_number().wait() {
+            Ok(block_number) => block_number.low_u64(),
+            Err(err) => {
+                log::warn!("can not get ethereum block number, reason: {:?}", err);
+                return self.is_lagging;
+            }
+        };
+
+        let lag = head_block_number.saturating_sub(indexed_block_number);
+        let is_lagging = lag > self.config.graph_node_max_lag_blocks;
+        if is_lagging && !self.is_lagging {
+            log::error!(
+                "graph node is lagging: indexed block {}, ethereum head {}, lag {} blocks",
+                indexed_block_number,
+                head_block_number,
+                lag
+            );
+        } else if !is_lagging && self.is_lagging {
+            log::info!(
+                "graph node caught up: indexed block {}, ethereum head {}",
+                indexed_block_number,
+                head_block_number
+            );
+        }
+        if !is_lagging {
+            listener::report_graph_node_healthy();
+        }
+        self.is_lagging = is_lagging;
+        is_lagging
     }
 
 
+    fn publish_debug_state(&self) {
+        debug_state::publish(
+            "graph_node_event_listener",
+            serde_json::json!({
+                "is_lagging": self.is_lagging,
+                "messages_offset": self.messages_offset,
+                "bridge_messages_offset": self.bridge_messages_offset,
+                "account_messages_offset": self.account_messages_offset,
+                "limit_messages_offset": self.limit_messages_offset,
+                "validators_list_messages_offset": self.validators_list_messages_offset,
+            }),
+        );
+    }
+
     fn handle_blocked_accounts(&self) {
         log::debug!("handling blocked accounts ");
         let events = self
@@ -234,90 +354,176 @@ impl EventListener {
             });
     }
 
+    /// Replays the messages still in one of the unfinalized statuses, except
+    /// those older than `UNFINALIZED_MIN_BLOCK` or `UNFINALIZED_MAX_AGE_BLOCKS`,
+    /// which are recorded in `DATA_DIR/skipped_unfinalized.jsonl` instead.
     fn handle_unfinalized_events(&self) {
         log::debug!("handling unfinalized events ");
 
-        const UNFINALIZED_STATUSES: [messages_by_status::Status; 4] = [
-            messages_by_status::Status::PENDING,
-            messages_by_status::Status::WITHDRAW,
-            messages_by_status::Status::APPROVED,
-            messages_by_status::Status::CANCELED,
-        ];
-
-        let mut events: Vec<_> = UNFINALIZED_STATUSES
+        let events: Vec<_> = self
+            .config
+            .unfinalized_statuses
             .iter()
-            .cloned()
+            .map(|status| parse_status(status))
             .map(|status| {
-                self.get_messages_by_status(status)
-                    .unwrap_or_else(|_| vec![])
+                self.get_messages_by_status(status.clone())
+                    .unwrap_or_else(|err| {
+                        log::warn!(
+                            "can not get unfinalized messages, status={:?}, reason: {:?}",
+                            status,
+                            err
+                        );
+                        vec![]
+                    })
             })
             .flatten()
             .collect();
 
-        events.sort_by(|a, b| a.block_number().cmp(&b.block_number()));
-        self.send_events(events);
+        let from = listener::unfinalized_from(&self.web3, &self.config);
+        let (mut events, skipped): (Vec<_>, Vec<_>) = events
+            .into_iter()
+            .partition(|(event, _)| event.block_number() >= u128::from(from));
+        if !skipped.is_empty() {
+            let skipped = skipped.into_iter().map(|(event, _)| event).collect::<Vec<_>>();
+            log::warn!(
+                "skipping {} unfinalized messages from before block {}",
+                skipped.len(),
+                from
+            );
+            audit_skipped(&self.config.data_dir, &skipped, from);
+        }
+
+        events.sort_by(|(a, _), (b, _)| a.block_number().cmp(&b.block_number()));
+        self.replay(events);
+    }
+
+    /// Re-drives transfers stuck for `ORPHAN_MIN_AGE_BLOCKS`, see `Orphans`.
+    fn sweep_orphans(&mut self) {
+        let head_block_number = match self.web3.eth().block_number().wait() {
+            Ok(block_number) => block_number.low_u64(),
+            Err(err) => {
+                log::warn!("can not get ethereum block number, reason: {:?}", err);
+                return;
+            }
+        };
+        let before = head_block_number.saturating_sub(self.config.orphan_min_age_blocks);
+        let mut stuck = vec![];
+        for status in ORPHAN_STATUSES.iter() {
+            match self.get_messages_by_status(status.clone()) {
+                Ok(events) => stuck.extend(events.into_iter().filter(|(event, _)| match event {
+                    Event::Unknown { .. } => false,
+                    event => event.block_number() <= u128::from(before),
+                })),
+                // a partial sweep would forget the re-drives of the rest
+                Err(err) => {
+                    log::warn!("can not sweep orphans, status={:?}, reason: {:?}", status, err);
+                    return;
+                }
+            }
+        }
+        self.orphans.sweep(stuck, &self.bus, &self.dead_letters);
+    }
+
+    /// Sends the replayed events in chunks of `REPLAY_CHUNK_SIZE`, pausing in
+    /// between, so thousands of messages do not reach the executor at once.
+    fn replay(&self, events: Vec<Converted>) {
+        let total = events.len();
+        let chunk_size = match self.config.replay_chunk_size {
+            0 => total.max(1),
+            chunk_size => chunk_size,
+        };
+        let mut replayed = 0;
+        for chunk in events.chunks(chunk_size) {
+            if replayed > 0 && !self.pause(self.config.replay_chunk_pause_secs) {
+                log::warn!("graph listener was replaced during the replay, stopping");
+                return;
+            }
+            self.send_events(chunk.to_vec());
+            replayed += chunk.len();
+            log::info!("replayed {}/{} unfinalized messages", replayed, total);
+        }
     }
 
+    /// Sleeps while beating, a long replay must not look stalled to the
+    /// watchdog. Returns false once the listener has been replaced.
+    fn pause(&self, secs: u64) -> bool {
+        for _ in 0..secs {
+            thread::sleep(Duration::from_secs(1));
+            if !self.heartbeat.beat() {
+                return false;
+            }
+        }
+        self.heartbeat.beat()
+    }
+
+    /// The entity queries are independent, so they run concurrently and a
+    /// poll cycle takes as long as the slowest one instead of their sum.
     fn handle_last_events(&mut self) {
+        let url = &self.config.graph_node_api_url;
+        let all_messages = spawn_query("all_messages", url, self.messages_offset, get_all_messages);
+        let all_bridge_messages = spawn_query(
+            "all_bridge_messages",
+            url,
+            self.bridge_messages_offset,
+            get_all_bridge_messages,
+        );
+        let all_account_messages = spawn_query(
+            "all_account_messages",
+            url,
+            self.account_messages_offset,
+            get_all_account_messages,
+        );
+        let all_limit_messages = spawn_query(
+            "all_limit_messages",
+            url,
+            self.limit_messages_offset,
+            get_all_limit_messages,
+        );
+        let all_validators_list_messages = spawn_query(
+            "all_validators_list_messages",
+            url,
+            self.validators_list_messages_offset,
+            get_all_validators_list_messages,
+        );
+
         let mut events = vec![];
-        let mut all_messages = self
-            .get_all_messages()
-            .or_else(|err| {
-                log::warn!("can not get all_messages, reason: {:?}", err);
-                Ok(vec![])
-            })
-            .map_err(|_: reqwest::Error| ())
-            .expect("can not get all_messages");
-        let mut all_bridge_messages = self
-            .get_all_bridge_messages()
-            .or_else(|err| {
-                log::warn!("can not get all_bridge_messages, reason: {:?}", err);
-                Ok(vec![])
-            })
-            .map_err(|_: reqwest::Error| ())
-            .expect("can not get all_bridge_messages");
-        let mut all_account_messages = self
-            .get_all_account_messages()
-            .or_else(|err| {
-                log::warn!("can not get all_account_messages, reason: {:?}", err);
-                Ok(vec![])
-            })
-            .map_err(|_: reqwest::Error| ())
-            .expect("can not get all_account_messages");
-        let mut all_limit_messages = self
-            .get_all_limit_messages()
-            .or_else(|err| {
-                log::warn!("can not get all_limit_messages, reason: {:?}", err);
-                Ok(vec![])
-            })
-            .map_err(|_: reqwest::Error| ())
-            .expect("can not get all_limit_messages");
-        let mut all_validators_list_messages = self
-            .get_all_validators_list_messages()
-            .or_else(|err| {
-                log::warn!(
-                    "can not get all_validators_list_messages, reason: {:?}",
-                    err
-                );
-                Ok(vec![])
-            })
-            .map_err(|_: reqwest::Error| ())
-            .expect("can not get all_validators_list_messages");
-
-            events.append(all_messages.as_mut());
-        events.append(all_bridge_messages.as_mut());
-        events.append(all_account_messages.as_mut());
-        events.append(all_limit_messages.as_mut());
-        events.append(all_validators_list_messages.as_mut());
-        events.sort_by(|a, b| a.block_number().cmp(&b.block_number()));
+        if let Some(block_number) = join_query(all_messages, &mut events) {
+            self.update_messages_offset(block_number);
+        }
+        if let Some(block_number) = join_query(all_bridge_messages, &mut events) {
+            self.update_bridge_messages_offset(block_number);
+        }
+        if let Some(block_number) = join_query(all_account_messages, &mut events) {
+            self.update_account_messages_offset(block_number);
+        }
+        if let Some(block_number) = join_query(all_limit_messages, &mut events) {
+            self.update_limit_messages_offset(block_number);
+        }
+        if let Some(block_number) = join_query(all_validators_list_messages, &mut events) {
+            self.update_validators_list_messages_offset(block_number);
+        }
+        events.sort_by(|(a, _), (b, _)| a.block_number().cmp(&b.block_number()));
         self.send_events(events);
     }
 
-    fn send_events(&self, events: Vec<Event>) {
-        events
-            .iter()
-            .cloned()
-            .for_each(|event| self.controller_tx.send(event).expect("can not send event"));
+    fn send_events(&self, events: Vec<Converted>) {
+        let source = Source::GraphNode;
+        listener::send_events(&self.config, &self.bus, &self.dead_letters, source, events);
+    }
+
+    fn get_indexed_block_number(&self) -> Result<u64, reqwest::Error> {
+        let request_body = IndexingStatus::build_query(indexing_status::Variables);
+        let client = net::http_client();
+        let mut res = client
+            .post(&self.config.graph_node_api_url)
+            .json(&request_body)
+            .send()?;
+        let response_body: Response<indexing_status::ResponseData> = res.json()?;
+        Ok(response_body
+            .data
+            .and_then(|data| data.meta)
+            .map(|meta| meta.block.number as u64)
+            .unwrap_or(0))
     }
 
     fn get_max_block_number_of_messages(&self) -> Result<u64, reqwest::Error> {
@@ -325,7 +531,7 @@ impl EventListener {
             MaxBlockNumberOfMessages::build_query(max_block_number_of_messages::Variables {
                 block_number: self.messages_offset as i64,
             });
-        let client = reqwest::Client::new();
+        let client = net::http_client();
         let mut res = client
             .post(&self.config.graph_node_api_url)
             .json(&request_body)
@@ -351,7 +557,7 @@ impl EventListener {
                 block_number: self.bridge_messages_offset as i64,
             },
         );
-        let client = reqwest::Client::new();
+        let client = net::http_client();
         let mut res = client
             .post(&self.config.graph_node_api_url)
             .json(&request_body)
@@ -378,7 +584,7 @@ impl EventListener {
                 block_number: self.account_messages_offset as i64,
             },
         );
-        let client = reqwest::Client::new();
+        let client = net::http_client();
         let mut res = client
             .post(&self.config.graph_node_api_url)
             .json(&request_body)
@@ -405,7 +611,7 @@ impl EventListener {
                 block_number: self.limit_messages_offset as i64,
             },
         );
-        let client = reqwest::Client::new();
+        let client = net::http_client();
         let mut res = client
             .post(&self.config.graph_node_api_url)
             .json(&request_body)
@@ -432,7 +638,7 @@ impl EventListener {
                 block_number: self.validators_list_messages_offset as i64,
             },
         );
-        let client = reqwest::Client::new();
+        let client = net::http_client();
         let mut res = client
             .post(&self.config.graph_node_api_url)
             .json(&request_body)
@@ -453,48 +659,16 @@ impl EventListener {
         }
     }
 
-    fn get_all_messages(&mut self) -> Result<Vec<Event>, reqwest::Error> {
-        let request_body = AllMessages::build_query(all_messages::Variables {
-            block_number: self.messages_offset as i64,
-        });
-        let client = reqwest::Client::new();
-        let mut res = client
-            .post(&self.config.graph_node_api_url)
-            .json(&request_body)
-            .send()?;
-        let response_body: Response<all_messages::ResponseData> = res.json()?;
-        let messages = response_body
-            .data
-            .expect("can not get response_data")
-            .messages;
-
-        messages
-            .iter()
-            .map(|message| {
-                message
-                    .eth_block_number
-                    .parse()
-                    .expect("can not parse eth_block_number")
-            })
-            .max()
-            .and_then(|eth_block_number| {
-                self.update_messages_offset(eth_block_number);
-                Some(eth_block_number)
-            });
-
-        Ok(messages.iter().map(Into::into).collect())
-    }
-
     fn get_messages_by_status(
         &self,
         status: messages_by_status::Status,
-    ) -> Result<Vec<Event>, reqwest::Error> {
+    ) -> Result<Vec<Converted>, reqwest::Error> {
         log::info!("getting unfinalized transactions, status={:?}", status);
         let request_body = MessagesByStatus::build_query(messages_by_status::Variables {
             eth_block_number: 0,
             status: status.clone(),
         });
-        let client = reqwest::Client::new();
+        let client = net::http_client();
         let mut res = client
             .post(&self.config.graph_node_api_url)
             .json(&request_body)
@@ -510,144 +684,18 @@ impl EventListener {
             messages.len(),
             status
         );
-        Ok(messages.iter().map(Into::into).collect())
-    }
-
-    fn get_all_bridge_messages(&mut self) -> Result<Vec<Event>, reqwest::Error> {
-        let request_body = AllBridgeMessages::build_query(all_bridge_messages::Variables {
-            block_number: self.bridge_messages_offset as i64,
-        });
-        let client = reqwest::Client::new();
-        let mut res = client
-            .post(&self.config.graph_node_api_url)
-            .json(&request_body)
-            .send()?;
-        let response_body: Response<all_bridge_messages::ResponseData> = res.json()?;
-        let bridge_messages = response_body
-            .data
-            .expect("can not get response_data")
-            .bridge_messages;
-
-        bridge_messages
-            .iter()
-            .map(|bridge_message| {
-                bridge_message
-                    .eth_block_number
-                    .parse()
-                    .expect("can not parse eth_block_number")
-            })
-            .max()
-            .and_then(|eth_block_number| {
-                self.update_bridge_messages_offset(eth_block_number);
-                Some(eth_block_number)
-            });
-
-        Ok(bridge_messages.iter().map(Into::into).collect())
-    }
-
-    fn get_all_account_messages(&mut self) -> Result<Vec<Event>, reqwest::Error> {
-        let request_body = AllAccountMessages::build_query(all_account_messages::Variables {
-            block_number: self.account_messages_offset as i64,
-        });
-        let client = reqwest::Client::new();
-        let mut res = client
-            .post(&self.config.graph_node_api_url)
-            .json(&request_body)
-            .send()?;
-        let response_body: Response<all_account_messages::ResponseData> = res.json()?;
-        let account_messages = response_body
-            .data
-            .expect("can not get response_data")
-            .account_messages;
-
-        account_messages
-            .iter()
-            .map(|account_message| {
-                account_message
-                    .eth_block_number
-                    .parse()
-                    .expect("can not parse eth_block_number")
-            })
-            .max()
-            .and_then(|eth_block_number| {
-                self.update_account_messages_offset(eth_block_number);
-                Some(eth_block_number)
-            });
-
-        Ok(account_messages.iter().map(Into::into).collect())
-    }
-
-    fn get_all_limit_messages(&mut self) -> Result<Vec<Event>, reqwest::Error> {
-        let request_body = AllLimitMessages::build_query(all_limit_messages::Variables {
-            block_number: self.limit_messages_offset as i64,
-        });
-        let client = reqwest::Client::new();
-        let mut res = client
-            .post(&self.config.graph_node_api_url)
-            .json(&request_body)
-            .send()?;
-        let response_body: Response<all_limit_messages::ResponseData> = res.json()?;
-        let limit_messages = response_body
-            .data
-            .expect("can not get response_data")
-            .limit_messages;
-
-        limit_messages
+        Ok(messages
             .iter()
-            .map(|limit_message| {
-                limit_message
-                    .eth_block_number
-                    .parse()
-                    .expect("can not parse eth_block_number")
-            })
-            .max()
-            .and_then(|eth_block_number| {
-                self.update_limit_messages_offset(eth_block_number);
-                Some(eth_block_number)
-            });
-
-        Ok(limit_messages.iter().map(Into::into).collect())
+            .map(|message| (message.into(), Entity::new("message", &message.id)))
+            .collect())
     }
 
-    fn get_all_validators_list_messages(&mut self) -> Result<Vec<Event>, reqwest::Error> {
-        let request_body =
-            AllValidatorsListMessages::build_query(all_validators_list_messages::Variables {
-                block_number: self.validators_list_messages_offset as i64,
-            });
-        let client = reqwest::Client::new();
-        let mut res = client
-            .post(&self.config.graph_node_api_url)
-            .json(&request_body)
-            .send()?;
-        let response_body: Response<all_validators_list_messages::ResponseData> = res.json()?;
-        let validators_list_messages = response_body
-            .data
-            .expect("can not get response_data")
-            .validators_list_messages;
-
-        validators_list_messages
-            .iter()
-            .map(|validators_list_message| {
-                validators_list_message
-                    .eth_block_number
-                    .parse()
-                    .expect("can not parse eth_block_number")
-            })
-            .max()
-            .and_then(|eth_block_number| {
-                self.update_validators_list_messages_offset(eth_block_number);
-                Some(eth_block_number)
-            });
-
-        Ok(validators_list_messages.iter().map(Into::into).collect())
-    }
-
-    fn get_events_for_blocked_accounts(&self) -> Result<Vec<Event>, reqwest::Error> {
+    fn get_events_for_blocked_accounts(&self) -> Result<Vec<Converted>, reqwest::Error> {
         let request_body = AllAccounts::build_query(all_accounts::Variables {
-            timestamp: begin_of_this_day().to_string(),
+            timestamp: clock::begin_of_this_day().to_string(),
             status: all_accounts::AccountStatus::BLOCKED,
         });
-        let client = reqwest::Client::new();
+        let client = net::http_client();
         let mut res = client
             .post(&self.config.graph_node_api_url)
             .json(&request_body)
@@ -658,7 +706,10 @@ impl EventListener {
             .expect("can not get response_data")
             .accounts;
 
-        Ok(accounts.iter().map(Into::into).collect())
+        Ok(accounts
+            .iter()
+            .map(|account| (account.into(), Entity::new("account", &account.id)))
+            .collect())
     }
 
     fn update_messages_offset(&mut self, block_number: u64) {
@@ -693,50 +744,244 @@ impl EventListener {
     }
 }
 
+#[derive(Serialize)]
+struct Skipped<'a> {
+    event: &'a Event,
+    replay_from: u64,
+    skipped_at: i64,
+}
+
+fn audit_skipped(data_dir: &str, events: &[Event], replay_from: u64) {
+    let path = Path::new(data_dir).join(SKIPPED_FILE_NAME);
+    let skipped_at = time::now_utc().to_timespec().sec;
+    let result = AppendLog::open(path.clone()).and_then(|file| {
+        events.iter().try_for_each(|event| {
+            let skipped = Skipped {
+                event,
+                replay_from,
+                skipped_at,
+            };
+            let line = serde_json::to_string(&skipped).expect("event serializes");
+            file.append(&line)
+        })
+    });
+    if let Err(e) = result {
+        log::error!("can not record skipped messages in {:?}: {}, they were {:?}", path, e, events);
+    }
+}
+
+/// Statuses of transfers that are done on one chain and wait for the other.
+const ORPHAN_STATUSES: [messages_by_status::Status; 2] =
+    [messages_by_status::Status::APPROVED, messages_by_status::Status::WITHDRAW];
+
+/// Events of one entity query and the highest block number among them.
+type Page = (Vec<Converted>, Option<u64>);
+
+struct Query {
+    name: &'static str,
+    handle: thread::JoinHandle<Result<Page, reqwest::Error>>,
+}
+
+fn spawn_query(
+    name: &'static str,
+    url: &str,
+    offset: u64,
+    get: fn(&str, u64) -> Result<Page, reqwest::Error>,
+) -> Query {
+    let url = url.to_string();
+    Query {
+        name,
+        handle: thread::Builder::new()
+            .name(format!("graph_node_{}", name))
+            .spawn(move || get(&url, offset))
+            .expect("can not start graph node query"),
+    }
+}
+
+/// Appends the events of the query, returns the block number to continue
+/// from. A failed query is logged and retried from the same offset.
+fn join_query(query: Query, events: &mut Vec<Converted>) -> Option<u64> {
+    match query.handle.join() {
+        Ok(Ok((mut page, block_number))) => {
+            events.append(&mut page);
+            block_number
+        }
+        Ok(Err(err)) => {
+            log::warn!("can not get {}, reason: {:?}", query.name, err);
+            None
+        }
+        // an unexpected response stops the listener, as before
+        Err(panic) => std::panic::resume_unwind(panic),
+    }
+}
+
+fn get_all_messages(url: &str, offset: u64) -> Result<Page, reqwest::Error> {
+    let request_body = AllMessages::build_query(all_messages::Variables {
+        block_number: offset as i64,
+    });
+    let client = net::http_client();
+    let mut res = client.post(url).json(&request_body).send()?;
+    let response_body: Response<all_messages::ResponseData> = res.json()?;
+    let messages = response_body
+        .data
+        .expect("can not get response_data")
+        .messages;
+
+    Ok((
+        messages
+            .iter()
+            .map(|message| (message.into(), Entity::new("message", &message.id)))
+            .collect(),
+        max_block_number(messages.iter().map(|message| &message.eth_block_number)),
+    ))
+}
+
+fn get_all_bridge_messages(url: &str, offset: u64) -> Result<Page, reqwest::Error> {
+    let request_body = AllBridgeMessages::build_query(all_bridge_messages::Variables {
+        block_number: offset as i64,
+    });
+    let client = net::http_client();
+    let mut res = client.post(url).json(&request_body).send()?;
+    let response_body: Response<all_bridge_messages::ResponseData> = res.json()?;
+    let bridge_messages = response_body
+        .data
+        .expect("can not get response_data")
+        .bridge_messages;
+
+    Ok((
+        bridge_messages
+            .iter()
+            .map(|message| (message.into(), Entity::new("bridgeMessage", &message.id)))
+            .collect(),
+        max_block_number(
+            bridge_messages
+                .iter()
+                .map(|bridge_message| &bridge_message.eth_block_number),
+        ),
+    ))
+}
+
+fn get_all_account_messages(url: &str, offset: u64) -> Result<Page, reqwest::Error> {
+    let request_body = AllAccountMessages::build_query(all_account_messages::Variables {
+        block_number: offset as i64,
+    });
+    let client = net::http_client();
+    let mut res = client.post(url).json(&request_body).send()?;
+    let response_body: Response<all_account_messages::ResponseData> = res.json()?;
+    let account_messages = response_body
+        .data
+        .expect("can not get response_data")
+        .account_messages;
+
+    Ok((
+        account_messages
+            .iter()
+            .map(|message| (message.into(), Entity::new("accountMessage", &message.id)))
+            .collect(),
+        max_block_number(
+            account_messages
+                .iter()
+                .map(|account_message| &account_message.eth_block_number),
+        ),
+    ))
+}
+
+fn get_all_limit_messages(url: &str, offset: u64) -> Result<Page, reqwest::Error> {
+    let request_body = AllLimitMessages::build_query(all_limit_messages::Variables {
+        block_number: offset as i64,
+    });
+    let client = net::http_client();
+    let mut res = client.post(url).json(&request_body).send()?;
+    let response_body: Response<all_limit_messages::ResponseData> = res.json()?;
+    let limit_messages = response_body
+        .data
+        .expect("can not get response_data")
+        .limit_messages;
+
+    Ok((
+        limit_messages
+            .iter()
+            .map(|message| (message.into(), Entity::new("limitMessage", &message.id)))
+            .collect(),
+        max_block_number(
+            limit_messages
+                .iter()
+                .map(|limit_message| &limit_message.eth_block_number),
+        ),
+    ))
+}
+
+fn get_all_validators_list_messages(url: &str, offset: u64) -> Result<Page, reqwest::Error> {
+    let request_body =
+        AllValidatorsListMessages::build_query(all_validators_list_messages::Variables {
+            block_number: offset as i64,
+        });
+    let client = net::http_client();
+    let mut res = client.post(url).json(&request_body).send()?;
+    let response_body: Response<all_validators_list_messages::ResponseData> = res.json()?;
+    let validators_list_messages = response_body
+        .data
+        .expect("can not get response_data")
+        .validators_list_messages;
+
+    Ok((
+        validators_list_messages
+            .iter()
+            .map(|message| (message.into(), Entity::new("validatorsListMessage", &message.id)))
+            .collect(),
+        max_block_number(
+            validators_list_messages
+                .iter()
+                .map(|validators_list_message| &validators_list_message.eth_block_number),
+        ),
+    ))
+}
+
+fn max_block_number<'a>(block_numbers: impl Iterator<Item = &'a String>) -> Option<u64> {
+    block_numbers
+        .map(|block_number| block_number.parse().expect("can not parse eth_block_number"))
+        .max()
+}
+
 impl From<&all_messages::AllMessagesMessages> for Event {
     fn from(message: &all_messages::AllMessagesMessages) -> Event {
         log::debug!("converting all_messages query result to Event: {:?}", message.clone());
         match (&message.status, &message.direction) {
             (all_messages::Status::PENDING, all_messages::Direction::ETH2SUB) => {
-                Event::EthRelayMessage(
-                    parse_h256(&message.id),
-                    parse_h160(&message.eth_address),
-                    parse_h256(&message.sub_address),
-                    parse_u256(&message.amount),
-                    parse_u128(&message.eth_block_number),
-                )
+                Event::EthRelayMessage {
+                    message_id: parse_h256(&message.id),
+                    sender: parse_h160(&message.eth_address),
+                    recipient: parse_h256(&message.sub_address),
+                    amount: parse_u256(&message.amount),
+                    token: Some(parse_h160(&message.token)),
+                    block_number: parse_u128(&message.eth_block_number),
+                }
             }
             (all_messages::Status::APPROVED, all_messages::Direction::ETH2SUB) => {
-                Event::EthApprovedRelayMessage(
-                    parse_h256(&message.id),
-                    parse_h160(&message.eth_address),
-                    parse_h256(&message.sub_address),
-                    parse_u256(&message.amount),
-                    parse_u128(&message.eth_block_number),
-                )
+                Event::EthApprovedRelayMessage {
+                    message_id: parse_h256(&message.id),
+                    sender: parse_h160(&message.eth_address),
+                    recipient: parse_h256(&message.sub_address),
+                    amount: parse_u256(&message.amount),
+                    block_number: parse_u128(&message.eth_block_number),
+                }
             }
             (all_messages::Status::CANCELED, all_messages::Direction::ETH2SUB) => {
-                Event::EthRevertMessage(
-                    parse_h256(&message.id),
-                    parse_h160(&message.eth_address),
-                    parse_u256(&message.amount),
-                    parse_u128(&message.eth_block_number),
-                )
+                Event::EthRevertMessage {
+                    message_id: parse_h256(&message.id),
+                    sender: parse_h160(&message.eth_address),
+                    amount: parse_u256(&message.amount),
+                    block_number: parse_u128(&message.eth_block_number),
+                }
             }
             (all_messages::Status::WITHDRAW, all_messages::Direction::SUB2ETH) => {
-                Event::EthWithdrawMessage(
-                    parse_h256(&message.id),
-                    parse_u128(&message.eth_block_number),
-                )
+                Event::EthWithdrawMessage {
+                    message_id: parse_h256(&message.id),
+                    block_number: parse_u128(&message.eth_block_number),
+                }
             }
 
-            (_, _) => Event::EthApprovedRelayMessage(
-                parse_h256(&message.id),
-                parse_h160(&message.eth_address),
-                parse_h256(&message.sub_address),
-                parse_u256(&message.amount),
-                parse_u128(&message.eth_block_number),
-            ),
+            (_, _) => unknown("all_messages", &message.id, message, &message.eth_block_number),
         }
     }
 }
@@ -746,44 +991,44 @@ impl From<&messages_by_status::MessagesByStatusMessages> for Event {
         log::debug!("converting messages_by_status query result to Event: {:?}", message.clone());
         match (&message.status, &message.direction) {
             (messages_by_status::Status::PENDING, messages_by_status::Direction::ETH2SUB) => {
-                Event::EthRelayMessage(
-                    parse_h256(&message.id),
-                    parse_h160(&message.eth_address),
-                    parse_h256(&message.sub_address),
-                    parse_u256(&message.amount),
-                    parse_u128(&message.eth_block_number),
-                )
+                Event::EthRelayMessage {
+                    message_id: parse_h256(&message.id),
+                    sender: parse_h160(&message.eth_address),
+                    recipient: parse_h256(&message.sub_address),
+                    amount: parse_u256(&message.amount),
+                    token: Some(parse_h160(&message.token)),
+                    block_number: parse_u128(&message.eth_block_number),
+                }
             }
             (messages_by_status::Status::APPROVED, messages_by_status::Direction::ETH2SUB) => {
-                Event::EthApprovedRelayMessage(
-                    parse_h256(&message.id),
-                    parse_h160(&message.eth_address),
-                    parse_h256(&message.sub_address),
-                    parse_u256(&message.amount),
-                    parse_u128(&message.eth_block_number),
-                )
+                Event::EthApprovedRelayMessage {
+                    message_id: parse_h256(&message.id),
+                    sender: parse_h160(&message.eth_address),
+                    recipient: parse_h256(&message.sub_address),
+                    amount: parse_u256(&message.amount),
+                    block_number: parse_u128(&message.eth_block_number),
+                }
             }
             (messages_by_status::Status::CANCELED, messages_by_status::Direction::ETH2SUB) => {
-                Event::EthRevertMessage(
-                    parse_h256(&message.id),
-                    parse_h160(&message.eth_address),
-                    parse_u256(&message.amount),
-                    parse_u128(&message.eth_block_number),
-                )
+                Event::EthRevertMessage {
+                    message_id: parse_h256(&message.id),
+                    sender: parse_h160(&message.eth_address),
+                    amount: parse_u256(&message.amount),
+                    block_number: parse_u128(&message.eth_block_number),
+                }
             }
             (messages_by_status::Status::WITHDRAW, messages_by_status::Direction::SUB2ETH) => {
-                Event::EthWithdrawMessage(
-                    parse_h256(&message.id),
-                    parse_u128(&message.eth_block_number),
-                )
+                Event::EthWithdrawMessage {
+                    message_id: parse_h256(&message.id),
+                    block_number: parse_u128(&message.eth_block_number),
+                }
             }
 
-            (_, _) => Event::EthApprovedRelayMessage(
-                parse_h256(&message.id),
-                parse_h160(&message.eth_address),
-                parse_h256(&message.sub_address),
-                parse_u256(&message.amount),
-                parse_u128(&message.eth_block_number),
+            (_, _) => unknown(
+                "messages_by_status",
+                &message.id,
+                message,
+                &message.eth_block_number,
             ),
         }
     }
@@ -792,28 +1037,29 @@ impl From<&messages_by_status::MessagesByStatusMessages> for Event {
 impl From<&all_bridge_messages::AllBridgeMessagesBridgeMessages> for Event {
     fn from(message: &all_bridge_messages::AllBridgeMessagesBridgeMessages) -> Self {
         match &message.action {
-            all_bridge_messages::BridgeMessageAction::PAUSE => Event::EthBridgePausedMessage(
-                parse_h256(&message.id),
-                parse_u128(&message.eth_block_number),
-            ),
-            all_bridge_messages::BridgeMessageAction::RESUME => Event::EthBridgeResumedMessage(
-                parse_h256(&message.id),
-                parse_u128(&message.eth_block_number),
-            ),
-            all_bridge_messages::BridgeMessageAction::START => Event::EthBridgeStartedMessage(
-                parse_h256(&message.id),
-                parse_maybe_h160(&message.sender),
-                parse_u128(&message.eth_block_number),
-            ),
-            all_bridge_messages::BridgeMessageAction::STOP => Event::EthBridgeStoppedMessage(
-                parse_h256(&message.id),
-                parse_maybe_h160(&message.sender),
-                parse_u128(&message.eth_block_number),
-            ),
-            _ => Event::EthBridgeStoppedMessage(
-                parse_h256(&message.id),
-                parse_maybe_h160(&message.sender),
-                parse_u128(&message.eth_block_number),
+            all_bridge_messages::BridgeMessageAction::PAUSE => Event::EthBridgePausedMessage {
+                message_id: parse_h256(&message.id),
+                block_number: parse_u128(&message.eth_block_number),
+            },
+            all_bridge_messages::BridgeMessageAction::RESUME => Event::EthBridgeResumedMessage {
+                message_id: parse_h256(&message.id),
+                block_number: parse_u128(&message.eth_block_number),
+            },
+            all_bridge_messages::BridgeMessageAction::START => Event::EthBridgeStartedMessage {
+                message_id: parse_h256(&message.id),
+                sender: parse_maybe_h160(&message.sender),
+                block_number: parse_u128(&message.eth_block_number),
+            },
+            all_bridge_messages::BridgeMessageAction::STOP => Event::EthBridgeStoppedMessage {
+                message_id: parse_h256(&message.id),
+                sender: parse_maybe_h160(&message.sender),
+                block_number: parse_u128(&message.eth_block_number),
+            },
+            _ => unknown(
+                "all_bridge_messages",
+                &message.id,
+                message,
+                &message.eth_block_number,
             ),
         }
     }
@@ -825,45 +1071,81 @@ impl From<&all_account_messages::AllAccountMessagesAccountMessages> for Event {
             (
                 all_account_messages::AccountMessageAction::PAUSE,
                 all_account_messages::Direction::ETH2SUB,
-            ) => Event::EthHostAccountPausedMessage(
-                parse_h256(&message.id),
-                parse_maybe_h160(&message.eth_address),
-                parse_u64(&message.timestamp),
-                parse_u128(&message.eth_block_number),
-            ),
+            ) => match parse_maybe_h160(&message.eth_address) {
+                Some(address) => Event::EthHostAccountPausedMessage {
+                    message_id: parse_h256(&message.id),
+                    account: address,
+                    timestamp: parse_u64(&message.timestamp),
+                    block_number: parse_u128(&message.eth_block_number),
+                },
+                None => missing_address(
+                    "all_account_messages",
+                    "eth_address",
+                    &message.id,
+                    message,
+                    &message.eth_block_number,
+                ),
+            },
             (
                 all_account_messages::AccountMessageAction::RESUME,
                 all_account_messages::Direction::ETH2SUB,
-            ) => Event::EthHostAccountResumedMessage(
-                parse_h256(&message.id),
-                parse_maybe_h160(&message.eth_address),
-                parse_u64(&message.timestamp),
-                parse_u128(&message.eth_block_number),
-            ),
+            ) => match parse_maybe_h160(&message.eth_address) {
+                Some(address) => Event::EthHostAccountResumedMessage {
+                    message_id: parse_h256(&message.id),
+                    account: address,
+                    timestamp: parse_u64(&message.timestamp),
+                    block_number: parse_u128(&message.eth_block_number),
+                },
+                None => missing_address(
+                    "all_account_messages",
+                    "eth_address",
+                    &message.id,
+                    message,
+                    &message.eth_block_number,
+                ),
+            },
             (
                 all_account_messages::AccountMessageAction::PAUSE,
                 all_account_messages::Direction::SUB2ETH,
-            ) => Event::EthGuestAccountPausedMessage(
-                parse_h256(&message.id),
-                parse_maybe_h256(&message.sub_address),
-                parse_u64(&message.timestamp),
-                parse_u128(&message.eth_block_number),
-            ),
+            ) => match parse_maybe_h256(&message.sub_address) {
+                Some(address) => Event::EthGuestAccountPausedMessage {
+                    message_id: parse_h256(&message.id),
+                    account: address,
+                    timestamp: parse_u64(&message.timestamp),
+                    block_number: parse_u128(&message.eth_block_number),
+                },
+                None => missing_address(
+                    "all_account_messages",
+                    "sub_address",
+                    &message.id,
+                    message,
+                    &message.eth_block_number,
+                ),
+            },
             (
                 all_account_messages::AccountMessageAction::RESUME,
                 all_account_messages::Direction::SUB2ETH,
-            ) => Event::EthGuestAccountResumedMessage(
-                parse_h256(&message.id),
-                parse_maybe_h256(&message.sub_address),
-                parse_u64(&message.timestamp),
-                parse_u128(&message.eth_block_number),
-            ),
+            ) => match parse_maybe_h256(&message.sub_address) {
+                Some(address) => Event::EthGuestAccountResumedMessage {
+                    message_id: parse_h256(&message.id),
+                    account: address,
+                    timestamp: parse_u64(&message.timestamp),
+                    block_number: parse_u128(&message.eth_block_number),
+                },
+                None => missing_address(
+                    "all_account_messages",
+                    "sub_address",
+                    &message.id,
+                    message,
+                    &message.eth_block_number,
+                ),
+            },
 
-            (_, _) => Event::EthGuestAccountResumedMessage(
-                parse_h256(&message.id),
-                parse_maybe_h256(&message.sub_address),
-                parse_u64(&message.timestamp),
-                parse_u128(&message.eth_block_number),
+            (_, _) => unknown(
+                "all_account_messages",
+                &message.id,
+                message,
+                &message.eth_block_number,
             ),
         }
     }
@@ -872,24 +1154,24 @@ impl From<&all_account_messages::AllAccountMessagesAccountMessages> for Event {
 impl From<&all_accounts::AllAccountsAccounts> for Event {
     fn from(message: &all_accounts::AllAccountsAccounts) -> Self {
         match &message.kind {
-            all_accounts::AccountKind::ETH => Event::EthHostAccountPausedMessage(
-                parse_h256(&message.message_id),
-                parse_h160(&message.id),
-                parse_u64(&message.timestamp),
-                parse_u128(&message.eth_block_number),
-            ),
-            all_accounts::AccountKind::SUB => Event::EthGuestAccountPausedMessage(
-                parse_h256(&message.message_id),
-                parse_h256(&message.id),
-                parse_u64(&message.timestamp),
-                parse_u128(&message.eth_block_number),
-            ),
+            all_accounts::AccountKind::ETH => Event::EthHostAccountPausedMessage {
+                message_id: parse_h256(&message.message_id),
+                account: parse_h160(&message.id),
+                timestamp: parse_u64(&message.timestamp),
+                block_number: parse_u128(&message.eth_block_number),
+            },
+            all_accounts::AccountKind::SUB => Event::EthGuestAccountPausedMessage {
+                message_id: parse_h256(&message.message_id),
+                account: parse_h256(&message.id),
+                timestamp: parse_u64(&message.timestamp),
+                block_number: parse_u128(&message.eth_block_number),
+            },
 
-            _ => Event::EthGuestAccountPausedMessage(
-                parse_h256(&message.message_id),
-                parse_h256(&message.id),
-                parse_u64(&message.timestamp),
-                parse_u128(&message.eth_block_number),
+            _ => unknown(
+                "all_accounts",
+                &message.message_id,
+                message,
+                &message.eth_block_number,
             ),
         }
     }
@@ -897,20 +1179,36 @@ impl From<&all_accounts::AllAccountsAccounts> for Event {
 
 impl From<&all_limit_messages::AllLimitMessagesLimitMessages> for Event {
     fn from(message: &all_limit_messages::AllLimitMessagesLimitMessages) -> Self {
-        Event::EthSetNewLimits(
-            parse_h256(&message.id),
-            parse_u128(&message.min_host_transaction_value).into(),
-            parse_u128(&message.max_host_transaction_value).into(),
-            parse_u128(&message.day_host_max_limit).into(),
-            parse_u128(&message.day_host_max_limit_for_one_address).into(),
-            parse_u128(&message.max_host_pending_transaction_limit).into(),
-            parse_u128(&message.min_guest_transaction_value).into(),
-            parse_u128(&message.max_guest_transaction_value).into(),
-            parse_u128(&message.day_guest_max_limit).into(),
-            parse_u128(&message.day_guest_max_limit_for_one_address).into(),
-            parse_u128(&message.max_guest_pending_transaction_limit).into(),
-            parse_u128(&message.eth_block_number),
-        )
+        Event::EthSetNewLimits {
+            message_id: parse_h256(&message.id),
+            limits: EthLimits {
+                min_host_transaction_value: parse_u128(&message.min_host_transaction_value).into(),
+                max_host_transaction_value: parse_u128(&message.max_host_transaction_value).into(),
+                day_host_max_limit: parse_u128(&message.day_host_max_limit).into(),
+                day_host_max_limit_for_one_address: parse_u128(
+                    &message.day_host_max_limit_for_one_address,
+                )
+                .into(),
+                max_host_pending_transaction_limit: parse_u128(
+                    &message.max_host_pending_transaction_limit,
+                )
+                .into(),
+                guest: GuestLimits {
+                    min_transaction_value: parse_u128(&message.min_guest_transaction_value).into(),
+                    max_transaction_value: parse_u128(&message.max_guest_transaction_value).into(),
+                    day_max_limit: parse_u128(&message.day_guest_max_limit).into(),
+                    day_max_limit_for_one_address: parse_u128(
+                        &message.day_guest_max_limit_for_one_address,
+                    )
+                    .into(),
+                    max_pending_transaction_limit: parse_u128(
+                        &message.max_guest_pending_transaction_limit,
+                    )
+                    .into(),
+                },
+            },
+            block_number: parse_u128(&message.eth_block_number),
+        }
     }
 }
 
@@ -920,16 +1218,60 @@ impl From<&all_validators_list_messages::AllValidatorsListMessagesValidatorsList
     fn from(
         message: &all_validators_list_messages::AllValidatorsListMessagesValidatorsListMessages,
     ) -> Self {
-        Event::EthValidatorsListMessage(
-            parse_h256(&message.id),
-            message
-                .new_validators
-                .iter()
-                .map(|s| parse_h256(&s))
-                .collect(),
-            parse_u256(&message.new_how_many_validators_decide),
-            parse_u128(&message.eth_block_number),
-        )
+        Event::EthValidatorsListMessage {
+            message_id: parse_h256(&message.id),
+            validators: message
+            .new_validators
+            .iter()
+            .map(|s| parse_h256(&s))
+            .collect(),
+            quorum: parse_u256(&message.new_how_many_validators_decide),
+            block_number: parse_u128(&message.eth_block_number),
+        }
+    }
+}
+
+/// Keeps a message that matches no event as is instead of guessing one.
+fn unknown<T: std::fmt::Debug>(origin: &str, id: &str, message: &T, block_number: &str) -> Event {
+    Event::Unknown {
+        message_id: parse_h256(id),
+        origin: origin.to_string(),
+        payload: format!("{:?}", message),
+        block_number: parse_u128(block_number),
+    }
+}
+
+/// A message without the address its event needs. Account pauses never get a
+/// made up address, it would pause or resume whoever owns it.
+fn missing_address<T: std::fmt::Debug>(
+    origin: &str,
+    field: &str,
+    id: &str,
+    message: &T,
+    block_number: &str,
+) -> Event {
+    log::error!("message {} from {} has no {}, skipping it: {:?}", id, origin, field, message);
+    metrics::increment(
+        "validator_missing_address_total",
+        &[("origin", origin), ("field", field)],
+    );
+    unknown(origin, id, message, block_number)
+}
+
+fn parse_status(status: &str) -> messages_by_status::Status {
+    match status {
+        "PENDING" => messages_by_status::Status::PENDING,
+        "WITHDRAW" => messages_by_status::Status::WITHDRAW,
+        "APPROVED" => messages_by_status::Status::APPROVED,
+        "CANCELED" => messages_by_status::Status::CANCELED,
+        "CONFIRMED" => messages_by_status::Status::CONFIRMED,
+        _ => {
+            log::warn!(
+                "status {:?} is unknown to the local schema, passing it to the graph node as is",
+                status
+            );
+            messages_by_status::Status::Other(status.to_string())
+        }
     }
 }
 
@@ -960,25 +1302,71 @@ fn parse_u256(maybe_u256: &str) -> U256 {
     u256
 }
 
-fn parse_maybe_h160(maybe_hash: &Option<String>) -> H160 {
-    const DEFAULT_ETH_ADDRESS: [u8; 20] = [0; 20];
+fn parse_maybe_h160(maybe_hash: &Option<String>) -> Option<H160> {
+    maybe_hash.as_ref().map(|hash| parse_h160(hash))
+}
 
-    maybe_hash
-        .as_ref()
-        .map(|hash| parse_h160(hash))
-        .unwrap_or_else(|| H160::from_slice(&DEFAULT_ETH_ADDRESS))
+fn parse_maybe_h256(maybe_hash: &Option<String>) -> Option<H256> {
+    maybe_hash.as_ref().map(|hash| parse_h256(hash))
 }
 
-fn parse_maybe_h256(maybe_hash: &Option<String>) -> H256 {
-    const DEFAULT_SUB_ADDRESS: [u8; 32] = [0; 32];
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    maybe_hash
-        .as_ref()
-        .map(|hash| parse_h256(hash))
-        .unwrap_or_else(|| H256::from_slice(&DEFAULT_SUB_ADDRESS))
-}
+    #[test]
+    fn max_block_number_test() {
+        let block_numbers = vec!["7".to_string(), "12".to_string(), "9".to_string()];
+        assert_eq!(Some(12), max_block_number(block_numbers.iter()));
+        assert_eq!(None, max_block_number(vec![].iter()));
+    }
+
+    #[test]
+    fn join_query_test() {
+        let query = spawn_query("test", "http://localhost:8000", 7, |_, offset| {
+            let event = Event::eth_withdraw_message(H256::zero(), 8);
+            Ok((vec![(event, Entity::new("message", "0x00"))], Some(offset + 1)))
+        });
+        let mut events = vec![];
+        assert_eq!(Some(8), join_query(query, &mut events));
+        assert_eq!(1, events.len());
+    }
 
-pub fn begin_of_this_day() -> u64 {
-    const SECONDS_IN_DAY: u64 = 24 * 60 * 60;
-    time::now().to_timespec().sec as u64 / SECONDS_IN_DAY * SECONDS_IN_DAY
+    #[test]
+    fn missing_address_test() {
+        let message = all_account_messages::AllAccountMessagesAccountMessages {
+            id: format!("0x{}", "01".repeat(32)),
+            action: all_account_messages::AccountMessageAction::PAUSE,
+            direction: all_account_messages::Direction::ETH2SUB,
+            eth_address: None,
+            sub_address: None,
+            timestamp: "1600000000".to_string(),
+            eth_block_number: "7".to_string(),
+        };
+        match Event::from(&message) {
+            Event::Unknown {
+                origin,
+                block_number: 7,
+                ..
+            } => assert_eq!("all_account_messages", origin),
+            event => panic!("a pause without an address became {:?}", event),
+        }
+        let message = all_account_messages::AllAccountMessagesAccountMessages {
+            eth_address: Some(format!("0x{}", "ab".repeat(20))),
+            ..message
+        };
+        assert_eq!("EthHostAccountPausedMessage", Event::from(&message).name());
+
+        // the subgraph never sets the sender, a stop must still be relayed
+        let message = all_bridge_messages::AllBridgeMessagesBridgeMessages {
+            id: format!("0x{}", "02".repeat(32)),
+            action: all_bridge_messages::BridgeMessageAction::STOP,
+            sender: None,
+            eth_block_number: "8".to_string(),
+        };
+        match Event::from(&message) {
+            Event::EthBridgeStoppedMessage { sender: None, block_number: 8, .. } => (),
+            event => panic!("a stop without a sender became {:?}", event),
+        }
+    }
 }
@@ -0,0 +1,430 @@
+use log;
+use serde::Serialize;
+use tokio02::{runtime::Runtime, task};
+use tonic::{metadata::MetadataMap, transport::Server, Request, Response, Status};
+use web3::types::H256;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::thread;
+
+use crate::admin_api::{Role, Tokens};
+use crate::admin_operations;
+use crate::admin_service::{parse_message_id, AdminService, Error};
+use crate::approvals::Decision;
+#[cfg(feature = "sqlite")]
+use crate::database::MessageFilter;
+use crate::transfer_index::Filter;
+
+mod proto {
+    tonic::include_proto!("validator.admin");
+}
+
+use proto::admin_server::{Admin, AdminServer};
+use proto::{
+    Approval, Approvals, Empty, FeeReport, IndexedMessageFilter, Json, MessageFees, MessageId,
+    MessageIds, Operation, Transfer, TransferFilter, Transfers,
+};
+
+/// The admin API as the gRPC service `validator.admin.Admin` in
+/// `proto/admin.proto`, for tooling with generated clients. It runs on a
+/// runtime of its own, the rest of the validator is on futures 0.1.
+pub fn serve(
+    address: SocketAddr,
+    tokens: Tokens,
+    service: Arc<AdminService>,
+) -> Result<thread::JoinHandle<()>, String> {
+    let mut runtime =
+        Runtime::new().map_err(|e| format!("can not start the gRPC runtime: {}", e))?;
+    let admin = AdminServer::new(GrpcAdmin { tokens, service });
+    Ok(thread::Builder::new()
+        .name("grpc_api".to_string())
+        .spawn(move || {
+            let server = Server::builder().add_service(admin).serve(address);
+            if let Err(e) = runtime.block_on(server) {
+                log::error!("gRPC API on {} stopped: {}", address, e);
+            }
+        })
+        .expect("can not start grpc_api"))
+}
+
+struct GrpcAdmin {
+    tokens: Tokens,
+    service: Arc<AdminService>,
+}
+
+impl GrpcAdmin {
+    fn authorize(&self, metadata: &MetadataMap, required: Role) -> Result<(), Status> {
+        let role = metadata
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|provided| self.tokens.role_of(provided))
+            .ok_or_else(|| Status::unauthenticated("missing or invalid token"))?;
+        if role < required {
+            return Err(Status::permission_denied("needs the operator token"));
+        }
+        Ok(())
+    }
+
+    /// Checks the caller's role and the message id of the request.
+    fn message_id(&self, request: &Request<MessageId>, required: Role) -> Result<H256, Status> {
+        self.authorize(request.metadata(), required)?;
+        parse_message_id(&request.get_ref().message_id).map_err(status)
+    }
+
+    /// Runs a call that reads or writes files or the database on the
+    /// blocking pool, so it does not hold up the threads serving requests.
+    async fn blocking<T, F>(&self, call: F) -> Result<T, Status>
+    where
+        T: Send + 'static,
+        F: FnOnce(&AdminService) -> Result<T, Error> + Send + 'static,
+    {
+        let service = self.service.clone();
+        task::spawn_blocking(move || call(&service))
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .map_err(status)
+    }
+}
+
+#[tonic::async_trait]
+impl Admin for GrpcAdmin {
+    async fn list_approvals(&self, request: Request<Empty>) -> Result<Response<Approvals>, Status> {
+        self.authorize(request.metadata(), Role::Observer)?;
+        let approvals = self
+            .service
+            .approvals()
+            .into_iter()
+            .map(|pending| Approval {
+                message_id: format!("{:?}", pending.event.message_id()),
+                event: to_json(&pending.event),
+                flagged_at: pending.flagged_at,
+                decision: pending
+                    .decision
+                    .map(|decision| format!("{:?}", decision))
+                    .unwrap_or_default(),
+            })
+            .collect();
+        Ok(Response::new(Approvals { approvals }))
+    }
+
+    async fn decide(&self, request: Request<proto::Decision>) -> Result<Response<Empty>, Status> {
+        self.authorize(request.metadata(), Role::Operator)?;
+        let request = request.into_inner();
+        let decision = if request.approve {
+            Decision::Approved
+        } else {
+            Decision::Rejected
+        };
+        let message_id = parse_message_id(&request.message_id).map_err(status)?;
+        self.blocking(move |service| service.decide(&message_id, decision))
+            .await?;
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn get_fees(&self, request: Request<Empty>) -> Result<Response<FeeReport>, Status> {
+        self.authorize(request.metadata(), Role::Observer)?;
+        let report = self.blocking(|service| service.fees()).await?;
+        Ok(Response::new(FeeReport {
+            ethereum: report.ethereum.to_string(),
+            substrate: report.substrate.to_string(),
+            messages: report.messages.iter().map(message_fees).collect(),
+        }))
+    }
+
+    async fn get_message_fees(
+        &self,
+        request: Request<MessageId>,
+    ) -> Result<Response<MessageFees>, Status> {
+        let message_id = self.message_id(&request, Role::Observer)?;
+        let fees = self
+            .blocking(move |service| service.message_fees(&message_id))
+            .await?;
+        Ok(Response::new(message_fees(&fees)))
+    }
+
+    async fn get_message(&self, request: Request<MessageId>) -> Result<Response<Json>, Status> {
+        let message_id = self.message_id(&request, Role::Observer)?;
+        let message = self
+            .blocking(move |service| service.message(&message_id))
+            .await?;
+        Ok(Response::new(Json {
+            json: message.to_string(),
+        }))
+    }
+
+    async fn list_transfers(
+        &self,
+        request: Request<TransferFilter>,
+    ) -> Result<Response<Transfers>, Status> {
+        self.authorize(request.metadata(), Role::Observer)?;
+        let filter = request.into_inner();
+        let filter = Filter {
+            address: Some(filter.address).filter(|address| !address.is_empty()),
+            since: Some(filter.since).filter(|since| *since > 0),
+            until: Some(filter.until).filter(|until| *until > 0),
+            from_block: Some(filter.from_block as u128).filter(|block| *block > 0),
+            to_block: Some(filter.to_block as u128).filter(|block| *block > 0),
+        };
+        let transfers = self
+            .blocking(move |service| service.transfers(&filter))
+            .await?
+            .into_iter()
+            .map(|transfer| Transfer {
+                message_id: format!("{:?}", transfer.message_id),
+                sender: transfer.sender.unwrap_or_default(),
+                recipient: transfer.recipient.unwrap_or_default(),
+                amount: transfer
+                    .amount
+                    .map(|amount| amount.to_string())
+                    .unwrap_or_default(),
+                stage: transfer.stage,
+                block_number: transfer.block_number as u64,
+                first_seen: transfer.first_seen,
+                updated_at: transfer.updated_at,
+                outcome: transfer.outcome.as_ref().map(to_json).unwrap_or_default(),
+            })
+            .collect();
+        Ok(Response::new(Transfers { transfers }))
+    }
+
+    #[cfg(feature = "sqlite")]
+    async fn list_indexed_messages(
+        &self,
+        request: Request<IndexedMessageFilter>,
+    ) -> Result<Response<Json>, Status> {
+        self.authorize(request.metadata(), Role::Observer)?;
+        let filter = request.into_inner();
+        let filter = MessageFilter {
+            status: Some(filter.status.to_uppercase()).filter(|status| !status.is_empty()),
+            address: Some(filter.address).filter(|address| !address.is_empty()),
+            from_block: Some(filter.from_block).filter(|block| *block > 0),
+            to_block: Some(filter.to_block).filter(|block| *block > 0),
+        };
+        let messages = self
+            .blocking(move |service| service.indexed_messages(&filter))
+            .await?;
+        Ok(Response::new(Json {
+            json: to_json(&messages),
+        }))
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    async fn list_indexed_messages(
+        &self,
+        _request: Request<IndexedMessageFilter>,
+    ) -> Result<Response<Json>, Status> {
+        Err(Status::unimplemented(
+            "the validator was built without the sqlite feature",
+        ))
+    }
+
+    async fn get_state(&self, request: Request<Empty>) -> Result<Response<Json>, Status> {
+        self.authorize(request.metadata(), Role::Observer)?;
+        Ok(Response::new(Json {
+            json: self.service.state().to_string(),
+        }))
+    }
+
+    async fn start_trace(&self, request: Request<MessageId>) -> Result<Response<Empty>, Status> {
+        let message_id = self.message_id(&request, Role::Operator)?;
+        self.service.start_trace(message_id);
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn stop_trace(&self, request: Request<MessageId>) -> Result<Response<Empty>, Status> {
+        let message_id = self.message_id(&request, Role::Operator)?;
+        self.service.stop_trace(&message_id).map_err(status)?;
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn list_traced(&self, request: Request<Empty>) -> Result<Response<MessageIds>, Status> {
+        self.authorize(request.metadata(), Role::Observer)?;
+        let message_ids = self
+            .service
+            .traced()
+            .iter()
+            .map(|message_id| format!("{:?}", message_id))
+            .collect();
+        Ok(Response::new(MessageIds { message_ids }))
+    }
+
+    async fn execute(
+        &self,
+        request: Request<Operation>,
+    ) -> Result<Response<proto::Submitted>, Status> {
+        self.authorize(request.metadata(), Role::Operator)?;
+        let request = request.into_inner();
+        let operation = admin_operations::parse(&request.kind, &request.args)
+            .map_err(Status::invalid_argument)?;
+        let submitted = self
+            .blocking(move |service| service.execute(operation))
+            .await?;
+        Ok(Response::new(proto::Submitted {
+            tx_hash: format!("{:?}", submitted.tx_hash),
+            nonce: submitted.nonce.to_string(),
+        }))
+    }
+}
+
+fn message_fees(fees: &crate::fees::MessageFees) -> MessageFees {
+    MessageFees {
+        message_id: format!("{:?}", fees.message_id),
+        ethereum: fees.ethereum.to_string(),
+        substrate: fees.substrate.to_string(),
+        transactions: fees.transactions as u64,
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("response serializes")
+}
+
+fn status(error: Error) -> Status {
+    match error {
+        Error::Invalid(e) => Status::invalid_argument(e),
+        Error::NotFound(e) => Status::not_found(e),
+        Error::Internal(e) => Status::internal(e),
+        Error::Unavailable(e) => Status::unavailable(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::approvals::Approvals;
+    use crate::links::Links;
+    use crate::secret::Secret;
+    use crate::transfer_index::TransferIndex;
+
+    fn admin() -> GrpcAdmin {
+        GrpcAdmin {
+            tokens: Tokens {
+                operator: Secret::new("operator".to_string()),
+                observer: Some(Secret::new("observer".to_string())),
+            },
+            service: Arc::new(AdminService::new(
+                Arc::new(Approvals::in_memory()),
+                "data".to_string(),
+                Links::default(),
+                Arc::new(TransferIndex::default()),
+            )),
+        }
+    }
+
+    fn authorized<T>(token: &str, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        let authorization = format!("Bearer {}", token).parse().unwrap();
+        request
+            .metadata_mut()
+            .insert("authorization", authorization);
+        request
+    }
+
+    fn message_id(token: &str, message_id: &str) -> Request<MessageId> {
+        authorized(
+            token,
+            MessageId {
+                message_id: message_id.to_string(),
+            },
+        )
+    }
+
+    fn run<F: std::future::Future>(future: F) -> F::Output {
+        Runtime::new().unwrap().block_on(future)
+    }
+
+    #[test]
+    fn authorize_tests() {
+        let admin = admin();
+        let valid = format!("0x{}", "01".repeat(32));
+        assert_eq!(
+            H256::from_slice(&[1; 32]),
+            admin
+                .message_id(&message_id("operator", &valid), Role::Operator)
+                .unwrap()
+        );
+        let code = |request, role| admin.message_id(&request, role).unwrap_err().code();
+        assert_eq!(
+            tonic::Code::Unauthenticated,
+            code(message_id("wrong", &valid), Role::Observer)
+        );
+        assert_eq!(
+            tonic::Code::PermissionDenied,
+            code(message_id("observer", &valid), Role::Operator)
+        );
+        assert_eq!(
+            tonic::Code::InvalidArgument,
+            code(message_id("observer", "0x01"), Role::Observer)
+        );
+    }
+
+    #[test]
+    fn decide_tests() {
+        let admin = admin();
+        let decide = |token, message_id: &str| {
+            let decision = proto::Decision {
+                message_id: message_id.to_string(),
+                approve: true,
+            };
+            run(admin.decide(authorized(token, decision)))
+                .unwrap_err()
+                .code()
+        };
+        let valid = format!("0x{}", "0a".repeat(32));
+        // reaches the approvals, which have nothing flagged
+        assert_eq!(tonic::Code::NotFound, decide("operator", &valid));
+        assert_eq!(tonic::Code::PermissionDenied, decide("observer", &valid));
+        assert_eq!(tonic::Code::InvalidArgument, decide("operator", "0x0a"));
+    }
+
+    #[test]
+    fn trace_tests() {
+        let admin = admin();
+        let traced = format!("0x{}", "0b".repeat(32));
+        let listed = || {
+            run(admin.list_traced(authorized("observer", Empty {})))
+                .unwrap()
+                .into_inner()
+                .message_ids
+        };
+        assert_eq!(
+            tonic::Code::PermissionDenied,
+            run(admin.start_trace(message_id("observer", &traced)))
+                .unwrap_err()
+                .code()
+        );
+        run(admin.start_trace(message_id("operator", &traced))).unwrap();
+        assert!(listed().contains(&traced));
+        run(admin.stop_trace(message_id("operator", &traced))).unwrap();
+        assert!(!listed().contains(&traced));
+        assert_eq!(
+            tonic::Code::NotFound,
+            run(admin.stop_trace(message_id("operator", &traced)))
+                .unwrap_err()
+                .code()
+        );
+    }
+    #[test]
+    fn execute_tests() {
+        let admin = admin();
+        let execute = |token, kind: &str| {
+            let operation = Operation {
+                kind: kind.to_string(),
+                args: vec![],
+            };
+            run(admin.execute(authorized(token, operation)))
+                .unwrap_err()
+                .code()
+        };
+        assert_eq!(
+            tonic::Code::PermissionDenied,
+            execute("observer", "bridge-call")
+        );
+        assert_eq!(
+            tonic::Code::InvalidArgument,
+            execute("operator", "bridge-call")
+        );
+        assert_eq!(tonic::Code::InvalidArgument, execute("operator", "mint"));
+    }
+}
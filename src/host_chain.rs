@@ -0,0 +1,114 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Specifics of the EVM chain the token bridge is deployed on. The presets
+/// only provide defaults: `ETH_CHAIN_ID`, `ETH_GAS_PRICE` and
+/// `ETH_CONFIRMATIONS` still override them. Every preset uses legacy gas
+/// pricing, a fixed `gasPrice` per transaction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HostChain {
+    pub name: &'static str,
+    pub chain_id: u64,
+    /// In wei.
+    pub gas_price: u64,
+    /// Blocks on top of a transaction before reorgs are no longer expected.
+    pub confirmations: u64,
+    pub block_time: Duration,
+}
+
+const GWEI: u64 = 1_000_000_000;
+
+pub const PRESETS: [HostChain; 7] = [
+    HostChain {
+        name: "ethereum",
+        chain_id: 1,
+        gas_price: 24 * GWEI,
+        confirmations: 12,
+        block_time: Duration::from_secs(13),
+    },
+    HostChain {
+        name: "rinkeby",
+        chain_id: 4,
+        gas_price: 24 * GWEI,
+        confirmations: 12,
+        block_time: Duration::from_secs(15),
+    },
+    HostChain {
+        name: "kovan",
+        chain_id: 42,
+        gas_price: 24 * GWEI,
+        confirmations: 12,
+        block_time: Duration::from_secs(4),
+    },
+    HostChain {
+        name: "bsc",
+        chain_id: 56,
+        gas_price: 5 * GWEI,
+        confirmations: 15,
+        block_time: Duration::from_secs(3),
+    },
+    HostChain {
+        name: "bsc-testnet",
+        chain_id: 97,
+        gas_price: 10 * GWEI,
+        confirmations: 15,
+        block_time: Duration::from_secs(3),
+    },
+    // Polygon reorgs run deeper than Ethereum's, hence the many confirmations
+    HostChain {
+        name: "polygon",
+        chain_id: 137,
+        gas_price: 30 * GWEI,
+        confirmations: 128,
+        block_time: Duration::from_secs(2),
+    },
+    HostChain {
+        name: "polygon-mumbai",
+        chain_id: 80001,
+        gas_price: 30 * GWEI,
+        confirmations: 128,
+        block_time: Duration::from_secs(2),
+    },
+];
+
+/// Rinkeby, the chain the validator used before presets existed.
+impl Default for HostChain {
+    fn default() -> Self {
+        PRESETS[1]
+    }
+}
+
+impl FromStr for HostChain {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let lowercase = name.trim().to_lowercase();
+        PRESETS
+            .iter()
+            .find(|preset| preset.name == lowercase)
+            .copied()
+            .ok_or_else(|| {
+                let names = PRESETS.iter().map(|preset| preset.name).collect::<Vec<_>>();
+                format!("unknown host chain {:?}, expected one of {:?}", name, names)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_str_tests() {
+        assert_eq!(
+            Ok(56),
+            "bsc".parse::<HostChain>().map(|chain| chain.chain_id)
+        );
+        assert_eq!(
+            Ok(137),
+            "Polygon".parse::<HostChain>().map(|chain| chain.chain_id)
+        );
+        assert!("solana".parse::<HostChain>().is_err());
+        assert_eq!(4, HostChain::default().chain_id);
+    }
+}
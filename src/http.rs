@@ -0,0 +1,255 @@
+use log;
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// How long a client may take to send its request or read the response.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The request line and headers together, a token and a query fit easily.
+const MAX_HEADER_BYTES: u64 = 16 * 1024;
+
+/// The endpoints read no body, requests announcing a larger one are refused.
+const MAX_BODY_BYTES: u64 = 64 * 1024;
+
+/// Connections handled at once per server, more are refused until one is done.
+const MAX_CONNECTIONS: usize = 16;
+
+/// The parts of an HTTP request the validator's endpoints look at.
+#[derive(Debug, Default, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Parameters of the query string, not percent-decoded.
+    pub query: HashMap<String, String>,
+    /// Header names are lowercase.
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: &'static str,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    pub fn text(body: String) -> Self {
+        Response {
+            status: "200 OK",
+            content_type: "text/plain; version=0.0.4",
+            body,
+        }
+    }
+
+    pub fn json(body: String) -> Self {
+        Response {
+            status: "200 OK",
+            content_type: "application/json",
+            body,
+        }
+    }
+
+    pub fn error(status: &'static str, message: &str) -> Self {
+        Response {
+            status,
+            content_type: "text/plain",
+            body: format!("{}\n", message),
+        }
+    }
+}
+
+/// Serves requests on `address`, each connection on a thread of its own so a
+/// slow client does not hold up the others, up to `MAX_CONNECTIONS` at once.
+pub fn serve<F>(
+    name: &str,
+    address: SocketAddr,
+    handler: F,
+) -> Result<thread::JoinHandle<()>, String>
+where
+    F: Fn(&Request) -> Response + Send + Sync + 'static,
+{
+    let listener =
+        TcpListener::bind(address).map_err(|e| format!("can not listen on {}: {}", address, e))?;
+    let name = name.to_string();
+    let handler = Arc::new(handler);
+    let connections = Arc::new(AtomicUsize::new(0));
+    Ok(thread::Builder::new()
+        .name(name.clone())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream.and_then(|stream| {
+                    stream.set_read_timeout(Some(TIMEOUT))?;
+                    stream.set_write_timeout(Some(TIMEOUT))?;
+                    Ok(stream)
+                }) {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        log::warn!("can not accept {} connection: {}", name, e);
+                        continue;
+                    }
+                };
+                if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                    connections.fetch_sub(1, Ordering::SeqCst);
+                    log::warn!("{} is busy, refusing a connection", name);
+                    let busy = Response::error("503 Service Unavailable", "too many connections");
+                    let _ = write_response(stream, &busy);
+                    continue;
+                }
+                let (name, handler, connections) =
+                    (name.clone(), handler.clone(), connections.clone());
+                let spawned = thread::Builder::new()
+                    .name(format!("{}_connection", name))
+                    .spawn(move || {
+                        if let Err(e) = respond(stream, &*handler) {
+                            log::debug!("{} request failed: {}", name, e);
+                        }
+                        connections.fetch_sub(1, Ordering::SeqCst);
+                    });
+                if let Err(e) = spawned {
+                    log::warn!("can not handle {} connection: {}", address, e);
+                }
+            }
+        })
+        .expect("can not started http server"))
+}
+
+fn respond<F>(stream: TcpStream, handler: &F) -> io::Result<()>
+where
+    F: Fn(&Request) -> Response,
+{
+    let response = match read_request(BufReader::new(&stream)) {
+        Ok(request) => handler(&request),
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            Response::error("413 Payload Too Large", &e.to_string())
+        }
+        Err(e) => return Err(e),
+    };
+    write_response(stream, &response)
+}
+
+fn write_response(mut stream: TcpStream, response: &Response) -> io::Result<()> {
+    write!(
+        stream,
+        concat!(
+            "HTTP/1.1 {}\r\n",
+            "Content-Type: {}\r\n",
+            "Content-Length: {}\r\n",
+            "Connection: close\r\n\r\n{}"
+        ),
+        response.status,
+        response.content_type,
+        response.body.len(),
+        response.body
+    )
+}
+
+/// Reads the request line and headers, at most `MAX_HEADER_BYTES`. A body is
+/// left unread. Requests over the limits fail as `InvalidData`.
+fn read_request<R: BufRead>(reader: R) -> io::Result<Request> {
+    let mut reader = reader.take(MAX_HEADER_BYTES);
+    let mut line = String::new();
+    read_line(&mut reader, &mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let mut target = parts.next().unwrap_or_default().splitn(2, '?');
+    let mut request = Request {
+        method,
+        path: target.next().unwrap_or_default().to_string(),
+        query: target
+            .next()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|parameter| !parameter.is_empty())
+            .map(|parameter| {
+                let mut parameter = parameter.splitn(2, '=');
+                let name = parameter.next().unwrap_or_default().to_string();
+                (name, parameter.next().unwrap_or_default().to_string())
+            })
+            .collect(),
+        headers: HashMap::new(),
+    };
+    loop {
+        line.clear();
+        if read_line(&mut reader, &mut line)? == 0 || line.trim().is_empty() {
+            let length = request.headers.get("content-length");
+            return match length.and_then(|length| length.parse::<u64>().ok()) {
+                Some(length) if length > MAX_BODY_BYTES => Err(too_large("body", MAX_BODY_BYTES)),
+                _ => Ok(request),
+            };
+        }
+        if let Some(colon) = line.find(':') {
+            request.headers.insert(
+                line[..colon].trim().to_lowercase(),
+                line[colon + 1..].trim().to_string(),
+            );
+        }
+    }
+}
+
+/// Reads one line, failing once the headers reach `MAX_HEADER_BYTES`.
+fn read_line<R: BufRead>(reader: &mut io::Take<R>, line: &mut String) -> io::Result<usize> {
+    let read = reader.read_line(line)?;
+    if reader.limit() == 0 {
+        return Err(too_large("headers", MAX_HEADER_BYTES));
+    }
+    Ok(read)
+}
+
+fn too_large(what: &str, limit: u64) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{} over {} bytes", what, limit),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_request_test() {
+        let raw = "POST /approvals/0x01/approve HTTP/1.1\r\nAuthorization: Bearer abc\r\n\r\n";
+        let request = read_request(raw.as_bytes()).unwrap();
+        assert_eq!("POST", request.method);
+        assert_eq!("/approvals/0x01/approve", request.path);
+        assert!(request.query.is_empty());
+        assert_eq!(
+            Some(&"Bearer abc".to_string()),
+            request.headers.get("authorization")
+        );
+
+        let raw = "GET /transfers?address=0x01&since=10&flag HTTP/1.1\r\n\r\n";
+        let request = read_request(raw.as_bytes()).unwrap();
+        assert_eq!("/transfers", request.path);
+        assert_eq!(Some(&"0x01".to_string()), request.query.get("address"));
+        assert_eq!(Some(&"10".to_string()), request.query.get("since"));
+        assert_eq!(Some(&String::new()), request.query.get("flag"));
+    }
+
+    #[test]
+    fn read_request_limits_test() {
+        let too_large = |raw: String| read_request(raw.as_bytes()).unwrap_err().kind();
+        let header = format!("X-Padding: {}\r\n", "a".repeat(MAX_HEADER_BYTES as usize));
+        assert_eq!(
+            io::ErrorKind::InvalidData,
+            too_large(format!("GET / HTTP/1.1\r\n{}\r\n", header))
+        );
+        assert_eq!(
+            io::ErrorKind::InvalidData,
+            too_large(format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(20_000)))
+        );
+        assert_eq!(
+            io::ErrorKind::InvalidData,
+            too_large("POST / HTTP/1.1\r\nContent-Length: 100000\r\n\r\n".to_string())
+        );
+        // a small body is left unread
+        let raw = "POST / HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}";
+        assert_eq!("POST", read_request(raw.as_bytes()).unwrap().method);
+    }
+}
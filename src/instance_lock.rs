@@ -0,0 +1,123 @@
+use log;
+use web3::{futures::Future, transports::WebSocket, types::BlockNumber};
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::process;
+use std::{thread, time::Duration};
+
+use crate::config::Config;
+
+const LOCK_FILE: &str = "validator.lock";
+
+/// An exclusive `flock` on the lock file in the data dir. The kernel
+/// releases it when the file is closed, also when the process dies, so keep
+/// it as long as the data dir is used.
+#[must_use]
+#[derive(Debug)]
+pub struct Lock(File);
+
+/// Refuses to start a second validator with the same keys, which would send
+/// transactions with the same nonces. A lock on the data dir catches a
+/// second process on the same host, watching the account nonce catches one
+/// running elsewhere. `FORCE_START` skips both checks, a follower has no
+/// account to watch.
+pub fn acquire(config: &Config) -> Result<Option<Lock>, String> {
+    if config.force_start {
+        log::warn!("FORCE_START is set, not checking for other validator instances");
+        return Ok(None);
+    }
+    let lock = lock_data_dir(&config.data_dir)?;
+    if config.instance_check_secs > 0 && !config.follower {
+        check_nonce_is_idle(config)?;
+    }
+    Ok(Some(lock))
+}
+
+/// Also taken by commands that must not run next to a validator. The file
+/// holds the pid of the holder, for the error message only.
+pub fn lock_data_dir(data_dir: &str) -> Result<Lock, String> {
+    let path = Path::new(data_dir).join(LOCK_FILE);
+    let error = |e: io::Error| format!("can not lock {:?}: {}", path, e);
+    fs::create_dir_all(data_dir).map_err(error)?;
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(&path)
+        .map_err(error)?;
+    // the lock belongs to the open file, opening it again in this process
+    // does not get it either
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let e = io::Error::last_os_error();
+        if e.kind() != io::ErrorKind::WouldBlock {
+            return Err(error(e));
+        }
+        let mut pid = String::new();
+        let _ = file.read_to_string(&mut pid);
+        return Err(format!(
+            "another validator (pid {}) uses {}, stop it first",
+            pid.trim(),
+            data_dir
+        ));
+    }
+    file.set_len(0)
+        .and_then(|()| file.write_all(process::id().to_string().as_bytes()))
+        .map_err(error)?;
+    Ok(Lock(file))
+}
+
+/// This validator has sent nothing yet, so a nonce that moves while it waits
+/// was used by someone else.
+fn check_nonce_is_idle(config: &Config) -> Result<(), String> {
+    let (_eloop, transport) = WebSocket::new(&config.eth_api_url)
+        .map_err(|e| format!("can not connect to ethereum node: {:?}", e))?;
+    let web3 = web3::Web3::new(transport);
+    let nonce = || {
+        web3.eth()
+            .transaction_count(config.eth_validator_address, Some(BlockNumber::Pending))
+            .wait()
+            .map_err(|e| format!("can not get validator nonce: {:?}", e))
+    };
+    log::info!(
+        "watching the validator nonce for {}s to detect other instances",
+        config.instance_check_secs
+    );
+    let before = nonce()?;
+    thread::sleep(Duration::from_secs(config.instance_check_secs));
+    let after = nonce()?;
+    if after != before {
+        return Err(format!(
+            "the nonce of {:?} moved from {} to {} during startup, another validator \
+             seems to use the same key; set FORCE_START=true to start anyway",
+            config.eth_validator_address, before, after
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn lock_data_dir_test() {
+        let dir = TestDir::new("instance_lock");
+        let data_dir = dir.path();
+        // a file left by a validator that died
+        fs::write(dir.join(LOCK_FILE), "1").unwrap();
+        let lock = lock_data_dir(data_dir).unwrap();
+        assert_eq!(
+            process::id().to_string(),
+            fs::read_to_string(dir.join(LOCK_FILE)).unwrap()
+        );
+        // e.g. `prune` next to a running validator
+        let e = lock_data_dir(data_dir).unwrap_err();
+        assert!(e.contains(&format!("pid {}", process::id())), "{}", e);
+        drop(lock);
+        assert!(lock_data_dir(data_dir).is_ok());
+    }
+}
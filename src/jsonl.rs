@@ -0,0 +1,147 @@
+use log;
+use serde::{de::DeserializeOwned, Serialize};
+
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::retention::AppendLog;
+
+/// Append-only `<data_dir>/<file_name>` with one JSON entry per line, as the
+/// validator keeps its records. A file that can not be opened or written is
+/// logged and otherwise ignored, records never stop the validator.
+#[derive(Debug)]
+pub struct JsonLog {
+    path: PathBuf,
+    file: Option<AppendLog>,
+}
+
+impl JsonLog {
+    /// `lost` tells operators what is missing while the file can not be
+    /// opened, e.g. "fees are not recorded".
+    pub fn open(data_dir: &str, file_name: &str, lost: &str) -> Self {
+        let path = Path::new(data_dir).join(file_name);
+        let file = AppendLog::open(path.clone())
+            .map_err(|e| log::error!("can not open {:?}, {}: {}", path, lost, e))
+            .ok();
+        JsonLog { path, file }
+    }
+
+    pub fn append<T: Serialize + Debug>(&self, entry: &T) {
+        if let Some(file) = &self.file {
+            let line = serde_json::to_string(entry).expect("entry serializes");
+            if let Err(e) = file.append(&line) {
+                log::error!("can not record {:?} in {:?}: {}", entry, self.path, e);
+            }
+        }
+    }
+}
+
+/// A `JsonLog` that records each key once, also across restarts.
+#[derive(Debug)]
+pub struct OnceLog<K> {
+    log: JsonLog,
+    recorded: Mutex<HashSet<K>>,
+}
+
+impl<K: Eq + Hash> OnceLog<K> {
+    /// Reads the keys recorded before with `key`.
+    pub fn open<T: DeserializeOwned>(
+        data_dir: &str,
+        file_name: &str,
+        lost: &str,
+        key: impl Fn(T) -> K,
+    ) -> Self {
+        let recorded = read(data_dir, file_name)
+            .unwrap_or_else(|e| {
+                log::error!("{}, {}", e, lost);
+                vec![]
+            })
+            .into_iter()
+            .map(key)
+            .collect();
+        OnceLog {
+            log: JsonLog::open(data_dir, file_name, lost),
+            recorded: Mutex::new(recorded),
+        }
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.recorded
+            .lock()
+            .expect("record lock poisoned")
+            .contains(key)
+    }
+
+    /// Appends the entry unless `key` was recorded before. Returns false
+    /// when it was.
+    pub fn record<T: Serialize + Debug>(&self, key: K, entry: impl FnOnce() -> T) -> bool {
+        let mut recorded = self.recorded.lock().expect("record lock poisoned");
+        if !recorded.insert(key) {
+            return false;
+        }
+        self.log.append(&entry());
+        true
+    }
+}
+
+/// Every entry of `<data_dir>/<file_name>`, oldest first, none if there is
+/// no file.
+pub fn read<T: DeserializeOwned>(data_dir: &str, file_name: &str) -> Result<Vec<T>, String> {
+    let path = Path::new(data_dir).join(file_name);
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(_) => return Ok(vec![]),
+    };
+    let mut entries = vec![];
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| format!("can not read {:?}: {}", path, e))?;
+        match serde_json::from_str::<T>(&line) {
+            Ok(entry) => entries.push(entry),
+            // a line cut short by a crash
+            Err(e) => log::warn!("skipping invalid line in {:?}: {}", path, e),
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+    use serde::Deserialize;
+    use std::fs;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Entry {
+        id: u8,
+    }
+
+    #[test]
+    fn once_log_test() {
+        let dir = TestDir::new("jsonl");
+        let log = OnceLog::open(dir.path(), "entries.jsonl", "", |entry: Entry| entry.id);
+        assert!(log.record(1, || Entry { id: 1 }));
+        assert!(!log.record(1, || Entry { id: 1 }));
+        assert!(log.contains(&1));
+        fs::write(
+            dir.join("entries.jsonl"),
+            "{\"id\": 1}\n{\"id\": 2}\n{\"id\": ",
+        )
+        .unwrap();
+
+        // a restart remembers what was recorded, a cut off line is skipped
+        let log = OnceLog::open(dir.path(), "entries.jsonl", "", |entry: Entry| entry.id);
+        assert!(log.contains(&2));
+        assert!(!log.record(2, || Entry { id: 2 }));
+        assert_eq!(
+            Ok(vec![Entry { id: 1 }, Entry { id: 2 }]),
+            read(dir.path(), "entries.jsonl")
+        );
+        assert_eq!(Ok(vec![]), read::<Entry>(dir.path(), "missing.jsonl"));
+    }
+}
@@ -0,0 +1,76 @@
+use url::Url;
+use web3::types::H256;
+
+use crate::config::Config;
+use crate::controller::Entity;
+use crate::submissions::Chain;
+
+/// Where operators can look at a transaction or a subgraph entity, for the
+/// admin API to hand out with what it reports.
+#[derive(Debug, Clone, Default)]
+pub struct Links {
+    /// Etherscan style, transactions are at `<url>/tx/<hash>`.
+    pub eth_explorer_url: Option<String>,
+    /// Subscan style, extrinsics are at `<url>/extrinsic/<hash>`.
+    pub sub_explorer_url: Option<String>,
+    pub graph_node_api_url: Option<String>,
+}
+
+impl Links {
+    pub fn new(config: &Config) -> Self {
+        Links {
+            eth_explorer_url: config.eth_explorer_url.clone(),
+            sub_explorer_url: config.sub_explorer_url.clone(),
+            graph_node_api_url: Some(config.graph_node_api_url.clone())
+                .filter(|url| !url.is_empty()),
+        }
+    }
+
+    pub fn tx(&self, chain: Chain, tx_hash: &H256) -> Option<String> {
+        match chain {
+            Chain::Ethereum => self
+                .eth_explorer_url
+                .as_ref()
+                .map(|url| format!("{}/tx/{:?}", url, tx_hash)),
+            Chain::Substrate => self
+                .sub_explorer_url
+                .as_ref()
+                .map(|url| format!("{}/extrinsic/{:?}", url, tx_hash)),
+        }
+    }
+
+    /// The graph node's GraphiQL page with a query for the entity.
+    pub fn entity(&self, entity: &Entity) -> Option<String> {
+        let url = self.graph_node_api_url.as_ref()?;
+        let query = format!(
+            "{{ {}(id: \"{}\") {{ __typename id ethBlockNumber }} }}",
+            entity.kind, entity.id
+        );
+        Url::parse_with_params(url, &[("query", query)])
+            .ok()
+            .map(Url::into_string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_tests() {
+        let links = Links {
+            eth_explorer_url: Some("https://etherscan.io".to_string()),
+            sub_explorer_url: None,
+            graph_node_api_url: Some("http://localhost:8000/subgraphs/name/bridge".to_string()),
+        };
+        let tx_hash = H256::from_slice(&[1; 32]);
+        assert_eq!(
+            Some(format!("https://etherscan.io/tx/0x{}", "01".repeat(32))),
+            links.tx(Chain::Ethereum, &tx_hash)
+        );
+        assert_eq!(None, links.tx(Chain::Substrate, &tx_hash));
+        let link = links.entity(&Entity::new("account", "0xab")).unwrap();
+        assert!(link.starts_with("http://localhost:8000/subgraphs/name/bridge?query="));
+        assert!(link.contains("account%28id%3A+%220xab%22%29"));
+    }
+}
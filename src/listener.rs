@@ -0,0 +1,125 @@
+use log;
+use web3::{futures::Future, transports::WebSocket};
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::bus::Bus;
+use crate::config::Config;
+use crate::controller::{Entity, Envelope, Event, Source};
+use crate::dead_letters::DeadLetters;
+use crate::metrics;
+
+/// An event and the entity it was converted from, a subgraph entity or a log
+/// of the token bridge.
+pub type Converted = (Event, Entity);
+
+/// When the graph node listener last found the subgraph healthy, in seconds
+/// since the epoch, 0 before it did.
+static GRAPH_NODE_HEALTHY_AT: AtomicU64 = AtomicU64::new(0);
+
+/// Called by the graph node listener after a poll that reached the subgraph
+/// within `GRAPH_NODE_MAX_LAG_BLOCKS` of the ethereum head.
+#[cfg(feature = "graph-node")]
+pub fn report_graph_node_healthy() {
+    GRAPH_NODE_HEALTHY_AT.store(now(), Ordering::Relaxed);
+}
+
+/// How long the graph node listener has not found the subgraph healthy,
+/// counted from `since` at the latest. A listener that stalls stops
+/// reporting as well, until the watchdog restarted it.
+pub fn graph_node_unhealthy_secs(since: u64) -> u64 {
+    let healthy_at = GRAPH_NODE_HEALTHY_AT.load(Ordering::Relaxed);
+    now().saturating_sub(healthy_at.max(since))
+}
+
+/// Seconds since the epoch.
+pub fn now() -> u64 {
+    time::now_utc().to_timespec().sec as u64
+}
+
+/// The first block whose unfinalized messages the graph node listener and
+/// the embedded indexer replay after a restart: `UNFINALIZED_MIN_BLOCK`, or
+/// `UNFINALIZED_MAX_AGE_BLOCKS` behind the ethereum head when that is later.
+pub fn unfinalized_from(web3: &web3::Web3<WebSocket>, config: &Config) -> u64 {
+    let head_block_number = web3
+        .eth()
+        .block_number()
+        .wait()
+        .map(|block_number| block_number.low_u64())
+        .map_err(|err| log::warn!("can not get ethereum block number, reason: {:?}", err))
+        .ok();
+    replay_from(
+        config.unfinalized_min_block,
+        config.unfinalized_max_age_blocks,
+        head_block_number,
+    )
+}
+
+/// The age limit is ignored while the ethereum head is unknown.
+fn replay_from(min_block: u64, max_age_blocks: u64, head_block_number: Option<u64>) -> u64 {
+    match head_block_number {
+        Some(head) if max_age_blocks > 0 => min_block.max(head.saturating_sub(max_age_blocks)),
+        _ => min_block,
+    }
+}
+
+/// Publishes the events of a listener. In strict mode messages that match no
+/// event stop here, they are kept in the dead letters for operators instead
+/// of reaching the controller.
+pub fn send_events(
+    config: &Config,
+    bus: &Bus,
+    dead_letters: &DeadLetters,
+    source: Source,
+    events: Vec<Converted>,
+) {
+    events.into_iter().for_each(|(event, entity)| {
+        if let Event::Unknown {
+            message_id,
+            origin,
+            payload,
+            block_number,
+        } = &event
+        {
+            if config.strict_mode {
+                if dead_letters.record(*message_id, origin, payload, *block_number) {
+                    log::error!(
+                        "strict mode, refusing message {:?} from {} that matches no known \
+                         event, moved to the dead letters: {}",
+                        message_id,
+                        origin,
+                        payload
+                    );
+                    metrics::increment("validator_dead_letters_total", &[("origin", origin)]);
+                }
+                return;
+            }
+        }
+        bus.publish(Envelope::new(event, source).with_entity(entity))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn graph_node_unhealthy_secs_test() {
+        // counted from when the indexer started to stand by at the latest
+        assert_eq!(0, graph_node_unhealthy_secs(now() + 60));
+        #[cfg(feature = "graph-node")]
+        {
+            report_graph_node_healthy();
+            assert!(graph_node_unhealthy_secs(0) <= 1);
+        }
+    }
+
+    #[test]
+    fn replay_from_tests() {
+        assert_eq!(0, replay_from(0, 0, Some(1_000)));
+        assert_eq!(500, replay_from(500, 0, Some(1_000)));
+        assert_eq!(900, replay_from(500, 100, Some(1_000)));
+        assert_eq!(500, replay_from(500, 100, None));
+        assert_eq!(0, replay_from(0, 5_000, Some(1_000)));
+    }
+}
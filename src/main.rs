@@ -1,42 +1,495 @@
-use dotenv::dotenv;
-use env_logger;
-//use log;
-use std::sync::mpsc::channel;
+use log;
+use primitives::crypto::Ss58Codec;
+use web3::futures::Future;
+use std::{
+    sync::Arc,
+    thread,
+    time::Duration,
+};
 
+#[cfg(feature = "admin-api")]
+mod admin_api;
+#[cfg(feature = "admin-api")]
+mod admin_operations;
+#[cfg(feature = "admin-api")]
+mod admin_service;
+mod approvals;
+mod attestations;
+mod auth_guard;
+mod bridge_contract;
+mod bus;
+#[cfg(feature = "graph-node")]
+mod canary;
+mod cli;
+mod clock;
 mod config;
+mod confirmed_transfers;
 mod controller;
 mod controller_storage;
+mod coordination;
+#[cfg(feature = "sqlite")]
+mod database;
+mod dead_letters;
+mod debug_state;
+#[cfg(feature = "graph-node")]
+mod divergence;
+mod drain;
+mod eth_indexer;
+mod eth_sync;
 mod ethereum_transactions;
 mod executor;
+mod fatal;
+mod fees;
+mod gas_runway;
+#[cfg(feature = "grpc")]
+mod grpc_api;
+#[cfg(feature = "graph-node")]
 mod graph_node_event_listener;
+mod host_chain;
+#[cfg(any(feature = "admin-api", feature = "metrics-exporter"))]
+mod http;
+mod instance_lock;
+mod jsonl;
+#[cfg(feature = "admin-api")]
+mod links;
+mod listener;
+mod message_trace;
+mod metrics;
+mod net;
+mod orphans;
+#[cfg(feature = "otlp")]
+mod otlp;
+mod policy;
+mod rate_limit;
+mod receipt_watcher;
+mod retention;
+mod scheduler;
+mod secret;
+#[cfg(feature = "secret-backends")]
+mod secret_backend;
+#[cfg(feature = "graph-node")]
+mod snapshot;
+mod spill_queue;
+mod startup;
 mod substrate_event_listener;
+mod submissions;
+mod substrate_sync;
 mod substrate_transactions;
+#[cfg(test)]
+mod test_dir;
+#[cfg(feature = "admin-api")]
+mod transfer_index;
+mod urgency;
+mod validator_set;
+mod versioned;
+#[cfg(feature = "graph-node")]
+mod watchdog;
 
 fn main() {
-    env_logger::init();
-    dotenv().ok();
-    
-    let config = config::Config::load().expect("can not load config");
-    
-    let (controller_tx, controller_rx) = channel();
-    let (executor_tx, executor_rx) = channel();
+    message_trace::init();
+    fatal::install_panic_hook();
+    let args = cli::Args::from_env().unwrap_or_else(|e| {
+        log::error!("{}", e);
+        std::process::exit(1)
+    });
+    if let cli::Command::VerifyAttestations(path) = &args.command {
+        match attestations::verify_file(path) {
+            Ok((checked, invalid)) => {
+                invalid.iter().for_each(|line| println!("invalid: {}", line));
+                println!("{} attestations, {} invalid", checked, invalid.len());
+                if !invalid.is_empty() {
+                    std::process::exit(1)
+                }
+            }
+            Err(e) => {
+                log::error!("{}", e);
+                std::process::exit(1)
+            }
+        }
+        return;
+    }
+    config::load_env_files(args.profile.as_ref().map(String::as_str)).unwrap_or_else(|e| {
+        log::error!("{}", e);
+        std::process::exit(1)
+    });
+    if let Some(profile) = &args.profile {
+        log::info!("using profile {:?}", profile);
+    }
 
-    let controller_thread = controller::spawn(config.clone(), controller_rx, executor_tx);
-    let executor_thread = executor::spawn(config.clone(), executor_rx);
-    let graph_node_event_listener_thread =
-        graph_node_event_listener::spawn(config.clone(), controller_tx.clone());
-    let substrate_event_listener_thread = substrate_event_listener::spawn(config, controller_tx);
+    let mut config = config::Config::load().unwrap_or_else(|e| {
+        log::error!("{}", e);
+        std::process::exit(1)
+    });
+    if let cli::Command::Submissions(message_id) = args.command {
+        match submissions::find(&config.data_dir, &message_id) {
+            Ok(found) => found.iter().for_each(|submission| {
+                println!(
+                    "{:?} {} {:?} {}",
+                    submission.chain, submission.call, submission.tx_hash, submission.submitted_at
+                )
+            }),
+            Err(e) => {
+                log::error!("{}", e);
+                std::process::exit(1)
+            }
+        }
+        return;
+    }
+    #[cfg(feature = "graph-node")]
+    if let cli::Command::Snapshot = args.command {
+        match snapshot::take(&config) {
+            Ok(state) => println!(
+                "{}",
+                serde_json::to_string_pretty(&state).expect("snapshot serializes")
+            ),
+            Err(e) => {
+                log::error!("{}", e);
+                std::process::exit(1)
+            }
+        }
+        return;
+    }
+    #[cfg(not(feature = "graph-node"))]
+    if let cli::Command::Snapshot = args.command {
+        log::error!("snapshot needs the graph node, this build has no graph-node feature");
+        std::process::exit(1)
+    }
+    if let cli::Command::Drain = args.command {
+        let drain = drain::Drain::new(&config.data_dir);
+        drain.request().unwrap_or_else(|e| {
+            log::error!("{}", e);
+            std::process::exit(1)
+        });
+        println!("waiting for in-flight submissions to finish");
+        while !drain.is_drained() {
+            thread::sleep(Duration::from_secs(1));
+        }
+        println!("drained, it is safe to stop the validator");
+        return;
+    }
+    if let cli::Command::Prune { audit_days } = args.command {
+        let retention = retention::Retention {
+            audit_days: audit_days.unwrap_or(config.retention.audit_days),
+            ..config.retention.clone()
+        };
+        if !retention.is_enabled() {
+            log::error!(
+                "nothing to prune, set RETENTION_AUDIT_DAYS or RETENTION_TRANSFER_DAYS or pass \
+                 --audit-days"
+            );
+            std::process::exit(1)
+        }
+        // a running validator keeps its files open and compacts on its own
+        let _lock = instance_lock::lock_data_dir(&config.data_dir).unwrap_or_else(|e| {
+            log::error!("{}", e);
+            std::process::exit(1)
+        });
+        match retention::prune(&config, &retention) {
+            Ok(pruned) => pruned
+                .iter()
+                .for_each(|(store, count)| println!("{}: {} pruned", store, count)),
+            Err(e) => {
+                log::error!("{}", e);
+                std::process::exit(1)
+            }
+        }
+        return;
+    }
+    #[cfg(all(feature = "graph-node", feature = "sqlite"))]
+    if let cli::Command::Migrate = args.command {
+        // the store of a running indexer must not be replaced
+        let _lock = instance_lock::lock_data_dir(&config.data_dir).unwrap_or_else(|e| {
+            log::error!("{}", e);
+            std::process::exit(1)
+        });
+        match eth_indexer::migrate(&config) {
+            Ok(migrated) => println!(
+                "{} messages, {} accounts, {} limit messages and {} candidate validators \
+                 migrated up to block {}",
+                migrated.messages,
+                migrated.accounts,
+                migrated.limit_messages,
+                migrated.candidate_validators,
+                migrated.block_number
+            ),
+            Err(e) => {
+                log::error!("{}", e);
+                std::process::exit(1)
+            }
+        }
+        return;
+    }
+    #[cfg(not(all(feature = "graph-node", feature = "sqlite")))]
+    if let cli::Command::Migrate = args.command {
+        log::error!(
+            "migrate needs the graph node and the database, this build lacks the graph-node or \
+             sqlite feature"
+        );
+        std::process::exit(1)
+    }
+    if config.follower {
+        log::info!("follower mode, mirroring the bridge state without keys");
+    } else {
+        log::info!("ethereum validator address: {:?}", config.eth_validator_address);
+    }
+    #[cfg(feature = "otlp")]
+    if let Some(endpoint) = config.otlp_endpoint.clone() {
+        match otlp::install(endpoint.clone(), config.otlp_service_name.clone()) {
+            Ok(()) => log::info!("exporting traces to {}", endpoint),
+            Err(e) => log::error!("{}", e),
+        }
+    }
+    if !config.follower {
+        check_substrate_validator(&mut config);
+        check_ethereum_validator(&config);
+    }
+    check_token_bridges_have_code(&config);
+
+    let _lock = instance_lock::acquire(&config).unwrap_or_else(|e| {
+        log::error!("{}", e);
+        std::process::exit(1)
+    });
+    #[cfg(feature = "metrics-exporter")]
+    if let Some(address) = config.metrics_address {
+        match metrics::serve(address) {
+            Ok(_) => log::info!("serving metrics on http://{}/metrics", address),
+            Err(e) => log::error!("{}", e),
+        }
+    }
+    let approvals = Arc::new(
+        approvals::Approvals::open(&config.data_dir).unwrap_or_else(|e| {
+            log::error!("{}", e);
+            std::process::exit(1)
+        }),
+    );
+    // subscribers first, publishers after
+    let bus = Arc::new(bus::Bus::new());
+    #[cfg(feature = "admin-api")]
+    {
+        #[cfg(feature = "grpc")]
+        let grpc_api_address = config.grpc_api_address;
+        #[cfg(not(feature = "grpc"))]
+        let grpc_api_address: Option<std::net::SocketAddr> = None;
+        if config.admin_api_address.is_some() || grpc_api_address.is_some() {
+            let service = Arc::new(admin_service::AdminService::new(
+                approvals.clone(),
+                config.data_dir.clone(),
+                links::Links::new(&config),
+                transfer_index::TransferIndex::spawn(&bus, &config),
+            ));
+            let tokens = config.admin_api_tokens.clone();
+            if let Some(address) = config.admin_api_address {
+                match admin_api::serve(address, tokens.clone(), service.clone()) {
+                    Ok(_) => log::info!("serving admin API on http://{}", address),
+                    Err(e) => log::error!("{}", e),
+                }
+            }
+            #[cfg(feature = "grpc")]
+            if let Some(address) = grpc_api_address {
+                match grpc_api::serve(address, tokens, service) {
+                    Ok(_) => log::info!("serving admin API over gRPC on {}", address),
+                    Err(e) => log::error!("{}", e),
+                }
+            }
+        }
+    }
+    net::warn_if_proxied("ETH_API_URL", &config.eth_api_url);
+    net::warn_if_proxied("SUB_API_URL", &config.sub_api_url);
+    drain::Drain::new(&config.data_dir).clear();
+    clock::check(&config);
+    clock::spawn(config.clone());
+    substrate_sync::spawn(config.clone());
+    eth_sync::spawn(config.clone());
+    if config.gas_runway_alert_hours > 0 && !config.observe_only {
+        gas_runway::spawn(config.clone());
+    }
+    if config.retention.is_enabled() {
+        retention::spawn(config.clone());
+    }
+    #[cfg(feature = "graph-node")]
+    match &config.canary {
+        Some(canary) if !config.observe_only => {
+            canary::spawn(config.clone(), canary.clone());
+        }
+        _ => (),
+    }
+
+    // each part is ready before the next one starts, so the listeners do not
+    // publish to a controller or executor that is still connecting
+    let startup_timeout = Duration::from_secs(config.startup_timeout_secs);
+    let (ready, controller_ready) = startup::readiness("controller");
+    let controller_thread = controller::spawn(config.clone(), bus.clone(), approvals, ready);
+    controller_ready.wait(startup_timeout);
+    let validator_set = Arc::new(validator_set::ValidatorSetCache::new());
+    if !config.follower {
+        watch_own_membership(&config, &validator_set);
+    }
+    let (ready, executor_ready) = startup::readiness("executor");
+    let executor_thread = executor::spawn(config.clone(), bus.clone(), validator_set, ready);
+    executor_ready.wait(startup_timeout);
+    // the embedded indexer replaces the graph node listener, or stands by for
+    // it with INDEXER_FAILOVER
+    let eth_indexer_thread = config.indexer.clone().map(|indexer| {
+        let (ready, indexer_ready) = startup::readiness("eth_indexer");
+        let thread = eth_indexer::spawn(config.clone(), indexer, bus.clone(), ready);
+        indexer_ready.wait(startup_timeout);
+        thread
+    });
+    #[cfg(feature = "graph-node")]
+    let graph_node_event_listener_thread = if config.has_graph_node_listener() {
+        let config = config.clone();
+        let bus = bus.clone();
+        let (ready, listener_ready) = startup::readiness("graph_node_event_listener");
+        let thread = watchdog::supervise(
+            "graph_node_event_listener",
+            Duration::from_secs(config.graph_node_watchdog_secs),
+            move |heartbeat| {
+                graph_node_event_listener::spawn(
+                    config.clone(),
+                    bus.clone(),
+                    heartbeat,
+                    ready.clone(),
+                )
+            },
+        );
+        listener_ready.wait(startup_timeout);
+        Some(thread)
+    } else {
+        None
+    };
+    let (ready, listener_ready) = startup::readiness("substrate_event_listener");
+    let substrate_event_listener_thread = substrate_event_listener::spawn(config, bus, ready);
+    listener_ready.wait(startup_timeout);
+    log::info!("validator started");
 
     let _ = controller_thread.join().expect("controller thread failed");
     let _ = executor_thread.join().expect("executor thread failed");
-    let _ = graph_node_event_listener_thread.join().expect("graph node thread failed");
+    if let Some(thread) = eth_indexer_thread {
+        let _ = thread.join().expect("eth indexer thread failed");
+    }
+    #[cfg(feature = "graph-node")]
+    if let Some(thread) = graph_node_event_listener_thread {
+        let _ = thread.join().expect("graph node thread failed");
+    }
     let _ = substrate_event_listener_thread.join().expect("substrate thread failed");
 }
 
+fn check_substrate_validator(config: &mut config::Config) {
+    let account = substrate_transactions::get_account_id(
+        config.sub_validator_mnemonic_phrase.expose(),
+    );
+    log::info!("substrate validator address: {}", account.to_ss58check());
+
+    let registered =
+        substrate_transactions::is_registered_validator(config.sub_api_url.clone(), &account)
+            .unwrap_or_else(|e| {
+                log::error!("{}", e);
+                std::process::exit(1)
+            });
+    if registered || config.observe_only {
+        return;
+    }
+    if config.observe_only_if_unregistered {
+        log::warn!(
+            "{} is not a registered bridge validator, starting in observe-only mode",
+            account.to_ss58check()
+        );
+        config.observe_only = true;
+    } else {
+        log::error!(
+            "{} is not a registered bridge validator, set OBSERVE_ONLY_IF_UNREGISTERED=true to run without signing",
+            account.to_ss58check()
+        );
+        std::process::exit(1)
+    }
+}
+
+/// Warns when this validator drops out of either chain's validator set.
+fn watch_own_membership(config: &config::Config, validator_set: &validator_set::ValidatorSetCache) {
+    let changes = validator_set.subscribe();
+    let eth_address = config.eth_validator_address;
+    let sub_account =
+        substrate_transactions::get_account_id(config.sub_validator_mnemonic_phrase.expose());
+    thread::Builder::new()
+        .name("validator_membership".to_string())
+        .spawn(move || {
+            let mut was_member = (false, false);
+            for set in changes {
+                let is_member = (
+                    set.ethereum.contains(&eth_address),
+                    set.substrate.contains(&sub_account),
+                );
+                if was_member.0 && !is_member.0 {
+                    log::warn!("{:?} was removed from the token bridge validators", eth_address);
+                }
+                if was_member.1 && !is_member.1 {
+                    log::warn!(
+                        "{} was removed from the Bridge pallet validators",
+                        sub_account.to_ss58check()
+                    );
+                }
+                was_member = is_member;
+            }
+        })
+        .expect("can not started validator membership watcher");
+}
+
+/// Only warns, the bridge validator set may be updated after the node starts.
+fn check_ethereum_validator(config: &config::Config) {
+    let (_eloop, transport) = web3::transports::WebSocket::new(&config.eth_api_url)
+        .expect("can not connect to ethereum node");
+    let bridge = bridge_contract::BridgeContract::new(
+        &web3::Web3::new(transport),
+        &config.token_bridge,
+    );
+    match (bridge.validators(), bridge.quorum()) {
+        (Ok(validators), Ok(quorum)) => {
+            log::info!(
+                "token bridge validators: {:?}, quorum: {}",
+                validators,
+                quorum
+            );
+            if !validators.contains(&config.eth_validator_address) {
+                log::warn!(
+                    "{:?} is not a token bridge validator, its transactions will be reverted",
+                    config.eth_validator_address
+                );
+            }
+        }
+        (Err(e), _) | (_, Err(e)) => log::warn!("can not read token bridge validators: {:?}", e),
+    }
+}
+
+/// A token bridge address without code is a typo or the wrong network, calls
+/// to it would succeed and do nothing.
+fn check_token_bridges_have_code(config: &config::Config) {
+    let (_eloop, transport) = web3::transports::WebSocket::new(&config.eth_api_url)
+        .expect("can not connect to ethereum node");
+    let web3 = web3::Web3::new(transport);
+    let token_bridges = config
+        .token_bridges
+        .iter()
+        .map(|(token_id, token_bridge)| (format!("token {}", token_id), token_bridge.address));
+    for (name, address) in
+        std::iter::once(("TOKEN_BRIDGE_ADDRESS".to_string(), config.token_bridge.address))
+            .chain(token_bridges)
+    {
+        match web3.eth().code(address, None).wait() {
+            Ok(code) if code.0.is_empty() => {
+                log::error!("{} {:?} has no contract code", name, address);
+                std::process::exit(1)
+            }
+            Ok(_) => (),
+            Err(e) => log::warn!("can not get the code of {} {:?}: {:?}", name, address, e),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::substrate_transactions::get_sr25519_pair;
+    use dotenv::dotenv;
     use substrate_api_client::Api;
 
     /// the whole purpose of the test to address some chain's runtime
@@ -64,16 +517,31 @@ mod tests {
     }
     
     #[test]
+    #[cfg(feature = "graph-node")]
     fn graph_listener_test() {
         dotenv().ok();
         let config = config::Config::load().expect("can not load config");
-        let (controller_tx, controller_rx) = channel();
-        let (executor_tx, executor_rx) = channel();
+        let bus = Arc::new(bus::Bus::new());
         
-        let controller_thread = controller::spawn(config.clone(), controller_rx, executor_tx);
-        let executor_thread = executor::spawn(config.clone(), executor_rx);
+        let controller_thread = controller::spawn(
+            config.clone(),
+            bus.clone(),
+            Arc::new(approvals::Approvals::in_memory()),
+            startup::readiness("controller").0,
+        );
+        let executor_thread = executor::spawn(
+            config.clone(),
+            bus.clone(),
+            Arc::new(validator_set::ValidatorSetCache::new()),
+            startup::readiness("executor").0,
+        );
         let graph_node_event_listener_thread =
-        graph_node_event_listener::spawn(config.clone(), controller_tx.clone());
+        graph_node_event_listener::spawn(
+            config.clone(),
+            bus.clone(),
+            watchdog::Heartbeat::new(),
+            startup::readiness("graph_node_event_listener").0,
+        );
         
         let _ = controller_thread.join().expect("controller thread failed");
         let _ = executor_thread.join().expect("executor thread failed");
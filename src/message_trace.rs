@@ -0,0 +1,126 @@
+use env_logger::{self, filter};
+use lazy_static::lazy_static;
+use log::{self, LevelFilter, Log, Metadata, Record};
+use web3::types::H256;
+
+use std::collections::BTreeSet;
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use crate::fatal;
+
+lazy_static! {
+    static ref TRACED: RwLock<BTreeSet<H256>> = RwLock::new(BTreeSet::new());
+    /// The level `RUST_LOG` asks for, restored when nothing is traced.
+    static ref BASE_LEVEL: RwLock<LevelFilter> = RwLock::new(LevelFilter::Error);
+}
+/// Whether any message is traced, so logging does not look at the set
+/// otherwise.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Logs everything `RUST_LOG` allows, and at every level whatever a thread
+/// does while it works on a traced message, see `fatal::message_context`.
+struct Logger {
+    filter: filter::Filter,
+    inner: env_logger::Logger,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.enabled(metadata) || is_traced_now()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.filter.matches(record) || is_traced_now() {
+            self.inner.log(record)
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+/// Replaces `env_logger::init`.
+pub fn init() {
+    let filter = filter::Builder::new()
+        .parse(&env::var("RUST_LOG").unwrap_or_default())
+        .build();
+    let inner = env_logger::Builder::new()
+        .filter_level(LevelFilter::Trace)
+        .build();
+    *BASE_LEVEL.write().expect("trace lock poisoned") = filter.filter();
+    log::set_max_level(filter.filter());
+    log::set_boxed_logger(Box::new(Logger { filter, inner })).expect("logger is set once");
+}
+
+/// Logs the processing of `message_id` at every level until `stop`.
+#[cfg(feature = "admin-api")]
+pub fn start(message_id: H256) {
+    TRACED
+        .write()
+        .expect("trace lock poisoned")
+        .insert(message_id);
+    ACTIVE.store(true, Ordering::Relaxed);
+    log::set_max_level(LevelFilter::Trace);
+    log::info!("tracing message {:?}", message_id);
+}
+
+/// Whether the message was traced. The global level is restored when the
+/// last traced message is stopped.
+#[cfg(feature = "admin-api")]
+pub fn stop(message_id: &H256) -> bool {
+    let mut traced = TRACED.write().expect("trace lock poisoned");
+    let removed = traced.remove(message_id);
+    if traced.is_empty() {
+        ACTIVE.store(false, Ordering::Relaxed);
+        log::set_max_level(*BASE_LEVEL.read().expect("trace lock poisoned"));
+    }
+    removed
+}
+
+#[cfg(feature = "admin-api")]
+pub fn traced() -> Vec<H256> {
+    TRACED
+        .read()
+        .expect("trace lock poisoned")
+        .iter()
+        .cloned()
+        .collect()
+}
+
+fn is_traced_now() -> bool {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return false;
+    }
+    match fatal::current_message_id() {
+        Some(message_id) => TRACED
+            .read()
+            .map(|traced| traced.contains(&message_id))
+            .unwrap_or(false),
+        None => false,
+    }
+}
+
+#[cfg(all(test, feature = "admin-api"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_tests() {
+        let message_id = H256::from_slice(&[7; 32]);
+        assert!(!is_traced_now());
+        start(message_id);
+        assert_eq!(vec![message_id], traced());
+        assert!(!is_traced_now());
+        {
+            let _context = fatal::message_context(message_id);
+            assert!(is_traced_now());
+        }
+        assert!(stop(&message_id));
+        assert!(!stop(&message_id));
+        let _context = fatal::message_context(message_id);
+        assert!(!is_traced_now());
+    }
+}
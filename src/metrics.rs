@@ -0,0 +1,197 @@
+use lazy_static::lazy_static;
+
+use std::collections::BTreeMap;
+#[cfg(feature = "metrics-exporter")]
+use std::fmt::Write;
+#[cfg(feature = "metrics-exporter")]
+use std::net::SocketAddr;
+use std::sync::Mutex;
+#[cfg(feature = "metrics-exporter")]
+use std::thread;
+
+#[cfg(feature = "metrics-exporter")]
+use crate::http::{self, Response};
+
+const BUCKET_COUNT: usize = 10;
+/// Upper bounds in seconds, from graph-node polling up to a stuck message.
+const BUCKETS: [f64; BUCKET_COUNT] = [0.1, 0.5, 1.0, 5.0, 15.0, 30.0, 60.0, 300.0, 900.0, 3600.0];
+
+type Labels = Vec<(&'static str, String)>;
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Observations per bucket, the last one counts what exceeds all bounds.
+    counts: [u64; BUCKET_COUNT + 1],
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        let bucket = BUCKETS
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(BUCKETS.len());
+        self.counts[bucket] += 1;
+        self.sum += value;
+    }
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    histograms: BTreeMap<&'static str, BTreeMap<Labels, Histogram>>,
+    gauges: BTreeMap<&'static str, BTreeMap<Labels, f64>>,
+    counters: BTreeMap<&'static str, BTreeMap<Labels, u64>>,
+}
+
+lazy_static! {
+    static ref REGISTRY: Mutex<Registry> = Mutex::new(Registry::default());
+}
+
+pub fn observe_seconds(name: &'static str, labels: &[(&'static str, &str)], seconds: f64) {
+    REGISTRY
+        .lock()
+        .expect("metrics lock poisoned")
+        .histograms
+        .entry(name)
+        .or_default()
+        .entry(to_labels(labels))
+        .or_default()
+        .observe(seconds);
+}
+
+pub fn set_gauge(name: &'static str, labels: &[(&'static str, &str)], value: f64) {
+    REGISTRY
+        .lock()
+        .expect("metrics lock poisoned")
+        .gauges
+        .entry(name)
+        .or_default()
+        .insert(to_labels(labels), value);
+}
+
+pub fn increment(name: &'static str, labels: &[(&'static str, &str)]) {
+    add(name, labels, 1)
+}
+
+pub fn add(name: &'static str, labels: &[(&'static str, &str)], count: u64) {
+    *REGISTRY
+        .lock()
+        .expect("metrics lock poisoned")
+        .counters
+        .entry(name)
+        .or_default()
+        .entry(to_labels(labels))
+        .or_default() += count;
+}
+
+fn to_labels(labels: &[(&'static str, &str)]) -> Labels {
+    labels
+        .iter()
+        .map(|(label, value)| (*label, value.to_string()))
+        .collect()
+}
+
+/// Renders all metrics in the Prometheus text format.
+#[cfg(feature = "metrics-exporter")]
+pub fn render() -> String {
+    let registry = REGISTRY.lock().expect("metrics lock poisoned");
+    let mut out = String::new();
+    for (name, histograms) in &registry.histograms {
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (labels, histogram) in histograms {
+            let mut cumulative = 0;
+            for (bucket, count) in histogram.counts.iter().enumerate() {
+                cumulative += count;
+                let bound = BUCKETS
+                    .get(bucket)
+                    .map(|bound| bound.to_string())
+                    .unwrap_or_else(|| "+Inf".to_string());
+                let _ = writeln!(
+                    out,
+                    "{}_bucket{} {}",
+                    name,
+                    format_labels(labels, Some(&bound)),
+                    cumulative
+                );
+            }
+            let labels = format_labels(labels, None);
+            let _ = writeln!(out, "{}_sum{} {}", name, labels, histogram.sum);
+            let _ = writeln!(out, "{}_count{} {}", name, labels, cumulative);
+        }
+    }
+    for (name, gauges) in &registry.gauges {
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        for (labels, value) in gauges {
+            let _ = writeln!(out, "{}{} {}", name, format_labels(labels, None), value);
+        }
+    }
+    for (name, counters) in &registry.counters {
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        for (labels, value) in counters {
+            let _ = writeln!(out, "{}{} {}", name, format_labels(labels, None), value);
+        }
+    }
+    out
+}
+
+#[cfg(feature = "metrics-exporter")]
+fn format_labels(labels: &Labels, le: Option<&str>) -> String {
+    let labels = labels
+        .iter()
+        .map(|(label, value)| (*label, value.as_str()))
+        .chain(le.map(|le| ("le", le)))
+        .map(|(label, value)| format!("{}=\"{}\"", label, value))
+        .collect::<Vec<_>>();
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels.join(","))
+    }
+}
+
+/// Serves `GET /metrics` on `address` for Prometheus to scrape.
+#[cfg(feature = "metrics-exporter")]
+pub fn serve(address: SocketAddr) -> Result<thread::JoinHandle<()>, String> {
+    http::serve("metrics", address, |request| {
+        if request.method == "GET" && request.path == "/metrics" {
+            Response::text(render())
+        } else {
+            Response::error("404 Not Found", "not found")
+        }
+    })
+}
+
+#[cfg(all(test, feature = "metrics-exporter"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_render_test() {
+        observe_seconds("test_wait_seconds", &[("variant", "A")], 0.3);
+        observe_seconds("test_wait_seconds", &[("variant", "A")], 7200.0);
+        let rendered = render();
+        assert!(rendered.contains("# TYPE test_wait_seconds histogram\n"));
+        assert!(rendered.contains("test_wait_seconds_bucket{variant=\"A\",le=\"0.1\"} 0\n"));
+        assert!(rendered.contains("test_wait_seconds_bucket{variant=\"A\",le=\"0.5\"} 1\n"));
+        assert!(rendered.contains("test_wait_seconds_bucket{variant=\"A\",le=\"+Inf\"} 2\n"));
+        assert!(rendered.contains("test_wait_seconds_sum{variant=\"A\"} 7200.3\n"));
+        assert!(rendered.contains("test_wait_seconds_count{variant=\"A\"} 2\n"));
+    }
+
+    #[test]
+    fn gauge_render_test() {
+        set_gauge("test_runway_hours", &[], 12.0);
+        set_gauge("test_runway_hours", &[], 11.5);
+        let rendered = render();
+        assert!(rendered.contains("# TYPE test_runway_hours gauge\ntest_runway_hours 11.5\n"));
+    }
+
+    #[test]
+    fn counter_render_test() {
+        increment("test_unknown_total", &[("origin", "all_messages")]);
+        increment("test_unknown_total", &[("origin", "all_messages")]);
+        let rendered = render();
+        assert!(rendered.contains("# TYPE test_unknown_total counter\n"));
+        assert!(rendered.contains("test_unknown_total{origin=\"all_messages\"} 2\n"));
+    }
+}
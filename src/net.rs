@@ -0,0 +1,123 @@
+use lazy_static::lazy_static;
+use log;
+use url::Url;
+
+use std::env;
+
+lazy_static! {
+    static ref PROXY: Proxy = Proxy::from_env().unwrap_or_default();
+}
+
+/// Egress proxy for outbound HTTP(S) requests. `PROXY_URL` is used for
+/// every request when set, otherwise `HTTPS_PROXY` and `HTTP_PROXY` (or
+/// their lowercase forms) per scheme. Hosts listed in `NO_PROXY`, and their
+/// subdomains, are reached directly.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Proxy {
+    pub http: Option<Url>,
+    pub https: Option<Url>,
+    pub no_proxy: Vec<String>,
+}
+
+impl Proxy {
+    pub fn from_env() -> Result<Self, String> {
+        let explicit = read_url(&["PROXY_URL"])?;
+        let http = read_url(&["HTTP_PROXY", "http_proxy"])?;
+        let https = read_url(&["HTTPS_PROXY", "https_proxy"])?;
+        let no_proxy = read_var(&["NO_PROXY", "no_proxy"])
+            .unwrap_or_default()
+            .split(',')
+            .map(|host| host.trim().trim_start_matches('.').to_lowercase())
+            .filter(|host| !host.is_empty())
+            .collect();
+        Ok(Proxy {
+            http: explicit.clone().or(http),
+            https: explicit.or(https),
+            no_proxy,
+        })
+    }
+
+    /// The proxy to reach `url` through, if any.
+    pub fn for_url(&self, url: &Url) -> Option<Url> {
+        let host = url
+            .host_str()?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_lowercase();
+        let bypass = self.no_proxy.iter().any(|pattern| {
+            pattern == "*" || host == *pattern || host.ends_with(&format!(".{}", pattern))
+        });
+        if bypass {
+            return None;
+        }
+        match url.scheme() {
+            "https" | "wss" => self.https.clone(),
+            _ => self.http.clone(),
+        }
+    }
+}
+
+/// An HTTP client that goes through the configured proxy. Use it instead of
+/// `reqwest::Client::new()` for every outbound request.
+#[cfg(any(feature = "graph-node", feature = "otlp", feature = "secret-backends"))]
+pub fn http_client() -> reqwest::Client {
+    let proxy = PROXY.clone();
+    reqwest::Client::builder()
+        .proxy(reqwest::Proxy::custom(move |url| proxy.for_url(url)))
+        .build()
+        .expect("can not build http client")
+}
+
+/// The WebSocket transports of web3 and the substrate client connect
+/// directly, warn when their endpoint should have gone through a proxy.
+pub fn warn_if_proxied(name: &str, url: &str) {
+    if let Some(proxy) = Url::parse(url).ok().and_then(|url| PROXY.for_url(&url)) {
+        log::warn!(
+            "{} is reached directly, WebSocket connections do not use the proxy {}; \
+             allow direct egress to it or add its host to NO_PROXY",
+            name,
+            proxy
+        );
+    }
+}
+
+fn read_var(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.trim().is_empty())
+}
+
+fn read_url(names: &[&str]) -> Result<Option<Url>, String> {
+    match read_var(names) {
+        Some(url) => Url::parse(url.trim())
+            .map(Some)
+            .map_err(|e| format!("{} must be a proxy url, got {:?}: {}", names[0], url, e)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn for_url_tests() {
+        let http = Url::parse("http://proxy:3128").unwrap();
+        let https = Url::parse("http://[fd00::1]:3129").unwrap();
+        let proxy = Proxy {
+            http: Some(http.clone()),
+            https: Some(https.clone()),
+            no_proxy: vec!["internal".to_string(), "::1".to_string()],
+        };
+        let url = |url| Url::parse(url).unwrap();
+        assert_eq!(Some(http), proxy.for_url(&url("http://graph.example.com/")));
+        assert_eq!(
+            Some(https),
+            proxy.for_url(&url("https://[2001:db8::1]:8443/"))
+        );
+        assert_eq!(None, proxy.for_url(&url("http://graph.internal:8000/")));
+        assert_eq!(None, proxy.for_url(&url("http://[::1]:8000/")));
+        assert_eq!(None, Proxy::default().for_url(&url("https://example.com/")));
+    }
+}
@@ -0,0 +1,149 @@
+use log;
+use web3::types::H256;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::bus::Bus;
+use crate::controller::{Entity, Envelope, Event, Source};
+use crate::dead_letters::DeadLetters;
+use crate::metrics;
+
+const FILE_NAME: &str = "orphan_redrives.json";
+
+/// Re-drives transfers stuck half way, approved or withdrawn on one chain
+/// but not finished on the other, as after an outage of a node the validator
+/// talks to. How often each was re-driven is kept in
+/// `<data_dir>/orphan_redrives.json`, so a restart does not reset the count.
+#[derive(Debug)]
+pub struct Orphans {
+    path: PathBuf,
+    max_redrives: u32,
+    redrives: HashMap<H256, u32>,
+}
+
+impl Orphans {
+    pub fn open(data_dir: &str, max_redrives: u32) -> Self {
+        let path = Path::new(data_dir).join(FILE_NAME);
+        let redrives = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                log::warn!("can not read {:?}, counting re-drives anew: {}", path, e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        Orphans {
+            path,
+            max_redrives,
+            redrives,
+        }
+    }
+
+    /// Re-drives the stuck transfers once more. Those still stuck after
+    /// `max_redrives` are dead-lettered for operators instead.
+    pub fn sweep(&mut self, stuck: Vec<(Event, Entity)>, bus: &Bus, dead_letters: &DeadLetters) {
+        metrics::set_gauge("validator_orphaned_messages", &[], stuck.len() as f64);
+        let (redrive, give_up) = self.take(stuck);
+        self.save();
+        for event in give_up {
+            let payload = format!("{:?}", event);
+            if dead_letters.record(
+                *event.message_id(),
+                "orphans",
+                &payload,
+                event.block_number(),
+            ) {
+                log::error!(
+                    "message {:?} is still stuck at {} after {} re-drives, moved to the dead \
+                     letters",
+                    event.message_id(),
+                    event.name(),
+                    self.max_redrives
+                );
+                metrics::increment("validator_dead_letters_total", &[("origin", "orphans")]);
+            }
+        }
+        for (event, entity) in redrive {
+            log::warn!(
+                "message {:?} is stuck at {} since block {}, re-driving it",
+                event.message_id(),
+                event.name(),
+                event.block_number()
+            );
+            metrics::increment(
+                "validator_orphan_redrives_total",
+                &[("stage", event.name())],
+            );
+            bus.publish(Envelope::new(event, Source::OrphanSweep).with_entity(entity));
+        }
+    }
+
+    /// Splits the stuck transfers into those to re-drive once more and those
+    /// re-driven `max_redrives` times already. Transfers that moved on are
+    /// forgotten.
+    fn take(&mut self, stuck: Vec<(Event, Entity)>) -> (Vec<(Event, Entity)>, Vec<Event>) {
+        let mut seen = HashMap::new();
+        let mut redrive = vec![];
+        let mut give_up = vec![];
+        for (event, entity) in stuck {
+            let message_id = *event.message_id();
+            let count = self.redrives.get(&message_id).copied().unwrap_or(0);
+            seen.insert(message_id, count);
+            if count >= self.max_redrives {
+                give_up.push(event);
+            } else {
+                seen.insert(message_id, count + 1);
+                redrive.push((event, entity));
+            }
+        }
+        self.redrives = seen;
+        (redrive, give_up)
+    }
+
+    fn save(&self) {
+        let json = serde_json::to_string(&self.redrives).expect("re-drive counts serialize");
+        let temporary = self.path.with_extension("json.tmp");
+        if let Err(e) =
+            fs::write(&temporary, json).and_then(|()| fs::rename(&temporary, &self.path))
+        {
+            log::error!("can not save the re-drive counts to {:?}: {}", self.path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    fn stuck(ids: &[u8]) -> Vec<(Event, Entity)> {
+        ids.iter()
+            .map(|id| {
+                let event = Event::eth_withdraw_message(H256::from_slice(&[*id; 32]), 7);
+                (event, Entity::new("message", "0x00"))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn take_test() {
+        let dir = TestDir::new("orphans");
+        let data_dir = dir.path();
+
+        let mut orphans = Orphans::open(data_dir, 2);
+        let (redrive, give_up) = orphans.take(stuck(&[1, 2]));
+        assert_eq!((2, 0), (redrive.len(), give_up.len()));
+        orphans.save();
+        // the counts survive a restart
+        let mut orphans = Orphans::open(data_dir, 2);
+        let (redrive, give_up) = orphans.take(stuck(&[1, 2]));
+        assert_eq!((2, 0), (redrive.len(), give_up.len()));
+        // 2 moved on, 1 is given up
+        let (redrive, give_up) = orphans.take(stuck(&[1]));
+        assert_eq!((0, 1), (redrive.len(), give_up.len()));
+        assert_eq!(1, orphans.redrives.len());
+        let (redrive, _) = orphans.take(stuck(&[2]));
+        assert_eq!(1, redrive.len());
+    }
+}
@@ -0,0 +1,308 @@
+use log;
+use rustc_hex::ToHex;
+use serde_json::{json, Value};
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Level, Metadata, Subscriber,
+};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::net;
+
+const BATCH_SIZE: usize = 512;
+const BATCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+thread_local! {
+    static CURRENT_SPANS: RefCell<Vec<u64>> = RefCell::new(vec![]);
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpanData {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent_span_id: Option<[u8; 8]>,
+    name: String,
+    start_time: u128,
+    end_time: u128,
+    attributes: Vec<(String, String)>,
+}
+
+struct LiveSpan {
+    data: SpanData,
+    references: usize,
+}
+
+/// A `tracing` subscriber that exports finished spans over OTLP/HTTP with the
+/// JSON encoding, so traces reach a collector without a sidecar.
+pub struct OtlpSubscriber {
+    next_id: AtomicU64,
+    spans: Mutex<HashMap<u64, LiveSpan>>,
+    finished: Mutex<Sender<SpanData>>,
+}
+
+/// Installs the subscriber globally and starts the thread that sends batches
+/// of spans to `<endpoint>/v1/traces`.
+pub fn install(endpoint: String, service_name: String) -> Result<(), String> {
+    let (finished, spans) = channel();
+    tracing::subscriber::set_global_default(OtlpSubscriber::new(finished))
+        .map_err(|e| format!("can not install OTLP exporter: {}", e))?;
+    thread::Builder::new()
+        .name("otlp_exporter".to_string())
+        .spawn(move || export(&endpoint, &service_name, spans))
+        .map_err(|e| format!("can not start OTLP exporter: {}", e))?;
+    Ok(())
+}
+
+impl OtlpSubscriber {
+    fn new(finished: Sender<SpanData>) -> Self {
+        OtlpSubscriber {
+            next_id: AtomicU64::new(1),
+            spans: Mutex::new(HashMap::new()),
+            finished: Mutex::new(finished),
+        }
+    }
+
+    fn lock_spans(&self) -> std::sync::MutexGuard<HashMap<u64, LiveSpan>> {
+        self.spans.lock().expect("otlp span lock poisoned")
+    }
+}
+
+impl Subscriber for OtlpSubscriber {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.is_span() && *metadata.level() <= Level::INFO
+    }
+
+    fn new_span(&self, attributes: &span::Attributes<'_>) -> span::Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let parent = if attributes.is_contextual() {
+            CURRENT_SPANS.with(|current| current.borrow().last().cloned())
+        } else {
+            attributes.parent().map(span::Id::into_u64)
+        };
+
+        let mut spans = self.lock_spans();
+        let parent = parent.and_then(|parent| spans.get(&parent));
+        let mut data = SpanData {
+            trace_id: parent.map_or_else(rand::random, |parent| parent.data.trace_id),
+            span_id: rand::random(),
+            parent_span_id: parent.map(|parent| parent.data.span_id),
+            name: attributes.metadata().name().to_string(),
+            start_time: now(),
+            end_time: 0,
+            attributes: vec![],
+        };
+        attributes.record(&mut AttributeVisitor(&mut data.attributes));
+        spans.insert(
+            id,
+            LiveSpan {
+                data,
+                references: 1,
+            },
+        );
+        span::Id::from_u64(id)
+    }
+
+    fn record(&self, id: &span::Id, values: &span::Record<'_>) {
+        if let Some(span) = self.lock_spans().get_mut(&id.into_u64()) {
+            values.record(&mut AttributeVisitor(&mut span.data.attributes));
+        }
+    }
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, _event: &Event<'_>) {}
+
+    fn enter(&self, id: &span::Id) {
+        CURRENT_SPANS.with(|current| current.borrow_mut().push(id.into_u64()));
+    }
+
+    fn exit(&self, id: &span::Id) {
+        CURRENT_SPANS.with(|current| {
+            let mut current = current.borrow_mut();
+            if let Some(position) = current.iter().rposition(|span| *span == id.into_u64()) {
+                current.remove(position);
+            }
+        });
+    }
+
+    fn clone_span(&self, id: &span::Id) -> span::Id {
+        if let Some(span) = self.lock_spans().get_mut(&id.into_u64()) {
+            span.references += 1;
+        }
+        id.clone()
+    }
+
+    fn try_close(&self, id: span::Id) -> bool {
+        let mut spans = self.lock_spans();
+        let closed = match spans.get_mut(&id.into_u64()) {
+            Some(span) => {
+                span.references -= 1;
+                span.references == 0
+            }
+            None => false,
+        };
+        if closed {
+            let mut span = spans.remove(&id.into_u64()).expect("span exists").data;
+            span.end_time = now();
+            let _ = self
+                .finished
+                .lock()
+                .expect("otlp sender lock poisoned")
+                .send(span);
+        }
+        closed
+    }
+}
+
+struct AttributeVisitor<'a>(&'a mut Vec<(String, String)>);
+
+impl<'a> Visit for AttributeVisitor<'a> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .push((field.name().to_string(), format!("{:?}", value)));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.push((field.name().to_string(), value.to_string()));
+    }
+}
+
+fn export(endpoint: &str, service_name: &str, spans: Receiver<SpanData>) {
+    let url = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+    let client = net::http_client();
+    let mut batch = vec![];
+    let mut deadline = Instant::now() + BATCH_TIMEOUT;
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let disconnected = match spans.recv_timeout(timeout) {
+            Ok(span) => {
+                batch.push(span);
+                false
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => true,
+        };
+        if batch.len() >= BATCH_SIZE || Instant::now() >= deadline || disconnected {
+            if !batch.is_empty() {
+                let body = encode(service_name, &batch);
+                match client
+                    .post(&url)
+                    .json(&body)
+                    .send()
+                    .and_then(|res| res.error_for_status())
+                {
+                    Ok(_) => log::debug!("exported {} spans to {}", batch.len(), url),
+                    Err(e) => log::warn!("can not export {} spans to {}: {}", batch.len(), url, e),
+                }
+                batch.clear();
+            }
+            deadline = Instant::now() + BATCH_TIMEOUT;
+        }
+        if disconnected {
+            return;
+        }
+    }
+}
+
+/// Encodes spans as an OTLP `ExportTraceServiceRequest` in the JSON mapping,
+/// where trace and span ids are hex strings.
+fn encode(service_name: &str, spans: &[SpanData]) -> Value {
+    let spans = spans
+        .iter()
+        .map(|span| {
+            let mut encoded = json!({
+                "traceId": span.trace_id.to_hex::<String>(),
+                "spanId": span.span_id.to_hex::<String>(),
+                "name": span.name,
+                "kind": 1,
+                "startTimeUnixNano": span.start_time.to_string(),
+                "endTimeUnixNano": span.end_time.to_string(),
+                "attributes": span
+                    .attributes
+                    .iter()
+                    .map(|(key, value)| attribute(key, value))
+                    .collect::<Vec<_>>(),
+            });
+            if let Some(parent_span_id) = span.parent_span_id {
+                encoded["parentSpanId"] = json!(parent_span_id.to_hex::<String>());
+            }
+            encoded
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "resourceSpans": [{
+            "resource": { "attributes": [attribute("service.name", service_name)] },
+            "scopeSpans": [{
+                "scope": { "name": "validator" },
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+fn attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+fn now() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_nanos())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn child_spans_share_the_trace_id() {
+        let (finished, spans) = channel();
+        tracing::subscriber::with_default(OtlpSubscriber::new(finished), || {
+            let parent = tracing::info_span!("event", message_id = "0x01");
+            let _enter = parent.enter();
+            tracing::info_span!("ethereum_call").in_scope(|| {});
+        });
+
+        let child = spans.recv().unwrap();
+        let parent = spans.recv().unwrap();
+        assert_eq!("ethereum_call", child.name);
+        assert_eq!("event", parent.name);
+        assert_eq!(parent.trace_id, child.trace_id);
+        assert_eq!(Some(parent.span_id), child.parent_span_id);
+        assert_eq!(
+            vec![("message_id".to_string(), "0x01".to_string())],
+            parent.attributes
+        );
+        assert!(parent.end_time >= parent.start_time);
+    }
+
+    #[test]
+    fn encode_test() {
+        let span = SpanData {
+            trace_id: [1; 16],
+            span_id: [2; 8],
+            parent_span_id: None,
+            name: "event".to_string(),
+            start_time: 1,
+            end_time: 2,
+            attributes: vec![("message_id".to_string(), "0x01".to_string())],
+        };
+        let encoded = encode("validator", &[span]);
+        let span = &encoded["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+        assert_eq!("01010101010101010101010101010101", span["traceId"]);
+        assert_eq!("0202020202020202", span["spanId"]);
+        assert_eq!(Value::Null, span["parentSpanId"]);
+        assert_eq!("2", span["endTimeUnixNano"]);
+        assert_eq!("0x01", span["attributes"][0]["value"]["stringValue"]);
+    }
+}
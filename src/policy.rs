@@ -0,0 +1,393 @@
+use web3::types::U256;
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::controller::{Direction, Event, EventType, Status};
+use crate::controller_storage::ControllerStorage;
+use crate::drain::Drain;
+
+pub const DEFAULT_POLICIES: &str = "status,drain,blocked_accounts,pending_transfers";
+
+/// What the controller does with an event once the policies have seen it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Forward,
+    /// Keep the event in the controller queue until the bridge is active.
+    Queue,
+    /// Keep the event in its sender's queue until the account is resumed.
+    HoldForAccount,
+    /// Keep the transfer until another one in the same direction finishes.
+    Defer(Direction),
+    /// Keep the event for a while, so operators can step in before it is acted on.
+    Timelock(Duration),
+    /// Keep the event until an operator approves or rejects it.
+    AwaitApproval,
+    /// Keep the new transfer until the drained validator is restarted.
+    HoldForDrain,
+}
+
+impl Verdict {
+    /// The action recorded in attestations.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Verdict::Forward => "forward",
+            Verdict::Queue => "queue",
+            Verdict::HoldForAccount => "hold_for_account",
+            Verdict::Defer(_) => "defer",
+            Verdict::Timelock(_) => "timelock",
+            Verdict::AwaitApproval => "await_approval",
+            Verdict::HoldForDrain => "hold_for_drain",
+        }
+    }
+}
+
+/// One gating decision of the controller. Policies are evaluated in the
+/// configured order and the first verdict other than `Forward` wins.
+pub trait Policy: fmt::Debug + Send {
+    fn check(&self, status: &Status, storage: &ControllerStorage, event: &Event) -> Verdict;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Status,
+    Drain,
+    BlockedAccounts,
+    PendingTransfers,
+    Timelock,
+    ManualApproval,
+}
+
+/// Transfers above `threshold` are held for `delay` by the timelock policy.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Timelock {
+    pub threshold: U256,
+    pub delay: Duration,
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "status" => Ok(Kind::Status),
+            "drain" => Ok(Kind::Drain),
+            "blocked_accounts" => Ok(Kind::BlockedAccounts),
+            "pending_transfers" => Ok(Kind::PendingTransfers),
+            "timelock" => Ok(Kind::Timelock),
+            "manual_approval" => Ok(Kind::ManualApproval),
+            _ => Err(format!(
+                "unknown policy {:?}, expected status, drain, blocked_accounts, \
+                 pending_transfers, timelock or manual_approval",
+                s
+            )),
+        }
+    }
+}
+
+pub fn chain(
+    kinds: &[Kind],
+    data_dir: &str,
+    timelock: Timelock,
+    approval_threshold: U256,
+) -> Vec<Box<dyn Policy>> {
+    kinds
+        .iter()
+        .map(|kind| -> Box<dyn Policy> {
+            match kind {
+                Kind::Status => Box::new(BridgeStatus),
+                Kind::Drain => Box::new(Draining(Drain::new(data_dir))),
+                Kind::BlockedAccounts => Box::new(BlockedAccounts),
+                Kind::PendingTransfers => Box::new(PendingTransfers),
+                Kind::Timelock => Box::new(LargeTransfers(timelock)),
+                Kind::ManualApproval => Box::new(FlaggedTransfers(approval_threshold)),
+            }
+        })
+        .collect()
+}
+
+pub fn evaluate(
+    policies: &[Box<dyn Policy>],
+    status: &Status,
+    storage: &ControllerStorage,
+    event: &Event,
+) -> Verdict {
+    policies
+        .iter()
+        .map(|policy| policy.check(status, storage, event))
+        .find(|verdict| *verdict != Verdict::Forward)
+        .unwrap_or(Verdict::Forward)
+}
+
+/// Holds every event while the bridge is not active.
+#[derive(Debug)]
+struct BridgeStatus;
+
+impl Policy for BridgeStatus {
+    fn check(&self, status: &Status, _storage: &ControllerStorage, _event: &Event) -> Verdict {
+        match status {
+            Status::Active => Verdict::Forward,
+            Status::NotReady | Status::Paused | Status::Stopped | Status::Halted => Verdict::Queue,
+        }
+    }
+}
+
+/// Holds new transfers while the validator is drained for maintenance, the
+/// later stages of those in flight go on so they can finish.
+#[derive(Debug)]
+struct Draining(Drain);
+
+impl Policy for Draining {
+    fn check(&self, _status: &Status, _storage: &ControllerStorage, event: &Event) -> Verdict {
+        if event.starts_transfer().is_some() && self.0.is_requested() {
+            Verdict::HoldForDrain
+        } else {
+            Verdict::Forward
+        }
+    }
+}
+
+/// Holds transfers from accounts paused on the bridge, except for those in
+/// flight when the account was paused during `ACCOUNT_PAUSE_GRACE_SECS`.
+#[derive(Debug)]
+struct BlockedAccounts;
+
+impl Policy for BlockedAccounts {
+    fn check(&self, _status: &Status, storage: &ControllerStorage, event: &Event) -> Verdict {
+        if event.event_type() == EventType::Transfer
+            && storage.is_account_blocked(event.sender())
+            && !storage.is_in_pause_grace(event, Instant::now())
+        {
+            Verdict::HoldForAccount
+        } else {
+            Verdict::Forward
+        }
+    }
+}
+
+/// Defers transfers beyond the pending transfers limit of their direction.
+#[derive(Debug)]
+struct PendingTransfers;
+
+impl Policy for PendingTransfers {
+    fn check(&self, _status: &Status, storage: &ControllerStorage, event: &Event) -> Verdict {
+        match event.starts_transfer() {
+            Some(direction) if !storage.can_start_transfer(direction, event.message_id()) => {
+                Verdict::Defer(direction)
+            }
+            _ => Verdict::Forward,
+        }
+    }
+}
+
+/// Holds the first event of a transfer that carries an amount above the
+/// threshold. Later events of the same message pass once it is released.
+#[derive(Debug)]
+struct LargeTransfers(Timelock);
+
+impl Policy for LargeTransfers {
+    fn check(&self, _status: &Status, storage: &ControllerStorage, event: &Event) -> Verdict {
+        match event.amount() {
+            Some(amount)
+                if event.event_type() == EventType::Transfer
+                    && amount > self.0.threshold
+                    && !storage.is_timelock_released(event.message_id()) =>
+            {
+                Verdict::Timelock(self.0.delay)
+            }
+            _ => Verdict::Forward,
+        }
+    }
+}
+
+/// Holds transfers above the threshold for an operator's decision, like
+/// the timelock does for a fixed time. Only stages that carry the amount are
+/// checked: a Substrate to Ethereum transfer is held at
+/// `SubApprovedRelayMessage`, before its tokens are released on Ethereum. The
+/// `SubRelayMessage` before it only approves the lock on Substrate, and the
+/// withdraw, mint, burn and cancel stages after follow what was decided.
+#[derive(Debug)]
+struct FlaggedTransfers(U256);
+
+impl Policy for FlaggedTransfers {
+    fn check(&self, _status: &Status, storage: &ControllerStorage, event: &Event) -> Verdict {
+        match event.amount() {
+            Some(amount)
+                if event.event_type() == EventType::Transfer
+                    && amount > self.0
+                    && !storage.is_approved(event.message_id()) =>
+            {
+                Verdict::AwaitApproval
+            }
+            _ => Verdict::Forward,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::Address;
+    use crate::test_dir::TestDir;
+    use web3::types::{H160, H256};
+
+    #[test]
+    fn first_holding_verdict_wins() {
+        let address = H160::from_slice(&[7; 20]);
+        let event = Event::eth_relay_message(
+            H256::from_slice(&[0; 32]),
+            address,
+            H256::from_slice(&[8; 32]),
+            1000.into(),
+            None,
+            10,
+        );
+        let mut storage = ControllerStorage::new();
+        storage.block_account(Address::Eth(address));
+        storage.set_max_pending_transfers(Direction::EthToSub, Some(0));
+
+        let all = chain(
+            &[Kind::Status, Kind::BlockedAccounts, Kind::PendingTransfers],
+            "data",
+            Timelock::default(),
+            U256::zero(),
+        );
+        assert_eq!(
+            Verdict::Queue,
+            evaluate(&all, &Status::Paused, &storage, &event)
+        );
+        assert_eq!(
+            Verdict::HoldForAccount,
+            evaluate(&all, &Status::Active, &storage, &event)
+        );
+
+        let limits_first = chain(
+            &[Kind::PendingTransfers, Kind::BlockedAccounts],
+            "data",
+            Timelock::default(),
+            U256::zero(),
+        );
+        assert_eq!(
+            Verdict::Defer(Direction::EthToSub),
+            evaluate(&limits_first, &Status::Active, &storage, &event)
+        );
+        assert_eq!(
+            Verdict::Forward,
+            evaluate(&[], &Status::Paused, &storage, &event)
+        );
+    }
+
+    #[test]
+    fn timelock_tests() {
+        let timelock = Timelock {
+            threshold: 1000.into(),
+            delay: Duration::from_secs(1800),
+        };
+        let policies = chain(&[Kind::Timelock], "data", timelock, U256::zero());
+        let event = |amount: u64| {
+            Event::eth_relay_message(
+                H256::from_slice(&[0; 32]),
+                H160::from_slice(&[7; 20]),
+                H256::from_slice(&[8; 32]),
+                amount.into(),
+                None,
+                10,
+            )
+        };
+        let mut storage = ControllerStorage::new();
+        assert_eq!(
+            Verdict::Forward,
+            evaluate(&policies, &Status::Active, &storage, &event(1000))
+        );
+        assert_eq!(
+            Verdict::Timelock(timelock.delay),
+            evaluate(&policies, &Status::Active, &storage, &event(1001))
+        );
+        storage.timelock(event(1001), std::time::Instant::now());
+        storage.release_timelocked(std::time::Instant::now());
+        assert_eq!(
+            Verdict::Forward,
+            evaluate(&policies, &Status::Active, &storage, &event(1001))
+        );
+    }
+
+    #[test]
+    fn flagged_transfers_tests() {
+        let policies = chain(
+            &[Kind::ManualApproval],
+            "data",
+            Timelock::default(),
+            1000.into(),
+        );
+        let message_id = H256::from_slice(&[0; 32]);
+        let approved = |amount: u64| {
+            Event::sub_approved_relay_message(
+                message_id,
+                H256::from_slice(&[8; 32]),
+                H160::from_slice(&[7; 20]),
+                amount.into(),
+                U256::zero(),
+                11,
+            )
+        };
+        let storage = ControllerStorage::new();
+        assert_eq!(
+            Verdict::Forward,
+            evaluate(&policies, &Status::Active, &storage, &approved(1000))
+        );
+        assert_eq!(
+            Verdict::AwaitApproval,
+            evaluate(&policies, &Status::Active, &storage, &approved(1001))
+        );
+        // stages without an amount pass, the one with it is held
+        let relay = Event::sub_relay_message(message_id, 10);
+        assert_eq!(
+            Verdict::Forward,
+            evaluate(&policies, &Status::Active, &storage, &relay)
+        );
+    }
+
+    #[test]
+    fn draining_tests() {
+        let dir = TestDir::new("policy");
+        let data_dir = dir.path();
+        let policies = chain(&[Kind::Drain], data_dir, Timelock::default(), U256::zero());
+        let relay = Event::eth_relay_message(
+            H256::from_slice(&[0; 32]),
+            H160::from_slice(&[7; 20]),
+            H256::from_slice(&[8; 32]),
+            1000.into(),
+            None,
+            10,
+        );
+        let approved = Event::eth_approved_relay_message(
+            H256::from_slice(&[0; 32]),
+            H160::from_slice(&[7; 20]),
+            H256::from_slice(&[8; 32]),
+            1000.into(),
+            11,
+        );
+        let storage = ControllerStorage::new();
+        assert_eq!(
+            Verdict::Forward,
+            evaluate(&policies, &Status::Active, &storage, &relay)
+        );
+        Drain::new(data_dir).request().unwrap();
+        assert_eq!(
+            Verdict::HoldForDrain,
+            evaluate(&policies, &Status::Active, &storage, &relay)
+        );
+        // transfers in flight go on
+        assert_eq!(
+            Verdict::Forward,
+            evaluate(&policies, &Status::Active, &storage, &approved)
+        );
+    }
+
+    #[test]
+    fn parse_kind_tests() {
+        assert_eq!(Ok(Kind::BlockedAccounts), " Blocked_Accounts".parse());
+        assert!("blacklist".parse::<Kind>().is_err());
+    }
+}
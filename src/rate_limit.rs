@@ -0,0 +1,99 @@
+use log;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Caps how many calls of each contract method or extrinsic are submitted
+/// per minute. Calls over the cap are handled again once a slot frees up
+/// instead of being dropped, which holds back only the messages that need
+/// the method.
+#[derive(Debug)]
+pub struct RateLimiter {
+    limits: HashMap<String, u32>,
+    sent: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: HashMap<String, u32>) -> Self {
+        RateLimiter {
+            limits,
+            sent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a slot for `method`, or returns when one frees up.
+    pub fn acquire(&self, method: &str) -> Result<(), Instant> {
+        let now = Instant::now();
+        self.try_acquire(method, now).map_err(|wait| {
+            log::info!("{} is rate limited, deferring it for {:?}", method, wait);
+            now + wait
+        })
+    }
+
+    /// Takes a slot, or returns how long until the oldest one frees up.
+    fn try_acquire(&self, method: &str, now: Instant) -> Result<(), Duration> {
+        let limit = match self.limits.get(method) {
+            Some(limit) => *limit as usize,
+            None => return Ok(()),
+        };
+        let mut sent = self.sent.lock().expect("rate limiter lock poisoned");
+        let sent = sent.entry(method.to_string()).or_default();
+        while let Some(oldest) = sent.front() {
+            if now.duration_since(*oldest) < WINDOW {
+                break;
+            }
+            sent.pop_front();
+        }
+        match sent.front() {
+            Some(oldest) if sent.len() >= limit => Err(WINDOW - now.duration_since(*oldest)),
+            _ => {
+                sent.push_back(now);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn try_acquire_tests() {
+        let limiter = RateLimiter::new(
+            vec![("approveTransfer".to_string(), 2)]
+                .into_iter()
+                .collect(),
+        );
+        let start = Instant::now();
+        assert_eq!(Ok(()), limiter.try_acquire("approveTransfer", start));
+        assert_eq!(
+            Ok(()),
+            limiter.try_acquire("approveTransfer", start + Duration::from_secs(10))
+        );
+        assert_eq!(
+            Err(Duration::from_secs(40)),
+            limiter.try_acquire("approveTransfer", start + Duration::from_secs(20))
+        );
+        // other methods are not limited
+        assert_eq!(Ok(()), limiter.try_acquire("mint", start));
+        assert_eq!(
+            Ok(()),
+            limiter.try_acquire("approveTransfer", start + Duration::from_secs(60))
+        );
+    }
+
+    #[test]
+    fn acquire_test() {
+        let limiter = RateLimiter::new(vec![("mint".to_string(), 1)].into_iter().collect());
+        let start = Instant::now();
+        assert_eq!(Ok(()), limiter.acquire("mint"));
+        // deferred until the slot frees up, not blocked
+        let at = limiter.acquire("mint").unwrap_err();
+        assert!(at > start + WINDOW - Duration::from_secs(1));
+        assert!(Instant::now() < start + Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,526 @@
+use log;
+use rustc_hex::FromHex;
+use serde_json::json;
+use web3::{
+    futures::Future,
+    transports::WebSocket,
+    types::{BlockNumber, Bytes, CallRequest, TransactionId, TransactionReceipt, H160, H256, U256},
+};
+
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+use crate::bus::Bus;
+use crate::config::Config;
+use crate::controller::{Envelope, Outcome};
+use crate::debug_state;
+use crate::ethereum_transactions;
+use crate::fatal;
+use crate::fees::FeeLog;
+use crate::submissions::Chain;
+use crate::urgency::Urgency;
+
+/// Replacements pay at most this many times `ETH_GAS_PRICE`.
+const MAX_REPLACEMENT_FACTOR: u64 = 4;
+/// The selector of `Error(string)`, what `require` and `revert` with a
+/// message return.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// A signed transaction the executor has sent to the token bridge, or for an
+/// admin operation.
+#[derive(Debug, Clone)]
+pub struct SentTransaction {
+    pub message_id: H256,
+    pub tx_hash: H256,
+    pub nonce: U256,
+    /// The contract the transaction calls and its calldata.
+    pub to: H160,
+    pub data: Vec<u8>,
+    /// The contract method, `call` adds the arguments.
+    pub function: String,
+    pub call: String,
+    pub gas_price: U256,
+    pub urgency: Urgency,
+    /// Hashes and gas prices of the transactions this one replaced at the
+    /// same nonce, any of them may still be mined instead.
+    pub replaced: Vec<(H256, U256)>,
+}
+
+impl SentTransaction {
+    /// Signs the transaction again, as the executor did, at `gas_price`.
+    fn sign(&self, config: &Config, gas_price: U256) -> Vec<u8> {
+        let access_list = config
+            .access_lists
+            .get(&self.function)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        ethereum_transactions::build_with_access_list(
+            config.eth_validator_private_key.clone(),
+            self.to,
+            self.nonce,
+            0,
+            gas_price.low_u64(),
+            config.eth_gas,
+            self.data.clone(),
+            config.eth_chain_id,
+            access_list,
+        )
+    }
+}
+
+#[derive(Debug)]
+struct Watched {
+    tx: SentTransaction,
+    mined_in: Option<u64>,
+    /// Head block when the transaction was last broadcast, set on the
+    /// first check.
+    broadcast_at: Option<u64>,
+}
+
+/// What a check found a watched transaction in.
+#[derive(Debug)]
+enum Checked {
+    /// Still has to be watched.
+    Pending(Watched),
+    /// Confirmed or failed, with the message id the outcome is reported for.
+    Done(H256, Outcome),
+}
+
+#[derive(Debug, PartialEq)]
+enum State {
+    Unmined,
+    Mined(u64),
+    Confirmed,
+    /// Was mined, but the block is no longer on the canonical chain.
+    Reorged,
+}
+
+/// Follows sent transactions until they have `ETH_CONFIRMATIONS` blocks on
+/// top and broadcasts them again, with the same nonce, if a reorg drops them.
+/// Urgent transactions that stay unmined are replaced at the same nonce with
+/// a higher gas price. The fee of a confirmed transaction is recorded and its
+/// outcome published on the bus.
+pub fn spawn(
+    config: Config,
+    sent_rx: Receiver<SentTransaction>,
+    bus: Arc<Bus>,
+    fees: Arc<FeeLog>,
+) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("receipt_watcher".to_string())
+        .spawn(move || {
+            let (_eloop, transport) =
+                WebSocket::new(&config.eth_api_url).expect("can not connect to ethereum node");
+            let web3 = web3::Web3::new(transport);
+            let mut watched: Vec<Watched> = vec![];
+            loop {
+                loop {
+                    match sent_rx.try_recv() {
+                        Ok(tx) => watched.push(Watched {
+                            tx,
+                            mined_in: None,
+                            broadcast_at: None,
+                        }),
+                        Err(TryRecvError::Empty) => break,
+                        Err(TryRecvError::Disconnected) if watched.is_empty() => return,
+                        Err(TryRecvError::Disconnected) => break,
+                    }
+                }
+                match web3.eth().block_number().wait() {
+                    Ok(head) => {
+                        watched = watched
+                            .into_iter()
+                            .filter_map(|watched| {
+                                let _context = fatal::message_context(watched.tx.message_id);
+                                match check(&web3, &config, &fees, head.low_u64(), watched) {
+                                    Checked::Pending(watched) => Some(watched),
+                                    Checked::Done(message_id, outcome) => {
+                                        bus.publish(Envelope::outcome(message_id, outcome));
+                                        None
+                                    }
+                                }
+                            })
+                            .collect();
+                    }
+                    Err(e) => log::warn!("can not get ethereum block number: {:?}", e),
+                }
+                debug_state::publish("receipt_watcher", debug_state(&watched));
+                // one poll per block
+                thread::sleep(config.host_chain.block_time);
+            }
+        })
+        .expect("can not started receipt watcher")
+}
+
+/// The sent transactions not confirmed yet.
+fn debug_state(watched: &[Watched]) -> serde_json::Value {
+    let pending = watched
+        .iter()
+        .map(|watched| {
+            json!({
+                "message_id": watched.tx.message_id,
+                "tx_hash": watched.tx.tx_hash,
+                "call": watched.tx.call,
+                "gas_price": watched.tx.gas_price,
+                "replaced": watched.tx.replaced.len(),
+                "mined_in": watched.mined_in,
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({ "pending_submissions": pending })
+}
+
+fn check(
+    web3: &web3::Web3<WebSocket>,
+    config: &Config,
+    fees: &FeeLog,
+    head: u64,
+    mut watched: Watched,
+) -> Checked {
+    let receipt = match receipt(web3, &mut watched.tx) {
+        Ok(receipt) => receipt,
+        Err(e) => {
+            log::warn!("can not get receipt of {:?}: {:?}", watched.tx.tx_hash, e);
+            return Checked::Pending(watched);
+        }
+    };
+    let receipt_block = receipt
+        .as_ref()
+        .and_then(|receipt| receipt.block_number)
+        .map(|block_number| block_number.low_u64());
+    match state(
+        watched.mined_in,
+        receipt_block,
+        head,
+        config.eth_confirmations,
+    ) {
+        State::Unmined => {
+            let broadcast_at = *watched.broadcast_at.get_or_insert(head);
+            if is_rebroadcast_due(watched.tx.urgency, broadcast_at, head) {
+                replace(web3, config, &mut watched.tx, head - broadcast_at);
+                watched.broadcast_at = Some(head);
+            }
+            Checked::Pending(watched)
+        }
+        State::Mined(block_number) => {
+            watched.mined_in = Some(block_number);
+            Checked::Pending(watched)
+        }
+        State::Confirmed => {
+            log::debug!("{} {:?} is confirmed", watched.tx.call, watched.tx.tx_hash);
+            let reverted = receipt.as_ref().and_then(|receipt| receipt.status) == Some(0u64.into());
+            if let Some(gas_used) = receipt.and_then(|receipt| receipt.gas_used) {
+                fees.record(
+                    watched.tx.message_id,
+                    Chain::Ethereum,
+                    &watched.tx.function,
+                    watched.tx.tx_hash,
+                    gas_used * watched.tx.gas_price,
+                    Some(watched.tx.gas_price),
+                );
+            }
+            if reverted {
+                let reason = receipt_block.and_then(|block_number| {
+                    revert_reason(web3, config, &watched.tx, block_number)
+                });
+                return failed(&watched.tx, reverted_reason(&watched.tx.call, reason));
+            }
+            let call = watched.tx.function.clone();
+            Checked::Done(
+                watched.tx.message_id,
+                Outcome::Confirmed(Chain::Ethereum, call, watched.tx.tx_hash),
+            )
+        }
+        State::Reorged => {
+            // nodes usually put transactions of dropped blocks back in the pool
+            if let Ok(Some(_)) = web3
+                .eth()
+                .transaction(TransactionId::Hash(watched.tx.tx_hash))
+                .wait()
+            {
+                log::warn!(
+                    "{} {:?} was dropped by a reorg and is pending again",
+                    watched.tx.call,
+                    watched.tx.tx_hash
+                );
+                watched.mined_in = None;
+                return Checked::Pending(watched);
+            }
+            log::warn!(
+                "{} {:?} was dropped by a reorg, sending it again",
+                watched.tx.call,
+                watched.tx.tx_hash
+            );
+            let raw = watched.tx.sign(config, watched.tx.gas_price);
+            match web3.eth().send_raw_transaction(Bytes::from(raw)).wait() {
+                Ok(_) => {
+                    watched.mined_in = None;
+                    Checked::Pending(watched)
+                }
+                Err(e) => {
+                    let reason = format!(
+                        "can not send {} {:?} again, its nonce may be taken: {:?}",
+                        watched.tx.call, watched.tx.tx_hash, e
+                    );
+                    failed(&watched.tx, reason)
+                }
+            }
+        }
+    }
+}
+
+/// The receipt of the transaction or, if one of those it replaced was mined
+/// instead, of that one, which then becomes the watched transaction.
+fn receipt(
+    web3: &web3::Web3<WebSocket>,
+    tx: &mut SentTransaction,
+) -> Result<Option<TransactionReceipt>, web3::Error> {
+    if let Some(receipt) = web3.eth().transaction_receipt(tx.tx_hash).wait()? {
+        return Ok(Some(receipt));
+    }
+    for index in 0..tx.replaced.len() {
+        let (tx_hash, gas_price) = tx.replaced[index];
+        if let Some(receipt) = web3.eth().transaction_receipt(tx_hash).wait()? {
+            log::info!(
+                "{} {:?} was mined instead of its replacement {:?}",
+                tx.call,
+                tx_hash,
+                tx.tx_hash
+            );
+            tx.replaced[index] = (tx.tx_hash, tx.gas_price);
+            tx.tx_hash = tx_hash;
+            tx.gas_price = gas_price;
+            return Ok(Some(receipt));
+        }
+    }
+    Ok(None)
+}
+
+/// Signs the transaction again with the same nonce and a higher gas price,
+/// so that it replaces the one waiting in the pool. At the highest price it
+/// is only broadcast again.
+fn replace(web3: &web3::Web3<WebSocket>, config: &Config, tx: &mut SentTransaction, waited: u64) {
+    let max_gas_price = U256::from(config.eth_gas_price.saturating_mul(MAX_REPLACEMENT_FACTOR));
+    let gas_price = bump(tx.gas_price, max_gas_price);
+    if gas_price == tx.gas_price {
+        log::info!(
+            "{} {:?} is not mined after {} blocks, broadcasting it again",
+            tx.call,
+            tx.tx_hash,
+            waited
+        );
+        // the node answers "already known" while it still has it
+        let raw = tx.sign(config, tx.gas_price);
+        if let Err(e) = web3.eth().send_raw_transaction(Bytes::from(raw)).wait() {
+            log::debug!("broadcasting {:?} again: {:?}", tx.tx_hash, e);
+        }
+        return;
+    }
+    let raw = tx.sign(config, gas_price);
+    match web3.eth().send_raw_transaction(Bytes::from(raw)).wait() {
+        Ok(tx_hash) => {
+            log::info!(
+                "{} {:?} is not mined after {} blocks, replaced it with {:?} at gas price {}",
+                tx.call,
+                tx.tx_hash,
+                waited,
+                tx_hash,
+                gas_price
+            );
+            tx.replaced.push((tx.tx_hash, tx.gas_price));
+            tx.tx_hash = tx_hash;
+            tx.gas_price = gas_price;
+        }
+        // e.g. the nonce is taken, as the transaction got mined meanwhile
+        Err(e) => log::warn!("can not replace {} {:?}: {:?}", tx.call, tx.tx_hash, e),
+    }
+}
+
+/// The gas price of a replacement, 12.5% more, which nodes require at least
+/// to replace a pending transaction, up to `max`.
+fn bump(gas_price: U256, max: U256) -> U256 {
+    if gas_price >= max {
+        return gas_price;
+    }
+    let bumped = gas_price + (gas_price + 7) / 8;
+    bumped.min(max)
+}
+
+fn is_rebroadcast_due(urgency: Urgency, broadcast_at: u64, head: u64) -> bool {
+    match urgency.rebroadcast_blocks() {
+        Some(blocks) => head >= broadcast_at + blocks,
+        None => false,
+    }
+}
+
+/// Why the call reverted, replayed with `eth_call` in the block it was mined
+/// in. Nodes answer a `require` that fails with its message as the error of
+/// the call, older ones as its return data. None when the node can not tell,
+/// e.g. as it no longer has the state of the block.
+fn revert_reason(
+    web3: &web3::Web3<WebSocket>,
+    config: &Config,
+    tx: &SentTransaction,
+    block_number: u64,
+) -> Option<String> {
+    let request = CallRequest {
+        from: Some(config.eth_validator_address),
+        to: tx.to,
+        gas: None,
+        gas_price: None,
+        value: None,
+        data: Some(Bytes::from(tx.data.clone())),
+    };
+    let block = BlockNumber::Number(block_number.into());
+    match web3.eth().call(request, Some(block)).wait() {
+        Ok(output) => decode_revert_reason(&output.0),
+        Err(e) => reason_in_error(&format!("{:?}", e)),
+    }
+}
+
+/// The reason in a node's error for a reverting call, decoded from its
+/// revert data when it has some, the whole error otherwise.
+fn reason_in_error(error: &str) -> Option<String> {
+    if !error.to_lowercase().contains("revert") {
+        log::debug!("can not replay the reverted call: {}", error);
+        return None;
+    }
+    error
+        .find("0x08c379a0")
+        .map(|at| {
+            error[at + 2..]
+                .chars()
+                .take_while(char::is_ascii_hexdigit)
+                .collect::<String>()
+        })
+        .and_then(|data| data.from_hex::<Vec<u8>>().ok())
+        .and_then(|data| decode_revert_reason(&data))
+        .or_else(|| Some(error.to_string()))
+}
+
+fn decode_revert_reason(output: &[u8]) -> Option<String> {
+    if output.len() < 4 || output[..4] != ERROR_SELECTOR {
+        return None;
+    }
+    match ethabi::decode(&[ethabi::ParamType::String], &output[4..])
+        .ok()?
+        .pop()?
+    {
+        ethabi::Token::String(reason) => Some(reason),
+        _ => None,
+    }
+}
+
+/// The failure reported for a reverted call, with the revert reason when
+/// there is one, so the auth guard can tell calls this validator is not
+/// allowed to make.
+fn reverted_reason(call: &str, reason: Option<String>) -> String {
+    match reason {
+        Some(reason) => format!("{} reverted: {}", call, reason),
+        None => format!("{} reverted", call),
+    }
+}
+
+fn failed(tx: &SentTransaction, reason: String) -> Checked {
+    let call = tx.function.clone();
+    Checked::Done(
+        tx.message_id,
+        Outcome::Failed(Chain::Ethereum, call, reason),
+    )
+}
+
+fn state(
+    mined_in: Option<u64>,
+    receipt_block: Option<u64>,
+    head: u64,
+    confirmations: u64,
+) -> State {
+    match (receipt_block, mined_in) {
+        (Some(block_number), _) if head + 1 >= block_number + confirmations => State::Confirmed,
+        (Some(block_number), _) => State::Mined(block_number),
+        (None, Some(_)) => State::Reorged,
+        (None, None) => State::Unmined,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth_guard::AuthGuard;
+    use rustc_hex::ToHex;
+
+    #[test]
+    fn state_tests() {
+        assert_eq!(State::Unmined, state(None, None, 100, 12));
+        assert_eq!(State::Mined(95), state(None, Some(95), 100, 12));
+        assert_eq!(State::Confirmed, state(Some(89), Some(89), 100, 12));
+        assert_eq!(State::Reorged, state(Some(95), None, 101, 12));
+        // re-mined in another block after a reorg
+        assert_eq!(State::Mined(99), state(Some(95), Some(99), 101, 12));
+    }
+
+    #[test]
+    fn is_rebroadcast_due_tests() {
+        assert!(!is_rebroadcast_due(Urgency::Normal, 100, 200));
+        assert!(!is_rebroadcast_due(Urgency::High, 100, 104));
+        assert!(is_rebroadcast_due(Urgency::High, 100, 105));
+        assert!(is_rebroadcast_due(Urgency::Critical, 100, 102));
+    }
+
+    #[test]
+    fn bump_tests() {
+        let max = U256::from(400);
+        assert_eq!(U256::from(113), bump(100.into(), max));
+        // rounded up, so the replacement is never underpriced
+        assert_eq!(U256::from(10), bump(8.into(), max));
+        assert_eq!(U256::from(2), bump(1.into(), max));
+        assert_eq!(max, bump(380.into(), max));
+        assert_eq!(max, bump(max, max));
+        // already paid more than the cap
+        assert_eq!(U256::from(500), bump(500.into(), max));
+    }
+    #[test]
+    fn revert_reason_tests() {
+        let message = "Caller is not a validator".to_string();
+        let mut output = ERROR_SELECTOR.to_vec();
+        output.extend(ethabi::encode(&[ethabi::Token::String(message.clone())]));
+        assert_eq!(Some(message.clone()), decode_revert_reason(&output));
+        assert_eq!(None, decode_revert_reason(&[]));
+        assert_eq!(None, decode_revert_reason(&output[4..]));
+
+        // how geth answers an eth_call that reverts
+        let error = format!(
+            "Rpc(Error {{ code: ServerError(3), message: \"execution reverted: {}\", \
+             data: Some(String(\"0x{}\")) }})",
+            message,
+            output.to_hex::<String>()
+        );
+        assert_eq!(Some(message.clone()), reason_in_error(&error));
+        let without_data =
+            "Rpc(Error { code: ServerError(-32000), message: \"execution reverted\" })";
+        assert_eq!(
+            Some(without_data.to_string()),
+            reason_in_error(without_data)
+        );
+        assert_eq!(
+            None,
+            reason_in_error("Rpc(Error { message: \"missing trie node\" })")
+        );
+
+        // the reason as reported trips the auth guard, a plain revert does not
+        let outcome = |reason| {
+            Outcome::Failed(
+                Chain::Ethereum,
+                "approveTransfer".to_string(),
+                reverted_reason("approveTransfer(0x01)", reason),
+            )
+        };
+        let mut guard = AuthGuard::new(1);
+        assert_eq!(None, guard.observe(&outcome(None)));
+        assert_eq!(
+            Some(Chain::Ethereum),
+            guard.observe(&outcome(reason_in_error(&error)))
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use lazy_static::lazy_static;
+use log;
+use serde_json::Value;
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use crate::attestations;
+#[cfg(all(feature = "sqlite", feature = "admin-api"))]
+use crate::clock;
+use crate::config::Config;
+#[cfg(feature = "sqlite")]
+use crate::database;
+use crate::dead_letters;
+use crate::fees;
+#[cfg(feature = "graph-node")]
+use crate::graph_node_event_listener;
+use crate::metrics;
+use crate::submissions;
+
+pub const DAY_SECS: u64 = 24 * 60 * 60;
+
+lazy_static! {
+    /// Counts the files compaction replaced. Appends hold it for reading, so
+    /// no line goes to a file while it is being replaced.
+    static ref GENERATION: RwLock<u64> = RwLock::new(0);
+}
+
+/// How many days of finished work are kept, 0 keeps it forever.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Retention {
+    /// Confirmed transfers listed by the admin API, in the database and in
+    /// memory. The confirmed transfers of `confirmed_transfers` are never
+    /// pruned, the executor relies on them to not confirm a replayed message
+    /// again.
+    pub transfer_days: u64,
+    /// Submissions, fees, attestations, dead letters and skipped messages.
+    pub audit_days: u64,
+    /// How often the running validator compacts.
+    pub interval: Duration,
+}
+
+impl Retention {
+    /// Whether anything in the data dir is pruned.
+    pub fn is_enabled(&self) -> bool {
+        self.audit_days > 0 || self.transfer_days > 0
+    }
+}
+
+/// An append-only JSON lines file that stays writable while compaction
+/// replaces it: the first line after that reopens it.
+#[derive(Debug)]
+pub struct AppendLog {
+    path: PathBuf,
+    file: Mutex<(File, u64)>,
+}
+
+impl AppendLog {
+    pub fn open(path: PathBuf) -> io::Result<Self> {
+        let generation = GENERATION.read().expect("compaction lock poisoned");
+        let file = open_append(&path)?;
+        Ok(AppendLog {
+            path,
+            file: Mutex::new((file, *generation)),
+        })
+    }
+
+    pub fn append(&self, line: &str) -> io::Result<()> {
+        let generation = GENERATION.read().expect("compaction lock poisoned");
+        let mut file = self.file.lock().expect("append log lock poisoned");
+        if file.1 != *generation {
+            *file = (open_append(&self.path)?, *generation);
+        }
+        writeln!(file.0, "{}", line)
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    path.parent().map_or(Ok(()), fs::create_dir_all)?;
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Drops the lines whose unix time in `field` is before `before` and returns
+/// how many. Lines without one, e.g. cut short by a crash, are kept. The file
+/// is read and rewritten while appends go on, only the lines appended
+/// meanwhile are copied over with them held.
+pub fn compact(path: &Path, field: &str, before: i64) -> Result<usize, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(format!("can not read {:?}: {}", path, e)),
+    };
+    // a line being appended is left to the copy of the appended lines
+    let content = &content[..content.rfind('\n').map_or(0, |end| end + 1)];
+    let mut kept = String::with_capacity(content.len());
+    let mut pruned = 0;
+    for line in content.lines() {
+        let time = serde_json::from_str::<Value>(line)
+            .ok()
+            .and_then(|entry| entry[field].as_i64());
+        match time {
+            Some(time) if time < before => pruned += 1,
+            _ => {
+                kept.push_str(line);
+                kept.push('\n');
+            }
+        }
+    }
+    if pruned == 0 {
+        return Ok(0);
+    }
+    // written aside first, a crash while compacting must not lose the file
+    let temporary = path.with_extension("jsonl.tmp");
+    let error = |e: io::Error| format!("can not compact {:?}: {}", path, e);
+    fs::write(&temporary, kept).map_err(error)?;
+    let mut generation = GENERATION.write().expect("compaction lock poisoned");
+    append_since(path, content.len() as u64, &temporary)
+        .and_then(|()| fs::rename(&temporary, path))
+        .map_err(error)?;
+    *generation += 1;
+    Ok(pruned)
+}
+
+/// Copies what was appended to `path` past `offset` to the end of `to`.
+fn append_since(path: &Path, offset: u64, to: &Path) -> io::Result<()> {
+    let mut appended = File::open(path)?;
+    appended.seek(SeekFrom::Start(offset))?;
+    let mut to = OpenOptions::new().append(true).open(to)?;
+    io::copy(&mut appended, &mut to).map(|_| ())
+}
+
+/// The files `RETENTION_AUDIT_DAYS` applies to, with the field that has the
+/// unix time of an entry. `confirmed_transfers` is not one of them, see
+/// `Retention::transfer_days`.
+fn files() -> Vec<(&'static str, &'static str)> {
+    let files = vec![
+        (submissions::FILE_NAME, "submitted_at"),
+        (fees::FILE_NAME, "recorded_at"),
+        (attestations::FILE_NAME, "attested_at"),
+        (dead_letters::FILE_NAME, "recorded_at"),
+    ];
+    #[cfg(feature = "graph-node")]
+    let files = [
+        files,
+        vec![(graph_node_event_listener::SKIPPED_FILE_NAME, "skipped_at")],
+    ]
+    .concat();
+    files
+}
+
+/// Prunes what is older than the retention allows and returns how many
+/// entries each file or table lost. Fees within `GAS_RUNWAY_WINDOW_SECS`
+/// are kept, the gas runway is estimated from them.
+pub fn prune(config: &Config, retention: &Retention) -> Result<Vec<(String, usize)>, String> {
+    let mut pruned = vec![];
+    #[cfg(all(feature = "sqlite", feature = "admin-api"))]
+    {
+        if retention.transfer_days > 0 {
+            let before = clock::now().saturating_sub(retention.transfer_days * DAY_SECS);
+            let transfers = database::shared(&config.data_dir)?
+                .lock()
+                .expect("database lock poisoned")
+                .prune_transfers(before)?;
+            pruned.push(("database transfers".to_string(), transfers));
+        }
+    }
+    if retention.audit_days == 0 {
+        return Ok(pruned);
+    }
+    let now = time::now_utc().to_timespec().sec;
+    let cutoff = now - (retention.audit_days * DAY_SECS) as i64;
+    let fees_cutoff = cutoff.min(now - config.gas_runway_window_secs as i64);
+    for (file_name, field) in files() {
+        let before = if file_name == fees::FILE_NAME {
+            fees_cutoff
+        } else {
+            cutoff
+        };
+        let path = Path::new(&config.data_dir).join(file_name);
+        pruned.push((file_name.to_string(), compact(&path, field, before)?));
+    }
+    #[cfg(feature = "sqlite")]
+    {
+        let (submissions, fees, attestations) = database::shared(&config.data_dir)?
+            .lock()
+            .expect("database lock poisoned")
+            .prune(cutoff, fees_cutoff)?;
+        pruned.push(("database submissions".to_string(), submissions));
+        pruned.push(("database fees".to_string(), fees));
+        pruned.push(("database attestations".to_string(), attestations));
+    }
+    Ok(pruned)
+}
+
+/// Compacts every `RETENTION_INTERVAL_SECS` while the validator runs.
+pub fn spawn(config: Config) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("retention".to_string())
+        .spawn(move || loop {
+            match prune(&config, &config.retention) {
+                Ok(pruned) => {
+                    for (store, count) in pruned.into_iter().filter(|(_, count)| *count > 0) {
+                        log::info!("pruned {} entries from {}", count, store);
+                        metrics::add(
+                            "validator_pruned_entries_total",
+                            &[("store", &store)],
+                            count as u64,
+                        );
+                    }
+                }
+                Err(e) => log::warn!("can not prune: {}", e),
+            }
+            thread::sleep(config.retention.interval);
+        })
+        .expect("can not start retention")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn compact_test() {
+        let dir = TestDir::new("retention");
+        let path = dir.join("log.jsonl");
+        let log = AppendLog::open(path.clone()).unwrap();
+        log.append(r#"{"id": 1, "at": 10}"#).unwrap();
+        log.append(r#"{"id": 2, "at": 20}"#).unwrap();
+        log.append(r#"{"id": 3, "#).unwrap();
+
+        assert_eq!(Ok(1), compact(&path, "at", 15));
+        assert_eq!(Ok(0), compact(&path, "at", 15));
+        // the open log writes to the compacted file
+        log.append(r#"{"id": 4, "at": 30}"#).unwrap();
+        assert_eq!(
+            "{\"id\": 2, \"at\": 20}\n{\"id\": 3, \n{\"id\": 4, \"at\": 30}\n",
+            fs::read_to_string(&path).unwrap()
+        );
+        assert_eq!(Ok(0), compact(&dir.join("missing.jsonl"), "at", 15));
+
+        // lines appended after the file was read survive the rewrite
+        let temporary = path.with_extension("jsonl.tmp");
+        fs::write(&temporary, "{\"id\": 4, \"at\": 30}\n").unwrap();
+        let read = fs::metadata(&path).unwrap().len();
+        log.append(r#"{"id": 5, "at": 40}"#).unwrap();
+        append_since(&path, read, &temporary).unwrap();
+        assert_eq!(
+            "{\"id\": 4, \"at\": 30}\n{\"id\": 5, \"at\": 40}\n",
+            fs::read_to_string(&temporary).unwrap()
+        );
+    }
+}
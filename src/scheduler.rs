@@ -0,0 +1,225 @@
+use futures::{future::lazy, Future};
+use tokio_threadpool::{Builder, Sender as Pool, ThreadPool};
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub type Job = Box<dyn FnOnce() -> Next + Send>;
+
+type Queues<K> = Arc<Mutex<HashMap<K, VecDeque<Job>>>>;
+
+/// What is left to do once a job ran.
+pub enum Next {
+    Done,
+    /// Runs the job at the instant. No worker waits for it meanwhile, the
+    /// later jobs with the same key do.
+    RetryAt(Instant, Job),
+}
+
+/// Runs jobs with different keys concurrently while jobs with the same key
+/// run one after another in the order they were scheduled.
+pub struct Scheduler<K> {
+    pool: ThreadPool,
+    queues: Queues<K>,
+    retries: Sender<(Instant, K, Job)>,
+}
+
+impl<K> Scheduler<K>
+where
+    K: Eq + Hash + Clone + Send + 'static,
+{
+    pub fn new(name: &str, workers: usize) -> Self {
+        let pool = Builder::new()
+            .pool_size(workers.max(1))
+            .name_prefix(format!("{}-", name))
+            .build();
+        let queues = Arc::new(Mutex::new(HashMap::new()));
+        let retries = spawn_retries(name, pool.sender().clone(), queues.clone());
+        Scheduler {
+            pool,
+            queues,
+            retries,
+        }
+    }
+
+    pub fn schedule(&self, key: K, job: Job) {
+        let mut queues = self.queues.lock().expect("scheduler lock poisoned");
+        // a key has a queue while one of its jobs is running or waits to be retried
+        if let Some(queue) = queues.get_mut(&key) {
+            queue.push_back(job);
+            return;
+        }
+        queues.insert(key.clone(), VecDeque::new());
+        drop(queues);
+        run(
+            self.pool.sender(),
+            self.queues.clone(),
+            self.retries.clone(),
+            key,
+            job,
+        );
+    }
+
+    pub fn activity(&self) -> Activity<K> {
+        Activity(self.queues.clone())
+    }
+
+    /// Waits for all scheduled jobs to finish, retries included.
+    pub fn shutdown(self) {
+        while !self.activity().is_idle() {
+            thread::sleep(Duration::from_millis(100));
+        }
+        let _ = self.pool.shutdown_on_idle().wait();
+    }
+}
+
+/// Keeps the jobs to retry until their time comes and runs them then.
+fn spawn_retries<K>(name: &str, pool: Pool, queues: Queues<K>) -> Sender<(Instant, K, Job)>
+where
+    K: Eq + Hash + Send + 'static,
+{
+    let (retries, retries_rx) = channel::<(Instant, K, Job)>();
+    let retries_tx = retries.clone();
+    thread::Builder::new()
+        .name(format!("{}-retries", name))
+        .spawn(move || {
+            let mut waiting = vec![];
+            loop {
+                let now = Instant::now();
+                let (due, later): (Vec<_>, Vec<_>) =
+                    waiting.into_iter().partition(|(at, _, _)| *at <= now);
+                waiting = later;
+                for (_, key, job) in due {
+                    run(&pool, queues.clone(), retries_tx.clone(), key, job);
+                }
+                let timeout = waiting
+                    .iter()
+                    .map(|(at, _, _)| at.duration_since(now))
+                    .min()
+                    .unwrap_or_else(|| Duration::from_secs(60));
+                match retries_rx.recv_timeout(timeout) {
+                    Ok(retry) => waiting.push(retry),
+                    Err(RecvTimeoutError::Timeout) => (),
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+        })
+        .expect("can not start scheduler retries");
+    retries
+}
+
+/// Runs the job and the ones queued after it with the same key, until the
+/// queue is empty or a job is to be retried.
+fn run<K>(pool: &Pool, queues: Queues<K>, retries: Sender<(Instant, K, Job)>, key: K, job: Job)
+where
+    K: Eq + Hash + Send + 'static,
+{
+    let _ = pool.spawn(lazy(move || {
+        let mut job = job;
+        loop {
+            if let Next::RetryAt(at, retry) = job() {
+                let _ = retries.send((at, key, retry));
+                return Ok(());
+            }
+            let mut queues = queues.lock().expect("scheduler lock poisoned");
+            let next = queues.get_mut(&key).and_then(|queue| queue.pop_front());
+            match next {
+                Some(next) => job = next,
+                None => {
+                    queues.remove(&key);
+                    return Ok(());
+                }
+            }
+        }
+    }));
+}
+
+/// Tells other threads whether the scheduler still has jobs to run.
+#[derive(Clone)]
+pub struct Activity<K>(Arc<Mutex<HashMap<K, VecDeque<Job>>>>);
+
+impl<K> Activity<K> {
+    pub fn is_idle(&self) -> bool {
+        self.0.lock().expect("scheduler lock poisoned").is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use std::time::Duration;
+
+    #[test]
+    fn same_key_jobs_run_in_order() {
+        let scheduler = Scheduler::new("test", 4);
+        let results = Arc::new(Mutex::new(vec![]));
+        for i in 0..20 {
+            let results = results.clone();
+            scheduler.schedule(
+                1,
+                Box::new(move || {
+                    std::thread::sleep(Duration::from_millis(1));
+                    results.lock().unwrap().push(i);
+                    Next::Done
+                }),
+            );
+        }
+        scheduler.shutdown();
+        assert_eq!((0..20).collect::<Vec<_>>(), *results.lock().unwrap());
+    }
+
+    #[test]
+    fn different_key_jobs_run_concurrently() {
+        let scheduler = Scheduler::new("test", 2);
+        let (tx, rx) = channel();
+        let (done_tx, done_rx) = channel();
+        scheduler.schedule(
+            1,
+            Box::new(move || {
+                let received = rx.recv_timeout(Duration::from_secs(5)).is_ok();
+                done_tx.send(received).unwrap();
+                Next::Done
+            }),
+        );
+        scheduler.schedule(
+            2,
+            Box::new(move || {
+                tx.send(()).unwrap();
+                Next::Done
+            }),
+        );
+        assert_eq!(Ok(true), done_rx.recv_timeout(Duration::from_secs(10)));
+        scheduler.shutdown();
+    }
+
+    #[test]
+    fn retried_jobs_free_the_worker_and_keep_their_place() {
+        let scheduler = Scheduler::new("test", 1);
+        let results = Arc::new(Mutex::new(vec![]));
+        let push = |i| {
+            let results = results.clone();
+            Box::new(move || {
+                results.lock().unwrap().push(i);
+                Next::Done
+            }) as Job
+        };
+        let retried = push(1);
+        let at = Instant::now() + Duration::from_millis(200);
+        scheduler.schedule(1, Box::new(move || Next::RetryAt(at, retried)));
+        scheduler.schedule(1, push(2));
+        // the only worker is free for other keys meanwhile
+        scheduler.schedule(2, push(3));
+        let activity = scheduler.activity();
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(vec![3], *results.lock().unwrap());
+        assert!(!activity.is_idle());
+        scheduler.shutdown();
+        assert_eq!(vec![3, 1, 2], *results.lock().unwrap());
+        assert!(activity.is_idle());
+    }
+}
@@ -0,0 +1,87 @@
+use std::fmt;
+use std::str::FromStr;
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Wrapper for keys and phrases that must never end up in logs.
+/// `Debug` and `Display` are redacted; the value is only reachable
+/// through `expose`.
+#[derive(Clone, Default, PartialEq)]
+pub struct Secret<T>(T);
+
+impl<T> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Secret(value)
+    }
+
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Secret(value)
+    }
+}
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({})", REDACTED)
+    }
+}
+
+impl<T> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+/// Where validator keys are loaded from. With any backend other than `Env`
+/// the config holds a reference to the secret instead of the secret itself:
+/// `<mount>/data/<path>#<field>` for Vault, a secret version resource name for
+/// GCP and a secret id or ARN for AWS. GCP and AWS references may also end in
+/// `#<field>` to pick one key out of a JSON secret.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Backend {
+    Env,
+    Vault,
+    Gcp,
+    Aws,
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Backend::Env
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "env" => Ok(Backend::Env),
+            "vault" => Ok(Backend::Vault),
+            "gcp" => Ok(Backend::Gcp),
+            "aws" => Ok(Backend::Aws),
+            _ => Err(format!(
+                "unknown secret backend {:?}, expected env, vault, gcp or aws",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_is_redacted() {
+        let secret = Secret::new("0x4646464646464646".to_string());
+        assert_eq!("Secret([REDACTED])", format!("{:?}", secret));
+        assert_eq!("[REDACTED]", format!("{}", secret));
+        assert_eq!("0x4646464646464646", secret.expose());
+    }
+}
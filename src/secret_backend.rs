@@ -0,0 +1,256 @@
+use hmac::{Hmac, Mac};
+use rustc_hex::ToHex;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use std::env;
+
+use crate::net;
+use crate::secret::Backend;
+
+const GCP_METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+const GCP_SECRET_MANAGER_URL: &str = "https://secretmanager.googleapis.com/v1";
+const AWS_SERVICE: &str = "secretsmanager";
+
+pub fn fetch(backend: &Backend, reference: &str) -> Result<String, String> {
+    let (path, field) = split_reference(reference);
+    match backend {
+        Backend::Env => Err("env backend does not fetch secrets".to_string()),
+        Backend::Vault => {
+            let field = field.ok_or("vault secret reference must end with #<field>")?;
+            fetch_from_vault(path, field)
+        }
+        Backend::Gcp => fetch_from_gcp(path).and_then(|secret| select_field(secret, field)),
+        Backend::Aws => fetch_from_aws(path).and_then(|secret| select_field(secret, field)),
+    }
+}
+
+fn fetch_from_vault(path: &str, field: &str) -> Result<String, String> {
+    let address = read_var("VAULT_ADDR")?;
+    let token = read_var("VAULT_TOKEN")?;
+    let url = format!("{}/v1/{}", address.trim_end_matches('/'), path);
+    let response: Value = net::http_client()
+        .get(&url)
+        .header("X-Vault-Token", token)
+        .send()
+        .and_then(|res| res.error_for_status())
+        .and_then(|mut res| res.json())
+        .map_err(|e| format!("vault request failed: {}", e))?;
+
+    // KV v2 nests the secret under data.data, KV v1 under data
+    response["data"]["data"][field]
+        .as_str()
+        .or_else(|| response["data"][field].as_str())
+        .map(str::to_string)
+        .ok_or_else(|| format!("vault secret {} has no field {:?}", path, field))
+}
+
+fn fetch_from_gcp(name: &str) -> Result<String, String> {
+    let token = match env::var("GCP_ACCESS_TOKEN") {
+        Ok(token) => token,
+        Err(_) => fetch_gcp_metadata_token()?,
+    };
+    let url = format!("{}/{}:access", GCP_SECRET_MANAGER_URL, name);
+    let response: Value = net::http_client()
+        .get(&url)
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .and_then(|res| res.error_for_status())
+        .and_then(|mut res| res.json())
+        .map_err(|e| format!("gcp secret manager request failed: {}", e))?;
+
+    let data = response["payload"]["data"]
+        .as_str()
+        .ok_or_else(|| format!("gcp secret {} has no payload", name))?;
+    let data = base64::decode(data).map_err(|_| "gcp secret payload is not base64".to_string())?;
+    String::from_utf8(data).map_err(|_| "gcp secret payload is not utf-8".to_string())
+}
+
+fn fetch_gcp_metadata_token() -> Result<String, String> {
+    let response: Value = net::http_client()
+        .get(GCP_METADATA_TOKEN_URL)
+        .header("Metadata-Flavor", "Google")
+        .send()
+        .and_then(|res| res.error_for_status())
+        .and_then(|mut res| res.json())
+        .map_err(|e| format!("can not get gcp access token from metadata server: {}", e))?;
+    response["access_token"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "gcp metadata server returned no access token".to_string())
+}
+
+fn fetch_from_aws(secret_id: &str) -> Result<String, String> {
+    let region = read_var("AWS_REGION")?;
+    let access_key = read_var("AWS_ACCESS_KEY_ID")?;
+    let secret_key = read_var("AWS_SECRET_ACCESS_KEY")?;
+    let session_token = env::var("AWS_SESSION_TOKEN").ok();
+
+    let host = format!("{}.{}.amazonaws.com", AWS_SERVICE, region);
+    let body = json!({ "SecretId": secret_id }).to_string();
+    let now = time::now_utc();
+    let amz_date = time::strftime("%Y%m%dT%H%M%SZ", &now).expect("valid time format");
+    let date = &amz_date[..8];
+
+    let mut headers = vec![
+        ("content-type", "application/x-amz-json-1.1".to_string()),
+        ("host", host.clone()),
+        ("x-amz-date", amz_date.clone()),
+        ("x-amz-target", "secretsmanager.GetSecretValue".to_string()),
+    ];
+    if let Some(session_token) = session_token {
+        headers.push(("x-amz-security-token", session_token));
+    }
+    headers.sort_by(|a, b| a.0.cmp(b.0));
+    let authorization = aws_authorization(
+        &access_key,
+        &secret_key,
+        &region,
+        date,
+        &amz_date,
+        &headers,
+        &body,
+    );
+
+    let mut request = net::http_client()
+        .post(&format!("https://{}/", host))
+        .header("Authorization", authorization)
+        .body(body);
+    for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+        request = request.header(name, value);
+    }
+    let response: Value = request
+        .send()
+        .and_then(|res| res.error_for_status())
+        .and_then(|mut res| res.json())
+        .map_err(|e| format!("aws secrets manager request failed: {}", e))?;
+
+    response["SecretString"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| format!("aws secret {} has no SecretString", secret_id))
+}
+
+/// Builds the AWS Signature Version 4 `Authorization` header for a POST to `/`.
+/// `headers` must be sorted by name and include `host` and `x-amz-date`.
+fn aws_authorization(
+    access_key: &str,
+    secret_key: &str,
+    region: &str,
+    date: &str,
+    amz_date: &str,
+    headers: &[(&str, String)],
+    body: &str,
+) -> String {
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "POST\n/\n\n{}\n{}\n{}",
+        canonical_headers,
+        signed_headers,
+        sha256_hex(body.as_bytes())
+    );
+    let scope = format!("{}/{}/{}/aws4_request", date, region, AWS_SERVICE);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        sha256_hex(canonical_request.as_bytes())
+    );
+    let signature: String = hmac_sha256(
+        &aws_signing_key(secret_key, date, region, AWS_SERVICE),
+        string_to_sign.as_bytes(),
+    )
+    .to_hex();
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        access_key, scope, signed_headers, signature
+    )
+}
+
+fn aws_signing_key(secret_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac_sha256(format!("AWS4{}", secret_key).as_bytes(), date.as_bytes());
+    let key = hmac_sha256(&key, region.as_bytes());
+    let key = hmac_sha256(&key, service.as_bytes());
+    hmac_sha256(&key, b"aws4_request")
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_varkey(key).expect("hmac accepts keys of any size");
+    mac.input(data);
+    mac.result().code().to_vec()
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).as_slice().to_hex()
+}
+
+fn split_reference(reference: &str) -> (&str, Option<&str>) {
+    match reference.rfind('#') {
+        Some(index) => (&reference[..index], Some(&reference[index + 1..])),
+        None => (reference, None),
+    }
+}
+
+fn select_field(secret: String, field: Option<&str>) -> Result<String, String> {
+    match field {
+        None => Ok(secret),
+        Some(field) => serde_json::from_str::<Value>(&secret)
+            .ok()
+            .and_then(|value| value[field].as_str().map(str::to_string))
+            .ok_or_else(|| format!("secret is not a JSON object with field {:?}", field)),
+    }
+}
+
+fn read_var(name: &str) -> Result<String, String> {
+    env::var(name).map_err(|_| format!("{} is not set", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_reference_tests() {
+        assert_eq!(
+            ("secret/data/validator", Some("eth_private_key")),
+            split_reference("secret/data/validator#eth_private_key")
+        );
+        assert_eq!(("arn:aws:secret", None), split_reference("arn:aws:secret"));
+    }
+
+    #[test]
+    fn select_field_tests() {
+        let secret = r#"{"mnemonic": "january february"}"#.to_string();
+        assert_eq!(
+            Ok("january february".to_string()),
+            select_field(secret.clone(), Some("mnemonic"))
+        );
+        assert_eq!(Ok(secret.clone()), select_field(secret.clone(), None));
+        assert!(select_field(secret, Some("private_key")).is_err());
+    }
+
+    /// Signing key example from the AWS Signature Version 4 documentation.
+    #[test]
+    fn aws_signing_key_test() {
+        let key = aws_signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d",
+            key.to_hex::<String>()
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use graphql_client::{GraphQLQuery, Response};
+use primitives::crypto::Ss58Codec;
+use serde::Serialize;
+use web3::{
+    futures::Future,
+    transports::WebSocket,
+    types::{H160, H256, U256},
+};
+
+use std::collections::BTreeMap;
+
+use crate::bridge_contract::BridgeContract;
+use crate::config::Config;
+use crate::net;
+use crate::substrate_transactions;
+
+/// Pallets whose storage is dumped: the bridge itself and the token registry.
+const PALLETS: [&str; 2] = ["Bridge", "Token"];
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "res/graph_node_schema.graphql",
+    query_path = "res/graph_node_snapshot.graphql",
+    response_derives = "Debug,Serialize"
+)]
+struct Snapshot;
+
+/// Bridge state on both chains at one point in time, for incident analysis
+/// and support tickets.
+#[derive(Debug, Serialize)]
+pub struct State {
+    taken_at: i64,
+    ethereum: Ethereum,
+    /// Bridge status, limits and unfinalized messages as indexed by the graph
+    /// node; the bridge contract ABI does not expose them.
+    graph_node: snapshot::ResponseData,
+    substrate: Substrate,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Ethereum {
+    pub token_bridge: H160,
+    pub block_number: u64,
+    pub validators: Vec<H160>,
+    pub quorum: U256,
+    pub validators_generation: U256,
+    pub pending_operations: Vec<Operation>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Operation {
+    pub operation: H256,
+    pub votes: U256,
+    pub mask: U256,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Substrate {
+    pub validator_accounts: Vec<String>,
+    /// Raw SCALE encoded key-value pairs per pallet, decode them with the
+    /// runtime metadata.
+    pub storage: BTreeMap<String, Vec<(String, String)>>,
+}
+
+/// Reads the bridge state from the Ethereum node, the graph node and the
+/// Substrate node. Fails if any of them can not be read, a partial snapshot
+/// would be misleading.
+pub fn take(config: &Config) -> Result<State, String> {
+    Ok(State {
+        taken_at: time::now_utc().to_timespec().sec,
+        ethereum: ethereum(config)?,
+        graph_node: graph_node(config)?,
+        substrate: substrate(config)?,
+    })
+}
+
+fn ethereum(config: &Config) -> Result<Ethereum, String> {
+    let (_eloop, transport) = WebSocket::new(&config.eth_api_url)
+        .map_err(|e| format!("can not connect to {}: {:?}", config.eth_api_url, e))?;
+    let web3 = web3::Web3::new(transport);
+    let bridge = BridgeContract::new(&web3, &config.token_bridge);
+    let error = |e| format!("can not read the token bridge: {:?}", e);
+    let pending_operations = bridge
+        .pending_operations()
+        .map_err(error)?
+        .into_iter()
+        .map(|operation| {
+            let votes = bridge.votes(operation).map_err(error)?;
+            Ok(Operation {
+                operation,
+                votes: votes.count,
+                mask: votes.mask,
+            })
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(Ethereum {
+        token_bridge: config.token_bridge.address,
+        block_number: web3
+            .eth()
+            .block_number()
+            .wait()
+            .map_err(|e| format!("can not get block number: {:?}", e))?
+            .low_u64(),
+        validators: bridge.validators().map_err(error)?,
+        quorum: bridge.quorum().map_err(error)?,
+        validators_generation: bridge.validators_generation().map_err(error)?,
+        pending_operations,
+    })
+}
+
+fn graph_node(config: &Config) -> Result<snapshot::ResponseData, String> {
+    let request_body = Snapshot::build_query(snapshot::Variables {
+        statuses: config
+            .unfinalized_statuses
+            .iter()
+            .map(|status| snapshot::Status::Other(status.clone()))
+            .collect(),
+    });
+    let response: Response<snapshot::ResponseData> = net::http_client()
+        .post(&config.graph_node_api_url)
+        .json(&request_body)
+        .send()
+        .and_then(|mut response| response.json())
+        .map_err(|e| format!("can not query the graph node: {}", e))?;
+    response
+        .data
+        .ok_or_else(|| format!("no data from the graph node, errors: {:?}", response.errors))
+}
+
+fn substrate(config: &Config) -> Result<Substrate, String> {
+    let validator_accounts =
+        substrate_transactions::get_validator_accounts(config.sub_api_url.clone())?
+            .iter()
+            .map(|account| account.to_ss58check())
+            .collect();
+    let storage = PALLETS
+        .iter()
+        .map(|pallet| {
+            let pairs =
+                substrate_transactions::get_storage_pairs(config.sub_api_url.clone(), pallet)?;
+            Ok((pallet.to_string(), pairs))
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(Substrate {
+        validator_accounts,
+        storage,
+    })
+}
@@ -0,0 +1,175 @@
+use log;
+use serde::{de::DeserializeOwned, Serialize};
+
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+/// A FIFO queue that keeps at most `capacity` items in memory and appends the
+/// rest to a file, one JSON document per line. Items come back out in the
+/// order they were pushed.
+#[derive(Debug)]
+pub struct SpillQueue<T> {
+    memory: VecDeque<T>,
+    capacity: usize,
+    path: Option<PathBuf>,
+    spilled: usize,
+    read_offset: u64,
+}
+
+impl<T> SpillQueue<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    pub fn in_memory() -> Self {
+        SpillQueue {
+            memory: VecDeque::new(),
+            capacity: usize::max_value(),
+            path: None,
+            spilled: 0,
+            read_offset: 0,
+        }
+    }
+
+    /// Queues only live as long as the process, so a file left over from a
+    /// previous run is discarded.
+    pub fn with_spill_file(path: PathBuf, capacity: usize) -> Self {
+        let _ = fs::remove_file(&path);
+        SpillQueue {
+            memory: VecDeque::new(),
+            capacity,
+            path: Some(path),
+            spilled: 0,
+            read_offset: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.memory.len() + self.spilled
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The items held in memory, front first, the spilled ones come after.
+    pub fn iter_in_memory(&self) -> impl Iterator<Item = &T> {
+        self.memory.iter()
+    }
+
+    pub fn spilled(&self) -> usize {
+        self.spilled
+    }
+
+    pub fn push_back(&mut self, item: T) {
+        if self.spilled == 0 && self.memory.len() < self.capacity {
+            self.memory.push_back(item);
+            return;
+        }
+        match self.spill(&item) {
+            Ok(()) => self.spilled += 1,
+            Err(e) => {
+                // keeping the order matters more than the memory cap
+                log::error!("can not spill queue to {:?}: {}", self.path, e);
+                let spilled = self.read_spilled(usize::max_value());
+                self.memory.extend(spilled);
+                self.memory.push_back(item);
+            }
+        }
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.memory.is_empty() {
+            let spilled = self.read_spilled(self.capacity);
+            self.memory.extend(spilled);
+        }
+        self.memory.pop_front()
+    }
+
+    fn spill(&self, item: &T) -> io::Result<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "no spill file"))?;
+        let mut line = serde_json::to_string(item)?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .write_all(line.as_bytes())
+    }
+
+    fn read_spilled(&mut self, max: usize) -> Vec<T> {
+        let mut items = vec![];
+        if self.spilled == 0 {
+            return items;
+        }
+        if let Err(e) = self.try_read_spilled(max, &mut items) {
+            log::error!(
+                "can not read spilled queue from {:?}, {} items lost: {}",
+                self.path,
+                self.spilled,
+                e
+            );
+            self.spilled = 0;
+        }
+        if self.spilled == 0 {
+            if let Some(path) = &self.path {
+                let _ = fs::remove_file(path);
+            }
+            self.read_offset = 0;
+        }
+        items
+    }
+
+    fn try_read_spilled(&mut self, max: usize, items: &mut Vec<T>) -> io::Result<()> {
+        let path = self.path.as_ref().expect("spilled items have a file");
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(self.read_offset))?;
+        let mut reader = BufReader::new(file);
+        let mut line = String::new();
+        while items.len() < max && self.spilled > 0 {
+            line.clear();
+            let read = reader.read_line(&mut line)?;
+            if read == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "spill file is truncated",
+                ));
+            }
+            self.read_offset += read as u64;
+            self.spilled -= 1;
+            items.push(serde_json::from_str(&line)?);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn spill_queue_keeps_order_beyond_capacity() {
+        let dir = TestDir::new("spill_queue");
+        let path = dir.join("spill.jsonl");
+        let mut queue = SpillQueue::with_spill_file(path.clone(), 2);
+        for i in 0..5u64 {
+            queue.push_back(i);
+        }
+        assert_eq!(5, queue.len());
+        assert_eq!(2, queue.memory.len());
+        assert!(path.exists());
+
+        assert_eq!(Some(0), queue.pop_front());
+        assert_eq!(Some(1), queue.pop_front());
+        queue.push_back(5);
+        let rest = std::iter::from_fn(|| queue.pop_front()).collect::<Vec<_>>();
+        assert_eq!(vec![2, 3, 4, 5], rest);
+        assert!(queue.is_empty());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,94 @@
+use log;
+
+use std::process;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+/// Handed to a subsystem, which reports once it can do its work or why it
+/// can not. Reports after the first are ignored, so a subsystem that is
+/// restarted can report again.
+#[derive(Debug, Clone)]
+pub struct Ready {
+    tx: Sender<Result<(), String>>,
+}
+
+impl Ready {
+    pub fn ok(&self) {
+        let _ = self.tx.send(Ok(()));
+    }
+
+    pub fn fail(&self, reason: String) {
+        let _ = self.tx.send(Err(reason));
+    }
+}
+
+/// What `main` waits on before it starts the next subsystem.
+#[derive(Debug)]
+pub struct Pending {
+    name: &'static str,
+    rx: Receiver<Result<(), String>>,
+}
+
+pub fn readiness(name: &'static str) -> (Ready, Pending) {
+    let (tx, rx) = channel();
+    (Ready { tx }, Pending { name, rx })
+}
+
+impl Pending {
+    fn wait_for(self, timeout: Duration) -> Result<(), String> {
+        match self.rx.recv_timeout(timeout) {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(reason)) => Err(format!("{} failed to start: {}", self.name, reason)),
+            Err(RecvTimeoutError::Timeout) => Err(format!(
+                "{} is not ready after {}s",
+                self.name,
+                timeout.as_secs()
+            )),
+            Err(RecvTimeoutError::Disconnected) => {
+                Err(format!("{} stopped before it was ready", self.name))
+            }
+        }
+    }
+
+    /// Aborts startup when the subsystem fails or does not get ready in time,
+    /// before anything after it is started.
+    pub fn wait(self, timeout: Duration) {
+        let name = self.name;
+        match self.wait_for(timeout) {
+            Ok(()) => log::info!("{} is ready", name),
+            Err(e) => {
+                log::error!("{}, aborting startup", e);
+                process::exit(1)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wait_for_tests() {
+        let timeout = Duration::from_millis(10);
+        let (ready, pending) = readiness("executor");
+        ready.clone().ok();
+        ready.fail("too late".to_string());
+        assert_eq!(Ok(()), pending.wait_for(timeout));
+
+        let (ready, pending) = readiness("executor");
+        ready.fail("can not connect".to_string());
+        assert_eq!(
+            Err("executor failed to start: can not connect".to_string()),
+            pending.wait_for(timeout)
+        );
+
+        let (ready, pending) = readiness("executor");
+        assert!(pending.wait_for(timeout).unwrap_err().contains("not ready"));
+        drop(ready);
+
+        let (ready, pending) = readiness("executor");
+        drop(ready);
+        assert!(pending.wait_for(timeout).unwrap_err().contains("stopped"));
+    }
+}
@@ -0,0 +1,133 @@
+#[cfg(feature = "sqlite")]
+use log;
+use serde::{Deserialize, Serialize};
+use web3::types::H256;
+
+#[cfg(feature = "sqlite")]
+use crate::database::{self, Shared};
+use crate::jsonl::{self, JsonLog};
+
+pub const FILE_NAME: &str = "submissions.jsonl";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Chain {
+    Ethereum,
+    Substrate,
+}
+
+/// A transaction or extrinsic this validator sent for a message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Submission {
+    pub message_id: H256,
+    pub chain: Chain,
+    pub call: String,
+    pub tx_hash: H256,
+    pub submitted_at: i64,
+}
+
+/// Append-only record of submissions in `<data_dir>/submissions.jsonl`, kept
+/// across restarts so operators can look up what was sent for a message.
+#[derive(Debug)]
+pub struct SubmissionLog {
+    log: JsonLog,
+    #[cfg(feature = "sqlite")]
+    database: Option<Shared>,
+}
+
+impl SubmissionLog {
+    pub fn open(data_dir: &str) -> Self {
+        SubmissionLog {
+            log: JsonLog::open(data_dir, FILE_NAME, "submissions are not recorded"),
+            #[cfg(feature = "sqlite")]
+            database: database::shared(data_dir).ok(),
+        }
+    }
+
+    pub fn record(&self, message_id: H256, chain: Chain, call: &str, tx_hash: H256) {
+        let submission = Submission {
+            message_id,
+            chain,
+            call: call.to_string(),
+            tx_hash,
+            submitted_at: time::now_utc().to_timespec().sec,
+        };
+        self.log.append(&submission);
+        #[cfg(feature = "sqlite")]
+        if let Some(database) = &self.database {
+            let recorded = database
+                .lock()
+                .expect("database lock poisoned")
+                .record_submission(&submission);
+            if let Err(e) = recorded {
+                log::error!("{}", e);
+            }
+        }
+    }
+}
+
+/// Returns all recorded submissions for the message, oldest first. Read from
+/// `submissions.jsonl` when there is no database.
+pub fn find(data_dir: &str, message_id: &H256) -> Result<Vec<Submission>, String> {
+    #[cfg(feature = "sqlite")]
+    {
+        if let Ok(database) = database::shared(data_dir) {
+            return database
+                .lock()
+                .expect("database lock poisoned")
+                .submissions(message_id);
+        }
+    }
+    Ok(read(data_dir)?
+        .into_iter()
+        .filter(|submission| submission.message_id == *message_id)
+        .collect())
+}
+
+/// Every submission in `submissions.jsonl`, oldest first.
+pub fn read(data_dir: &str) -> Result<Vec<Submission>, String> {
+    jsonl::read(data_dir, FILE_NAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn record_and_find_test() {
+        let dir = TestDir::new("submissions");
+        let data_dir = dir.path();
+        let message_id = H256::from_slice(&[1; 32]);
+        let log = SubmissionLog::open(data_dir);
+        log.record(
+            message_id,
+            Chain::Ethereum,
+            "approveTransfer",
+            H256::from_slice(&[2; 32]),
+        );
+        log.record(
+            H256::from_slice(&[3; 32]),
+            Chain::Substrate,
+            "mint",
+            H256::zero(),
+        );
+        log.record(
+            message_id,
+            Chain::Substrate,
+            "mint",
+            H256::from_slice(&[4; 32]),
+        );
+
+        let found = find(data_dir, &message_id).unwrap();
+        assert_eq!(
+            vec![
+                (Chain::Ethereum, H256::from_slice(&[2; 32])),
+                (Chain::Substrate, H256::from_slice(&[4; 32]))
+            ],
+            found
+                .iter()
+                .map(|submission| (submission.chain, submission.tx_hash))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -1,9 +1,9 @@
 use log;
 use web3::types::{H160, H256, U256};
 
-use codec::Decode;
+use codec::{Decode, Encode};
 use node_runtime::{bridge, bridge::RawEvent as BridgeEvent, AccountId};
-use primitives::{self, sr25519};
+use primitives::{self, hashing, sr25519};
 use substrate_api_client::{
     events::{EventsDecoder, RuntimeEvent},
     utils::hexstr_to_vec,
@@ -12,10 +12,14 @@ use substrate_api_client::{
 
 use std::convert::TryFrom;
 use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
 use std::thread;
 
+use crate::bus::Bus;
 use crate::config::Config;
-use crate::controller::Event;
+use crate::controller::{Envelope, Event, Source};
+use crate::startup::Ready;
+use crate::substrate_transactions::{self, Limits};
 
 #[derive(Debug, Clone)]
 struct EventListener {
@@ -25,11 +29,12 @@ struct EventListener {
 
 struct EventHandler {
     config: Config,
-    controller_tx: Sender<Event>,
+    bus: Arc<Bus>,
     events_out: Receiver<String>,
 }
 
-pub fn spawn(config: Config, controller_tx: Sender<Event>) -> thread::JoinHandle<()> {
+/// Reports ready once connected to the node, before it subscribes.
+pub fn spawn(config: Config, bus: Arc<Bus>, ready: Ready) -> thread::JoinHandle<()> {
     thread::Builder::new()
         .name("substrate_event_processor".to_string())
         .spawn(move || {
@@ -39,14 +44,14 @@ pub fn spawn(config: Config, controller_tx: Sender<Event>) -> thread::JoinHandle
                 .name("substrate_event_listener".to_string())
                 .spawn(move || {
                     let event_listener = EventListener::new(config, events_in);
-                    event_listener.start();
+                    event_listener.start(&ready);
                 })
                 .expect("can not start substrate_event_listener");
 
             let event_handler = thread::Builder::new()
                 .name("substrate_event_handler".to_string())
                 .spawn(move || {
-                    let event_handler = EventHandler::new(config2, controller_tx, events_out);
+                    let event_handler = EventHandler::new(config2, bus, events_out);
                     event_handler.start();
                 })
                 .expect("can not start substrate_event_handler");
@@ -62,24 +67,28 @@ impl EventListener {
         EventListener { config, events_in }
     }
 
-    fn start(&self) {
+    fn start(&self, ready: &Ready) {
         let sub_api = Api::<sr25519::Pair>::new(self.config.sub_api_url.clone());
+        ready.ok();
         sub_api.subscribe_events(self.events_in.clone());
     }
 }
 
 impl EventHandler {
-    fn new(config: Config, controller_tx: Sender<Event>, events_out: Receiver<String>) -> Self {
+    fn new(config: Config, bus: Arc<Bus>, events_out: Receiver<String>) -> Self {
         EventHandler {
             config,
-            controller_tx,
+            bus,
             events_out,
         }
     }
 
     fn start(&self) {
+        let mut limits = None;
         self.events_out.iter().for_each(|event| {
             log::debug!("[substrate] got event: {:?}", event);
+            // events arrive once per block, limits are checked as often
+            self.sync_limits(&mut limits);
 
             let unhex = hexstr_to_vec(event).expect("convert hexstr to vec failed");
             let mut er_enc = unhex.as_slice();
@@ -116,6 +125,37 @@ impl EventHandler {
         })
     }
 
+    /// Sends the pallet's limits to the controller when they differ from
+    /// `last`, the first time right away.
+    fn sync_limits(&self, last: &mut Option<Limits>) {
+        const BLOCK_NUMBER: u128 = 0;
+
+        let limits = match substrate_transactions::get_limits(self.config.sub_api_url.clone()) {
+            Ok(limits) => limits,
+            Err(e) => {
+                log::warn!("[substrate] {}", e);
+                return;
+            }
+        };
+        if *last == Some(limits) {
+            return;
+        }
+        *last = Some(limits);
+        let amounts = (
+            limits.min_tx_value,
+            limits.max_tx_value,
+            limits.day_max_limit,
+            limits.day_max_limit_for_one_address,
+            limits.max_pending_tx_limit,
+        );
+        let event = Event::sub_set_new_limits(
+            H256::from(hashing::blake2_256(&amounts.encode())),
+            limits.into(),
+            BLOCK_NUMBER,
+        );
+        self.bus.publish(Envelope::new(event, Source::SubstrateWs));
+    }
+
     fn handle_bridge_event(&self, event: BridgeEvent<AccountId, primitives::H256, u128, u32>) {
         const BLOCK_NUMBER: u128 = 0;
 
@@ -123,24 +163,24 @@ impl EventHandler {
         match &event {
             bridge::RawEvent::RelayMessage(message_id) => {
                 let event =
-                    Event::SubRelayMessage(H256::from_slice(message_id.as_bytes()), BLOCK_NUMBER);
-                self.controller_tx.send(event).expect("can not send event");
+                    Event::sub_relay_message(H256::from_slice(message_id.as_bytes()), BLOCK_NUMBER);
+                self.bus.publish(Envelope::new(event, Source::SubstrateWs));
             }
             bridge::RawEvent::ApprovedRelayMessage(message_id, token_id, from, to, amount) => {
                 let from: [u8; 32] = from.to_owned().into();
-                let event = Event::SubApprovedRelayMessage(
+                let event = Event::sub_approved_relay_message(
                     H256::from_slice(message_id.as_bytes()),
                     H256::from(from),
                     H160::from_slice(to.as_bytes()),
-                    U256::from(*token_id),
                     U256::from(*amount),
+                    U256::from(*token_id),
                     BLOCK_NUMBER,
                 );
-                self.controller_tx.send(event).expect("can not send event");
+                self.bus.publish(Envelope::new(event, Source::SubstrateWs));
             }
             bridge::RawEvent::BurnedMessage(message_id, token_id, from, to, amount) => {
                 let from: [u8; 32] = from.to_owned().into();
-                let event = Event::SubBurnedMessage(
+                let event = Event::sub_burned_message(
                     H256::from_slice(message_id.as_bytes()),
                     H256::from(from),
                     H160::from_slice(to.as_bytes()),
@@ -148,23 +188,23 @@ impl EventHandler {
                     U256::from(*token_id),
                     BLOCK_NUMBER,
                 );
-                self.controller_tx.send(event).expect("can not send event");
+                self.bus.publish(Envelope::new(event, Source::SubstrateWs));
             }
             bridge::RawEvent::MintedMessage(message_id, token_id) => {
-                let event = Event::SubMintedMessage(
+                let event = Event::sub_minted_message(
                     H256::from_slice(message_id.as_bytes()),
                     U256::from(*token_id),
                     BLOCK_NUMBER,
                 );
-                self.controller_tx.send(event).expect("can not send event");
+                self.bus.publish(Envelope::new(event, Source::SubstrateWs));
             }
             bridge::RawEvent::CancellationConfirmedMessage(message_id, token_id) => {
-                let event = Event::SubCancellationConfirmedMessage(
+                let event = Event::sub_cancellation_confirmed_message(
                     H256::from_slice(message_id.as_bytes()),
                     U256::from(*token_id),
                     BLOCK_NUMBER,
                 );
-                self.controller_tx.send(event).expect("can not send event");
+                self.bus.publish(Envelope::new(event, Source::SubstrateWs));
             }
             bridge::RawEvent::AccountPausedMessage(
                 message_id,
@@ -173,14 +213,14 @@ impl EventHandler {
                 token_id,
             ) => {
                 let sub_address: [u8; 32] = sub_address.to_owned().into();
-                let event = Event::SubAccountPausedMessage(
+                let event = Event::sub_account_paused_message(
                     H256::from_slice(message_id.as_bytes()),
                     H256::from(sub_address),
                     u64::from(*timestamp),
                     U256::from(*token_id),
                     BLOCK_NUMBER,
                 );
-                self.controller_tx.send(event).expect("can not send event");
+                self.bus.publish(Envelope::new(event, Source::SubstrateWs));
             }
             bridge::RawEvent::AccountResumedMessage(
                 message_id,
@@ -189,14 +229,14 @@ impl EventHandler {
                 token_id,
             ) => {
                 let sub_address: [u8; 32] = sub_address.to_owned().into();
-                let event = Event::SubAccountResumedMessage(
+                let event = Event::sub_account_resumed_message(
                     H256::from_slice(message_id.as_bytes()),
                     H256::from(sub_address),
                     u64::from(*timestamp),
                     U256::from(*token_id),
                     BLOCK_NUMBER,
                 );
-                self.controller_tx.send(event).expect("can not send event");
+                self.bus.publish(Envelope::new(event, Source::SubstrateWs));
             }
         }
     }
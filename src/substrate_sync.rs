@@ -0,0 +1,115 @@
+use log;
+use primitives::sr25519;
+use substrate_api_client::Api;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::metrics;
+use crate::substrate_transactions::{self, Health};
+
+/// How long a deferred extrinsic waits before it looks again whether the node
+/// caught up.
+pub const RECHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Set while the Substrate node reports that it is syncing.
+static SYNCING: AtomicBool = AtomicBool::new(false);
+
+/// A syncing node rejects extrinsics or includes them on a stale fork, and a
+/// node without peers that should have some is as far behind.
+fn is_synced(health: &Health) -> bool {
+    !health.is_syncing && !(health.should_have_peers && health.peers == 0)
+}
+
+/// Asks the node for its `system_health` and records whether it is synced.
+/// When it can not be asked the last answer stands.
+fn check(sub_api: &Api<sr25519::Pair>) -> bool {
+    match substrate_transactions::get_health(sub_api) {
+        Ok(health) => {
+            let synced = is_synced(&health);
+            if SYNCING.swap(!synced, Ordering::Relaxed) == synced {
+                if synced {
+                    log::info!("substrate node is synced again: {:?}", health);
+                } else {
+                    log::warn!(
+                        "substrate node is syncing, deferring extrinsics: {:?}",
+                        health
+                    );
+                }
+            }
+            metrics::set_gauge(
+                "validator_substrate_syncing",
+                &[],
+                if synced { 0.0 } else { 1.0 },
+            );
+            synced
+        }
+        Err(e) => {
+            log::warn!("can not get substrate node health: {}", e);
+            !SYNCING.load(Ordering::Relaxed)
+        }
+    }
+}
+
+/// Checks at startup and every `SUB_SYNC_CHECK_SECS` over one connection,
+/// and alerts once the node has been syncing for `SUB_SYNC_ALERT_SECS`.
+pub fn spawn(config: Config) -> thread::JoinHandle<()> {
+    let sub_api = Api::<sr25519::Pair>::new(config.sub_api_url.clone());
+    if !check(&sub_api) {
+        log::warn!("starting while the substrate node syncs, extrinsics wait until it is synced");
+    }
+    thread::Builder::new()
+        .name("substrate_sync".to_string())
+        .spawn(move || {
+            let mut syncing_since = None;
+            loop {
+                thread::sleep(Duration::from_secs(config.sub_sync_check_secs));
+                if check(&sub_api) {
+                    syncing_since = None;
+                    continue;
+                }
+                let since = *syncing_since.get_or_insert_with(Instant::now);
+                let syncing = since.elapsed();
+                metrics::set_gauge(
+                    "validator_substrate_syncing_seconds",
+                    &[],
+                    syncing.as_secs_f64(),
+                );
+                if syncing >= Duration::from_secs(config.sub_sync_alert_secs) {
+                    log::error!(
+                        "substrate node {} has been syncing for {}s, extrinsics are on hold",
+                        config.sub_api_url,
+                        syncing.as_secs()
+                    );
+                }
+            }
+        })
+        .expect("can not started substrate sync monitor")
+}
+
+/// Whether extrinsics can be submitted. Deferred ones are handled again
+/// after `RECHECK_INTERVAL` instead of waiting on a worker.
+pub fn accepts_extrinsics() -> bool {
+    !SYNCING.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_synced_tests() {
+        let health = |is_syncing, peers, should_have_peers| Health {
+            is_syncing,
+            peers,
+            should_have_peers,
+        };
+        assert!(is_synced(&health(false, 3, true)));
+        assert!(!is_synced(&health(true, 3, true)));
+        assert!(!is_synced(&health(false, 0, true)));
+        // a single dev node
+        assert!(is_synced(&health(false, 0, false)));
+    }
+}
@@ -1,135 +1,434 @@
+use codec::{Decode, Encode};
 use primitives::{
-    crypto::{AccountId32, Pair},
-    sr25519,
+    crypto::{AccountId32, Pair, Ss58Codec},
+    hashing, sr25519,
 };
-use substrate_api_client::{compose_extrinsic, XtStatus, Api};
+use rustc_hex::ToHex;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use substrate_api_client::{
+    compose_extrinsic,
+    events::{EventsDecoder, RuntimeEvent, SystemEvent},
+    utils::hexstr_to_vec,
+    Api, XtStatus,
+};
+
+use std::convert::TryFrom;
+
+use crate::secret::Secret;
+
+/// An extrinsic included in a finalized block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Finalized {
+    pub hash: primitives::H256,
+    pub fee: Option<u128>,
+}
 
 pub fn mint(
     sub_api_url: String,
-    signer_mnemonic_phrase: String,
+    signer_mnemonic_phrase: Secret<String>,
     message_id: primitives::H256,
     from: primitives::H160,
     to: AccountId32,
     token_id: u32,
     amount: u128,
-) {
-    let sub_api = Api::new(sub_api_url).set_signer(get_sr25519_pair(&signer_mnemonic_phrase));
+) -> Result<Finalized, String> {
+    let sub_api =
+        Api::new(sub_api_url).set_signer(get_sr25519_pair(signer_mnemonic_phrase.expose()));
     let ext = compose_extrinsic!(
         sub_api,
         "Bridge",
         "multi_signed_mint",
-        message_id,
-        from,
-        to,
-        token_id,
-        amount
+        mint_args(message_id, from, to, token_id, amount)
     );
     log::debug!("extrinsic: {:?}", ext);
-    //send and watch extrinsic until finalized
-    let tx_hash = sub_api.send_extrinsic(ext.hex_encode(), XtStatus::Finalized);
-
-    match tx_hash {
-        Ok(h) => log::info!("multi_signed_mint successdul, tx hash: {:?}", h),
-        Err(e) => log::info!("multi_signed_mint failed, error:{:?}", e),
+    let finalized = send_until_finalized(&sub_api, ext.hex_encode());
+    match &finalized {
+        Ok(finalized) => log::info!(
+            "multi_signed_mint successful, tx hash: {:?}",
+            finalized.hash
+        ),
+        Err(e) => log::info!("multi_signed_mint failed: {}", e),
     }
+    finalized
 }
 
 pub fn approve_transfer(
     sub_api_url: String,
-    signer_mnemonic_phrase: String,
+    signer_mnemonic_phrase: Secret<String>,
     message_id: primitives::H256,
-) {
-    let sub_api = Api::new(sub_api_url).set_signer(get_sr25519_pair(&signer_mnemonic_phrase));
+) -> Result<Finalized, String> {
+    let sub_api =
+        Api::new(sub_api_url).set_signer(get_sr25519_pair(signer_mnemonic_phrase.expose()));
     let ext = compose_extrinsic!(sub_api, "Bridge", "approve_transfer", message_id);
     log::debug!("extrinsic: {:?}", ext);
-    //send and watch extrinsic until finalized
-    let _tx_hash = sub_api.send_extrinsic(ext.hex_encode(), XtStatus::Finalized);
+    send_until_finalized(&sub_api, ext.hex_encode())
 }
 
 pub fn cancel_transfer(
     sub_api_url: String,
-    signer_mnemonic_phrase: String,
+    signer_mnemonic_phrase: Secret<String>,
     message_id: primitives::H256,
-) {
-    let sub_api = Api::new(sub_api_url).set_signer(get_sr25519_pair(&signer_mnemonic_phrase));
+) -> Result<Finalized, String> {
+    let sub_api =
+        Api::new(sub_api_url).set_signer(get_sr25519_pair(signer_mnemonic_phrase.expose()));
     let ext = compose_extrinsic!(sub_api, "Bridge", "cancel_transfer", message_id);
     log::debug!("extrinsic: {:?}", ext);
-    //send and watch extrinsic until finalized
-    let _tx_hash = sub_api.send_extrinsic(ext.hex_encode(), XtStatus::Finalized);
+    send_until_finalized(&sub_api, ext.hex_encode())
 }
 
 pub fn confirm_transfer(
     sub_api_url: String,
-    signer_mnemonic_phrase: String,
+    signer_mnemonic_phrase: Secret<String>,
     message_id: primitives::H256,
-) {
-    let sub_api = Api::new(sub_api_url).set_signer(get_sr25519_pair(&signer_mnemonic_phrase));
+) -> Result<Finalized, String> {
+    let sub_api =
+        Api::new(sub_api_url).set_signer(get_sr25519_pair(signer_mnemonic_phrase.expose()));
     let ext = compose_extrinsic!(sub_api, "Bridge", "confirm_transfer", message_id);
     log::debug!("extrinsic: {:?}", ext);
-    //send and watch extrinsic until finalized
-    let _tx_hash = sub_api.send_extrinsic(ext.hex_encode(), XtStatus::Finalized);
+    send_until_finalized(&sub_api, ext.hex_encode())
 }
 
-pub fn pause_bridge(sub_api_url: String, signer_mnemonic_phrase: String) {
-    let sub_api = Api::new(sub_api_url).set_signer(get_sr25519_pair(&signer_mnemonic_phrase));
+pub fn pause_bridge(
+    sub_api_url: String,
+    signer_mnemonic_phrase: Secret<String>,
+) -> Result<Finalized, String> {
+    let sub_api =
+        Api::new(sub_api_url).set_signer(get_sr25519_pair(signer_mnemonic_phrase.expose()));
     let ext = compose_extrinsic!(sub_api, "Bridge", "pause_bridge");
     log::debug!("extrinsic: {:?}", ext);
-    //send and watch extrinsic until finalized
-    let _tx_hash = sub_api.send_extrinsic(ext.hex_encode(), XtStatus::Finalized);
+    send_until_finalized(&sub_api, ext.hex_encode())
 }
 
-pub fn resume_bridge(sub_api_url: String, signer_mnemonic_phrase: String) {
-    let sub_api = Api::new(sub_api_url).set_signer(get_sr25519_pair(&signer_mnemonic_phrase));
+pub fn resume_bridge(
+    sub_api_url: String,
+    signer_mnemonic_phrase: Secret<String>,
+) -> Result<Finalized, String> {
+    let sub_api =
+        Api::new(sub_api_url).set_signer(get_sr25519_pair(signer_mnemonic_phrase.expose()));
     let ext = compose_extrinsic!(sub_api, "Bridge", "resume_bridge");
     log::debug!("extrinsic: {:?}", ext);
-    //send and watch extrinsic until finalized
-    let _tx_hash = sub_api.send_extrinsic(ext.hex_encode(), XtStatus::Finalized);
+    send_until_finalized(&sub_api, ext.hex_encode())
 }
 
 pub fn update_limits(
     sub_api_url: String,
-    signer_mnemonic_phrase: String,
+    signer_mnemonic_phrase: Secret<String>,
     min_guest_transaction_value: u128,
     max_guest_transaction_value: u128,
     day_guest_max_limit: u128,
     day_guest_max_limit_for_one_address: u128,
     max_guest_pending_transaction_limit: u128,
-) {
-    let sub_api = Api::new(sub_api_url).set_signer(get_sr25519_pair(&signer_mnemonic_phrase));
+) -> Result<Finalized, String> {
+    let sub_api =
+        Api::new(sub_api_url).set_signer(get_sr25519_pair(signer_mnemonic_phrase.expose()));
     let ext = compose_extrinsic!(
         sub_api,
         "Bridge",
         "update_limits",
-        min_guest_transaction_value,
-        max_guest_transaction_value,
-        day_guest_max_limit,
-        day_guest_max_limit_for_one_address,
-        max_guest_pending_transaction_limit
+        update_limits_args(
+            min_guest_transaction_value,
+            max_guest_transaction_value,
+            day_guest_max_limit,
+            day_guest_max_limit_for_one_address,
+            max_guest_pending_transaction_limit
+        )
     );
     log::debug!("extrinsic: {:?}", ext);
-    //send and watch extrinsic until finalized
-    let _tx_hash = sub_api.send_extrinsic(ext.hex_encode(), XtStatus::Finalized);
+    send_until_finalized(&sub_api, ext.hex_encode())
 }
 
 pub fn update_validator_list(
     sub_api_url: String,
-    signer_mnemonic_phrase: String,
+    signer_mnemonic_phrase: Secret<String>,
     message_id: primitives::H256,
     new_how_many_validators_decide: u64,
     new_validators: Vec<sr25519::Public>,
-) {
-    let sub_api = Api::new(sub_api_url).set_signer(get_sr25519_pair(&signer_mnemonic_phrase));
+) -> Result<Finalized, String> {
+    let sub_api =
+        Api::new(sub_api_url).set_signer(get_sr25519_pair(signer_mnemonic_phrase.expose()));
     let ext = compose_extrinsic!(
         sub_api,
         "Bridge",
         "update_validator_list",
-        message_id,
-        new_how_many_validators_decide,
-        new_validators
+        update_validator_list_args(message_id, new_how_many_validators_decide, new_validators)
     );
     log::debug!("extrinsic: {:?}", ext);
-    //send and watch extrinsic until finalized
-    let _tx_hash = sub_api.send_extrinsic(ext.hex_encode(), XtStatus::Finalized);
+    send_until_finalized(&sub_api, ext.hex_encode())
+}
+
+// A tuple encodes as its fields one after another, so passing the arguments
+// as one tuple gives the same call data and lets the tests pin their order.
+
+fn mint_args(
+    message_id: primitives::H256,
+    from: primitives::H160,
+    to: AccountId32,
+    token_id: u32,
+    amount: u128,
+) -> (primitives::H256, primitives::H160, AccountId32, u32, u128) {
+    (message_id, from, to, token_id, amount)
+}
+
+fn update_limits_args(
+    min_guest_transaction_value: u128,
+    max_guest_transaction_value: u128,
+    day_guest_max_limit: u128,
+    day_guest_max_limit_for_one_address: u128,
+    max_guest_pending_transaction_limit: u128,
+) -> (u128, u128, u128, u128, u128) {
+    (
+        min_guest_transaction_value,
+        max_guest_transaction_value,
+        day_guest_max_limit,
+        day_guest_max_limit_for_one_address,
+        max_guest_pending_transaction_limit,
+    )
+}
+
+fn update_validator_list_args(
+    message_id: primitives::H256,
+    new_how_many_validators_decide: u64,
+    new_validators: Vec<sr25519::Public>,
+) -> (primitives::H256, u64, Vec<sr25519::Public>) {
+    (message_id, new_how_many_validators_decide, new_validators)
+}
+
+/// Sends the extrinsic and watches it until finalized. An extrinsic that was
+/// finalized but failed to dispatch is an error too. The error keeps the
+/// node's reason, e.g. a bad signature or origin.
+fn send_until_finalized(sub_api: &Api<sr25519::Pair>, xt: String) -> Result<Finalized, String> {
+    let fee = query_fee(sub_api, &xt);
+    match sub_api.send_extrinsic(xt.clone(), XtStatus::Finalized) {
+        Ok(Some(hash)) => match dispatch_error(sub_api, hash, &xt) {
+            Ok(None) => Ok(Finalized { hash, fee }),
+            Ok(Some(error)) => Err(format!("extrinsic failed to dispatch: {}", error)),
+            Err(e) => {
+                log::warn!(
+                    "can not tell whether the extrinsic dispatched, taking it as done: {}",
+                    e
+                );
+                Ok(Finalized { hash, fee })
+            }
+        },
+        Ok(None) => Err("extrinsic was not finalized".to_string()),
+        Err(e) => {
+            log::warn!("extrinsic failed, error: {:?}", e);
+            Err(format!("extrinsic failed: {:?}", e))
+        }
+    }
+}
+
+/// The error of the extrinsic's `ExtrinsicFailed` event in the block it was
+/// finalized in, `None` if it dispatched.
+fn dispatch_error(
+    sub_api: &Api<sr25519::Pair>,
+    block_hash: primitives::H256,
+    xt: &str,
+) -> Result<Option<String>, String> {
+    let block_hash = format!("{:?}", block_hash);
+    let block: Value = serde_json::from_str(&rpc(sub_api, "chain_getBlock", json!([block_hash]))?)
+        .map_err(|e| format!("invalid block: {}", e))?;
+    let index = block["block"]["extrinsics"]
+        .as_array()
+        .and_then(|extrinsics| extrinsics.iter().position(|extrinsic| extrinsic == xt))
+        .ok_or("the extrinsic is not in the block")?;
+    let key = format!(
+        "0x{}{}",
+        hashing::twox_128(b"System").to_hex::<String>(),
+        hashing::twox_128(b"Events").to_hex::<String>()
+    );
+    let events: String =
+        serde_json::from_str(&rpc(sub_api, "state_getStorage", json!([key, block_hash]))?)
+            .map_err(|e| format!("invalid events: {}", e))?;
+    let events = hexstr_to_vec(events).map_err(|e| format!("invalid events: {:?}", e))?;
+    let decoder = EventsDecoder::try_from(sub_api.metadata.clone())
+        .map_err(|e| format!("can not decode events: {:?}", e))?;
+    let events = decoder
+        .decode_events(&mut events.as_slice())
+        .map_err(|e| format!("can not decode events: {:?}", e))?;
+    Ok(events.into_iter().find_map(|(phase, event)| match event {
+        RuntimeEvent::System(SystemEvent::ExtrinsicFailed(error, _))
+            if applies_extrinsic(&phase, index as u32) =>
+        {
+            Some(format!("{:?}", error))
+        }
+        _ => None,
+    }))
+}
+
+/// Whether the event phase is `ApplyExtrinsic(index)`, the first variant of
+/// the system pallet's `Phase`.
+fn applies_extrinsic(phase: &impl Encode, index: u32) -> bool {
+    phase.encode() == (0u8, index).encode()
+}
+
+fn rpc(sub_api: &Api<sr25519::Pair>, method: &str, params: Value) -> Result<String, String> {
+    let request = json!({
+        "jsonrpc": "2.0",
+        "method": method,
+        "params": params,
+        "id": "1",
+    });
+    sub_api
+        .get_request(request.to_string())
+        .ok_or_else(|| format!("can not get {}", method))
+}
+
+/// The fee the node expects the extrinsic to be charged, the `partialFee`
+/// of `payment_queryInfo`. Tips are not included, the validator sends none.
+fn query_fee(sub_api: &Api<sr25519::Pair>, xt: &str) -> Option<u128> {
+    let request = json!({
+        "jsonrpc": "2.0",
+        "method": "payment_queryInfo",
+        "params": [xt],
+        "id": "1",
+    });
+    let fee = sub_api
+        .get_request(request.to_string())
+        .and_then(|info| parse_partial_fee(&info));
+    if fee.is_none() {
+        log::warn!("can not get the fee of the extrinsic, it is not recorded");
+    }
+    fee
+}
+
+/// Depending on the node version the fee is a number, a decimal or a hex string.
+fn parse_partial_fee(info: &str) -> Option<u128> {
+    let info: Value = serde_json::from_str(info).ok()?;
+    match &info["partialFee"] {
+        Value::Number(fee) => fee.as_u64().map(u128::from),
+        Value::String(fee) if fee.starts_with("0x") => u128::from_str_radix(&fee[2..], 16).ok(),
+        Value::String(fee) => fee.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Checks that the account is in the Bridge pallet's validator set, so a
+/// misconfigured mnemonic is caught at startup instead of by failed extrinsics.
+pub fn is_registered_validator(sub_api_url: String, account: &AccountId32) -> Result<bool, String> {
+    let validators = get_validator_accounts(sub_api_url)?;
+    log::debug!(
+        "bridge validators: {:?}",
+        validators
+            .iter()
+            .map(|v| v.to_ss58check())
+            .collect::<Vec<_>>()
+    );
+    Ok(validators.contains(account))
+}
+
+pub fn get_validator_accounts(sub_api_url: String) -> Result<Vec<AccountId32>, String> {
+    let sub_api = Api::<sr25519::Pair>::new(sub_api_url);
+    sub_api
+        .get_storage_value("Bridge", "ValidatorAccounts", None)
+        .ok_or_else(|| "can not read Bridge validator accounts".to_string())
+}
+
+/// Accounts the Bridge pallet paused today, from its `DailyBlocked` storage
+/// keyed by the start of the day in milliseconds.
+pub fn get_paused_accounts(
+    sub_api_url: String,
+    day_start: u64,
+) -> Result<Vec<AccountId32>, String> {
+    let sub_api = Api::<sr25519::Pair>::new(sub_api_url);
+    let key = sub_api
+        .metadata
+        .module("Bridge")
+        .and_then(|module| module.storage("DailyBlocked"))
+        .and_then(|storage| storage.get_map::<u64, Vec<AccountId32>>())
+        .map_err(|e| format!("can not find Bridge DailyBlocked: {:?}", e))?
+        .key(day_start * 1000);
+    // unlike `get_storage_map`, tells a failed request from an empty entry
+    let key = format!("0x{}", key.0.to_hex::<String>());
+    parse_paused_accounts(&rpc(&sub_api, "state_getStorage", json!([key]))?)
+}
+
+/// The SCALE encoded accounts of a `DailyBlocked` entry, `null` for a day
+/// nobody was paused on.
+fn parse_paused_accounts(value: &str) -> Result<Vec<AccountId32>, String> {
+    let value: Option<String> =
+        serde_json::from_str(value).map_err(|e| format!("invalid paused accounts: {}", e))?;
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(vec![]),
+    };
+    let value = hexstr_to_vec(value).map_err(|e| format!("invalid paused accounts: {:?}", e))?;
+    Vec::<AccountId32>::decode(&mut value.as_slice())
+        .map_err(|e| format!("can not decode paused accounts: {:?}", e))
+}
+
+/// All storage of a pallet as SCALE encoded hex key-value pairs. Needs a node
+/// that allows the unsafe `state_getPairs` RPC.
+#[cfg(feature = "graph-node")]
+pub fn get_storage_pairs(
+    sub_api_url: String,
+    module: &str,
+) -> Result<Vec<(String, String)>, String> {
+    let sub_api = Api::<sr25519::Pair>::new(sub_api_url);
+    let prefix = format!(
+        "0x{}",
+        hashing::twox_128(module.as_bytes()).to_hex::<String>()
+    );
+    let request = json!({
+        "jsonrpc": "2.0",
+        "method": "state_getPairs",
+        "params": [prefix],
+        "id": "1",
+    });
+    let pairs = sub_api.get_request(request.to_string()).ok_or_else(|| {
+        format!(
+            "can not read the {} storage, is state_getPairs allowed?",
+            module
+        )
+    })?;
+    serde_json::from_str(&pairs).map_err(|e| format!("invalid {} storage: {}", module, e))
+}
+
+/// Guest limits in the Bridge pallet's `CurrentLimits` storage, in the field
+/// order of its `Limits` type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Decode)]
+pub struct Limits {
+    pub max_tx_value: u128,
+    pub day_max_limit: u128,
+    pub day_max_limit_for_one_address: u128,
+    pub max_pending_tx_limit: u128,
+    pub min_tx_value: u128,
+}
+
+pub fn get_limits(sub_api_url: String) -> Result<Limits, String> {
+    let sub_api = Api::<sr25519::Pair>::new(sub_api_url);
+    sub_api
+        .get_storage_value("Bridge", "CurrentLimits", None)
+        .ok_or_else(|| "can not read Bridge limits".to_string())
+}
+
+/// What the node answers to `system_health`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Health {
+    pub is_syncing: bool,
+    pub peers: u64,
+    pub should_have_peers: bool,
+}
+
+/// Takes the connection of the caller, health is asked for often.
+pub fn get_health(sub_api: &Api<sr25519::Pair>) -> Result<Health, String> {
+    let health = rpc(sub_api, "system_health", json!([]))?;
+    serde_json::from_str(&health).map_err(|e| format!("invalid system_health: {}", e))
+}
+
+/// Returns the timestamp of the latest block in seconds.
+pub fn get_timestamp(sub_api_url: String) -> Result<u64, String> {
+    let sub_api = Api::<sr25519::Pair>::new(sub_api_url);
+    let milliseconds: u64 = sub_api
+        .get_storage_value("Timestamp", "Now", None)
+        .ok_or("can not read Timestamp now")?;
+    Ok(milliseconds / 1000)
+}
+
+pub fn get_account_id(signer_mnemonic_phrase: &str) -> AccountId32 {
+    get_sr25519_pair(signer_mnemonic_phrase).public().into()
 }
 
 pub fn get_sr25519_pair(signer_mnemonic_phrase: &str) -> sr25519::Pair {
@@ -137,3 +436,116 @@ pub fn get_sr25519_pair(signer_mnemonic_phrase: &str) -> sr25519::Pair {
         .expect("valid mnemonic phrase")
         .0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codec::Encode;
+
+    // Expected hex is the SCALE encoding of the Bridge call arguments in the
+    // order the runtime declares them. Call indexes come from the node's
+    // metadata at runtime, so they are not pinned here.
+
+    #[test]
+    fn mint_args_encoding_test() {
+        let args = mint_args(
+            primitives::H256::from_slice(&[1; 32]),
+            primitives::H160::from_slice(&[2; 20]),
+            AccountId32::from([3; 32]),
+            1,
+            1_000_000_000_000_000_000,
+        );
+        assert_eq!(
+            concat!(
+                "0101010101010101010101010101010101010101010101010101010101010101",
+                "0202020202020202020202020202020202020202",
+                "0303030303030303030303030303030303030303030303030303030303030303",
+                "01000000",
+                "000064a7b3b6e00d0000000000000000"
+            ),
+            args.encode().to_hex::<String>()
+        );
+    }
+
+    #[test]
+    fn message_id_args_encoding_test() {
+        // approve_transfer, cancel_transfer and confirm_transfer
+        assert_eq!(
+            "0101010101010101010101010101010101010101010101010101010101010101",
+            primitives::H256::from_slice(&[1; 32])
+                .encode()
+                .to_hex::<String>()
+        );
+        // pause_bridge and resume_bridge take no arguments
+        assert!(().encode().is_empty());
+    }
+
+    #[test]
+    fn update_limits_args_encoding_test() {
+        let args = update_limits_args(100, 10_000, 50_000, 20_000, 30);
+        assert_eq!(
+            concat!(
+                "64000000000000000000000000000000",
+                "10270000000000000000000000000000",
+                "50c30000000000000000000000000000",
+                "204e0000000000000000000000000000",
+                "1e000000000000000000000000000000"
+            ),
+            args.encode().to_hex::<String>()
+        );
+    }
+
+    #[test]
+    fn update_validator_list_args_encoding_test() {
+        let args = update_validator_list_args(
+            primitives::H256::from_slice(&[1; 32]),
+            2,
+            vec![
+                sr25519::Public::from_raw([4; 32]),
+                sr25519::Public::from_raw([5; 32]),
+            ],
+        );
+        assert_eq!(
+            concat!(
+                "0101010101010101010101010101010101010101010101010101010101010101",
+                "0200000000000000",
+                "08",
+                "0404040404040404040404040404040404040404040404040404040404040404",
+                "0505050505050505050505050505050505050505050505050505050505050505"
+            ),
+            args.encode().to_hex::<String>()
+        );
+    }
+
+    #[test]
+    fn parse_partial_fee_tests() {
+        let info = |fee: &str| {
+            format!(
+                r#"{{"class":"normal","partialFee":{},"weight":10000}}"#,
+                fee
+            )
+        };
+        assert_eq!(Some(125_000_000), parse_partial_fee(&info("125000000")));
+        assert_eq!(
+            Some(125_000_000),
+            parse_partial_fee(&info(r#""125000000""#))
+        );
+        assert_eq!(
+            Some(125_000_000),
+            parse_partial_fee(&info(r#""0x7735940""#))
+        );
+        assert_eq!(None, parse_partial_fee(&info("null")));
+        assert_eq!(None, parse_partial_fee("null"));
+    }
+
+    #[test]
+    fn parse_paused_accounts_tests() {
+        assert_eq!(Ok(vec![]), parse_paused_accounts("null"));
+        let accounts = vec![AccountId32::from([1; 32]), AccountId32::from([2; 32])];
+        let value = format!(r#""0x{}""#, accounts.encode().to_hex::<String>());
+        assert_eq!(Ok(accounts), parse_paused_accounts(&value));
+        // an entry cut short is an error, not an empty list
+        assert!(parse_paused_accounts(r#""0x0801""#).is_err());
+        assert!(parse_paused_accounts("").is_err());
+    }
+}
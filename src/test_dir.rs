@@ -0,0 +1,35 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+/// A fresh `<temp_dir>/<name>_test_<pid>` for a test's data dir, removed
+/// again when dropped.
+pub struct TestDir {
+    dir: PathBuf,
+    path: String,
+}
+
+impl TestDir {
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("{}_test_{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.to_str().unwrap().to_string();
+        TestDir { dir, path }
+    }
+
+    /// The dir as the `data_dir` the validator takes.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn join(&self, file_name: &str) -> PathBuf {
+        self.dir.join(file_name)
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
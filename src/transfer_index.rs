@@ -0,0 +1,441 @@
+use log;
+use serde::Serialize;
+use web3::{
+    futures::Future,
+    transports::{EventLoopHandle, WebSocket},
+    types::{BlockId, BlockNumber, H256, U256},
+};
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::bus::{Bus, Topic};
+use crate::clock;
+use crate::config::Config;
+use crate::controller::{Address, Event, Outcome};
+#[cfg(feature = "sqlite")]
+use crate::database::{self, Shared};
+use crate::retention::DAY_SECS;
+
+/// Transfers kept in memory without a database, the least recently updated
+/// are dropped beyond.
+const MAX_IN_MEMORY: usize = 100_000;
+
+/// Block times kept, the cache starts over beyond.
+const MAX_BLOCK_TIMES: usize = 10_000;
+
+/// What the validator saw of one transfer.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Transfer {
+    pub message_id: H256,
+    pub sender: Option<String>,
+    pub recipient: Option<String>,
+    pub amount: Option<U256>,
+    /// The name of the latest event, e.g. `EthApprovedRelayMessage`.
+    pub stage: String,
+    /// The block of the first event seen.
+    pub block_number: u128,
+    /// Unix time of that block, or when the validator saw the event if it
+    /// has no Ethereum block.
+    pub first_seen: u64,
+    pub updated_at: u64,
+    /// What became of the latest call this validator made for it.
+    pub outcome: Option<Outcome>,
+}
+
+/// Which transfers to list, every part that is set has to match.
+#[derive(Debug, Default, PartialEq)]
+pub struct Filter {
+    /// Ethereum or Substrate address of the sender or the recipient.
+    pub address: Option<String>,
+    /// Unix time window of `first_seen`.
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    pub from_block: Option<u128>,
+    pub to_block: Option<u128>,
+}
+
+impl Filter {
+    fn matches(&self, transfer: &Transfer) -> bool {
+        let involves = |address: &String| {
+            let address = Some(address.to_lowercase());
+            transfer.sender == address || transfer.recipient == address
+        };
+        let seen = transfer.first_seen;
+        let block = transfer.block_number;
+        self.address.as_ref().map_or(true, involves)
+            && self.since.map_or(true, |since| seen >= since)
+            && self.until.map_or(true, |until| seen <= until)
+            && self.from_block.map_or(true, |from| block >= from)
+            && self.to_block.map_or(true, |to| block <= to)
+    }
+}
+
+/// The transfers seen, kept up to date from the bus for the admin API. They
+/// are kept in the `transfers` table of the database and survive restarts,
+/// see `retention::prune` for when they are dropped. Without a database the
+/// last `MAX_IN_MEMORY` updated since startup are kept in memory, confirmed
+/// ones only for `RETENTION_TRANSFER_DAYS`.
+#[derive(Debug, Default)]
+pub struct TransferIndex {
+    transfers: Mutex<HashMap<H256, Transfer>>,
+    #[cfg(feature = "sqlite")]
+    database: Option<Shared>,
+}
+
+impl TransferIndex {
+    #[cfg(feature = "sqlite")]
+    pub fn open(data_dir: &str) -> Self {
+        TransferIndex {
+            database: database::shared(data_dir).ok(),
+            ..TransferIndex::default()
+        }
+    }
+
+    /// Subscribes before the publishers start, so call it before them.
+    pub fn spawn(bus: &Bus, config: &Config) -> Arc<Self> {
+        let events = bus.subscribe(&[Topic::Transfers, Topic::Outcomes]);
+        #[cfg(feature = "sqlite")]
+        let index = Arc::new(TransferIndex::open(&config.data_dir));
+        #[cfg(not(feature = "sqlite"))]
+        let index = Arc::new(TransferIndex::default());
+        let recorder = index.clone();
+        let eth_api_url = config.eth_api_url.clone();
+        let retention = config.retention.clone();
+        thread::Builder::new()
+            .name("transfer_index".to_string())
+            .spawn(move || {
+                let mut block_times = BlockTimes::connect(&eth_api_url);
+                let mut pruned_at = clock::now();
+                events.iter().for_each(|envelope| {
+                    recorder.record(&envelope.event, |block| block_times.get(block));
+                    let now = clock::now();
+                    if retention.transfer_days > 0
+                        && now >= pruned_at + retention.interval.as_secs()
+                    {
+                        recorder.prune(now.saturating_sub(retention.transfer_days * DAY_SECS));
+                        pruned_at = now;
+                    }
+                })
+            })
+            .expect("can not started transfer index");
+        index
+    }
+
+    /// `block_time` is asked for the time of the block of a transfer's first
+    /// event.
+    fn record(&self, event: &Event, block_time: impl FnOnce(u128) -> Option<u64>) {
+        let now = clock::now();
+        let first_seen = || block_time(event.block_number()).unwrap_or(now);
+        #[cfg(feature = "sqlite")]
+        {
+            if let Some(database) = &self.database {
+                let database = database.lock().expect("database lock poisoned");
+                let recorded = database.transfer(event.message_id()).and_then(|transfer| {
+                    match update(transfer, event, now, first_seen) {
+                        Some(transfer) => database.save_transfer(&transfer),
+                        None => Ok(()),
+                    }
+                });
+                if let Err(e) = recorded {
+                    log::error!("{}", e);
+                }
+                return;
+            }
+        }
+        let mut transfers = self.transfers.lock().expect("transfer index lock poisoned");
+        let message_id = *event.message_id();
+        if let Some(transfer) = update(transfers.remove(&message_id), event, now, first_seen) {
+            transfers.insert(message_id, transfer);
+        }
+        evict(&mut transfers, MAX_IN_MEMORY);
+    }
+
+    /// Drops the transfers in memory whose latest call was confirmed before
+    /// `before`. Those in the database are pruned with the other stores.
+    fn prune(&self, before: u64) {
+        let mut transfers = self.transfers.lock().expect("transfer index lock poisoned");
+        transfers.retain(|_, transfer| match transfer.outcome {
+            Some(Outcome::Confirmed(..)) => transfer.updated_at >= before,
+            _ => true,
+        });
+    }
+
+    /// Oldest first.
+    pub fn find(&self, filter: &Filter) -> Result<Vec<Transfer>, String> {
+        #[cfg(feature = "sqlite")]
+        {
+            if let Some(database) = &self.database {
+                return database
+                    .lock()
+                    .expect("database lock poisoned")
+                    .transfers(filter);
+            }
+        }
+        let transfers = self.transfers.lock().expect("transfer index lock poisoned");
+        let mut found = transfers
+            .values()
+            .filter(|transfer| filter.matches(transfer))
+            .cloned()
+            .collect::<Vec<_>>();
+        found.sort_by_key(|transfer| (transfer.first_seen, transfer.block_number));
+        Ok(found)
+    }
+}
+
+/// Drops the least recently updated tenth of the transfers once there are
+/// more than `capacity`.
+fn evict(transfers: &mut HashMap<H256, Transfer>, capacity: usize) {
+    if transfers.len() <= capacity {
+        return;
+    }
+    let mut oldest = transfers
+        .values()
+        .map(|transfer| (transfer.updated_at, transfer.message_id))
+        .collect::<Vec<_>>();
+    oldest.sort_unstable();
+    let drop = transfers.len() - capacity + capacity / 10;
+    for (_, message_id) in oldest.iter().take(drop) {
+        transfers.remove(message_id);
+    }
+}
+
+/// The transfer after `event`, none for the outcome of a transfer not seen.
+fn update(
+    transfer: Option<Transfer>,
+    event: &Event,
+    now: u64,
+    first_seen: impl FnOnce() -> u64,
+) -> Option<Transfer> {
+    if let Event::Outcome { outcome, .. } = event {
+        return transfer.map(|transfer| Transfer {
+            outcome: Some(outcome.clone()),
+            updated_at: now,
+            ..transfer
+        });
+    }
+    let mut transfer = transfer.unwrap_or_else(|| Transfer {
+        message_id: *event.message_id(),
+        sender: None,
+        recipient: None,
+        amount: None,
+        stage: String::new(),
+        block_number: event.block_number(),
+        first_seen: first_seen(),
+        updated_at: now,
+        outcome: None,
+    });
+    // later stages do not repeat every detail
+    if let Some(sender) = event.sender() {
+        transfer.sender = Some(address_string(sender));
+    }
+    if let Some(recipient) = event.recipient() {
+        transfer.recipient = Some(address_string(recipient));
+    }
+    if let Some(amount) = event.amount() {
+        transfer.amount = Some(amount);
+    }
+    transfer.stage = event.name().to_string();
+    transfer.updated_at = now;
+    Some(transfer)
+}
+
+/// Unix times of Ethereum blocks, the events carry only the block number.
+struct BlockTimes {
+    web3: Option<(EventLoopHandle, web3::Web3<WebSocket>)>,
+    times: HashMap<u128, u64>,
+}
+
+impl BlockTimes {
+    fn connect(eth_api_url: &str) -> Self {
+        let web3 = match WebSocket::new(eth_api_url) {
+            Ok((eloop, transport)) => Some((eloop, web3::Web3::new(transport))),
+            Err(e) => {
+                log::warn!(
+                    "can not connect to {}, transfers are timed by the validator clock: {:?}",
+                    eth_api_url,
+                    e
+                );
+                None
+            }
+        };
+        BlockTimes {
+            web3,
+            times: HashMap::new(),
+        }
+    }
+
+    /// None for block 0, e.g. from the Substrate listener, or when the node
+    /// does not answer.
+    fn get(&mut self, block_number: u128) -> Option<u64> {
+        if block_number == 0 {
+            return None;
+        }
+        if let Some(time) = self.times.get(&block_number) {
+            return Some(*time);
+        }
+        let (_, web3) = self.web3.as_ref()?;
+        let number = BlockNumber::Number((block_number as u64).into());
+        let time = match web3.eth().block(BlockId::Number(number)).wait() {
+            Ok(block) => block.map(|block| block.timestamp.low_u64())?,
+            Err(e) => {
+                log::warn!("can not get the time of block {}: {:?}", block_number, e);
+                return None;
+            }
+        };
+        if self.times.len() >= MAX_BLOCK_TIMES {
+            self.times.clear();
+        }
+        self.times.insert(block_number, time);
+        Some(time)
+    }
+}
+
+fn address_string(address: Address) -> String {
+    match address {
+        Address::Eth(address) => format!("{:?}", address),
+        Address::Sub(address) => format!("{:?}", address),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::submissions::Chain;
+    #[cfg(feature = "sqlite")]
+    use crate::test_dir::TestDir;
+    use web3::types::H160;
+
+    fn block_time(block_number: u128) -> Option<u64> {
+        Some(block_number as u64 * 100)
+    }
+
+    #[test]
+    fn find_tests() {
+        let index = TransferIndex::default();
+        check_find(&index);
+
+        let confirmed = Outcome::Confirmed(
+            Chain::Ethereum,
+            "withdrawTransfer".to_string(),
+            H256::zero(),
+        );
+        index.record(
+            &Event::outcome(H256::from_slice(&[3; 32]), confirmed),
+            block_time,
+        );
+        index.prune(clock::now() + 1);
+        assert_eq!(
+            vec![H256::from_slice(&[1; 32])],
+            index
+                .find(&Filter::default())
+                .unwrap()
+                .iter()
+                .map(|transfer| transfer.message_id)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn evict_test() {
+        let index = TransferIndex::default();
+        for id in 1..=12 {
+            index.record(
+                &Event::eth_withdraw_message(H256::from_slice(&[id; 32]), 1),
+                |_| None,
+            );
+        }
+        let mut transfers = index.transfers.lock().unwrap();
+        for (id, transfer) in transfers.iter_mut() {
+            transfer.updated_at = id.as_bytes()[0] as u64;
+        }
+        evict(&mut transfers, 10);
+        // the least recently updated go, and one tenth more
+        let mut kept = transfers
+            .keys()
+            .map(|id| id.as_bytes()[0])
+            .collect::<Vec<_>>();
+        kept.sort_unstable();
+        assert_eq!((4..=12).collect::<Vec<_>>(), kept);
+        evict(&mut transfers, 10);
+        assert_eq!(9, transfers.len());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn find_in_database_tests() {
+        let dir = TestDir::new("transfer_index");
+        let data_dir = dir.path();
+        check_find(&TransferIndex::open(data_dir));
+        // kept across restarts
+        assert_eq!(
+            1,
+            TransferIndex::open(data_dir)
+                .find(&Filter::default())
+                .unwrap()
+                .len()
+        );
+    }
+
+    fn check_find(index: &TransferIndex) {
+        let message_id = H256::from_slice(&[1; 32]);
+        let eth_address = H160::from_slice(&[0xab; 20]);
+        let sub_address = H256::from_slice(&[2; 32]);
+        index.record(
+            &Event::eth_relay_message(
+                message_id,
+                eth_address,
+                sub_address,
+                U256::from(100),
+                None,
+                10,
+            ),
+            block_time,
+        );
+        index.record(
+            &Event::eth_withdraw_message(H256::from_slice(&[3; 32]), 12),
+            block_time,
+        );
+        index.record(
+            &Event::sub_minted_message(message_id, U256::zero(), 11),
+            block_time,
+        );
+        let failed = Outcome::Failed(
+            Chain::Ethereum,
+            "confirmTransfer".to_string(),
+            "".to_string(),
+        );
+        index.record(&Event::outcome(message_id, failed.clone()), block_time);
+
+        let by_address = index
+            .find(&Filter {
+                address: Some(format!("0x{}", "AB".repeat(20))),
+                ..Filter::default()
+            })
+            .unwrap();
+        assert_eq!(1, by_address.len());
+        assert_eq!("SubMintedMessage", by_address[0].stage);
+        assert_eq!(Some(format!("{:?}", sub_address)), by_address[0].recipient);
+        assert_eq!(Some(U256::from(100)), by_address[0].amount);
+        assert_eq!(10, by_address[0].block_number);
+        assert_eq!(1_000, by_address[0].first_seen);
+        assert_eq!(Some(failed), by_address[0].outcome);
+
+        let by_block = index
+            .find(&Filter {
+                from_block: Some(11),
+                ..Filter::default()
+            })
+            .unwrap();
+        assert_eq!(1, by_block.len());
+        assert_eq!(H256::from_slice(&[3; 32]), by_block[0].message_id);
+        assert_eq!(2, index.find(&Filter::default()).unwrap().len());
+        // first seen at the time of block 12
+        let later = Filter {
+            since: Some(1_100),
+            ..Filter::default()
+        };
+        assert_eq!(1, index.find(&later).unwrap().len());
+    }
+}
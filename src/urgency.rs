@@ -0,0 +1,71 @@
+use std::str::FromStr;
+
+/// How urgently a token bridge call has to be mined. Calls that become
+/// invalid after an on-chain deadline, e.g. within a cancellation window,
+/// pay more for gas and are broadcast again sooner while they wait.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Urgency {
+    Normal,
+    High,
+    Critical,
+}
+
+impl Default for Urgency {
+    fn default() -> Self {
+        Urgency::Normal
+    }
+}
+
+impl FromStr for Urgency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "normal" => Ok(Urgency::Normal),
+            "high" => Ok(Urgency::High),
+            "critical" => Ok(Urgency::Critical),
+            _ => Err(format!(
+                "unknown urgency {:?}, expected normal, high or critical",
+                s
+            )),
+        }
+    }
+}
+
+impl Urgency {
+    /// The gas price to pay when `ETH_GAS_PRICE` is `base`.
+    pub fn gas_price(self, base: u64) -> u64 {
+        let percent = match self {
+            Urgency::Normal => 100,
+            Urgency::High => 150,
+            Urgency::Critical => 200,
+        };
+        base.saturating_mul(percent) / 100
+    }
+
+    /// How many blocks an unmined transaction waits before it is broadcast
+    /// again, in case the node dropped it. Normal calls wait for the reorg
+    /// handling only.
+    pub fn rebroadcast_blocks(self) -> Option<u64> {
+        match self {
+            Urgency::Normal => None,
+            Urgency::High => Some(5),
+            Urgency::Critical => Some(2),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn urgency_tests() {
+        assert_eq!(Ok(Urgency::Critical), " Critical".parse());
+        assert!("urgent".parse::<Urgency>().is_err());
+        assert_eq!(20, Urgency::Normal.gas_price(20));
+        assert_eq!(30, Urgency::High.gas_price(20));
+        assert_eq!(40, Urgency::Critical.gas_price(20));
+        assert_eq!(None, Urgency::Normal.rebroadcast_blocks());
+    }
+}
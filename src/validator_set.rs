@@ -0,0 +1,116 @@
+use log;
+use primitives::crypto::AccountId32;
+use web3::{
+    types::{H160, U256},
+    Transport,
+};
+
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+
+use crate::bridge_contract::BridgeContract;
+use crate::substrate_transactions;
+
+/// Validators as both chains currently see them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidatorSet {
+    pub ethereum: Vec<H160>,
+    pub ethereum_quorum: U256,
+    pub substrate: Vec<AccountId32>,
+}
+
+/// Cached validator sets shared by the threads that need them. The executor
+/// refreshes it at startup and after every validator list change it relays.
+#[derive(Debug, Default)]
+pub struct ValidatorSetCache {
+    current: Mutex<ValidatorSet>,
+    subscribers: Mutex<Vec<Sender<ValidatorSet>>>,
+}
+
+impl ValidatorSetCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn current(&self) -> ValidatorSet {
+        self.current
+            .lock()
+            .expect("validator set lock poisoned")
+            .clone()
+    }
+
+    /// The receiver gets the current set right away and every changed set after.
+    pub fn subscribe(&self) -> Receiver<ValidatorSet> {
+        let (tx, rx) = channel();
+        let mut subscribers = self.subscribers.lock().expect("subscribers lock poisoned");
+        let _ = tx.send(
+            self.current
+                .lock()
+                .expect("validator set lock poisoned")
+                .clone(),
+        );
+        subscribers.push(tx);
+        rx
+    }
+
+    /// Reads both sets from the chains. A chain that can not be read keeps
+    /// its cached set.
+    pub fn refresh<T: Transport>(&self, bridge: &BridgeContract<T>, sub_api_url: &str) {
+        let mut set = self
+            .current
+            .lock()
+            .expect("validator set lock poisoned")
+            .clone();
+        match (bridge.validators(), bridge.quorum()) {
+            (Ok(validators), Ok(quorum)) => {
+                set.ethereum = validators;
+                set.ethereum_quorum = quorum;
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                log::warn!("can not read token bridge validators: {:?}", e)
+            }
+        }
+        match substrate_transactions::get_validator_accounts(sub_api_url.to_string()) {
+            Ok(validators) => set.substrate = validators,
+            Err(e) => log::warn!("{}", e),
+        }
+        self.update(set);
+    }
+
+    fn update(&self, set: ValidatorSet) {
+        let mut subscribers = self.subscribers.lock().expect("subscribers lock poisoned");
+        let mut current = self.current.lock().expect("validator set lock poisoned");
+        if *current == set {
+            return;
+        }
+        log::info!("validator set changed from {:?} to {:?}", *current, set);
+        *current = set;
+        subscribers.retain(|subscriber| subscriber.send(current.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_notifies_on_change_test() {
+        let cache = ValidatorSetCache::new();
+        let changes = cache.subscribe();
+        assert_eq!(Ok(ValidatorSet::default()), changes.try_recv());
+
+        let set = ValidatorSet {
+            ethereum: vec![H160::from_slice(&[1; 20])],
+            ethereum_quorum: U256::from(1),
+            substrate: vec![AccountId32::from([2; 32])],
+        };
+        cache.update(set.clone());
+        cache.update(set.clone());
+        assert_eq!(Ok(set), changes.try_recv());
+        assert!(changes.try_recv().is_err());
+
+        drop(changes);
+        cache.update(ValidatorSet::default());
+        assert!(cache.subscribers.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,250 @@
+use log;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Map, Value};
+
+/// Upgrades the data of a stored document from one version to the next.
+pub type Migration = fn(Value) -> Result<Value, String>;
+
+/// Guest limits in the order version 1 events listed them.
+const GUEST_LIMITS: [&str; 5] = [
+    "min_transaction_value",
+    "max_transaction_value",
+    "day_max_limit",
+    "day_max_limit_for_one_address",
+    "max_pending_transaction_limit",
+];
+
+/// Host limits in the order version 1 events listed them.
+const HOST_LIMITS: [&str; 5] = [
+    "min_host_transaction_value",
+    "max_host_transaction_value",
+    "day_host_max_limit",
+    "day_host_max_limit_for_one_address",
+    "max_host_pending_transaction_limit",
+];
+
+/// Stores `data` as `{"version": <n>, "data": ...}`, the latest version is
+/// one past the number of migrations.
+pub fn to_string<T: Serialize>(data: &T, migrations: &[Migration]) -> serde_json::Result<String> {
+    serde_json::to_string(&json!({
+        "version": migrations.len() + 1,
+        "data": data,
+    }))
+}
+
+/// Reads a document stored by this or an older validator. Documents written
+/// before they had a version are version 1, the data on its own. Each
+/// migration from the stored version on runs in turn.
+pub fn from_str<T: DeserializeOwned>(json: &str, migrations: &[Migration]) -> Result<T, String> {
+    let latest = migrations.len() + 1;
+    let (version, mut data) =
+        match serde_json::from_str::<Value>(json).map_err(|e| e.to_string())? {
+            Value::Object(mut document) if is_versioned(&document) => {
+                let version = document["version"].as_u64().unwrap_or(0) as usize;
+                (version, document.remove("data").unwrap_or(Value::Null))
+            }
+            data => (1, data),
+        };
+    if version == 0 || version > latest {
+        return Err(format!(
+            "version {} is unknown, this validator reads versions 1 to {}",
+            version, latest
+        ));
+    }
+    if version < latest {
+        log::info!(
+            "upgrading stored data from version {} to {}",
+            version,
+            latest
+        );
+    }
+    for migration in &migrations[version - 1..] {
+        data = migration(data)?;
+    }
+    serde_json::from_value(data).map_err(|e| e.to_string())
+}
+
+fn is_versioned(document: &Map<String, Value>) -> bool {
+    document.len() == 2 && document.contains_key("version") && document.contains_key("data")
+}
+
+/// Version 1 events had positional fields, e.g.
+/// `{"EthWithdrawMessage": ["0x..", 10]}`, since version 2 they are named and
+/// limits are grouped. Events that are named already are kept.
+pub fn name_event_fields(event: Value) -> Result<Value, String> {
+    let mut event = match event {
+        Value::Object(event) if event.len() == 1 => event,
+        event => return Err(format!("not an event: {}", event)),
+    };
+    let (variant, slot) = event.iter_mut().next().expect("an event has a variant");
+    let names = v1_fields(variant).ok_or_else(|| format!("unknown event {}", variant))?;
+    let values = match slot.take() {
+        Value::Array(values) if values.len() == names.len() => values,
+        named @ Value::Object(_) => {
+            *slot = named;
+            return Ok(Value::Object(event));
+        }
+        values => return Err(format!("{} can not have the fields {}", variant, values)),
+    };
+    let mut fields = names
+        .into_iter()
+        .map(String::from)
+        .zip(values)
+        .collect::<Map<_, _>>();
+    match variant.as_str() {
+        "EthSetNewLimits" => {
+            group(&mut fields, &GUEST_LIMITS, "guest");
+            let mut limits = HOST_LIMITS.to_vec();
+            limits.push("guest");
+            group(&mut fields, &limits, "limits");
+        }
+        "SubSetNewLimits" => group(&mut fields, &GUEST_LIMITS, "limits"),
+        _ => (),
+    }
+    *slot = Value::Object(fields);
+    Ok(Value::Object(event))
+}
+
+fn v1_fields(variant: &str) -> Option<Vec<&'static str>> {
+    let fields: &[&str] = match variant {
+        "EthBridgePausedMessage"
+        | "EthBridgeResumedMessage"
+        | "EthWithdrawMessage"
+        | "SubRelayMessage" => &["message_id", "block_number"],
+        "EthBridgeStartedMessage" | "EthBridgeStoppedMessage" => {
+            &["message_id", "sender", "block_number"]
+        }
+        "EthRelayMessage" | "EthApprovedRelayMessage" => &[
+            "message_id",
+            "sender",
+            "recipient",
+            "amount",
+            "block_number",
+        ],
+        "EthRevertMessage" => &["message_id", "sender", "amount", "block_number"],
+        "EthHostAccountPausedMessage"
+        | "EthHostAccountResumedMessage"
+        | "EthGuestAccountPausedMessage"
+        | "EthGuestAccountResumedMessage" => {
+            &["message_id", "account", "timestamp", "block_number"]
+        }
+        "EthSetNewLimits" => {
+            return Some(
+                [
+                    &["message_id"][..],
+                    &HOST_LIMITS[..],
+                    &GUEST_LIMITS[..],
+                    &["block_number"][..],
+                ]
+                .concat(),
+            )
+        }
+        "EthValidatorsListMessage" => &["message_id", "validators", "quorum", "block_number"],
+        "SubApprovedRelayMessage" | "SubBurnedMessage" => &[
+            "message_id",
+            "sender",
+            "recipient",
+            "amount",
+            "token_id",
+            "block_number",
+        ],
+        "SubMintedMessage" | "SubCancellationConfirmedMessage" => {
+            &["message_id", "token_id", "block_number"]
+        }
+        "SubAccountPausedMessage" | "SubAccountResumedMessage" => &[
+            "message_id",
+            "account",
+            "timestamp",
+            "token_id",
+            "block_number",
+        ],
+        "SubSetNewLimits" => {
+            return Some(
+                [
+                    &["message_id"][..],
+                    &GUEST_LIMITS[..],
+                    &["block_number"][..],
+                ]
+                .concat(),
+            )
+        }
+        "Unknown" => &["message_id", "origin", "payload", "block_number"],
+        "Outcome" => &["message_id", "outcome"],
+        _ => return None,
+    };
+    Some(fields.to_vec())
+}
+
+/// Moves `names` out of `fields` into an object of their own.
+fn group(fields: &mut Map<String, Value>, names: &[&str], into: &str) {
+    let grouped = names
+        .iter()
+        .filter_map(|name| fields.remove(*name).map(|value| (name.to_string(), value)))
+        .collect::<Map<_, _>>();
+    fields.insert(into.to_string(), Value::Object(grouped));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::Event;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn from_str_tests() {
+        fn double(data: Value) -> Result<Value, String> {
+            Ok(json!(data.as_u64().ok_or("not a number")? * 2))
+        }
+        let migrations: [Migration; 2] = [double, double];
+        assert_eq!(Ok(12), from_str::<u64>("3", &migrations));
+        assert_eq!(
+            Ok(6),
+            from_str::<u64>(r#"{"version": 2, "data": 3}"#, &migrations)
+        );
+        assert_eq!(
+            Ok(3),
+            from_str::<u64>(&to_string(&3, &migrations).unwrap(), &migrations)
+        );
+        assert!(from_str::<u64>(r#"{"version": 4, "data": 3}"#, &migrations).is_err());
+        // a map that happens to have other keys is not an envelope
+        let map = r#"{"version": 1, "other": 2}"#;
+        assert_eq!(
+            2,
+            from_str::<BTreeMap<String, u64>>(map, &[]).unwrap()["other"]
+        );
+    }
+
+    #[test]
+    fn name_event_fields_test() {
+        let fixture: Vec<Value> =
+            serde_json::from_str(include_str!("../res/fixtures/events_v1.json")).unwrap();
+        let events = fixture
+            .into_iter()
+            .map(|event| {
+                name_event_fields(event)
+                    .and_then(|e| serde_json::from_value(e).map_err(|e| e.to_string()))
+            })
+            .collect::<Result<Vec<Event>, _>>()
+            .unwrap();
+        // one of each kind
+        assert_eq!(24, events.len());
+        match &events[12] {
+            Event::EthSetNewLimits { limits, .. } => {
+                assert_eq!(5.into(), limits.max_host_pending_transaction_limit);
+                assert_eq!(10.into(), limits.guest.max_pending_transaction_limit);
+            }
+            event => panic!("expected limits, got {:?}", event),
+        }
+        match &events[21] {
+            Event::SubSetNewLimits { limits, .. } => {
+                assert_eq!(5.into(), limits.max_pending_transaction_limit)
+            }
+            event => panic!("expected limits, got {:?}", event),
+        }
+
+        let named = serde_json::to_value(&events[0]).unwrap();
+        assert_eq!(Ok(named.clone()), name_event_fields(named));
+        assert!(name_event_fields(json!({"Nonsense": []})).is_err());
+        assert!(name_event_fields(json!({"EthWithdrawMessage": ["0x00"]})).is_err());
+    }
+}
@@ -0,0 +1,100 @@
+use log;
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Progress signal of one worker. The watchdog retires the heartbeat of a
+/// stalled worker, so if it ever wakes up it stops instead of running next
+/// to its replacement.
+#[derive(Clone, Debug)]
+pub struct Heartbeat {
+    last_beat: Arc<AtomicU64>,
+    retired: Arc<AtomicBool>,
+}
+
+impl Heartbeat {
+    pub fn new() -> Self {
+        Heartbeat {
+            last_beat: Arc::new(AtomicU64::new(now())),
+            retired: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Records progress and returns false once the worker has been replaced.
+    pub fn beat(&self) -> bool {
+        self.last_beat.store(now(), Ordering::Relaxed);
+        !self.retired.load(Ordering::Relaxed)
+    }
+
+    fn seconds_since_last_beat(&self) -> u64 {
+        now().saturating_sub(self.last_beat.load(Ordering::Relaxed))
+    }
+
+    fn retire(&self) {
+        self.retired.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Runs the worker started by `start` and starts a new one whenever the
+/// current one makes no progress for `timeout`.
+pub fn supervise<F>(name: &str, timeout: Duration, start: F) -> thread::JoinHandle<()>
+where
+    F: Fn(Heartbeat) -> thread::JoinHandle<()> + Send + 'static,
+{
+    let name = name.to_string();
+    thread::Builder::new()
+        .name(format!("{}_watchdog", name))
+        .spawn(move || {
+            let mut heartbeat = Heartbeat::new();
+            start(heartbeat.clone());
+            loop {
+                thread::sleep(Duration::from_secs(1));
+                let stalled_for = heartbeat.seconds_since_last_beat();
+                if stalled_for > timeout.as_secs() {
+                    log::error!(
+                        "{} made no progress for {}s, restarting it",
+                        name,
+                        stalled_for
+                    );
+                    heartbeat.retire();
+                    heartbeat = Heartbeat::new();
+                    start(heartbeat.clone());
+                }
+            }
+        })
+        .expect("can not started watchdog")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn stalled_worker_is_replaced() {
+        let (started_tx, started_rx) = channel();
+        supervise("test_worker", Duration::from_secs(0), move |heartbeat| {
+            let started_tx = started_tx.clone();
+            thread::spawn(move || {
+                let _ = started_tx.send(heartbeat.clone());
+                // never beats again
+                thread::sleep(Duration::from_secs(60));
+            })
+        });
+
+        let first = started_rx.recv().unwrap();
+        started_rx
+            .recv_timeout(Duration::from_secs(10))
+            .expect("worker was not restarted");
+        assert!(!first.beat());
+    }
+}